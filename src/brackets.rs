@@ -0,0 +1,474 @@
+// Bracket handling for the text editor
+// Auto-closing pairs, surround-selection, jump to matching bracket and select enclosing brackets
+
+use gtk4::prelude::*;
+use gtk4::{gdk, gio, glib, EventControllerKey, TextBuffer, TextIter, TextMark};
+use sourceview5::{prelude::*, Buffer, View};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::utils::ObjectState;
+
+/// Pairs used when neither the settings nor the built-in table say otherwise
+const DEFAULT_PAIRS: &str = "()[]{}\"\"''";
+
+/// Built-in pairs for languages where the defaults get in the way
+///
+/// For example, a single quote starts a lifetime in Rust and a quoted form in Lisp.
+const LANGUAGE_PAIRS: &[(&str, &str)] = &[
+    ("rust", "()[]{}\"\""),
+    ("commonlisp", "()[]{}\"\""),
+    ("scheme", "()[]{}\"\""),
+    ("clojure", "()[]{}\"\""),
+    ("markdown", "()[]{}\"\"``"),
+    ("javascript", "()[]{}\"\"''``"),
+    ("typescript", "()[]{}\"\"''``"),
+    ("sh", "()[]{}\"\"''``"),
+    ("go", "()[]{}\"\"''``"),
+];
+
+/// Closing characters inserted by auto-close, each with a mark right before it
+///
+/// Only these are stepped over by typing the same character or removed with their
+/// opening partner by Backspace. Entries are dropped once their character is gone
+/// or the cursor works on another line.
+const AUTO_CLOSED: ObjectState<Rc<RefCell<Vec<(TextMark, char)>>>> = ObjectState::new("brackets-auto-closed");
+
+/// Bracket behavior for one language
+///
+/// Read from the settings file, with keys for the language id or "default":
+/// - `brackets.pairs.<language>`: the pairs as consecutive open/close characters, e.g. `()[]{}""`
+/// - `brackets.auto_close.<language>`: insert the closing character automatically (true/false)
+/// - `brackets.surround.<language>`: wrap the selection when typing an opening character (true/false)
+/// - `brackets.matching.<language>`: enable Ctrl+M and Ctrl+Shift+M (true/false)
+#[derive(Clone, Debug)]
+pub struct BracketConfig {
+    pub pairs: Vec<(char, char)>,
+    pub auto_close: bool,
+    pub surround: bool,
+    pub matching: bool,
+}
+
+impl BracketConfig {
+    /// Returns the configuration for a language key (see `syntax::language_key`)
+    pub fn for_language(language_key: &str) -> Self {
+        // Only the bracket keys are read, under the settings lock, as this runs on every key press
+        crate::settings::with_settings(|settings| {
+            // Language-specific setting first, then the "default" setting
+            let lookup = |name: &str| {
+                settings
+                    .get(&format!("brackets.{}.{}", name, language_key))
+                    .or_else(|| settings.get(&format!("brackets.{}.default", name)))
+            };
+            let lookup_bool = |name: &str| lookup(name).and_then(|value| value.parse().ok()).unwrap_or(true);
+
+            let pairs = match lookup("pairs") {
+                Some(pairs_text) => parse_pairs(pairs_text),
+                None => parse_pairs(
+                    LANGUAGE_PAIRS
+                        .iter()
+                        .find(|(id, _)| *id == language_key)
+                        .map(|(_, pairs)| *pairs)
+                        .unwrap_or(DEFAULT_PAIRS),
+                ),
+            };
+
+            BracketConfig {
+                pairs,
+                auto_close: lookup_bool("auto_close"),
+                surround: lookup_bool("surround"),
+                matching: lookup_bool("matching"),
+            }
+        })
+    }
+
+    /// Returns the closing character for an opening character
+    fn closing_for(&self, open: char) -> Option<char> {
+        self.pairs.iter().find(|(o, _)| *o == open).map(|(_, c)| *c)
+    }
+
+    /// Checks whether a character closes one of the pairs
+    fn is_closing(&self, character: char) -> bool {
+        self.pairs.iter().any(|(_, c)| *c == character)
+    }
+
+    /// Bracket pairs only (quotes have the same open and close character and cannot nest)
+    fn bracket_pairs(&self) -> Vec<(char, char)> {
+        self.pairs.iter().copied().filter(|(o, c)| o != c).collect()
+    }
+}
+
+/// Parses a pairs string ("()[]") into (open, close) tuples, ignoring a trailing odd character
+fn parse_pairs(text: &str) -> Vec<(char, char)> {
+    let characters: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+    characters.chunks_exact(2).map(|pair| (pair[0], pair[1])).collect()
+}
+
+/// Returns the bracket configuration for the language of a buffer
+fn config_for_buffer(buffer: &TextBuffer) -> BracketConfig {
+    let language_key = buffer
+        .downcast_ref::<Buffer>()
+        .map(crate::syntax::language_key)
+        .unwrap_or_else(|| "text".to_string());
+    BracketConfig::for_language(&language_key)
+}
+
+/// Adds bracket helpers to a source view
+///
+/// - Typing an opening character inserts its closing partner; typing the closing
+///   character right before an auto-inserted one steps over it
+/// - Typing an opening character with a selection wraps the selection
+/// - Backspace inside an empty auto-closed pair removes both characters
+/// - Ctrl+M jumps to the matching bracket
+/// - Ctrl+Shift+M selects the contents of the enclosing brackets (again to include the brackets)
+pub fn attach(view: &View) {
+    let key_controller = EventControllerKey::new();
+    key_controller.set_propagation_phase(gtk4::PropagationPhase::Capture);
+    let view_clone = view.clone();
+    key_controller.connect_key_pressed(move |_, keyval, _, state| handle_key(&view_clone, keyval, state));
+    view.add_controller(key_controller);
+
+    // Bracket navigation as named actions, so the keys can be rebound
+    let action_group = gio::SimpleActionGroup::new();
+    let commands: [(&str, fn(&View)); 2] = [
+        ("jump-to-match", jump_to_matching_bracket),
+        ("select-enclosing", select_enclosing_brackets),
+    ];
+    for (name, command) in commands {
+        let action = gio::SimpleAction::new(name, None);
+        let view = view.clone();
+        action.connect_activate(move |_, _| {
+            if config_for_buffer(&view.buffer()).matching {
+                crate::macros::record_action(&format!("brackets.{}", name));
+                command(&view);
+            }
+        });
+        action_group.add_action(&action);
+    }
+    view.insert_action_group("brackets", Some(&action_group));
+}
+
+/// Handles a key press for the bracket helpers
+pub fn handle_key(view: &View, keyval: gdk::Key, state: gdk::ModifierType) -> glib::Propagation {
+    let ctrl = state.contains(gdk::ModifierType::CONTROL_MASK);
+    let alt = state.contains(gdk::ModifierType::ALT_MASK);
+
+    // Typing at several carets is handled by the multi-cursor code
+    if crate::multi_cursor::has_extra_carets(view) || !view.is_editable() {
+        return glib::Propagation::Proceed;
+    }
+
+    let buffer = view.buffer();
+
+    if ctrl || alt {
+        return glib::Propagation::Proceed;
+    }
+
+    if keyval == gdk::Key::BackSpace {
+        return if delete_empty_pair(&buffer) {
+            glib::Propagation::Stop
+        } else {
+            glib::Propagation::Proceed
+        };
+    }
+
+    let Some(character) = keyval.to_unicode() else {
+        return glib::Propagation::Proceed;
+    };
+    if insert_with_pairs(&buffer, character) {
+        view.scroll_mark_onscreen(&buffer.get_insert());
+        glib::Propagation::Stop
+    } else {
+        glib::Propagation::Proceed
+    }
+}
+
+/// Handles a typed character: surround, skip-over or auto-close
+///
+/// Returns true when the character was handled and must not be inserted again.
+fn insert_with_pairs(buffer: &TextBuffer, character: char) -> bool {
+    let config = config_for_buffer(buffer);
+    let selection = buffer.selection_bounds();
+    let cursor = buffer.iter_at_mark(&buffer.get_insert());
+    let next_char = if cursor.is_end() { None } else { Some(cursor.char()) };
+
+    // Surround the selection with the pair
+    if let (Some((start, end)), Some(close)) = (selection.as_ref(), config.closing_for(character)) {
+        if !config.surround {
+            return false;
+        }
+        let (start_offset, end_offset) = (start.offset(), end.offset());
+
+        buffer.begin_user_action();
+        let mut end_iter = buffer.iter_at_offset(end_offset);
+        buffer.insert(&mut end_iter, &close.to_string());
+        let mut start_iter = buffer.iter_at_offset(start_offset);
+        buffer.insert(&mut start_iter, &character.to_string());
+        buffer.end_user_action();
+
+        // Keep the original text selected, now inside the pair
+        buffer.select_range(&buffer.iter_at_offset(start_offset + 1), &buffer.iter_at_offset(end_offset + 1));
+        return true;
+    }
+    if selection.is_some() {
+        return false;
+    }
+
+    // Step over a closing character inserted by auto-close
+    if config.auto_close
+        && config.is_closing(character)
+        && next_char == Some(character)
+        && take_auto_closed(buffer, &cursor, character)
+    {
+        let mut after = cursor.clone();
+        after.forward_char();
+        buffer.place_cursor(&after);
+        return true;
+    }
+
+    // Insert the closing partner
+    let Some(close) = config.closing_for(character) else {
+        return false;
+    };
+    if !config.auto_close || !should_auto_close(&config, &cursor, character, close, next_char) {
+        return false;
+    }
+
+    buffer.begin_user_action();
+    buffer.insert_at_cursor(&format!("{}{}", character, close));
+    let mut between = buffer.iter_at_mark(&buffer.get_insert());
+    between.backward_char();
+    buffer.place_cursor(&between);
+    buffer.end_user_action();
+
+    // Right gravity keeps the mark before the closing character while typing in between
+    let mark = buffer.create_mark(None, &between, false);
+    auto_closed(buffer).borrow_mut().push((mark, close));
+    true
+}
+
+/// Returns the auto-inserted closing characters of a buffer
+fn auto_closed(buffer: &TextBuffer) -> Rc<RefCell<Vec<(TextMark, char)>>> {
+    AUTO_CLOSED.get(buffer).unwrap_or_else(|| {
+        let marks = Rc::new(RefCell::new(Vec::new()));
+        AUTO_CLOSED.set(buffer, marks.clone());
+        marks
+    })
+}
+
+/// Checks whether `character` at the cursor was inserted by auto-close, and forgets it if so
+///
+/// Also drops the entries that are no longer valid.
+fn take_auto_closed(buffer: &TextBuffer, cursor: &TextIter, character: char) -> bool {
+    let mut found = false;
+    auto_closed(buffer).borrow_mut().retain(|(mark, close)| {
+        let iter = buffer.iter_at_mark(mark);
+        let valid = !iter.is_end() && iter.char() == *close && iter.line() == cursor.line();
+        let taken = valid && !found && *close == character && iter.offset() == cursor.offset();
+        found |= taken;
+        if !valid || taken {
+            buffer.delete_mark(mark);
+        }
+        valid && !taken
+    });
+    found
+}
+
+/// Decides whether typing `open` should also insert `close`
+///
+/// Pairs are only closed before whitespace, punctuation or a closing character, and
+/// quotes are not closed right after a word character (e.g. in "don't").
+fn should_auto_close(config: &BracketConfig, cursor: &TextIter, open: char, close: char, next_char: Option<char>) -> bool {
+    let next_allows = match next_char {
+        None => true,
+        Some(next) => next.is_whitespace() || config.is_closing(next) || matches!(next, ';' | ',' | '.' | ':'),
+    };
+    if !next_allows {
+        return false;
+    }
+
+    if open == close {
+        let mut previous = cursor.clone();
+        if previous.backward_char() {
+            let previous_char = previous.char();
+            if previous_char.is_alphanumeric() || previous_char == '_' || previous_char == open {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/// Deletes both characters of an empty auto-closed pair around the cursor
+///
+/// Returns true when a pair was deleted.
+fn delete_empty_pair(buffer: &TextBuffer) -> bool {
+    if buffer.has_selection() {
+        return false;
+    }
+    let config = config_for_buffer(buffer);
+    if !config.auto_close {
+        return false;
+    }
+
+    let cursor = buffer.iter_at_mark(&buffer.get_insert());
+    let mut before = cursor.clone();
+    if !before.backward_char() || cursor.is_end() {
+        return false;
+    }
+    if config.closing_for(before.char()) != Some(cursor.char()) || !take_auto_closed(buffer, &cursor, cursor.char()) {
+        return false;
+    }
+
+    let mut after = cursor.clone();
+    after.forward_char();
+    buffer.begin_user_action();
+    buffer.delete(&mut before, &mut after);
+    buffer.end_user_action();
+    true
+}
+
+/// Checks whether a position is code (not inside a string or comment)
+///
+/// Without syntax highlighting every position counts as code.
+fn is_code(buffer: &TextBuffer, iter: &TextIter) -> bool {
+    match buffer.downcast_ref::<Buffer>() {
+        Some(source_buffer) => {
+            !source_buffer.iter_has_context_class(iter, "string")
+                && !source_buffer.iter_has_context_class(iter, "comment")
+        }
+        None => true,
+    }
+}
+
+/// Finds the bracket matching the one at `iter`, skipping brackets in strings and comments
+///
+/// Returns the position of the matching bracket character.
+pub fn find_matching_bracket(buffer: &TextBuffer, iter: &TextIter, pairs: &[(char, char)]) -> Option<TextIter> {
+    let character = iter.char();
+    let in_code = is_code(buffer, iter);
+    // Only compare brackets in the same kind of context (code vs. string/comment)
+    let counts = |position: &TextIter| is_code(buffer, position) == in_code;
+
+    if let Some((open, close)) = pairs.iter().find(|(open, _)| *open == character).copied() {
+        let mut depth = 0;
+        let mut position = iter.clone();
+        while position.forward_char() && !position.is_end() {
+            let current = position.char();
+            if (current == open || current == close) && counts(&position) {
+                if current == open {
+                    depth += 1;
+                } else if depth == 0 {
+                    return Some(position);
+                } else {
+                    depth -= 1;
+                }
+            }
+        }
+        return None;
+    }
+
+    if let Some((open, close)) = pairs.iter().find(|(_, close)| *close == character).copied() {
+        let mut depth = 0;
+        let mut position = iter.clone();
+        while position.backward_char() {
+            let current = position.char();
+            if (current == open || current == close) && counts(&position) {
+                if current == close {
+                    depth += 1;
+                } else if depth == 0 {
+                    return Some(position);
+                } else {
+                    depth -= 1;
+                }
+            }
+        }
+    }
+
+    None
+}
+
+/// Moves the cursor to the bracket matching the one next to it
+///
+/// The bracket after the cursor is preferred, then the one before it.
+fn jump_to_matching_bracket(view: &View) {
+    let buffer = view.buffer();
+    let pairs = config_for_buffer(&buffer).bracket_pairs();
+    let cursor = buffer.iter_at_mark(&buffer.get_insert());
+
+    let mut candidates = vec![cursor.clone()];
+    let mut before = cursor.clone();
+    if before.backward_char() {
+        candidates.push(before);
+    }
+
+    for candidate in candidates {
+        if candidate.is_end() {
+            continue;
+        }
+        let character = candidate.char();
+        if !pairs.iter().any(|(open, close)| *open == character || *close == character) {
+            continue;
+        }
+        if let Some(matching) = find_matching_bracket(&buffer, &candidate, &pairs) {
+            buffer.place_cursor(&matching);
+            view.scroll_mark_onscreen(&buffer.get_insert());
+            return;
+        }
+    }
+}
+
+/// Selects the contents of the innermost brackets around the selection
+///
+/// When the contents are already selected, the brackets themselves are added,
+/// and the next call moves on to the enclosing pair.
+fn select_enclosing_brackets(view: &View) {
+    let buffer = view.buffer();
+    let pairs = config_for_buffer(&buffer).bracket_pairs();
+
+    let (selection_start, selection_end) = buffer
+        .selection_bounds()
+        .unwrap_or_else(|| {
+            let cursor = buffer.iter_at_mark(&buffer.get_insert());
+            (cursor.clone(), cursor)
+        });
+
+    // Walk backwards to the nearest unmatched opening bracket
+    let mut position = selection_start.clone();
+    let mut depths = vec![0i32; pairs.len()];
+    while position.backward_char() {
+        let current = position.char();
+        if !is_code(&buffer, &position) {
+            continue;
+        }
+        if let Some(index) = pairs.iter().position(|(_, close)| *close == current) {
+            depths[index] += 1;
+        } else if let Some(index) = pairs.iter().position(|(open, _)| *open == current) {
+            if depths[index] > 0 {
+                depths[index] -= 1;
+                continue;
+            }
+
+            let Some(closing) = find_matching_bracket(&buffer, &position, &pairs) else {
+                continue;
+            };
+            if closing.offset() < selection_end.offset() {
+                continue; // This pair closes before the selection ends
+            }
+
+            let mut inner_start = position.clone();
+            inner_start.forward_char();
+            let already_inner = inner_start.offset() == selection_start.offset() && closing.offset() == selection_end.offset();
+
+            if already_inner {
+                // Expand to include the brackets
+                let mut outer_end = closing.clone();
+                outer_end.forward_char();
+                buffer.select_range(&position, &outer_end);
+            } else {
+                buffer.select_range(&inner_start, &closing);
+            }
+            return;
+        }
+    }
+}
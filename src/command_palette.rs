@@ -0,0 +1,405 @@
+// Command palette for the text editor
+// Ctrl+Shift+P lists every action with its shortcut and runs the one picked by fuzzy search
+
+use gtk4::prelude::*;
+use gtk4::{
+    gdk, gio, glib, ApplicationWindow, Box as GtkBox, EventControllerKey, Label, ListBox, ListBoxRow, Orientation,
+    ScrolledWindow, SearchEntry,
+};
+use sourceview5::View;
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use crate::utils::ObjectState;
+
+/// Number of recently used commands remembered between sessions
+const MAX_RECENT_COMMANDS: usize = 20;
+
+/// Number of rows shown for a query
+const MAX_RESULTS: usize = 50;
+
+/// Detailed action name of each row
+const ROW_ACTION: ObjectState<String> = ObjectState::new("palette-action");
+
+/// A command offered in the palette
+#[derive(Clone)]
+struct PaletteEntry {
+    /// Detailed action name, as activated ("win.save", "fold.fold-level(2)")
+    action: String,
+    title: String,
+    /// Shortcut label, empty when unbound
+    shortcut: String,
+    /// Position in the recently used list, 0 for the most recent
+    recent_rank: Option<usize>,
+}
+
+/// Returns the path of the file remembering recently used commands
+fn recent_commands_path() -> PathBuf {
+    crate::settings::get_config_dir().join("recent_commands")
+}
+
+/// Loads the recently used commands, most recent first
+fn load_recent_commands() -> Vec<String> {
+    fs::read_to_string(recent_commands_path())
+        .map(|contents| contents.lines().map(str::trim).filter(|line| !line.is_empty()).map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+/// Moves a command to the front of the recently used list
+fn remember_command(action: &str) {
+    let mut recent = load_recent_commands();
+    recent.retain(|known| known != action);
+    recent.insert(0, action.to_string());
+    recent.truncate(MAX_RECENT_COMMANDS);
+    if let Err(e) = fs::write(recent_commands_path(), recent.join("\n") + "\n") {
+        eprintln!("Failed to save recent commands: {}", e);
+    }
+}
+
+/// Turns an action name into a title ("save-as" becomes "Save As")
+fn title_from_action_name(name: &str) -> String {
+    name.split(['-', '_'])
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Lists the commands available from the window and, when the focus was in an editor, from that editor
+///
+/// Window and application actions are read from the action groups themselves, so
+/// actions registered later show up without changes here. Actions that need a
+/// parameter are only offered through the detailed names of the keybindings table.
+fn collect_entries(window: &ApplicationWindow, focused_view: Option<&View>) -> Vec<PaletteEntry> {
+    let bindings = crate::keybindings::current_bindings();
+    let recent = load_recent_commands();
+    let mut actions: Vec<String> = Vec::new();
+
+    let mut add_group = |prefix: &str, group: &gio::ActionGroup| {
+        let mut names: Vec<String> = group.list_actions().iter().map(|name| name.to_string()).collect();
+        names.sort();
+        for name in names {
+            if group.action_parameter_type(&name).is_some() || !group.is_action_enabled(&name) {
+                continue;
+            }
+            actions.push(format!("{}.{}", prefix, name));
+        }
+    };
+    add_group("win", window.upcast_ref::<gio::ActionGroup>());
+    if let Some(application) = window.application() {
+        add_group("app", application.upcast_ref::<gio::ActionGroup>());
+    }
+
+    // Editor commands of the focused view, and commands with a fixed parameter
+    for command in crate::keybindings::COMMANDS {
+        let is_window_action = command.action.starts_with("win.") || command.action.starts_with("app.");
+        if (is_window_action || focused_view.is_some()) && !actions.iter().any(|action| action == command.action) {
+            actions.push(command.action.to_string());
+        }
+    }
+    // The palette doesn't list itself
+    actions.retain(|action| action != "win.command-palette");
+
+    actions
+        .into_iter()
+        .map(|action| {
+            let title = crate::keybindings::COMMANDS
+                .iter()
+                .find(|command| command.action == action)
+                .map(|command| command.title.to_string())
+                .unwrap_or_else(|| title_from_action_name(action.split_once('.').map_or(action.as_str(), |(_, name)| name)));
+            let shortcut = bindings
+                .get(&action)
+                .and_then(|accels| accels.first())
+                .map(|accel| crate::keybindings::accel_label(accel))
+                .unwrap_or_default();
+            let recent_rank = recent.iter().position(|known| *known == action);
+            PaletteEntry { action, title, shortcut, recent_rank }
+        })
+        .collect()
+}
+
+/// Scores how well a query matches a text, or None when it doesn't match
+///
+/// The query's characters must appear in order. Matches at the start of a word and
+/// runs of consecutive characters score higher; characters skipped in between lower
+/// the score.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let query: Vec<char> = query.to_lowercase().chars().filter(|c| !c.is_whitespace()).collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+
+    let mut score = 0;
+    let mut query_index = 0;
+    let mut previous_match: Option<usize> = None;
+    for (index, character) in text.iter().enumerate() {
+        if query_index == query.len() {
+            break;
+        }
+        if *character != query[query_index] {
+            continue;
+        }
+        let word_start = index == 0 || !text[index - 1].is_alphanumeric();
+        score += 1;
+        if word_start {
+            score += 8;
+        }
+        match previous_match {
+            Some(previous) if previous + 1 == index => score += 5,
+            Some(previous) => score -= (index - previous - 1).min(5) as i32,
+            None => score -= index.min(10) as i32,
+        }
+        previous_match = Some(index);
+        query_index += 1;
+    }
+
+    (query_index == query.len()).then_some(score)
+}
+
+/// Orders the entries for a query: best matches first, recently used commands breaking ties
+///
+/// An empty query lists the recently used commands first, then everything by title.
+fn rank_entries(entries: &[PaletteEntry], query: &str) -> Vec<PaletteEntry> {
+    let recent_bonus = |entry: &PaletteEntry| {
+        entry.recent_rank.map(|rank| (MAX_RECENT_COMMANDS - rank) as i32).unwrap_or(0)
+    };
+
+    let mut ranked: Vec<(i32, PaletteEntry)> = entries
+        .iter()
+        .filter_map(|entry| {
+            let title_score = fuzzy_score(query, &entry.title);
+            let action_score = fuzzy_score(query, &entry.action).map(|score| score - 2);
+            let score = title_score.max(action_score)?;
+            Some((score * 4 + recent_bonus(entry), entry.clone()))
+        })
+        .collect();
+
+    if query.trim().is_empty() {
+        ranked.sort_by(|(_, a), (_, b)| {
+            let rank = |entry: &PaletteEntry| entry.recent_rank.unwrap_or(usize::MAX);
+            rank(a).cmp(&rank(b)).then_with(|| a.title.cmp(&b.title))
+        });
+    } else {
+        ranked.sort_by(|(score_a, a), (score_b, b)| score_b.cmp(score_a).then_with(|| a.title.cmp(&b.title)));
+    }
+    ranked.into_iter().map(|(_, entry)| entry).take(MAX_RESULTS).collect()
+}
+
+/// Creates the row showing one command: title, recent-use rank and shortcut
+fn create_entry_row(entry: &PaletteEntry) -> ListBoxRow {
+    let row_box = GtkBox::new(Orientation::Horizontal, 8);
+    row_box.set_margin_top(4);
+    row_box.set_margin_bottom(4);
+    row_box.set_margin_start(8);
+    row_box.set_margin_end(8);
+
+    let title_box = GtkBox::new(Orientation::Vertical, 0);
+    title_box.set_hexpand(true);
+    let title = Label::new(Some(&entry.title));
+    title.set_halign(gtk4::Align::Start);
+    let action = Label::new(Some(&entry.action));
+    action.set_halign(gtk4::Align::Start);
+    action.add_css_class("caption");
+    action.add_css_class("dim-label");
+    title_box.append(&title);
+    title_box.append(&action);
+    row_box.append(&title_box);
+
+    if let Some(rank) = entry.recent_rank {
+        let recent = Label::new(Some(&format!("recent #{}", rank + 1)));
+        recent.add_css_class("caption");
+        recent.add_css_class("accent");
+        row_box.append(&recent);
+    }
+
+    let shortcut = Label::new(Some(&entry.shortcut));
+    shortcut.add_css_class("dim-label");
+    row_box.append(&shortcut);
+
+    let row = ListBoxRow::new();
+    row.set_child(Some(&row_box));
+    ROW_ACTION.set(&row, entry.action.clone());
+    row
+}
+
+/// Shows the command palette over the main window
+///
+/// The focus goes back to the widget that had it before a command runs, so editor
+/// commands such as "lines.duplicate" apply to the editor the user was in.
+pub fn show_command_palette(window: &ApplicationWindow) {
+    let previous_focus = gtk4::prelude::GtkWindowExt::focus(window);
+    let focused_view = previous_focus
+        .as_ref()
+        .and_then(|widget| widget.ancestor(View::static_type()))
+        .and_then(|widget| widget.downcast::<View>().ok());
+    let entries = collect_entries(window, focused_view.as_ref());
+
+    // Runs a command from the widget that had the focus
+    let run_command = {
+        let window = window.clone();
+        move |row: &ListBoxRow| {
+            let Some(action) = ROW_ACTION.get(row) else {
+                return;
+            };
+            let action = action.as_str();
+            let target: gtk4::Widget = match previous_focus.as_ref() {
+                Some(widget) => {
+                    widget.grab_focus();
+                    widget.clone()
+                }
+                None => window.clone().upcast(),
+            };
+            remember_command(action);
+            if !crate::keybindings::activate_action(&target, action) {
+                eprintln!("Command '{}' is not available here", action);
+            }
+        }
+    };
+    let (palette, _container, search_entry, list) = create_popup(window, "Type a command", run_command);
+    palette.add_css_class("command-palette");
+
+    // Fills the list for the current query and selects the best match
+    let fill_list = move |query: &str| {
+        while let Some(child) = list.first_child() {
+            list.remove(&child);
+        }
+        for entry in rank_entries(&entries, query) {
+            list.append(&create_entry_row(&entry));
+        }
+        if let Some(first) = list.row_at_index(0) {
+            list.select_row(Some(&first));
+        }
+    };
+    fill_list("");
+    search_entry.connect_search_changed(move |entry| fill_list(&entry.text()));
+
+    palette.present();
+    search_entry.grab_focus();
+}
+
+/// Creates a popup with a search entry over a list of results, as used by the command palette and quick open
+///
+/// Callers keep what each row stands for on the row as an `ObjectState`. Up/Down and
+/// Page Up/Down move through the rows while typing, Enter or a click closes the popup
+/// and passes the selected row to `on_pick`, Escape closes it. The returned box holds
+/// the entry and the list, so callers can add widgets of their own.
+pub fn create_popup(
+    window: &ApplicationWindow,
+    placeholder: &str,
+    on_pick: impl Fn(&ListBoxRow) + 'static,
+) -> (gtk4::Window, GtkBox, SearchEntry, ListBox) {
+    let popup = gtk4::Window::builder()
+        .transient_for(window)
+        .modal(true)
+        .decorated(false)
+        .default_width(560)
+        .default_height(420)
+        .destroy_with_parent(true)
+        .build();
+
+    let container = GtkBox::new(Orientation::Vertical, 6);
+    container.set_margin_top(8);
+    container.set_margin_bottom(8);
+    container.set_margin_start(8);
+    container.set_margin_end(8);
+
+    let search_entry = SearchEntry::new();
+    search_entry.set_placeholder_text(Some(placeholder));
+    container.append(&search_entry);
+
+    let list = ListBox::new();
+    list.set_selection_mode(gtk4::SelectionMode::Browse);
+    let scrolled = ScrolledWindow::builder().child(&list).vexpand(true).build();
+    container.append(&scrolled);
+    popup.set_child(Some(&container));
+
+    // Close first so the focus is back on the main window when the pick runs
+    let pick: Rc<dyn Fn(&ListBoxRow)> = {
+        let popup = popup.clone();
+        Rc::new(move |row: &ListBoxRow| {
+            popup.close();
+            on_pick(row);
+        })
+    };
+
+    let pick_for_rows = pick.clone();
+    list.connect_row_activated(move |_, row| pick_for_rows(row));
+
+    let list_for_activate = list.clone();
+    search_entry.connect_activate(move |_| {
+        if let Some(row) = list_for_activate.selected_row() {
+            pick(&row);
+        }
+    });
+
+    // Up and Down move through the results while typing, Escape closes
+    let key_controller = EventControllerKey::new();
+    key_controller.set_propagation_phase(gtk4::PropagationPhase::Capture);
+    let popup_for_keys = popup.clone();
+    let search_entry_for_keys = search_entry.clone();
+    let list_for_keys = list.clone();
+    key_controller.connect_key_pressed(move |_, keyval, _, _| {
+        let step = match keyval {
+            gdk::Key::Escape => {
+                popup_for_keys.close();
+                return glib::Propagation::Stop;
+            }
+            gdk::Key::Up | gdk::Key::KP_Up => -1,
+            gdk::Key::Down | gdk::Key::KP_Down => 1,
+            gdk::Key::Page_Up => -10,
+            gdk::Key::Page_Down => 10,
+            _ => return glib::Propagation::Proceed,
+        };
+        let list = &list_for_keys;
+        let current = list.selected_row().map(|row| row.index()).unwrap_or(0);
+        let mut last = 0;
+        while list.row_at_index(last + 1).is_some() {
+            last += 1;
+        }
+        if let Some(row) = list.row_at_index((current + step).clamp(0, last)) {
+            // Focusing the row scrolls it into view; typing then continues in the entry
+            list.select_row(Some(&row));
+            row.grab_focus();
+            search_entry_for_keys.grab_focus();
+            search_entry_for_keys.set_position(-1);
+        }
+        glib::Propagation::Stop
+    });
+    popup.add_controller(key_controller);
+
+    (popup, container, search_entry, list)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_characters_must_appear_in_order() {
+        assert_eq!(fuzzy_score("", "Save"), Some(0));
+        assert!(fuzzy_score("sv", "Save").is_some());
+        assert_eq!(fuzzy_score("vs", "Save"), None);
+        assert_eq!(fuzzy_score("savex", "Save"), None);
+    }
+
+    #[test]
+    fn case_and_spaces_in_the_query_are_ignored() {
+        assert_eq!(fuzzy_score("Save As", "save as"), fuzzy_score("saveas", "SAVE AS"));
+    }
+
+    #[test]
+    fn word_starts_and_runs_score_higher() {
+        let score = |query: &str, text: &str| fuzzy_score(query, text).unwrap();
+        assert!(score("sa", "Save As") > score("sa", "Unsaved"));
+        assert!(score("dup", "Duplicate Lines") > score("dup", "Delete Unused Paragraphs"));
+    }
+}
@@ -0,0 +1,189 @@
+// Editor commands shared by the keymaps
+// Save, open and close go through the same buttons and handlers as the toolbar and tabs
+
+use gtk4::prelude::*;
+use gtk4::{Button, Label};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+
+use crate::handlers::NewTabDependencies;
+
+thread_local! {
+    // Tabs, buttons and paths the commands work on
+    static DEPENDENCIES: RefCell<Option<NewTabDependencies>> = RefCell::new(None);
+    // Header bar button showing the open dialog
+    static OPEN_BUTTON: RefCell<Option<Button>> = RefCell::new(None);
+}
+
+/// Stores the editor state used by the commands
+pub fn set_dependencies(deps: NewTabDependencies, open_button: &Button) {
+    DEPENDENCIES.with(|dependencies| *dependencies.borrow_mut() = Some(deps));
+    OPEN_BUTTON.with(|button| *button.borrow_mut() = Some(open_button.clone()));
+}
+
+/// Saves the active tab like the Save button
+pub fn save() {
+    save_then(|| {});
+}
+
+/// Saves the active tab like the Save button, then runs `on_saved`
+///
+/// For an untitled tab `on_saved` runs after the save dialog is accepted; it
+/// doesn't run when saving fails or is cancelled.
+pub fn save_then(on_saved: impl FnOnce() + 'static) {
+    with_dependencies(|deps| {
+        crate::handlers::save_active_tab(
+            &deps.editor_notebook,
+            &deps.active_tab_path,
+            &deps.file_path_manager,
+            &deps.window,
+            &deps.file_list_box,
+            &deps.current_dir,
+            on_saved,
+        );
+    });
+}
+
+/// Saves the active tab under a new name like the Save As button
+pub fn save_as() {
+    with_dependencies(|deps| {
+        crate::handlers::save_active_tab_as(
+            &deps.editor_notebook,
+            &deps.active_tab_path,
+            &deps.file_path_manager,
+            &deps.window,
+            &deps.file_list_box,
+            &deps.current_dir,
+            || {},
+        );
+    });
+}
+
+/// Saves the active tab under the given path, as Save As does after its dialog
+///
+/// Returns whether the file was written.
+pub fn save_as_path(path: &Path) -> bool {
+    let mut saved = false;
+    with_dependencies(|deps| {
+        let notebook = &deps.editor_notebook;
+        let (Some(page_num), Some((_, buffer))) = (notebook.current_page(), crate::handlers::get_active_text_view_and_buffer(notebook)) else {
+            return;
+        };
+        saved = crate::handlers::save_tab_as(
+            notebook,
+            page_num,
+            &buffer,
+            path,
+            &deps.active_tab_path,
+            &deps.file_path_manager,
+            &deps.file_list_box,
+            &deps.current_dir,
+        );
+    });
+    saved
+}
+
+/// Shows the open dialog like the Open button
+pub fn open() {
+    let button = OPEN_BUTTON.with(|button| button.borrow().clone());
+    match button {
+        Some(button) => button.emit_clicked(),
+        None => eprintln!("Editor commands are not available yet"),
+    }
+}
+
+/// Opens a file like the file list does, or focuses its tab
+pub fn open_path(path: &Path) {
+    with_dependencies(|deps| {
+        crate::handlers::open_file(deps, &path.to_path_buf());
+    });
+}
+
+/// Returns the directory shown in the file list
+pub fn current_dir() -> Option<PathBuf> {
+    DEPENDENCIES.with(|dependencies| dependencies.borrow().as_ref().map(|deps| deps.current_dir.borrow().clone()))
+}
+
+/// Returns the paths of the files open in tabs
+pub fn open_file_paths() -> Vec<PathBuf> {
+    DEPENDENCIES.with(|dependencies| {
+        dependencies
+            .borrow()
+            .as_ref()
+            .map(|deps| deps.file_path_manager.borrow().values().cloned().collect())
+            .unwrap_or_default()
+    })
+}
+
+/// Closes the window, asking about unsaved tabs first
+pub fn quit() {
+    with_dependencies(|deps| deps.window.close());
+}
+
+/// Closes the active tab through its close button
+///
+/// The close button asks about unsaved changes; `discard` clears the unsaved
+/// marker first, so the tab closes without asking.
+pub fn close_active_tab(discard: bool) {
+    with_dependencies(|deps| {
+        let notebook = &deps.editor_notebook;
+        let Some(page) = notebook.current_page().and_then(|page_num| notebook.nth_page(Some(page_num))) else {
+            return;
+        };
+        let Some(tab_box) = notebook.tab_label(&page) else {
+            return;
+        };
+
+        if discard {
+            if let Some(label) = tab_box.first_child().and_then(|child| child.downcast::<Label>().ok()) {
+                let text = label.text();
+                if let Some(name) = text.strip_prefix('*') {
+                    label.set_text(name);
+                }
+            }
+        }
+
+        let mut child = tab_box.first_child();
+        while let Some(widget) = child {
+            if let Some(button) = widget.downcast_ref::<Button>() {
+                button.emit_clicked();
+                return;
+            }
+            child = widget.next_sibling();
+        }
+    });
+}
+
+/// Resolves a path typed by the user against the directory of the active file
+pub fn resolve_path(path: &str) -> PathBuf {
+    let path = match path.strip_prefix("~/") {
+        Some(rest) => home::home_dir().map(|home| home.join(rest)).unwrap_or_else(|| PathBuf::from(path)),
+        None => PathBuf::from(path),
+    };
+    if path.is_absolute() {
+        return path;
+    }
+    let base = DEPENDENCIES.with(|dependencies| {
+        dependencies.borrow().as_ref().map(|deps| {
+            deps.active_tab_path
+                .borrow()
+                .as_ref()
+                .and_then(|active| active.parent().map(|parent| parent.to_path_buf()))
+                .unwrap_or_else(|| deps.current_dir.borrow().clone())
+        })
+    });
+    match base {
+        Some(base) => base.join(path),
+        None => path,
+    }
+}
+
+/// Runs a closure with the editor dependencies, if they were set
+fn with_dependencies(f: impl FnOnce(&NewTabDependencies)) {
+    // Clone first: the closure may open or close tabs, which can re-enter this module
+    let deps = DEPENDENCIES.with(|dependencies| dependencies.borrow().clone());
+    match deps {
+        Some(deps) => f(&deps),
+        None => eprintln!("Editor commands are not available yet"),
+    }
+}
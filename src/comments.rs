@@ -0,0 +1,270 @@
+// Comment toggling for the text editor
+// Ctrl+/ toggles line comments and Ctrl+Shift+/ toggles block comments on the current line or selection
+
+use gtk4::prelude::*;
+use gtk4::{gio, TextBuffer, TextIter};
+use sourceview5::{prelude::*, Buffer, View};
+
+use crate::utils::selected_lines;
+
+/// Comment tokens for a buffer
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CommentTokens {
+    /// Line comment start, e.g. "//"
+    pub line: Option<String>,
+    /// Block comment start and end, e.g. ("/*", "*/")
+    pub block: Option<(String, String)>,
+}
+
+/// Returns the comment tokens for a buffer
+///
+/// Tokens come from the language metadata (`line-comment-start`, `block-comment-start`
+/// and `block-comment-end`). Anything missing there, including every plain-text buffer,
+/// falls back to the settings table keyed by `syntax::language_key`:
+/// `comment.line.<key>=#` and `comment.block.<key>=<start> <end>`.
+pub fn comment_tokens(buffer: &Buffer) -> CommentTokens {
+    let mut tokens = CommentTokens::default();
+
+    if let Some(language) = buffer.language() {
+        tokens.line = language.metadata("line-comment-start").map(|token| token.to_string());
+        if let (Some(start), Some(end)) = (
+            language.metadata("block-comment-start"),
+            language.metadata("block-comment-end"),
+        ) {
+            tokens.block = Some((start.to_string(), end.to_string()));
+        }
+    }
+
+    if tokens.line.is_none() || tokens.block.is_none() {
+        let settings = crate::settings::get_settings();
+        let key = crate::syntax::language_key(buffer);
+
+        if tokens.line.is_none() {
+            tokens.line = settings
+                .get(&format!("comment.line.{}", key))
+                .filter(|token| !token.is_empty())
+                .cloned();
+        }
+        if tokens.block.is_none() {
+            tokens.block = settings
+                .get(&format!("comment.block.{}", key))
+                .and_then(|value| value.split_once(' '))
+                .map(|(start, end)| (start.trim().to_string(), end.trim().to_string()))
+                .filter(|(start, end)| !start.is_empty() && !end.is_empty());
+        }
+    }
+
+    tokens
+}
+
+/// Adds the comment commands and their shortcuts (Ctrl+/ and Ctrl+Shift+/ by default) to a source view
+pub fn attach(view: &View) {
+    // Named actions so the commands can be rebound in keybindings.conf
+    let action_group = gio::SimpleActionGroup::new();
+    let commands: [(&str, fn(&View)); 2] = [("toggle-line", toggle_line_comment), ("toggle-block", toggle_block_comment)];
+    for (name, command) in commands {
+        let action = gio::SimpleAction::new(name, None);
+        let view = view.clone();
+        action.connect_activate(move |_, _| {
+            if view.is_editable() && !crate::multi_cursor::has_extra_carets(&view) {
+                crate::macros::record_action(&format!("comments.{}", name));
+                command(&view);
+            }
+        });
+        action_group.add_action(&action);
+    }
+    view.insert_action_group("comments", Some(&action_group));
+}
+
+/// Toggles line comments on the lines of the selection, or the cursor line
+///
+/// When every non-blank line is already commented the comments are removed,
+/// otherwise the token is inserted at the smallest indentation of the lines.
+/// Languages without a line comment token get a block comment around the lines.
+pub fn toggle_line_comment(view: &View) {
+    let Ok(buffer) = view.buffer().downcast::<Buffer>() else {
+        return;
+    };
+    let tokens = comment_tokens(&buffer);
+    let Some(token) = tokens.line else {
+        if tokens.block.is_some() {
+            toggle_block_comment(view);
+        } else {
+            println!("No comment syntax known for '{}'", crate::syntax::language_key(&buffer));
+        }
+        return;
+    };
+
+    let (first_line, last_line) = selected_lines(&buffer);
+    let lines: Vec<(i32, String)> = (first_line..=last_line)
+        .map(|line| (line, line_text(&buffer, line)))
+        .filter(|(_, text)| !text.trim().is_empty())
+        .collect();
+    if lines.is_empty() {
+        return;
+    }
+
+    let all_commented = lines.iter().all(|(_, text)| text.trim_start().starts_with(token.as_str()));
+    let had_selection = buffer.has_selection();
+
+    buffer.begin_user_action();
+    if all_commented {
+        for (line, text) in &lines {
+            let indent = leading_whitespace_chars(text);
+            let after_token: String = text.chars().skip(indent + token.chars().count()).take(1).collect();
+            let remove = token.chars().count() + usize::from(after_token == " ");
+
+            let mut start = line_iter(&buffer, *line, indent);
+            let mut end = line_iter(&buffer, *line, indent + remove);
+            buffer.delete(&mut start, &mut end);
+        }
+    } else {
+        let indent = lines.iter().map(|(_, text)| leading_whitespace_chars(text)).min().unwrap_or(0);
+        let insertion = format!("{} ", token);
+        for (line, _) in &lines {
+            let mut position = line_iter(&buffer, *line, indent);
+            buffer.insert(&mut position, &insertion);
+        }
+    }
+    buffer.end_user_action();
+
+    // Keep whole lines selected so the command can be repeated
+    if had_selection {
+        select_lines(&buffer, first_line, last_line);
+    }
+}
+
+/// Toggles a block comment around the selection, or around the text of the cursor line
+///
+/// An existing block comment is removed when the selection (ignoring surrounding
+/// whitespace) starts and ends with the tokens, or sits right between them.
+/// Languages without block comments get line comments instead.
+pub fn toggle_block_comment(view: &View) {
+    let Ok(buffer) = view.buffer().downcast::<Buffer>() else {
+        return;
+    };
+    let tokens = comment_tokens(&buffer);
+    let Some((open, close)) = tokens.block else {
+        if tokens.line.is_some() {
+            toggle_line_comment(view);
+        } else {
+            println!("No comment syntax known for '{}'", crate::syntax::language_key(&buffer));
+        }
+        return;
+    };
+
+    let had_selection = buffer.has_selection();
+    let (start, end) = match buffer.selection_bounds() {
+        Some(bounds) => bounds,
+        None => {
+            // The text of the cursor line without its indentation
+            let line = buffer.iter_at_mark(&buffer.get_insert()).line();
+            let text = line_text(&buffer, line);
+            if text.trim().is_empty() {
+                return;
+            }
+            let first = leading_whitespace_chars(&text);
+            let last = text.chars().count() - text.chars().rev().take_while(|c| c.is_whitespace()).count();
+            (line_iter(&buffer, line, first), line_iter(&buffer, line, last))
+        }
+    };
+    let (start_offset, end_offset) = (start.offset(), end.offset());
+    let open_len = open.chars().count() as i32;
+    let close_len = close.chars().count() as i32;
+
+    // Range of the text without surrounding whitespace
+    let text = buffer.text(&start, &end, true).to_string();
+    let leading = text.chars().take_while(|c| c.is_whitespace()).count() as i32;
+    let trailing = text.chars().rev().take_while(|c| c.is_whitespace()).count() as i32;
+    let inner_start = start_offset + leading;
+    let inner_end = (end_offset - trailing).max(inner_start);
+    let trimmed = text.trim();
+
+    buffer.begin_user_action();
+    let (new_start, new_end) = if trimmed.len() >= open.len() + close.len()
+        && trimmed.starts_with(open.as_str())
+        && trimmed.ends_with(close.as_str())
+    {
+        // The selection is a comment: remove its tokens
+        delete_range(&buffer, inner_end - close_len, inner_end);
+        delete_range(&buffer, inner_start, inner_start + open_len);
+        (start_offset, end_offset - open_len - close_len)
+    } else if text_at(&buffer, start_offset - open_len, start_offset) == open
+        && text_at(&buffer, end_offset, end_offset + close_len) == close
+    {
+        // The selection is the inside of a comment: remove the tokens around it
+        delete_range(&buffer, end_offset, end_offset + close_len);
+        delete_range(&buffer, start_offset - open_len, start_offset);
+        (start_offset - open_len, end_offset - open_len)
+    } else {
+        // Pad the tokens with a space unless the comment spans whole lines
+        let multiline = trimmed.contains('\n');
+        let open_text = if multiline { open.clone() } else { format!("{} ", open) };
+        let close_text = if multiline { close.clone() } else { format!(" {}", close) };
+
+        let mut end_iter = buffer.iter_at_offset(inner_end);
+        buffer.insert(&mut end_iter, &close_text);
+        let mut start_iter = buffer.iter_at_offset(inner_start);
+        buffer.insert(&mut start_iter, &open_text);
+        (
+            start_offset,
+            end_offset + open_text.chars().count() as i32 + close_text.chars().count() as i32,
+        )
+    };
+    buffer.end_user_action();
+
+    if had_selection {
+        buffer.select_range(&buffer.iter_at_offset(new_start), &buffer.iter_at_offset(new_end));
+    }
+}
+
+/// Selects whole lines, from the start of `first_line` to the end of `last_line`
+fn select_lines(buffer: &TextBuffer, first_line: i32, last_line: i32) {
+    let start = buffer.iter_at_line(first_line).unwrap_or_else(|| buffer.start_iter());
+    let mut end = buffer.iter_at_line(last_line).unwrap_or_else(|| buffer.end_iter());
+    if !end.ends_line() {
+        end.forward_to_line_end();
+    }
+    buffer.select_range(&start, &end);
+}
+
+/// Returns the text of a line without its line break
+fn line_text(buffer: &TextBuffer, line: i32) -> String {
+    let Some(start) = buffer.iter_at_line(line) else {
+        return String::new();
+    };
+    let mut end = start.clone();
+    if !end.ends_line() {
+        end.forward_to_line_end();
+    }
+    buffer.text(&start, &end, true).to_string()
+}
+
+/// Returns an iterator at a character offset within a line
+fn line_iter(buffer: &TextBuffer, line: i32, offset: usize) -> TextIter {
+    let mut iter = buffer.iter_at_line(line).unwrap_or_else(|| buffer.end_iter());
+    iter.forward_chars(offset as i32);
+    iter
+}
+
+/// Counts the whitespace characters at the start of a line
+fn leading_whitespace_chars(text: &str) -> usize {
+    text.chars().take_while(|c| *c == ' ' || *c == '\t').count()
+}
+
+/// Returns the buffer text between two character offsets (empty when out of range)
+fn text_at(buffer: &TextBuffer, start: i32, end: i32) -> String {
+    if start < 0 || end > buffer.char_count() || start > end {
+        return String::new();
+    }
+    buffer
+        .text(&buffer.iter_at_offset(start), &buffer.iter_at_offset(end), false)
+        .to_string()
+}
+
+/// Deletes the text between two character offsets
+fn delete_range(buffer: &TextBuffer, start: i32, end: i32) {
+    let mut start_iter = buffer.iter_at_offset(start);
+    let mut end_iter = buffer.iter_at_offset(end);
+    buffer.delete(&mut start_iter, &mut end_iter);
+}
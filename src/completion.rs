@@ -0,0 +1,505 @@
+// Code completion for the text editor
+// Word, filesystem path, language keyword and snippet providers for every source view
+
+use gtk4::prelude::*;
+use gtk4::{gdk, gio, glib, EventControllerKey, Notebook, TextBuffer, TextIter};
+use sourceview5::subclass::prelude::*;
+use sourceview5::{
+    prelude::*, Buffer, CompletionActivation, CompletionCell, CompletionColumn, CompletionContext,
+    CompletionProposal, CompletionProvider, CompletionWords, LanguageManager, View,
+};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::rc::Rc;
+
+use crate::utils::ObjectState;
+
+/// Whether a view's interactive completion is blocked (manual mode)
+const MANUAL_COMPLETION: ObjectState<bool> = ObjectState::new("manual-completion");
+
+/// Keyword provider of a view (one per view since keywords depend on the language)
+const KEYWORD_PROVIDER: ObjectState<CompletionWords> = ObjectState::new("keyword-completion-provider");
+
+/// Most entries offered by the path provider for one directory
+const MAX_PATH_PROPOSALS: usize = 200;
+
+// Providers shared by every view
+thread_local! {
+    // Words from all open buffers; buffers are registered as tabs are added
+    static WORDS_PROVIDER: CompletionWords = {
+        let words = CompletionWords::new(Some("Words"));
+        words.set_priority(10);
+        words
+    };
+    static PATH_PROVIDER: PathProvider = PathProvider::new();
+    static SNIPPET_PROVIDER: crate::snippets::SnippetProvider = crate::snippets::SnippetProvider::new();
+}
+
+thread_local! {
+    // Keywords read from each language definition, by language id
+    static LANGUAGE_KEYWORDS: RefCell<HashMap<String, Rc<Vec<String>>>> = RefCell::new(HashMap::new());
+}
+
+/// `<keyword>` entries of a language definition
+static KEYWORD_ELEMENT: Lazy<Regex> = Lazy::new(|| Regex::new(r"<keyword>\s*([^<]*?)\s*</keyword>").unwrap());
+
+/// Contexts a language definition borrows from another one, like `ref="c:keywords"`
+static LANGUAGE_REFERENCE: Lazy<Regex> = Lazy::new(|| Regex::new(r#"ref="([\w-]+):"#).unwrap());
+
+/// Returns the keywords of a language (empty for unknown languages)
+///
+/// The keywords come from the `<keyword>` entries of the language's sourceview
+/// definition and of the definitions it refers to (C for C++, for example). Entries
+/// that are regular expressions rather than plain words are left out.
+pub fn language_keywords(language_id: &str) -> Rc<Vec<String>> {
+    if let Some(keywords) = LANGUAGE_KEYWORDS.with(|cache| cache.borrow().get(language_id).cloned()) {
+        return keywords;
+    }
+
+    let mut keywords = BTreeSet::new();
+    let mut pending = vec![language_id.to_string()];
+    let mut visited = HashSet::new();
+    while let Some(id) = pending.pop() {
+        if !visited.insert(id.clone()) {
+            continue;
+        }
+        let Some(definition) = language_definition_path(&id).and_then(|path| fs::read_to_string(path).ok()) else {
+            continue;
+        };
+        for captures in KEYWORD_ELEMENT.captures_iter(&definition) {
+            let keyword = &captures[1];
+            let mut characters = keyword.chars();
+            let is_word = characters.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+                && characters.all(|c| c.is_alphanumeric() || c == '_');
+            if is_word {
+                keywords.insert(keyword.to_string());
+            }
+        }
+        pending.extend(LANGUAGE_REFERENCE.captures_iter(&definition).map(|captures| captures[1].to_string()));
+    }
+
+    let keywords = Rc::new(keywords.into_iter().collect::<Vec<_>>());
+    LANGUAGE_KEYWORDS.with(|cache| cache.borrow_mut().insert(language_id.to_string(), keywords.clone()));
+    keywords
+}
+
+/// Finds the `.lang` file defining a language in sourceview's search path
+///
+/// Most files are named after the language id; the others are found by the id
+/// in their `<language>` element.
+fn language_definition_path(language_id: &str) -> Option<PathBuf> {
+    let directories: Vec<PathBuf> = LanguageManager::new()
+        .search_path()
+        .iter()
+        .map(|directory| PathBuf::from(directory.as_str()))
+        .collect();
+
+    let file_name = format!("{}.lang", language_id);
+    if let Some(path) = directories.iter().map(|directory| directory.join(&file_name)).find(|path| path.is_file()) {
+        return Some(path);
+    }
+
+    let id_attribute = format!("id=\"{}\"", language_id);
+    directories
+        .iter()
+        .filter_map(|directory| fs::read_dir(directory).ok())
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "lang"))
+        .find(|path| {
+            fs::read_to_string(path).is_ok_and(|definition| {
+                definition
+                    .lines()
+                    .find(|line| line.contains("<language "))
+                    .is_some_and(|line| line.contains(&id_attribute))
+            })
+        })
+}
+
+/// Adds completion to a source view
+///
+/// The view gets the shared word and path providers and its own keyword provider
+/// (a CompletionWords reading a hidden buffer filled with the keywords of the
+/// view's language). Ctrl+Space shows completion on demand.
+pub fn attach(view: &View) {
+    let buffer = view.buffer();
+
+    // Keywords follow the buffer's language
+    let keyword_buffer = TextBuffer::new(None);
+    let keywords_provider = CompletionWords::new(Some("Keywords"));
+    keywords_provider.set_priority(20);
+    keywords_provider.register(&keyword_buffer);
+    if let Some(source_buffer) = buffer.downcast_ref::<Buffer>() {
+        fill_keyword_buffer(&keyword_buffer, source_buffer);
+        let keyword_buffer_clone = keyword_buffer.clone();
+        source_buffer.connect_language_notify(move |source_buffer| {
+            fill_keyword_buffer(&keyword_buffer_clone, source_buffer);
+        });
+    }
+    // Keep the view's keyword provider so settings changes can switch it
+    KEYWORD_PROVIDER.set(view, keywords_provider);
+
+    let completion = view.completion();
+    completion.set_select_on_show(true);
+    completion.set_remember_info_visibility(true);
+
+    // Ctrl+Space shows completion even below the automatic character count
+    let key_controller = EventControllerKey::new();
+    key_controller.set_propagation_phase(gtk4::PropagationPhase::Capture);
+    let view_clone = view.clone();
+    key_controller.connect_key_pressed(move |_, keyval, _, state| {
+        let ctrl = state.contains(gdk::ModifierType::CONTROL_MASK);
+        let alt = state.contains(gdk::ModifierType::ALT_MASK);
+        if ctrl && !alt && keyval == gdk::Key::space {
+            view_clone.completion().show();
+            return glib::Propagation::Stop;
+        }
+        glib::Propagation::Proceed
+    });
+    view.add_controller(key_controller);
+
+    apply_completion_settings(view);
+}
+
+/// Fills the hidden keyword buffer with the keywords of a buffer's language
+fn fill_keyword_buffer(keyword_buffer: &TextBuffer, buffer: &Buffer) {
+    let language_id = buffer.language().map(|language| language.id().to_string()).unwrap_or_default();
+    keyword_buffer.set_text(&language_keywords(&language_id).join("\n"));
+}
+
+/// Applies the completion settings to a view
+///
+/// Settings: `completion.words`, `completion.paths`, `completion.keywords` and
+/// `completion.snippets` switch the providers, and `completion.min_chars` is the word length at which completion
+/// appears while typing (0 = only with Ctrl+Space). Called when a view is created
+/// and when settings change.
+pub fn apply_completion_settings(view: &View) {
+    let settings = crate::settings::get_settings();
+    let min_chars = settings
+        .get("completion.min_chars")
+        .and_then(|value| value.parse::<u32>().ok())
+        .unwrap_or(3);
+    let completion = view.completion();
+
+    let words = WORDS_PROVIDER.with(|words| words.clone());
+    let paths = PATH_PROVIDER.with(|paths| paths.clone());
+    let snippets = SNIPPET_PROVIDER.with(|snippets| snippets.clone());
+    let keywords = KEYWORD_PROVIDER.get(view);
+
+    let mut providers: Vec<(CompletionProvider, bool)> = vec![
+        (words.clone().upcast(), settings.get_bool("completion.words", true)),
+        (paths.upcast(), settings.get_bool("completion.paths", true)),
+        (snippets.upcast(), settings.get_bool("completion.snippets", true)),
+    ];
+    if let Some(keywords) = &keywords {
+        providers.push((keywords.clone().upcast(), settings.get_bool("completion.keywords", true)));
+    }
+    for (provider, enabled) in providers {
+        // Removing first avoids adding a provider twice
+        completion.remove_provider(&provider);
+        if enabled {
+            completion.add_provider(&provider);
+        }
+    }
+
+    // Word providers only appear on their own once the word is long enough
+    let minimum_word_size = min_chars.max(1);
+    words.set_minimum_word_size(minimum_word_size);
+    if let Some(keywords) = keywords {
+        keywords.set_minimum_word_size(minimum_word_size);
+    }
+
+    // A count of 0 turns off completion while typing
+    // (blocking nests, so it is only changed when switching modes)
+    let manual = min_chars == 0;
+    let was_manual = MANUAL_COMPLETION.get(view).unwrap_or(false);
+    if manual && !was_manual {
+        completion.block_interactive();
+    } else if !manual && was_manual {
+        completion.unblock_interactive();
+    }
+    MANUAL_COMPLETION.set(view, manual);
+}
+
+/// Registers the buffers of all text tabs with the shared word provider
+///
+/// Tabs added later are registered as they appear and unregistered when closed.
+pub fn connect_notebook(notebook: &Notebook) {
+    let words = WORDS_PROVIDER.with(|words| words.clone());
+
+    for page_num in 0..notebook.n_pages() {
+        if let Some(page) = notebook.nth_page(Some(page_num)) {
+            if let Some(text_view) = crate::handlers::find_text_view_in_page(&page) {
+                words.register(&text_view.buffer());
+            }
+        }
+    }
+
+    let words_for_added = words.clone();
+    notebook.connect_page_added(move |_, page, _| {
+        if let Some(text_view) = crate::handlers::find_text_view_in_page(page) {
+            words_for_added.register(&text_view.buffer());
+        }
+    });
+    notebook.connect_page_removed(move |_, page, _| {
+        if let Some(text_view) = crate::handlers::find_text_view_in_page(page) {
+            words.unregister(&text_view.buffer());
+        }
+    });
+}
+
+/// The path typed before the cursor inside a string literal
+///
+/// `typed` is the whole path so far (e.g. "../src/ma"), and `name_start` is where
+/// its last component ("ma") starts in the buffer.
+struct TypedPath {
+    typed: String,
+    name_start: TextIter,
+}
+
+/// Finds the path being typed at the cursor, if the cursor is inside a string literal
+fn typed_path_at_cursor(buffer: &TextBuffer) -> Option<TypedPath> {
+    let cursor = buffer.iter_at_mark(&buffer.get_insert());
+    let mut start = cursor.clone();
+    while start.backward_char() {
+        let character = start.char();
+        if character.is_whitespace() || matches!(character, '"' | '\'' | '`' | '(' | '<' | '=') {
+            start.forward_char();
+            break;
+        }
+    }
+
+    if !is_inside_string(buffer, &cursor) {
+        return None;
+    }
+
+    let typed = buffer.text(&start, &cursor, false).to_string();
+    let mut name_start = cursor.clone();
+    let name_length = typed.rsplit('/').next().map(|name| name.chars().count()).unwrap_or(0);
+    name_start.backward_chars(name_length as i32);
+    Some(TypedPath { typed, name_start })
+}
+
+/// Checks whether the position just before `iter` is inside a string literal
+///
+/// Uses syntax highlighting when available, otherwise counts the quotes before the
+/// cursor on its line.
+fn is_inside_string(buffer: &TextBuffer, iter: &TextIter) -> bool {
+    let mut before = iter.clone();
+    if !before.backward_char() {
+        return false;
+    }
+    if let Some(source_buffer) = buffer.downcast_ref::<Buffer>() {
+        if source_buffer.language().is_some() {
+            return source_buffer.iter_has_context_class(&before, "string");
+        }
+    }
+
+    let mut line_start = iter.clone();
+    line_start.set_line_offset(0);
+    let line_before = buffer.text(&line_start, iter, false);
+    ['"', '\''].iter().any(|quote| line_before.matches(*quote).count() % 2 == 1)
+}
+
+/// Lists the entries of the directory a typed path points into
+///
+/// Relative paths are resolved against the directory of the buffer's file, or the
+/// working directory for untitled buffers. Hidden entries are only listed when the
+/// typed name starts with a dot. Directories come first.
+fn path_proposals(buffer: &TextBuffer, typed: &str) -> Vec<PathProposal> {
+    let (directory_part, name_prefix) = match typed.rfind('/') {
+        Some(index) => (&typed[..=index], &typed[index + 1..]),
+        None => ("", typed),
+    };
+
+    let base_dir = buffer
+        .downcast_ref::<Buffer>()
+        .and_then(crate::syntax::buffer_file_path)
+        .and_then(|path| path.parent().map(Path::to_path_buf))
+        .or_else(|| std::env::current_dir().ok())
+        .unwrap_or_else(|| PathBuf::from("."));
+    let directory = if directory_part.starts_with('/') {
+        PathBuf::from(directory_part)
+    } else if let Some(rest) = directory_part.strip_prefix("~/") {
+        home::home_dir().unwrap_or_default().join(rest)
+    } else {
+        base_dir.join(directory_part)
+    };
+
+    let Ok(entries) = std::fs::read_dir(&directory) else {
+        return Vec::new();
+    };
+    let mut found: Vec<(String, bool)> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+            (name.starts_with(name_prefix) && (!name.starts_with('.') || name_prefix.starts_with('.')))
+                .then_some((name, is_dir))
+        })
+        .collect();
+    found.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.to_lowercase().cmp(&b.0.to_lowercase())));
+    found.truncate(MAX_PATH_PROPOSALS);
+
+    found.into_iter().map(|(name, is_dir)| PathProposal::new(&name, is_dir)).collect()
+}
+
+mod imp {
+    use super::*;
+
+    /// One file or directory offered by the path provider
+    #[derive(Default)]
+    pub struct PathProposal {
+        pub name: RefCell<String>,
+        pub is_dir: Cell<bool>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for PathProposal {
+        const NAME: &'static str = "BasadoPathProposal";
+        type Type = super::PathProposal;
+        type ParentType = glib::Object;
+        type Interfaces = (CompletionProposal,);
+    }
+
+    impl ObjectImpl for PathProposal {}
+    impl CompletionProposalImpl for PathProposal {}
+
+    /// Completes file and directory names inside string literals
+    #[derive(Default)]
+    pub struct PathProvider;
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for PathProvider {
+        const NAME: &'static str = "BasadoPathCompletionProvider";
+        type Type = super::PathProvider;
+        type ParentType = glib::Object;
+        type Interfaces = (CompletionProvider,);
+    }
+
+    impl ObjectImpl for PathProvider {}
+
+    impl CompletionProviderImpl for PathProvider {
+        fn title(&self) -> Option<glib::GString> {
+            Some("Paths".into())
+        }
+
+        fn is_trigger(&self, iter: &TextIter, c: char) -> bool {
+            c == '/' && is_inside_string(&iter.buffer(), iter)
+        }
+
+        fn populate_future(
+            &self,
+            context: &CompletionContext,
+        ) -> Pin<Box<dyn Future<Output = Result<gio::ListModel, glib::Error>> + 'static>> {
+            let store = gio::ListStore::new::<super::PathProposal>();
+
+            if let Some(buffer) = context.buffer() {
+                let buffer = buffer.upcast::<TextBuffer>();
+                if let Some(path) = typed_path_at_cursor(&buffer) {
+                    // While typing, only offer paths once something path-like was typed
+                    let interactive = context.activation() == CompletionActivation::Interactive;
+                    if !interactive || path.typed.contains('/') || path.typed.starts_with('.') {
+                        store.extend_from_slice(&path_proposals(&buffer, &path.typed));
+                    }
+                }
+            }
+
+            Box::pin(async move { Ok(store.upcast::<gio::ListModel>()) })
+        }
+
+        fn refilter(&self, context: &CompletionContext, model: &gio::ListModel) {
+            let (Some(buffer), Some(store)) = (context.buffer(), model.downcast_ref::<gio::ListStore>()) else {
+                return;
+            };
+            let Some(path) = typed_path_at_cursor(buffer.upcast_ref()) else {
+                store.remove_all();
+                return;
+            };
+            let prefix = path.typed.rsplit('/').next().unwrap_or("").to_string();
+            store.retain(|item| {
+                item.downcast_ref::<super::PathProposal>()
+                    .is_some_and(|proposal| proposal.name().starts_with(&prefix))
+            });
+        }
+
+        fn display(&self, _context: &CompletionContext, proposal: &CompletionProposal, cell: &CompletionCell) {
+            let Some(proposal) = proposal.downcast_ref::<super::PathProposal>() else {
+                return;
+            };
+            match cell.column() {
+                CompletionColumn::Icon => {
+                    cell.set_icon_name(if proposal.is_dir() { "folder-symbolic" } else { "text-x-generic-symbolic" });
+                }
+                CompletionColumn::TypedText => cell.set_text(Some(&proposal.completion_text())),
+                _ => cell.set_text(None),
+            }
+        }
+
+        fn activate(&self, context: &CompletionContext, proposal: &CompletionProposal) {
+            let (Some(buffer), Some(proposal)) = (context.buffer(), proposal.downcast_ref::<super::PathProposal>()) else {
+                return;
+            };
+            let buffer = buffer.upcast::<TextBuffer>();
+            let Some(path) = typed_path_at_cursor(&buffer) else {
+                return;
+            };
+
+            let mut start = path.name_start.clone();
+            let mut end = buffer.iter_at_mark(&buffer.get_insert());
+            buffer.begin_user_action();
+            buffer.delete(&mut start, &mut end);
+            buffer.insert(&mut start, &proposal.completion_text());
+            buffer.end_user_action();
+        }
+    }
+}
+
+glib::wrapper! {
+    /// A file or directory offered by the path provider
+    pub struct PathProposal(ObjectSubclass<imp::PathProposal>) @implements CompletionProposal;
+}
+
+impl PathProposal {
+    fn new(name: &str, is_dir: bool) -> Self {
+        let proposal: Self = glib::Object::new();
+        proposal.imp().name.replace(name.to_string());
+        proposal.imp().is_dir.set(is_dir);
+        proposal
+    }
+
+    fn name(&self) -> String {
+        self.imp().name.borrow().clone()
+    }
+
+    fn is_dir(&self) -> bool {
+        self.imp().is_dir.get()
+    }
+
+    /// Text inserted for the proposal (directories get a trailing slash)
+    fn completion_text(&self) -> String {
+        if self.is_dir() {
+            format!("{}/", self.name())
+        } else {
+            self.name()
+        }
+    }
+}
+
+glib::wrapper! {
+    /// Completion provider for file and directory names inside string literals
+    pub struct PathProvider(ObjectSubclass<imp::PathProvider>) @implements CompletionProvider;
+}
+
+impl PathProvider {
+    fn new() -> Self {
+        glib::Object::new()
+    }
+}
@@ -0,0 +1,603 @@
+// Hunspell dictionaries for the spell checker
+// Reads the .aff/.dic pairs installed on the system and checks words against their stems and affix rules
+
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Maximum number of suggestions offered for a misspelled word
+const MAX_SUGGESTIONS: usize = 8;
+
+/// Maximum number of candidates tried two edits away from a misspelled word
+const MAX_SECOND_EDITS: usize = 100_000;
+
+/// Options that make Hunspell join words into compounds
+const COMPOUND_FLAG_OPTIONS: &[&str] =
+    &["COMPOUNDFLAG", "COMPOUNDBEGIN", "COMPOUNDMIDDLE", "COMPOUNDEND", "COMPOUNDFIRST", "COMPOUNDLAST"];
+
+/// Letters tried by suggestions when the .aff file has no TRY line
+const DEFAULT_TRY: &str = "esianrtolcdugmphbyfvkwzESIANRTOLCDUGMPHBYFVKWZ'";
+
+/// An affix flag; long and numeric flags are packed into the same integer
+type Flag = u32;
+
+/// How flags are written in a dictionary (the .aff FLAG option)
+#[derive(Clone, Copy, PartialEq)]
+enum FlagType {
+    /// One character per flag (the default, also used for UTF-8)
+    Char,
+    /// Two characters per flag
+    Long,
+    /// Comma-separated decimal numbers
+    Numeric,
+}
+
+/// A prefix or suffix rule from the .aff file
+struct Affix {
+    flag: Flag,
+    /// Text removed from the stem before the affix is added
+    strip: String,
+    /// Text the affix adds
+    add: String,
+    /// Condition the stem must meet, anchored at the affix side
+    condition: Option<Regex>,
+    /// Whether the rule combines with affixes on the other side of the word
+    cross_product: bool,
+}
+
+/// A spelling dictionary loaded from a Hunspell .aff/.dic pair
+///
+/// Only a subset of the Hunspell format is read:
+/// - SET UTF-8 or ISO8859-1 (the default)
+/// - FLAG long and FLAG num, besides single character flags
+/// - PFX and SFX rules with their conditions and cross products
+/// - TRY, NEEDAFFIX and FORBIDDENWORD
+///
+/// Twofold affixes and the suggestion tables (REP, MAP, …) are ignored. Dictionaries
+/// that need more are rejected rather than misreported: flag aliases (AF), other
+/// encodings, and compounding of words. Compound rules that only join numbers, like
+/// en_US's ordinals, are allowed since words next to digits are never checked.
+pub struct Dictionary {
+    words: HashMap<String, Vec<Flag>>,
+    prefixes: Vec<Affix>,
+    suffixes: Vec<Affix>,
+    try_chars: Vec<char>,
+    need_affix: Option<Flag>,
+    forbidden: Option<Flag>,
+}
+
+/// Returns the directories searched for dictionaries, the editor's own first
+pub fn dictionary_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![crate::settings::get_config_dir().join("dictionaries")];
+    if let Some(home) = home::home_dir() {
+        dirs.push(home.join("Library").join("Spelling"));
+    }
+    for dir in ["/usr/share/hunspell", "/usr/share/myspell", "/usr/share/myspell/dicts", "/usr/local/share/hunspell"] {
+        dirs.push(PathBuf::from(dir));
+    }
+    dirs
+}
+
+/// Lists the languages with an installed dictionary ("en_US", "fr_FR", …), sorted
+pub fn available_languages() -> Vec<String> {
+    let mut languages: Vec<String> = Vec::new();
+    for dir in dictionary_dirs() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let path = entry.path();
+            if path.extension().is_some_and(|extension| extension == "dic") && path.with_extension("aff").exists() {
+                if let Some(language) = path.file_stem().map(|stem| stem.to_string_lossy().to_string()) {
+                    if !languages.contains(&language) {
+                        languages.push(language);
+                    }
+                }
+            }
+        }
+    }
+    languages.sort();
+    languages
+}
+
+/// Returns the language of the user's locale ("en_US" for LANG=en_US.UTF-8), if set
+pub fn locale_language() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .map(|value| value.split(['.', '@']).next().unwrap_or_default().to_string())
+        .find(|language| !language.is_empty() && language != "C" && language != "POSIX")
+}
+
+/// Decodes dictionary bytes in the encoding named by the .aff SET option
+///
+/// Only UTF-8 and ISO 8859-1 are supported; None for any other encoding.
+fn decode(bytes: &[u8], encoding: &str) -> Option<String> {
+    match encoding.to_uppercase().replace(['-', '_'], "").as_str() {
+        "UTF8" => Some(String::from_utf8_lossy(bytes).to_string()),
+        // Every ISO 8859-1 byte is the Unicode character of the same number
+        "ISO88591" => Some(bytes.iter().map(|&byte| byte as char).collect()),
+        _ => None,
+    }
+}
+
+/// Returns an error for a dictionary this reader can't handle
+fn unsupported(reason: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, reason)
+}
+
+/// Turns a Hunspell affix condition ("[^aeiou]y") into an anchored regular expression
+fn compile_condition(condition: &str, is_prefix: bool) -> Option<Regex> {
+    if condition == "." {
+        return None;
+    }
+    let mut pattern = String::new();
+    let mut in_class = false;
+    for character in condition.chars() {
+        match character {
+            '[' => {
+                in_class = true;
+                pattern.push('[');
+            }
+            ']' => {
+                in_class = false;
+                pattern.push(']');
+            }
+            '^' if in_class => pattern.push('^'),
+            '.' if !in_class => pattern.push('.'),
+            _ => pattern.push_str(&regex::escape(&character.to_string())),
+        }
+    }
+    let anchored = if is_prefix { format!("^(?:{})", pattern) } else { format!("(?:{})$", pattern) };
+    Regex::new(&anchored).ok()
+}
+
+impl Dictionary {
+    /// Loads the dictionary of a language from the first directory that has it
+    pub fn load(language: &str) -> Option<Dictionary> {
+        let dic_path = dictionary_dirs()
+            .into_iter()
+            .map(|dir| dir.join(format!("{}.dic", language)))
+            .find(|path| path.exists() && path.with_extension("aff").exists())?;
+        match Dictionary::from_files(&dic_path.with_extension("aff"), &dic_path) {
+            Ok(dictionary) => {
+                println!("Loaded {} dictionary from {}", language, dic_path.display());
+                Some(dictionary)
+            }
+            Err(e) => {
+                eprintln!("Failed to load dictionary {}: {}", dic_path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Reads a dictionary from its .aff and .dic files
+    pub fn from_files(aff_path: &Path, dic_path: &Path) -> std::io::Result<Dictionary> {
+        Dictionary::parse(&fs::read(aff_path)?, &fs::read(dic_path)?)
+    }
+
+    /// Reads a dictionary from the contents of its .aff and .dic files
+    ///
+    /// Fails for dictionaries outside the supported subset, see `Dictionary`.
+    fn parse(aff_bytes: &[u8], dic_bytes: &[u8]) -> std::io::Result<Dictionary> {
+        let encoding = String::from_utf8_lossy(aff_bytes)
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("SET ").map(|encoding| encoding.trim().to_string()))
+            .unwrap_or_else(|| "ISO8859-1".to_string());
+        let (Some(aff), Some(dic)) = (decode(aff_bytes, &encoding), decode(dic_bytes, &encoding)) else {
+            return Err(unsupported(format!("unsupported encoding {}", encoding)));
+        };
+
+        let mut dictionary = Dictionary {
+            words: HashMap::new(),
+            prefixes: Vec::new(),
+            suffixes: Vec::new(),
+            try_chars: DEFAULT_TRY.chars().collect(),
+            need_affix: None,
+            forbidden: None,
+        };
+
+        // Options first: the flag type decides how the affix rules are read
+        let mut flag_type = FlagType::Char;
+        for line in aff.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                ["FLAG", "long", ..] => flag_type = FlagType::Long,
+                ["FLAG", "num", ..] => flag_type = FlagType::Numeric,
+                // Flags would be alias numbers rather than the flags themselves
+                ["AF", ..] => return Err(unsupported("flag aliases (AF) are not supported".to_string())),
+                _ => {}
+            }
+        }
+        let parse_flag = |text: &str| Dictionary::parse_flags(text, flag_type).first().copied();
+
+        // Flags of the words that take part in compounds
+        let mut compound_flags: Vec<Flag> = Vec::new();
+        for line in aff.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                [option, flag, ..] if COMPOUND_FLAG_OPTIONS.contains(option) => compound_flags.extend(parse_flag(flag)),
+                ["COMPOUNDRULE", rule] if rule.parse::<usize>().is_err() => {
+                    compound_flags.extend(Dictionary::parse_compound_rule(rule, flag_type))
+                }
+                _ => {}
+            }
+        }
+
+        // Affix rules share their header's cross product setting
+        let mut cross_products: HashMap<(bool, Flag), bool> = HashMap::new();
+        for line in aff.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                ["TRY", chars, ..] => dictionary.try_chars = chars.chars().collect(),
+                ["NEEDAFFIX", flag, ..] => dictionary.need_affix = parse_flag(flag),
+                ["FORBIDDENWORD", flag, ..] => dictionary.forbidden = parse_flag(flag),
+                [kind @ ("PFX" | "SFX"), flag, cross, count]
+                    if (*cross == "Y" || *cross == "N") && count.parse::<usize>().is_ok() =>
+                {
+                    if let Some(flag) = parse_flag(flag) {
+                        cross_products.insert((*kind == "PFX", flag), *cross == "Y");
+                    }
+                }
+                [kind @ ("PFX" | "SFX"), flag, strip, add, rest @ ..] => {
+                    let is_prefix = *kind == "PFX";
+                    let Some(flag) = parse_flag(flag) else {
+                        continue;
+                    };
+                    let strip = if *strip == "0" { String::new() } else { strip.to_string() };
+                    // Continuation flags after '/' (twofold affixes) are not supported
+                    let add = add.split('/').next().unwrap_or_default();
+                    let add = if add == "0" { String::new() } else { add.to_string() };
+                    let affix = Affix {
+                        flag,
+                        strip,
+                        add,
+                        condition: compile_condition(rest.first().copied().unwrap_or("."), is_prefix),
+                        cross_product: cross_products.get(&(is_prefix, flag)).copied().unwrap_or(false),
+                    };
+                    if is_prefix {
+                        dictionary.prefixes.push(affix);
+                    } else {
+                        dictionary.suffixes.push(affix);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // The first line of the .dic file is the word count
+        for line in dic.lines().skip(1) {
+            let entry = line.split(['\t', ' ']).next().unwrap_or_default();
+            if entry.is_empty() {
+                continue;
+            }
+            let (word, flags) = match entry.split_once('/') {
+                Some((word, flags)) => (word, Dictionary::parse_flags(flags, flag_type)),
+                None => (entry, Vec::new()),
+            };
+            if !word.chars().any(|character| character.is_ascii_digit())
+                && flags.iter().any(|flag| compound_flags.contains(flag))
+            {
+                return Err(unsupported(format!("compound words (like {}) are not supported", word)));
+            }
+            dictionary.words.entry(word.to_string()).or_default().extend(flags);
+        }
+
+        Ok(dictionary)
+    }
+
+    /// Returns the flags a COMPOUNDRULE pattern ("n*1t" or "(aa)(bb)*") joins
+    fn parse_compound_rule(rule: &str, flag_type: FlagType) -> Vec<Flag> {
+        if flag_type == FlagType::Char {
+            return rule.chars().filter(|character| !"*?".contains(*character)).map(|character| character as Flag).collect();
+        }
+        rule.split(['(', ')'])
+            .filter(|part| !part.is_empty() && *part != "*" && *part != "?")
+            .flat_map(|part| Dictionary::parse_flags(part, flag_type))
+            .collect()
+    }
+
+    /// Splits a flag list ("ABC", "AaBb" or "12,34") into flags
+    fn parse_flags(text: &str, flag_type: FlagType) -> Vec<Flag> {
+        match flag_type {
+            FlagType::Char => text.chars().map(|character| character as Flag).collect(),
+            FlagType::Long => {
+                let chars: Vec<char> = text.chars().collect();
+                chars
+                    .chunks(2)
+                    .map(|pair| ((pair[0] as Flag) << 16) | pair.get(1).map_or(0, |&second| second as Flag))
+                    .collect()
+            }
+            FlagType::Numeric => text.split(',').filter_map(|number| number.trim().parse().ok()).collect(),
+        }
+    }
+
+    /// Checks whether a stem exists with every given flag
+    fn has_stem(&self, stem: &str, flags: &[Flag]) -> bool {
+        self.words.get(stem).is_some_and(|stem_flags| {
+            flags.iter().all(|flag| stem_flags.contains(flag))
+                && !self.forbidden.is_some_and(|forbidden| stem_flags.contains(&forbidden))
+        })
+    }
+
+    /// Checks a word in exactly this case, as a stem or with affixes
+    fn check_exact(&self, word: &str) -> bool {
+        if let Some(flags) = self.words.get(word) {
+            let blocked = [self.need_affix, self.forbidden].into_iter().flatten().any(|flag| flags.contains(&flag));
+            if !blocked {
+                return true;
+            }
+        }
+
+        // Suffix only
+        for suffix in &self.suffixes {
+            if let Some(stem) = self.strip_suffix(word, suffix) {
+                if self.has_stem(&stem, &[suffix.flag]) {
+                    return true;
+                }
+            }
+        }
+
+        // Prefix, optionally combined with a suffix
+        for prefix in &self.prefixes {
+            let Some(stem) = self.strip_prefix(word, prefix) else {
+                continue;
+            };
+            if self.has_stem(&stem, &[prefix.flag]) {
+                return true;
+            }
+            if !prefix.cross_product {
+                continue;
+            }
+            for suffix in self.suffixes.iter().filter(|suffix| suffix.cross_product) {
+                if let Some(inner) = self.strip_suffix(&stem, suffix) {
+                    if self.has_stem(&inner, &[prefix.flag, suffix.flag]) {
+                        return true;
+                    }
+                }
+            }
+        }
+        false
+    }
+
+    /// Undoes a suffix rule on a word, returning the stem if the rule applies
+    fn strip_suffix(&self, word: &str, suffix: &Affix) -> Option<String> {
+        let base = word.strip_suffix(suffix.add.as_str())?;
+        if base.is_empty() && suffix.strip.is_empty() {
+            return None;
+        }
+        let stem = format!("{}{}", base, suffix.strip);
+        suffix.condition.as_ref().is_none_or(|condition| condition.is_match(&stem)).then_some(stem)
+    }
+
+    /// Undoes a prefix rule on a word, returning the stem if the rule applies
+    fn strip_prefix(&self, word: &str, prefix: &Affix) -> Option<String> {
+        let base = word.strip_prefix(prefix.add.as_str())?;
+        if base.is_empty() && prefix.strip.is_empty() {
+            return None;
+        }
+        let stem = format!("{}{}", prefix.strip, base);
+        prefix.condition.as_ref().is_none_or(|condition| condition.is_match(&stem)).then_some(stem)
+    }
+
+    /// Checks a word, accepting capitalized and all-caps forms of lowercase entries
+    pub fn check(&self, word: &str) -> bool {
+        if self.check_exact(word) {
+            return true;
+        }
+        let lowercase = word.to_lowercase();
+        if lowercase == word {
+            return false;
+        }
+        let is_all_caps = word.chars().all(|character| !character.is_lowercase());
+        let is_capitalized = word.chars().skip(1).all(|character| !character.is_uppercase());
+        if is_capitalized || is_all_caps {
+            if self.check_exact(&lowercase) {
+                return true;
+            }
+            if is_all_caps && self.check_exact(&capitalize(&lowercase)) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Suggests corrections for a misspelled word, closest first
+    ///
+    /// Tries every single edit (a missing, extra, swapped or wrong letter, or a
+    /// missing space), then up to `MAX_SECOND_EDITS` words two edits away if none
+    /// of those is a word. Slow for long words, so it is run off the main thread.
+    pub fn suggest(&self, word: &str, accepts: impl Fn(&str) -> bool) -> Vec<String> {
+        let is_capitalized = word.chars().next().is_some_and(|first| first.is_uppercase());
+        let lowercase = word.to_lowercase();
+        let mut suggestions: Vec<String> = Vec::new();
+        let mut seen: HashSet<String> = HashSet::new();
+
+        let mut consider = |candidate: String, suggestions: &mut Vec<String>| {
+            if suggestions.len() >= MAX_SUGGESTIONS || !seen.insert(candidate.clone()) {
+                return;
+            }
+            let valid = match candidate.split_once(' ') {
+                Some((first, second)) => self.check(first) && self.check(second),
+                None => self.check(&candidate) || accepts(&candidate),
+            };
+            if valid {
+                suggestions.push(if is_capitalized { capitalize(&candidate) } else { candidate });
+            }
+        };
+
+        let first_edits = self.edits(&lowercase);
+        for candidate in first_edits.iter().cloned() {
+            consider(candidate, &mut suggestions);
+        }
+        if suggestions.is_empty() && lowercase.chars().count() <= 12 {
+            let mut tried = 0;
+            for edit in first_edits.iter().filter(|edit| !edit.contains(' ')) {
+                for candidate in self.edits(edit) {
+                    if !candidate.contains(' ') {
+                        consider(candidate, &mut suggestions);
+                        tried += 1;
+                    }
+                }
+                if suggestions.len() >= MAX_SUGGESTIONS || tried >= MAX_SECOND_EDITS {
+                    break;
+                }
+            }
+        }
+        suggestions
+    }
+
+    /// Lists the words one edit away from a word, most likely mistakes first
+    fn edits(&self, word: &str) -> Vec<String> {
+        let chars: Vec<char> = word.chars().collect();
+        let to_string = |chars: &[char]| chars.iter().collect::<String>();
+        let mut edits = Vec::new();
+
+        // Swapped neighbours
+        for index in 0..chars.len().saturating_sub(1) {
+            let mut swapped = chars.clone();
+            swapped.swap(index, index + 1);
+            edits.push(to_string(&swapped));
+        }
+        // Wrong letter
+        for index in 0..chars.len() {
+            for &replacement in &self.try_chars {
+                if replacement != chars[index] && !replacement.is_uppercase() {
+                    let mut replaced = chars.clone();
+                    replaced[index] = replacement;
+                    edits.push(to_string(&replaced));
+                }
+            }
+        }
+        // Extra letter
+        for index in 0..chars.len() {
+            let mut removed = chars.clone();
+            removed.remove(index);
+            edits.push(to_string(&removed));
+        }
+        // Missing letter
+        for index in 0..=chars.len() {
+            for &inserted in &self.try_chars {
+                if !inserted.is_uppercase() {
+                    let mut extended = chars.clone();
+                    extended.insert(index, inserted);
+                    edits.push(to_string(&extended));
+                }
+            }
+        }
+        // Missing space
+        for index in 1..chars.len() {
+            edits.push(format!("{} {}", to_string(&chars[..index]), to_string(&chars[index..])));
+        }
+        edits
+    }
+}
+
+/// Uppercases the first letter of a word
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AFF: &str = "SET UTF-8
+TRY esianrtolcdugmphbyfvkwz
+NEEDAFFIX X
+FORBIDDENWORD F
+PFX U Y 1
+PFX U 0 un .
+SFX S Y 2
+SFX S y ies [^aeiou]y
+SFX S 0 s [aeiou]y
+SFX D Y 1
+SFX D 0 d e
+";
+
+    const DIC: &str = "6
+fly/S
+day/S
+tie/UD
+hello
+wor/XS
+hellos/F
+";
+
+    fn parse(aff: &str, dic: &str) -> std::io::Result<Dictionary> {
+        Dictionary::parse(aff.as_bytes(), dic.as_bytes())
+    }
+
+    #[test]
+    fn suffix_conditions_pick_the_rule() {
+        let dictionary = parse(AFF, DIC).unwrap();
+        assert!(dictionary.check("flies"));
+        assert!(dictionary.check("days"));
+        assert!(!dictionary.check("flys"));
+        assert!(!dictionary.check("daies"));
+    }
+
+    #[test]
+    fn prefixes_combine_with_suffixes() {
+        let dictionary = parse(AFF, DIC).unwrap();
+        assert!(dictionary.check("untie"));
+        assert!(dictionary.check("tied"));
+        assert!(dictionary.check("untied"));
+        assert!(!dictionary.check("unfly"));
+    }
+
+    #[test]
+    fn capitals_and_special_flags() {
+        let dictionary = parse(AFF, DIC).unwrap();
+        assert!(dictionary.check("Hello"));
+        assert!(dictionary.check("HELLO"));
+        assert!(!dictionary.check("hELLO"));
+        assert!(!dictionary.check("helo"));
+        // NEEDAFFIX stems only count with an affix, FORBIDDENWORD entries never
+        assert!(!dictionary.check("wor"));
+        assert!(!dictionary.check("hellos"));
+    }
+
+    #[test]
+    fn long_and_numeric_flags() {
+        let long = parse("FLAG long\nSFX Aa Y 1\nSFX Aa 0 s .\n", "1\ncat/Aa\n").unwrap();
+        assert!(long.check("cats"));
+        let numeric = parse("FLAG num\nSFX 12 Y 1\nSFX 12 0 s .\n", "1\ndog/3,12\n").unwrap();
+        assert!(numeric.check("dogs"));
+    }
+
+    #[test]
+    fn latin1_is_the_default_encoding() {
+        let dictionary = Dictionary::parse(b"TRY abc\n", b"1\ncaf\xe9\n").unwrap();
+        assert!(dictionary.check("café"));
+    }
+
+    #[test]
+    fn unsupported_dictionaries_are_rejected() {
+        assert!(parse("SET ISO8859-15\n", "1\nword\n").is_err());
+        assert!(parse("SET ISO8859-2\n", "1\nword\n").is_err());
+        assert!(parse("AF 1\nAF AB\n", "1\nword/1\n").is_err());
+        assert!(parse("COMPOUNDFLAG Z\n", "1\nhaus/Z\n").is_err());
+        assert!(parse("COMPOUNDRULE 1\nCOMPOUNDRULE ab\n", "1\nsea/a\n").is_err());
+    }
+
+    #[test]
+    fn compound_rules_over_numbers_are_allowed() {
+        let aff = "COMPOUNDMIN 1\nONLYINCOMPOUND c\nCOMPOUNDRULE 2\nCOMPOUNDRULE n*1t\nCOMPOUNDRULE n*mp\n";
+        let dictionary = parse(aff, "4\n0/nm\n1/n1\n1st/p\nfirst\n").unwrap();
+        assert!(dictionary.check("first"));
+    }
+
+    #[test]
+    fn suggestions_keep_the_capital() {
+        let dictionary = parse(AFF, DIC).unwrap();
+        assert_eq!(dictionary.suggest("helo", |_| false).first().map(String::as_str), Some("hello"));
+        assert!(dictionary.suggest("Flise", |_| false).contains(&"Flies".to_string()));
+        assert_eq!(dictionary.suggest("tei", |candidate| candidate == "tea"), vec!["tie", "tea"]);
+    }
+}
@@ -0,0 +1,566 @@
+// EditorConfig support for the text editor
+// Reads .editorconfig files above an opened file and applies their properties to its view and saves
+
+use gtk4::prelude::*;
+use gtk4::TextBuffer;
+use regex::Regex;
+use sourceview5::{prelude::*, View};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Name of the files looked up in the file's directory and its parents
+const EDITORCONFIG_FILE_NAME: &str = ".editorconfig";
+
+/// Collects the EditorConfig properties that apply to a file
+///
+/// Walks up from the file's directory until a file with `root = true` (or the
+/// filesystem root). Closer files override farther ones, and later sections override
+/// earlier ones. Keys are lowercased, as are the values of the standard properties;
+/// properties set to `unset` are removed.
+pub fn properties_for_file(file_path: &Path) -> HashMap<String, String> {
+    let file_path = match file_path.canonicalize() {
+        Ok(path) => path,
+        Err(_) => file_path.to_path_buf(),
+    };
+    let file_str = file_path.to_string_lossy().replace('\\', "/");
+
+    // Find the config files, nearest first
+    let mut config_files = Vec::new();
+    let mut directory = file_path.parent();
+    while let Some(dir) = directory {
+        let config_path = dir.join(EDITORCONFIG_FILE_NAME);
+        if let Ok(contents) = fs::read_to_string(&config_path) {
+            let config = parse_editorconfig(&contents);
+            let is_root = config.root;
+            config_files.push((dir.to_path_buf(), config));
+            if is_root {
+                break;
+            }
+        }
+        directory = dir.parent();
+    }
+
+    // Apply them from the farthest to the nearest
+    let mut properties = HashMap::new();
+    for (dir, config) in config_files.iter().rev() {
+        let dir_str = dir.to_string_lossy().replace('\\', "/");
+        for section in &config.sections {
+            if section_matches(&section.glob, &dir_str, &file_str) {
+                for (key, value) in &section.properties {
+                    properties.insert(key.clone(), value.clone());
+                }
+            }
+        }
+    }
+
+    properties.retain(|_, value| value != "unset");
+    normalize_indentation(&mut properties);
+    properties
+}
+
+/// A parsed .editorconfig file
+#[derive(Debug, Default)]
+struct EditorConfigFile {
+    /// `root = true` in the preamble stops the search for parent files
+    root: bool,
+    sections: Vec<Section>,
+}
+
+/// A `[glob]` section with its properties in file order
+#[derive(Debug)]
+struct Section {
+    glob: String,
+    properties: Vec<(String, String)>,
+}
+
+/// Parses the contents of a .editorconfig file
+fn parse_editorconfig(contents: &str) -> EditorConfigFile {
+    let mut config = EditorConfigFile::default();
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+
+        if line.starts_with('[') && line.ends_with(']') {
+            config.sections.push(Section {
+                glob: line[1..line.len() - 1].to_string(),
+                properties: Vec::new(),
+            });
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_lowercase();
+        let mut value = value.trim().to_string();
+        if is_standard_property(&key) {
+            value = value.to_lowercase();
+        }
+
+        match config.sections.last_mut() {
+            Some(section) => section.properties.push((key, value)),
+            None if key == "root" => config.root = value.eq_ignore_ascii_case("true"),
+            None => {}
+        }
+    }
+
+    config
+}
+
+/// Checks whether a property has case-insensitive values per the specification
+fn is_standard_property(key: &str) -> bool {
+    matches!(
+        key,
+        "indent_style"
+            | "indent_size"
+            | "tab_width"
+            | "end_of_line"
+            | "charset"
+            | "trim_trailing_whitespace"
+            | "insert_final_newline"
+            | "max_line_length"
+            | "root"
+    )
+}
+
+/// Fills in indent_size and tab_width from each other, as the specification requires
+fn normalize_indentation(properties: &mut HashMap<String, String>) {
+    if properties.get("indent_style").map(String::as_str) == Some("tab") && !properties.contains_key("indent_size") {
+        properties.insert("indent_size".to_string(), "tab".to_string());
+    }
+    match properties.get("indent_size").cloned() {
+        Some(size) if size == "tab" => {
+            if let Some(tab_width) = properties.get("tab_width").cloned() {
+                properties.insert("indent_size".to_string(), tab_width);
+            }
+        }
+        Some(size) => {
+            properties.entry("tab_width".to_string()).or_insert(size);
+        }
+        None => {}
+    }
+}
+
+/// Checks whether a section glob from the config file in `dir` matches a file
+///
+/// Globs without a slash match the file name in any subdirectory; globs with a
+/// slash are relative to the config file's directory.
+fn section_matches(glob: &str, dir: &str, file: &str) -> bool {
+    let dir = dir.trim_end_matches('/');
+    let pattern = if glob.contains('/') {
+        format!("^{}/{}$", regex::escape(dir), glob_to_regex(glob.trim_start_matches('/')))
+    } else {
+        format!("^{}/(?:.*/)?{}$", regex::escape(dir), glob_to_regex(glob))
+    };
+
+    match Regex::new(&pattern) {
+        Ok(regex) => regex.is_match(file),
+        Err(e) => {
+            eprintln!("Invalid EditorConfig section [{}]: {}", glob, e);
+            false
+        }
+    }
+}
+
+/// Converts an EditorConfig glob into a regular expression
+///
+/// Supports `*`, `**`, `?`, `[chars]`, `[!chars]`, `{a,b}` and `{num1..num2}`.
+fn glob_to_regex(glob: &str) -> String {
+    let chars: Vec<char> = glob.chars().collect();
+    let mut regex = String::new();
+    let mut brace_depth = 0;
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                regex.push_str(".*");
+                i += 1;
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '[' => match chars[i + 1..].iter().position(|c| *c == ']') {
+                Some(length) if !chars[i + 1..i + 1 + length].contains(&'/') => {
+                    let class: String = chars[i + 1..i + 1 + length].iter().collect();
+                    regex.push('[');
+                    let class = match class.strip_prefix('!') {
+                        Some(negated) => {
+                            regex.push('^');
+                            negated.to_string()
+                        }
+                        None => class,
+                    };
+                    regex.push_str(&class.replace('\\', "\\\\").replace('[', "\\[").replace('^', "\\^"));
+                    regex.push(']');
+                    i += length + 1;
+                }
+                _ => regex.push_str("\\["),
+            },
+            '{' => {
+                let closing = chars[i + 1..].iter().position(|c| *c == '}');
+                let content: Option<String> = closing.map(|length| chars[i + 1..i + 1 + length].iter().collect());
+                match content.as_deref().and_then(numeric_range) {
+                    Some((low, high)) => {
+                        // Any integer in the range, checked by listing the candidates
+                        let numbers: Vec<String> = (low..=high).map(|n| n.to_string()).collect();
+                        regex.push_str(&format!("(?:{})", numbers.join("|")));
+                        i += closing.unwrap_or(0) + 1;
+                    }
+                    None if content.as_deref().is_some_and(|c| c.contains(',')) => {
+                        regex.push_str("(?:");
+                        brace_depth += 1;
+                    }
+                    None => regex.push_str("\\{"),
+                }
+            }
+            ',' if brace_depth > 0 => regex.push('|'),
+            '}' if brace_depth > 0 => {
+                regex.push(')');
+                brace_depth -= 1;
+            }
+            '\\' if i + 1 < chars.len() => {
+                regex.push_str(&regex::escape(&chars[i + 1].to_string()));
+                i += 1;
+            }
+            other => regex.push_str(&regex::escape(&other.to_string())),
+        }
+        i += 1;
+    }
+
+    regex
+}
+
+/// Parses a `num1..num2` brace range (limited in size to keep the regex small)
+fn numeric_range(content: &str) -> Option<(i64, i64)> {
+    let (low, high) = content.split_once("..")?;
+    let (low, high): (i64, i64) = (low.trim().parse().ok()?, high.trim().parse().ok()?);
+    let (low, high) = (low.min(high), low.max(high));
+    (high - low <= 1000).then_some((low, high))
+}
+
+/// Applies the view-related EditorConfig properties of a file to its view
+///
+/// Sets the indentation (`indent_style`, `indent_size`, `tab_width`) and shows the
+/// right margin at `max_line_length`. Called after indentation detection, so these
+/// values win over both the detected style and the global settings.
+pub fn apply_to_view(view: &View, file_path: &Path) {
+    let properties = properties_for_file(file_path);
+    if properties.is_empty() {
+        return;
+    }
+    println!("Applying EditorConfig to {:?}: {:?}", file_path, properties);
+
+    match properties.get("indent_style").map(String::as_str) {
+        Some("space") => view.set_insert_spaces_instead_of_tabs(true),
+        Some("tab") => view.set_insert_spaces_instead_of_tabs(false),
+        _ => {}
+    }
+    if let Some(tab_width) = properties.get("tab_width").and_then(|w| w.parse::<u32>().ok()).filter(|w| *w > 0) {
+        view.set_tab_width(tab_width);
+    }
+    match properties.get("indent_size").map(String::as_str) {
+        Some("tab") => view.set_indent_width(-1),
+        Some(size) => {
+            if let Some(size) = size.parse::<i32>().ok().filter(|s| *s > 0) {
+                view.set_indent_width(size);
+            }
+        }
+        None => {}
+    }
+
+    match properties.get("max_line_length").map(String::as_str) {
+        Some("off") => view.set_show_right_margin(false),
+        Some(length) => {
+            if let Some(length) = length.parse::<u32>().ok().filter(|l| *l > 0) {
+                view.set_right_margin_position(length);
+                view.set_show_right_margin(true);
+            }
+        }
+        None => {}
+    }
+}
+
+/// Reads a file for editing, decoded with its EditorConfig `charset`
+///
+/// Files without a charset are read as UTF-8. Bytes that are invalid in the
+/// charset are an `InvalidData` error, as with `fs::read_to_string`.
+pub fn read_file(file_path: &Path) -> io::Result<String> {
+    let bytes = fs::read(file_path)?;
+    let properties = properties_for_file(file_path);
+    decode_text(&bytes, properties.get("charset").map(String::as_str))
+        .map_err(|message| io::Error::new(io::ErrorKind::InvalidData, message))
+}
+
+/// Saves a buffer to `file_path`, formatted as its EditorConfig properties say
+///
+/// `trim_trailing_whitespace` and `insert_final_newline` are applied to a copy of
+/// the text, which is then given the `end_of_line` line endings and encoded in the
+/// `charset`. Text that can't be encoded fails the save before anything is written.
+/// Only once the file is written are the whitespace changes made to the buffer too
+/// (as one undoable action), so it matches the file.
+pub fn save_buffer(buffer: &TextBuffer, file_path: &Path) -> io::Result<()> {
+    let properties = properties_for_file(file_path);
+    let property = |key: &str| properties.get(key).map(String::as_str);
+    let trim = property("trim_trailing_whitespace") == Some("true");
+    let final_newline = match property("insert_final_newline") {
+        Some("true") => Some(true),
+        Some("false") => Some(false),
+        _ => None,
+    };
+
+    let original = buffer.text(&buffer.start_iter(), &buffer.end_iter(), true).to_string();
+    let formatted = format_whitespace(&original, trim, final_newline);
+    let text = match property("end_of_line") {
+        Some("lf") => formatted.replace("\r\n", "\n"),
+        Some("crlf") => formatted.replace("\r\n", "\n").replace('\n', "\r\n"),
+        Some("cr") => formatted.replace("\r\n", "\n").replace('\n', "\r"),
+        _ => formatted.clone(),
+    };
+    let bytes = encode_text(&text, property("charset")).map_err(|message| io::Error::new(io::ErrorKind::InvalidData, message))?;
+    fs::write(file_path, bytes)?;
+
+    if formatted != original {
+        buffer.begin_user_action();
+        if trim {
+            trim_trailing_whitespace(buffer);
+        }
+        match final_newline {
+            Some(true) => ensure_final_newline(buffer),
+            Some(false) => remove_final_newlines(buffer),
+            None => {}
+        }
+        buffer.end_user_action();
+    }
+    Ok(())
+}
+
+/// Applies `trim_trailing_whitespace` and `insert_final_newline` to a copy of a text
+fn format_whitespace(text: &str, trim: bool, final_newline: Option<bool>) -> String {
+    let mut formatted = if trim {
+        text.split('\n')
+            .map(|line| match line.strip_suffix('\r') {
+                Some(line) => format!("{}\r", line.trim_end_matches([' ', '\t'])),
+                None => line.trim_end_matches([' ', '\t']).to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    } else {
+        text.to_string()
+    };
+    match final_newline {
+        Some(true) if !formatted.is_empty() && !formatted.ends_with(['\n', '\r']) => formatted.push('\n'),
+        Some(false) => formatted.truncate(formatted.trim_end_matches(['\n', '\r']).len()),
+        _ => {}
+    }
+    formatted
+}
+
+/// Encodes text in an EditorConfig charset (UTF-8 for unknown values)
+///
+/// Fails with a message naming the first character latin1 can't represent.
+fn encode_text(text: &str, charset: Option<&str>) -> Result<Vec<u8>, String> {
+    let bytes = match charset {
+        Some("utf-8-bom") => {
+            let mut bytes = vec![0xEF, 0xBB, 0xBF];
+            bytes.extend_from_slice(text.as_bytes());
+            bytes
+        }
+        Some("utf-16le") => {
+            let mut bytes = vec![0xFF, 0xFE];
+            bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+            bytes
+        }
+        Some("utf-16be") => {
+            let mut bytes = vec![0xFE, 0xFF];
+            bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+            bytes
+        }
+        Some("latin1") => {
+            let mut bytes = Vec::with_capacity(text.len());
+            for (line, line_text) in text.split('\n').enumerate() {
+                if line > 0 {
+                    bytes.push(b'\n');
+                }
+                for character in line_text.chars() {
+                    match u8::try_from(u32::from(character)) {
+                        Ok(byte) => bytes.push(byte),
+                        Err(_) => {
+                            return Err(format!("'{}' on line {} can't be saved as latin1", character, line + 1));
+                        }
+                    }
+                }
+            }
+            bytes
+        }
+        _ => text.as_bytes().to_vec(),
+    };
+    Ok(bytes)
+}
+
+/// Decodes file contents in an EditorConfig charset (UTF-8 for unknown values)
+///
+/// The byte order mark of `utf-8-bom` and the UTF-16 charsets is removed.
+fn decode_text(bytes: &[u8], charset: Option<&str>) -> Result<String, String> {
+    let utf16 = |bytes: &[u8], bom: [u8; 2], from_bytes: fn([u8; 2]) -> u16| {
+        let bytes = bytes.strip_prefix(&bom).unwrap_or(bytes);
+        let pairs = bytes.chunks_exact(2);
+        if !pairs.remainder().is_empty() {
+            return Err("odd number of bytes in UTF-16 text".to_string());
+        }
+        let units = pairs.map(|pair| from_bytes([pair[0], pair[1]]));
+        char::decode_utf16(units)
+            .collect::<Result<String, _>>()
+            .map_err(|e| format!("invalid UTF-16 text: {}", e))
+    };
+
+    match charset {
+        Some("utf-16le") => utf16(bytes, [0xFF, 0xFE], u16::from_le_bytes),
+        Some("utf-16be") => utf16(bytes, [0xFE, 0xFF], u16::from_be_bytes),
+        Some("latin1") => Ok(bytes.iter().map(|byte| char::from(*byte)).collect()),
+        Some("utf-8-bom") => {
+            let bytes = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(bytes);
+            String::from_utf8(bytes.to_vec()).map_err(|e| e.to_string())
+        }
+        _ => String::from_utf8(bytes.to_vec()).map_err(|e| e.to_string()),
+    }
+}
+
+/// Removes spaces and tabs at the end of every line
+fn trim_trailing_whitespace(buffer: &TextBuffer) {
+    for line in 0..buffer.line_count() {
+        let Some(mut line_end) = buffer.iter_at_line(line) else {
+            continue;
+        };
+        if !line_end.ends_line() {
+            line_end.forward_to_line_end();
+        }
+        let mut whitespace_start = line_end.clone();
+        while !whitespace_start.starts_line() {
+            let mut previous = whitespace_start.clone();
+            previous.backward_char();
+            if previous.char() != ' ' && previous.char() != '\t' {
+                break;
+            }
+            whitespace_start = previous;
+        }
+        if whitespace_start.offset() < line_end.offset() {
+            buffer.delete(&mut whitespace_start, &mut line_end);
+        }
+    }
+}
+
+/// Adds a newline at the end of a non-empty buffer that lacks one
+fn ensure_final_newline(buffer: &TextBuffer) {
+    let mut end = buffer.end_iter();
+    let mut last = end.clone();
+    if last.backward_char() && last.char() != '\n' && last.char() != '\r' {
+        buffer.insert(&mut end, "\n");
+    }
+}
+
+/// Removes the line breaks at the end of the buffer
+fn remove_final_newlines(buffer: &TextBuffer) {
+    let mut end = buffer.end_iter();
+    let mut start = end.clone();
+    while start.backward_char() {
+        if start.char() != '\n' && start.char() != '\r' {
+            start.forward_char();
+            break;
+        }
+    }
+    if start.offset() < end.offset() {
+        buffer.delete(&mut start, &mut end);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_utf8_with_and_without_bom() {
+        assert_eq!(encode_text("hé", None).unwrap(), "hé".as_bytes());
+        assert_eq!(encode_text("hé", Some("utf-8")).unwrap(), "hé".as_bytes());
+        assert_eq!(encode_text("a", Some("utf-8-bom")).unwrap(), vec![0xEF, 0xBB, 0xBF, b'a']);
+    }
+
+    #[test]
+    fn encodes_utf16_with_bom() {
+        assert_eq!(encode_text("a€", Some("utf-16le")).unwrap(), vec![0xFF, 0xFE, b'a', 0x00, 0xAC, 0x20]);
+        assert_eq!(encode_text("a€", Some("utf-16be")).unwrap(), vec![0xFE, 0xFF, 0x00, b'a', 0x20, 0xAC]);
+    }
+
+    #[test]
+    fn encodes_latin1_and_rejects_other_characters() {
+        assert_eq!(encode_text("né\nÿ", Some("latin1")).unwrap(), vec![b'n', 0xE9, b'\n', 0xFF]);
+        let error = encode_text("ok\nzł", Some("latin1")).unwrap_err();
+        assert!(error.contains("'ł' on line 2"), "{}", error);
+    }
+
+    #[test]
+    fn decodes_what_it_encodes() {
+        for charset in [None, Some("utf-8-bom"), Some("utf-16le"), Some("utf-16be"), Some("latin1")] {
+            let bytes = encode_text("caf\u{e9}\r\nend", charset).unwrap();
+            assert_eq!(decode_text(&bytes, charset).unwrap(), "caf\u{e9}\r\nend", "{:?}", charset);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_bytes() {
+        assert!(decode_text(&[0xE9], None).is_err());
+        assert!(decode_text(&[0xFF, 0xFE, b'a'], Some("utf-16le")).is_err());
+    }
+
+    #[test]
+    fn formats_whitespace_on_a_copy() {
+        assert_eq!(format_whitespace("a \t\nb  \r\nc", true, None), "a\nb\r\nc");
+        assert_eq!(format_whitespace("a", false, Some(true)), "a\n");
+        assert_eq!(format_whitespace("", false, Some(true)), "");
+        assert_eq!(format_whitespace("a\n\n\r\n", false, Some(false)), "a");
+    }
+
+    #[test]
+    fn globs_without_a_slash_match_in_subdirectories() {
+        assert!(section_matches("*.rs", "/p", "/p/main.rs"));
+        assert!(section_matches("*.rs", "/p/", "/p/src/deep/lib.rs"));
+        assert!(!section_matches("*.rs", "/p", "/other/main.rs"));
+        assert!(!section_matches("*.rs", "/p", "/p/main.rsx"));
+    }
+
+    #[test]
+    fn globs_with_a_slash_are_anchored() {
+        assert!(section_matches("src/*.rs", "/p", "/p/src/main.rs"));
+        assert!(section_matches("/src/*.rs", "/p", "/p/src/main.rs"));
+        assert!(!section_matches("src/*.rs", "/p", "/p/lib/src/main.rs"));
+        assert!(!section_matches("src/*.rs", "/p", "/p/src/deep/main.rs"));
+        assert!(section_matches("src/**.rs", "/p", "/p/src/deep/main.rs"));
+    }
+
+    #[test]
+    fn braces_list_alternatives_and_ranges() {
+        assert!(section_matches("*.{js,ts}", "/p", "/p/a.ts"));
+        assert!(!section_matches("*.{js,ts}", "/p", "/p/a.tsx"));
+        assert!(section_matches("file{1..12}.txt", "/p", "/p/file12.txt"));
+        assert!(!section_matches("file{1..12}.txt", "/p", "/p/file13.txt"));
+        assert!(section_matches("{a}.txt", "/p", "/p/{a}.txt"));
+    }
+
+    #[test]
+    fn classes_can_be_negated() {
+        assert!(section_matches("[abc].md", "/p", "/p/b.md"));
+        assert!(section_matches("[!abc].md", "/p", "/p/d.md"));
+        assert!(!section_matches("[!abc].md", "/p", "/p/a.md"));
+    }
+
+    #[test]
+    fn an_unterminated_class_is_literal() {
+        assert!(section_matches("[ab.md", "/p", "/p/[ab.md"));
+        assert!(!section_matches("[ab.md", "/p", "/p/a.md"));
+        assert!(section_matches("[a/b].md", "/p", "/p/[a/b].md"));
+    }
+}
@@ -0,0 +1,470 @@
+// Emacs keybindings for the text editor
+// Movement, mark and region, the kill ring, incremental search and C-x prefix chords
+
+use gtk4::prelude::*;
+use gtk4::{gdk, gio, glib, DeleteType, EventControllerKey, MovementStep, Notebook};
+use sourceview5::View;
+use std::cell::{Cell, RefCell};
+
+use crate::commands;
+use crate::settings::Keymap;
+use crate::utils::ObjectState;
+
+// Buffer mark set by C-SPC; while it exists, movement extends the region
+const MARK_NAME: &str = "emacs-mark";
+// Number of kills kept in the kill ring
+const KILL_RING_SIZE: usize = 60;
+
+/// The previous command, which decides whether a kill appends and whether M-y works
+#[derive(Clone, Copy, PartialEq)]
+enum LastCommand {
+    Other,
+    Kill,
+    // Character offsets of the yanked text and its kill ring entry
+    Yank { start: i32, end: i32, index: usize },
+}
+
+/// Emacs key controller of each source view; disabled while the Emacs keymap is off
+const EMACS_INPUT: ObjectState<EventControllerKey> = ObjectState::new("emacs-input");
+
+thread_local! {
+    // Killed text shared by every view, most recent first
+    static KILL_RING: RefCell<Vec<String>> = RefCell::new(Vec::new());
+    static LAST_COMMAND: Cell<LastCommand> = Cell::new(LastCommand::Other);
+    // Set after C-x while waiting for the second key of the chord
+    static PREFIX_PENDING: Cell<bool> = Cell::new(false);
+}
+
+/// Checks whether the Emacs keymap is selected in the settings
+pub fn is_enabled() -> bool {
+    crate::settings::get_settings().get_keymap() == Keymap::Emacs
+}
+
+/// Adds Emacs input to a source view
+///
+/// The key controller runs in the capture phase ahead of the editor's own shortcuts,
+/// so keys such as C-n, C-s and C-d mean their Emacs commands. It stays inactive
+/// until the Emacs keymap is selected.
+pub fn attach(view: &View) {
+    let key_controller = EventControllerKey::new();
+    key_controller.set_propagation_phase(gtk4::PropagationPhase::None);
+    let view_clone = view.clone();
+    key_controller.connect_key_pressed(move |_, keyval, _, state| handle_key(&view_clone, keyval, state));
+    view.add_controller(key_controller.clone());
+
+    // Editing deactivates the mark, like Emacs' transient mark mode
+    view.buffer().connect_changed(deactivate_mark);
+
+    EMACS_INPUT.set(view, key_controller);
+}
+
+/// Turns Emacs input on or off for a view, following the settings
+pub fn apply_emacs_settings(view: &View) {
+    let enabled = is_enabled();
+    if let Some(key_controller) = EMACS_INPUT.get(view) {
+        let phase = if enabled { gtk4::PropagationPhase::Capture } else { gtk4::PropagationPhase::None };
+        key_controller.set_propagation_phase(phase);
+        if !enabled {
+            deactivate_mark(&view.buffer());
+        }
+    }
+}
+
+/// Checks whether a view's Emacs input is switched on
+fn is_active(view: &View) -> bool {
+    EMACS_INPUT.get(view).is_some_and(|key_controller| key_controller.propagation_phase() != gtk4::PropagationPhase::None)
+}
+
+/// Handles an Emacs key in a source view
+///
+/// Keys without an Emacs meaning here (typing, Tab, Ctrl+Z, the function keys)
+/// are left to the view and the rest of the editor.
+fn handle_key(view: &View, keyval: gdk::Key, state: gdk::ModifierType) -> glib::Propagation {
+    if crate::multi_cursor::is_modifier_key(keyval)
+        || crate::multi_cursor::has_extra_carets(view)
+        || !is_active(view)
+    {
+        return glib::Propagation::Proceed;
+    }
+    let ctrl = state.contains(gdk::ModifierType::CONTROL_MASK);
+    let alt = state.contains(gdk::ModifierType::ALT_MASK);
+
+    if PREFIX_PENDING.with(|pending| pending.replace(false)) {
+        run_prefix_command(Some(view), keyval, ctrl);
+        LAST_COMMAND.with(|last| last.set(LastCommand::Other));
+        return glib::Propagation::Stop;
+    }
+
+    let command = if ctrl && !alt {
+        match keyval {
+            gdk::Key::a => move_point(view, MovementStep::ParagraphEnds, -1),
+            gdk::Key::e => move_point(view, MovementStep::ParagraphEnds, 1),
+            gdk::Key::f => move_point(view, MovementStep::LogicalPositions, 1),
+            gdk::Key::b => move_point(view, MovementStep::LogicalPositions, -1),
+            gdk::Key::n => move_point(view, MovementStep::DisplayLines, 1),
+            gdk::Key::p => move_point(view, MovementStep::DisplayLines, -1),
+            gdk::Key::v => move_point(view, MovementStep::Pages, 1),
+            gdk::Key::space => {
+                set_mark(&view.buffer());
+                LastCommand::Other
+            }
+            gdk::Key::g => keyboard_quit(view),
+            gdk::Key::d => {
+                view.emit_delete_from_cursor(DeleteType::Chars, 1);
+                LastCommand::Other
+            }
+            gdk::Key::k => kill_line(view),
+            gdk::Key::w => kill_region(view),
+            gdk::Key::y => yank(view),
+            gdk::Key::s => incremental_search(view, false),
+            gdk::Key::r => incremental_search(view, true),
+            gdk::Key::slash | gdk::Key::underscore => undo(view),
+            gdk::Key::x => {
+                PREFIX_PENDING.with(|pending| pending.set(true));
+                LastCommand::Other
+            }
+            _ => return proceed(),
+        }
+    } else if alt && !ctrl {
+        match keyval {
+            gdk::Key::f => move_point(view, MovementStep::Words, 1),
+            gdk::Key::b => move_point(view, MovementStep::Words, -1),
+            gdk::Key::v => move_point(view, MovementStep::Pages, -1),
+            gdk::Key::less => move_point(view, MovementStep::BufferEnds, -1),
+            gdk::Key::greater => move_point(view, MovementStep::BufferEnds, 1),
+            gdk::Key::w => copy_region(view),
+            gdk::Key::y => yank_pop(view),
+            gdk::Key::d => kill_word(view, true),
+            gdk::Key::BackSpace => kill_word(view, false),
+            gdk::Key::x => {
+                crate::keybindings::activate_action(view, "win.command-palette");
+                LastCommand::Other
+            }
+            _ => return proceed(),
+        }
+    } else {
+        return proceed();
+    };
+
+    LAST_COMMAND.with(|last| last.set(command));
+    glib::Propagation::Stop
+}
+
+/// Lets a key through to the view, ending any run of kills or yanks
+fn proceed() -> glib::Propagation {
+    LAST_COMMAND.with(|last| last.set(LastCommand::Other));
+    glib::Propagation::Proceed
+}
+
+/// Handles the C-x prefix when the focus is outside the source views
+///
+/// Called by the window's shortcut handler, so C-x C-f and C-x C-c also work
+/// with the focus on the file list or with no tab open.
+pub fn handle_window_key(keyval: gdk::Key, state: gdk::ModifierType) -> glib::Propagation {
+    if crate::multi_cursor::is_modifier_key(keyval) {
+        return glib::Propagation::Proceed;
+    }
+    let ctrl = state.contains(gdk::ModifierType::CONTROL_MASK);
+    let alt = state.contains(gdk::ModifierType::ALT_MASK);
+
+    if PREFIX_PENDING.with(|pending| pending.replace(false)) {
+        run_prefix_command(None, keyval, ctrl);
+        return glib::Propagation::Stop;
+    }
+    if ctrl && !alt && keyval == gdk::Key::x {
+        PREFIX_PENDING.with(|pending| pending.set(true));
+        return glib::Propagation::Stop;
+    }
+    glib::Propagation::Proceed
+}
+
+/// Checks whether a key has an Emacs meaning, so window shortcuts must leave it alone
+///
+/// No shortcut is registered for these keys while the Emacs keymap is selected.
+/// With the focus in a source view Emacs input handles them; elsewhere they do
+/// nothing rather than run a default shortcut such as Ctrl+S or Ctrl+O.
+pub fn is_reserved_key(keyval: gdk::Key, state: gdk::ModifierType) -> bool {
+    let ctrl = state.contains(gdk::ModifierType::CONTROL_MASK);
+    let alt = state.contains(gdk::ModifierType::ALT_MASK);
+    if ctrl && !alt {
+        return matches!(
+            keyval,
+            gdk::Key::a | gdk::Key::b | gdk::Key::d | gdk::Key::e | gdk::Key::f | gdk::Key::g | gdk::Key::k
+                | gdk::Key::n | gdk::Key::o | gdk::Key::p | gdk::Key::q | gdk::Key::r | gdk::Key::s
+                | gdk::Key::v | gdk::Key::w | gdk::Key::x | gdk::Key::y | gdk::Key::space
+                | gdk::Key::slash | gdk::Key::underscore
+        );
+    }
+    if alt && !ctrl {
+        return matches!(
+            keyval,
+            gdk::Key::b | gdk::Key::d | gdk::Key::f | gdk::Key::v | gdk::Key::w | gdk::Key::x | gdk::Key::y
+                | gdk::Key::less | gdk::Key::greater | gdk::Key::BackSpace
+        );
+    }
+    false
+}
+
+/// Runs the second key of a C-x chord
+///
+/// Commands that edit text need a view and do nothing outside one.
+fn run_prefix_command(view: Option<&View>, keyval: gdk::Key, ctrl: bool) {
+    match (keyval, ctrl) {
+        (gdk::Key::s, true) => commands::save(),
+        (gdk::Key::w, true) => commands::save_as(),
+        (gdk::Key::f, true) => commands::open(),
+        (gdk::Key::c, true) => commands::quit(),
+        (gdk::Key::k, false) => commands::close_active_tab(false),
+        (gdk::Key::h, false) => {
+            if let Some(view) = view {
+                let buffer = view.buffer();
+                set_mark(&buffer);
+                buffer.select_range(&buffer.start_iter(), &buffer.end_iter());
+            }
+        }
+        (gdk::Key::u, false) => {
+            if let Some(view) = view {
+                undo(view);
+            }
+        }
+        _ => println!(
+            "Emacs: C-x {}{} is undefined",
+            if ctrl { "C-" } else { "" },
+            keyval.name().map(|name| name.to_string()).unwrap_or_default()
+        ),
+    }
+}
+
+/// Moves the cursor, extending the region while the mark is active
+fn move_point(view: &View, step: MovementStep, count: i32) -> LastCommand {
+    let extend = mark_active(&view.buffer());
+    view.emit_move_cursor(step, count, extend);
+    LastCommand::Other
+}
+
+/// Checks whether C-SPC set a mark that hasn't been deactivated since
+fn mark_active(buffer: &gtk4::TextBuffer) -> bool {
+    buffer.mark(MARK_NAME).is_some()
+}
+
+/// Sets the mark at the cursor (C-SPC); the region then follows the cursor
+fn set_mark(buffer: &gtk4::TextBuffer) {
+    let cursor = buffer.iter_at_mark(&buffer.get_insert());
+    buffer.select_range(&cursor, &cursor);
+    match buffer.mark(MARK_NAME) {
+        Some(mark) => buffer.move_mark(&mark, &cursor),
+        None => {
+            buffer.create_mark(Some(MARK_NAME), &cursor, true);
+        }
+    }
+}
+
+/// Deactivates the mark, leaving the selection as it is
+fn deactivate_mark(buffer: &gtk4::TextBuffer) {
+    if let Some(mark) = buffer.mark(MARK_NAME) {
+        buffer.delete_mark(&mark);
+    }
+}
+
+/// Cancels the mark and the selection (C-g)
+fn keyboard_quit(view: &View) -> LastCommand {
+    let buffer = view.buffer();
+    deactivate_mark(&buffer);
+    let cursor = buffer.iter_at_mark(&buffer.get_insert());
+    buffer.place_cursor(&cursor);
+    LastCommand::Other
+}
+
+/// Undoes the last change (C-/, C-_ and C-x u)
+fn undo(view: &View) -> LastCommand {
+    let buffer = view.buffer();
+    if view.is_editable() && buffer.can_undo() {
+        buffer.undo();
+    }
+    LastCommand::Other
+}
+
+/// Kills from the cursor to the end of the line, or the line break when already there (C-k)
+fn kill_line(view: &View) -> LastCommand {
+    let buffer = view.buffer();
+    let start = buffer.iter_at_mark(&buffer.get_insert());
+    let mut end = start.clone();
+    if end.ends_line() {
+        end.forward_char();
+    } else {
+        end.forward_to_line_end();
+    }
+    kill(view, start, end, false)
+}
+
+/// Kills the word after (M-d) or before (M-Backspace) the cursor
+fn kill_word(view: &View, forward: bool) -> LastCommand {
+    let buffer = view.buffer();
+    let mut start = buffer.iter_at_mark(&buffer.get_insert());
+    let mut end = start.clone();
+    if forward {
+        end.forward_word_end();
+    } else {
+        start.backward_word_start();
+    }
+    kill(view, start, end, !forward)
+}
+
+/// Kills the region (C-w)
+fn kill_region(view: &View) -> LastCommand {
+    match view.buffer().selection_bounds() {
+        Some((start, end)) => kill(view, start, end, false),
+        None => LastCommand::Other,
+    }
+}
+
+/// Copies the region to the kill ring without deleting it (M-w)
+fn copy_region(view: &View) -> LastCommand {
+    let buffer = view.buffer();
+    if let Some((start, end)) = buffer.selection_bounds() {
+        push_kill(&buffer.text(&start, &end, true), false, false);
+        share_kill(view);
+    }
+    keyboard_quit(view)
+}
+
+/// Deletes a range and adds it to the kill ring
+///
+/// Kills right after another kill join the same entry, so repeated C-k yanks
+/// back as one block; backward kills are put in front of it.
+fn kill(view: &View, mut start: gtk4::TextIter, mut end: gtk4::TextIter, backward: bool) -> LastCommand {
+    let buffer = view.buffer();
+    if !view.is_editable() || start == end {
+        return LastCommand::Other;
+    }
+    let append = LAST_COMMAND.with(|last| last.get()) == LastCommand::Kill;
+    push_kill(&buffer.text(&start, &end, true), append, backward);
+    share_kill(view);
+
+    buffer.begin_user_action();
+    buffer.delete(&mut start, &mut end);
+    buffer.end_user_action();
+    LastCommand::Kill
+}
+
+/// Adds text to the kill ring, or to its latest entry when appending
+fn push_kill(text: &str, append: bool, prepend: bool) {
+    KILL_RING.with(|ring| {
+        let mut ring = ring.borrow_mut();
+        match ring.first_mut() {
+            Some(latest) if append => {
+                if prepend {
+                    latest.insert_str(0, text);
+                } else {
+                    latest.push_str(text);
+                }
+            }
+            _ => {
+                ring.insert(0, text.to_string());
+                ring.truncate(KILL_RING_SIZE);
+            }
+        }
+    });
+}
+
+/// Puts the latest kill on the clipboard so other applications can paste it
+fn share_kill(view: &View) {
+    if let Some(latest) = KILL_RING.with(|ring| ring.borrow().first().cloned()) {
+        view.clipboard().set_text(&latest);
+    }
+}
+
+/// Inserts the latest kill at the cursor (C-y)
+///
+/// Text copied since the last kill, in this editor or in another application,
+/// is put on the kill ring first, so C-y also pastes from the clipboard.
+fn yank(view: &View) -> LastCommand {
+    if !view.is_editable() {
+        return LastCommand::Other;
+    }
+    let clipboard = view.clipboard();
+    let local_text = clipboard
+        .content()
+        .and_then(|content| content.value(glib::Type::STRING).ok())
+        .and_then(|value| value.get::<String>().ok());
+    if let Some(text) = local_text {
+        add_clipboard_text(&text);
+        return insert_kill(view, 0);
+    }
+
+    // Another application owns the clipboard: read it, then yank
+    let view = view.clone();
+    clipboard.read_text_async(None::<&gio::Cancellable>, move |result| {
+        if let Ok(Some(text)) = result {
+            add_clipboard_text(&text);
+        }
+        let command = insert_kill(&view, 0);
+        LAST_COMMAND.with(|last| last.set(command));
+    });
+    LastCommand::Other
+}
+
+/// Puts clipboard text on the kill ring unless it is the latest kill already
+fn add_clipboard_text(text: &str) {
+    let is_latest = KILL_RING.with(|ring| ring.borrow().first().map(String::as_str) == Some(text));
+    if !text.is_empty() && !is_latest {
+        push_kill(text, false, false);
+    }
+}
+
+/// Inserts a kill ring entry at the cursor
+fn insert_kill(view: &View, index: usize) -> LastCommand {
+    let Some(text) = KILL_RING.with(|ring| ring.borrow().get(index).cloned()) else {
+        return LastCommand::Other;
+    };
+    let buffer = view.buffer();
+    buffer.begin_user_action();
+    let start = buffer.iter_at_mark(&buffer.get_insert()).offset();
+    buffer.insert_at_cursor(&text);
+    let end = buffer.iter_at_mark(&buffer.get_insert()).offset();
+    buffer.end_user_action();
+    view.scroll_mark_onscreen(&buffer.get_insert());
+    LastCommand::Yank { start, end, index }
+}
+
+/// Replaces the text just yanked with the previous kill ring entry (M-y)
+fn yank_pop(view: &View) -> LastCommand {
+    let LastCommand::Yank { start, end, index } = LAST_COMMAND.with(|last| last.get()) else {
+        println!("Emacs: previous command was not a yank");
+        return LastCommand::Other;
+    };
+    let ring_length = KILL_RING.with(|ring| ring.borrow().len());
+    let yanked = KILL_RING.with(|ring| ring.borrow().get(index).cloned());
+    let buffer = view.buffer();
+    let mut start_iter = buffer.iter_at_offset(start);
+    let mut end_iter = buffer.iter_at_offset(end);
+
+    // Leave the text alone if it was changed since the yank
+    if ring_length == 0 || yanked.as_deref() != Some(buffer.text(&start_iter, &end_iter, true).as_str()) {
+        return LastCommand::Other;
+    }
+
+    // The insert's own user action nests in this one, so M-y is a single undo step
+    buffer.begin_user_action();
+    buffer.delete(&mut start_iter, &mut end_iter);
+    buffer.place_cursor(&start_iter);
+    let yank = insert_kill(view, (index + 1) % ring_length);
+    buffer.end_user_action();
+    yank
+}
+
+/// Starts an incremental search, or moves to the next (C-s) or previous (C-r) match
+///
+/// The find bar searches as you type; inside it, C-s and C-r keep moving
+/// between matches and C-g closes it.
+fn incremental_search(view: &View, backwards: bool) -> LastCommand {
+    let Some(notebook) = view.ancestor(Notebook::static_type()).and_then(|widget| widget.downcast::<Notebook>().ok()) else {
+        return LastCommand::Other;
+    };
+    if crate::search::is_find_bar_open(&notebook) {
+        crate::search::find_in_current_tab(&notebook, backwards);
+    } else {
+        crate::search::show_find_bar(&notebook, false);
+    }
+    LastCommand::Other
+}
@@ -0,0 +1,861 @@
+// Project-wide "Find in Files" for the text editor
+// Searches every file under the current directory in a background thread and lists the matches,
+// optionally replacing a selection of them across the whole tree
+
+use gtk4::prelude::*;
+use gtk4::{
+    glib, pango, Box as GtkBox, Button, CheckButton, Entry, Label, ListBox, ListBoxRow, Notebook,
+    Orientation, Revealer, ScrolledWindow, Stack, TextBuffer, ToggleButton,
+};
+use gtk4::{ButtonsType, DialogFlags, MessageDialog, MessageType};
+use regex::{Regex, RegexBuilder};
+use std::cell::{Cell, RefCell};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+use crate::handlers::NewTabDependencies;
+use crate::project;
+
+/// Name of the sidebar stack page holding the panel
+pub const SIDEBAR_PAGE_NAME: &str = "search";
+
+/// Stop collecting results after this many matches to keep the list responsive
+const MAX_RESULTS: usize = 10_000;
+
+/// Maximum number of characters shown for a line preview
+const MAX_PREVIEW_CHARS: usize = 200;
+
+/// Options controlling how the query is interpreted
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SearchOptions {
+    pub regex: bool,
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+}
+
+/// A single match inside a line
+#[derive(Clone, Debug)]
+pub struct LineMatch {
+    /// Zero-based line number
+    pub line: usize,
+    /// Zero-based column of the match start, in characters
+    pub column: usize,
+    /// Full text of the line, without its line terminator
+    pub line_text: String,
+    /// Byte range of the match within `line_text`
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Messages streamed from the search thread to the UI
+enum SearchMessage {
+    /// All matches found in one file
+    FileMatches(PathBuf, Vec<LineMatch>),
+    /// The search ended (normally, by cancellation or by hitting the result limit)
+    Finished { files_scanned: usize, cancelled: bool, truncated: bool },
+}
+
+/// Builds the regular expression for a query and its options
+///
+/// Plain-text queries are escaped, whole-word matching wraps the pattern in word boundaries.
+pub fn build_regex(query: &str, options: SearchOptions) -> Result<Regex, regex::Error> {
+    let mut pattern = if options.regex { query.to_string() } else { regex::escape(query) };
+    if options.whole_word {
+        pattern = format!(r"\b(?:{})\b", pattern);
+    }
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!options.case_sensitive)
+        .multi_line(true)
+        .build()
+}
+
+/// Finds every match of `regex` in `text`, one entry per match
+pub fn find_matches_in_text(regex: &Regex, text: &str) -> Vec<LineMatch> {
+    let mut matches = Vec::new();
+    for (line_index, line) in text.lines().enumerate() {
+        for found in regex.find_iter(line) {
+            // Skip empty matches (e.g. "^" or "\b") which have nothing to show
+            if found.start() == found.end() {
+                continue;
+            }
+            matches.push(LineMatch {
+                line: line_index,
+                column: line[..found.start()].chars().count(),
+                line_text: line.to_string(),
+                start: found.start(),
+                end: found.end(),
+            });
+        }
+    }
+    matches
+}
+
+/// Computes the replacement text for a match
+///
+/// In regex mode the template may refer to capture groups (`$1`, `${name}`);
+/// otherwise it is inserted literally.
+pub fn expand_replacement(regex: &Regex, line_match: &LineMatch, template: &str, use_captures: bool) -> String {
+    if !use_captures {
+        return template.to_string();
+    }
+
+    // Re-run the regex at the match position to recover its capture groups
+    match regex.captures_at(&line_match.line_text, line_match.start) {
+        Some(captures) if captures.get(0).map(|m| m.start()) == Some(line_match.start) => {
+            let mut expanded = String::new();
+            captures.expand(template, &mut expanded);
+            expanded
+        }
+        _ => template.to_string(),
+    }
+}
+
+/// Runs a search over a directory tree, sending results as each file is scanned
+fn run_search(root: PathBuf, regex: Regex, cancelled: Arc<AtomicBool>, sender: mpsc::Sender<SearchMessage>) {
+    let mut files_scanned = 0;
+    let mut total_matches = 0;
+    let mut truncated = false;
+
+    project::walk_files(&root, &cancelled, &mut |path| {
+        files_scanned += 1;
+
+        let Some(text) = project::read_text_file(path) else {
+            return true; // Binary, too large or unreadable - keep going
+        };
+
+        let matches = find_matches_in_text(&regex, &text);
+        if matches.is_empty() {
+            return true;
+        }
+
+        total_matches += matches.len();
+        if sender.send(SearchMessage::FileMatches(path.to_path_buf(), matches)).is_err() {
+            return false; // The UI stopped listening
+        }
+
+        if total_matches >= MAX_RESULTS {
+            truncated = true;
+            return false;
+        }
+        true
+    });
+
+    let _ = sender.send(SearchMessage::Finished {
+        files_scanned,
+        cancelled: cancelled.load(Ordering::Relaxed),
+        truncated,
+    });
+}
+
+/// Builds Pango markup for a line preview with the match highlighted
+///
+/// When a replacement is given, the match is struck through and followed by the new text.
+pub fn preview_markup(line_text: &str, start: usize, end: usize, replacement: Option<&str>) -> String {
+    // Drop leading indentation so previews line up
+    let indent = line_text.len() - line_text.trim_start().len();
+    let (start, end) = (start.max(indent), end.max(indent));
+    let visible = &line_text[indent..];
+    let (start, end) = (start - indent, end - indent);
+
+    // Truncate very long lines, keeping the match in view when possible
+    let cut = visible
+        .char_indices()
+        .nth(MAX_PREVIEW_CHARS)
+        .map(|(index, _)| index)
+        .unwrap_or(visible.len());
+    let cut = cut.max(end.min(visible.len()));
+
+    let before = glib::markup_escape_text(&visible[..start]);
+    let matched = glib::markup_escape_text(&visible[start..end]);
+    let after = glib::markup_escape_text(&visible[end..cut]);
+    let ellipsis = if cut < visible.len() { "…" } else { "" };
+
+    let highlighted = match replacement {
+        Some(replacement) => format!(
+            "<span background=\"#f66151\" foreground=\"#000000\" strikethrough=\"true\">{}</span><span background=\"#8ff0a4\" foreground=\"#000000\" weight=\"bold\">{}</span>",
+            matched,
+            glib::markup_escape_text(replacement)
+        ),
+        None => format!(
+            "<span background=\"#f6d32d\" foreground=\"#000000\" weight=\"bold\">{}</span>",
+            matched
+        ),
+    };
+
+    format!("{}{}{}{}", before, highlighted, after, ellipsis)
+}
+
+/// A result row's target: the file and zero-based position to jump to
+#[derive(Clone)]
+struct ResultTarget {
+    path: PathBuf,
+    line: usize,
+    column: usize,
+}
+
+/// A match shown in the results list, with its checkbox and preview label
+struct MatchEntry {
+    line_match: LineMatch,
+    check: CheckButton,
+    label: Label,
+}
+
+/// All results of one file, with the header checkbox that toggles them together
+struct FileResult {
+    path: PathBuf,
+    header_check: CheckButton,
+    matches: Vec<MatchEntry>,
+}
+
+/// Shared state and widgets of the panel, used by its signal handlers
+struct PanelState {
+    results_list: ListBox,
+    status_label: Label,
+    stop_button: Button,
+    replace_toggle: ToggleButton,
+    replace_entry: Entry,
+    apply_button: Button,
+
+    /// Targets for each row of the results list (None for file header rows)
+    targets: RefCell<Vec<Option<ResultTarget>>>,
+    /// Results grouped by file, in display order
+    files: RefCell<Vec<FileResult>>,
+    /// Regex of the last search, used to expand replacements
+    regex: RefCell<Option<Regex>>,
+    /// Whether the last search was a regex search (replacements may use capture groups)
+    regex_mode: Cell<bool>,
+    /// Cancellation flag of the running search, if any
+    cancel_flag: RefCell<Option<Arc<AtomicBool>>>,
+    /// Incremented for every new search so stale results are ignored
+    generation: Cell<u64>,
+    /// Number of files and matches shown so far
+    file_count: Cell<usize>,
+    match_count: Cell<usize>,
+}
+
+/// Creates the "Find in Files" panel for the sidebar
+///
+/// The panel searches the directory that is current when the search starts.
+/// Activate a result to open the file at the match. In replace mode every match
+/// gets a checkbox and a preview, and the checked matches can be replaced at once.
+pub fn create_find_in_files_panel(deps: &NewTabDependencies) -> GtkBox {
+    let panel = GtkBox::new(Orientation::Vertical, 5);
+    panel.add_css_class("find-in-files-panel");
+    panel.set_margin_start(4);
+    panel.set_margin_end(4);
+    panel.set_margin_top(4);
+
+    // Query entry with the same option toggles as the in-buffer find bar
+    let query_entry = Entry::new();
+    query_entry.set_placeholder_text(Some("Search in files"));
+    query_entry.set_hexpand(true);
+
+    let regex_toggle = crate::search::create_option_toggle(".*", "Use regular expressions");
+    let case_toggle = crate::search::create_option_toggle("Aa", "Match case");
+    let word_toggle = crate::search::create_option_toggle("W", "Match whole words only");
+
+    let options_box = GtkBox::new(Orientation::Horizontal, 0);
+    options_box.add_css_class("linked");
+    options_box.append(&regex_toggle);
+    options_box.append(&case_toggle);
+    options_box.append(&word_toggle);
+
+    let replace_toggle = ToggleButton::new();
+    replace_toggle.set_icon_name("edit-find-replace-symbolic");
+    replace_toggle.set_tooltip_text(Some("Replace in files"));
+
+    let query_row = GtkBox::new(Orientation::Horizontal, 4);
+    query_row.append(&query_entry);
+    query_row.append(&options_box);
+    query_row.append(&replace_toggle);
+
+    // Replace row, revealed by the replace toggle
+    let replace_entry = Entry::new();
+    replace_entry.set_placeholder_text(Some("Replace with ($1 for groups)"));
+    replace_entry.set_hexpand(true);
+
+    let apply_button = Button::with_label("Replace Selected");
+    apply_button.set_tooltip_text(Some("Replace the checked matches"));
+    apply_button.set_sensitive(false);
+
+    let replace_row = GtkBox::new(Orientation::Horizontal, 4);
+    replace_row.append(&replace_entry);
+    replace_row.append(&apply_button);
+
+    let replace_revealer = Revealer::new();
+    replace_revealer.set_transition_type(gtk4::RevealerTransitionType::SlideDown);
+    replace_revealer.set_child(Some(&replace_row));
+
+    // Status line with a stop button for long searches
+    let status_label = Label::new(None);
+    status_label.set_halign(gtk4::Align::Start);
+    status_label.set_hexpand(true);
+    status_label.set_ellipsize(pango::EllipsizeMode::End);
+    status_label.add_css_class("caption");
+
+    let stop_button = Button::from_icon_name("process-stop-symbolic");
+    stop_button.set_tooltip_text(Some("Stop searching"));
+    stop_button.add_css_class("flat");
+    stop_button.set_sensitive(false);
+
+    let status_row = GtkBox::new(Orientation::Horizontal, 4);
+    status_row.append(&status_label);
+    status_row.append(&stop_button);
+
+    // Results, grouped by file
+    let results_list = ListBox::new();
+    results_list.set_selection_mode(gtk4::SelectionMode::Single);
+    results_list.add_css_class("find-in-files-results");
+
+    let results_scrolled = ScrolledWindow::builder()
+        .hscrollbar_policy(gtk4::PolicyType::Never)
+        .vscrollbar_policy(gtk4::PolicyType::Automatic)
+        .child(&results_list)
+        .vexpand(true)
+        .build();
+
+    panel.append(&query_row);
+    panel.append(&replace_revealer);
+    panel.append(&status_row);
+    panel.append(&results_scrolled);
+
+    let state = Rc::new(PanelState {
+        results_list: results_list.clone(),
+        status_label,
+        stop_button: stop_button.clone(),
+        replace_toggle: replace_toggle.clone(),
+        replace_entry: replace_entry.clone(),
+        apply_button: apply_button.clone(),
+        targets: RefCell::new(Vec::new()),
+        files: RefCell::new(Vec::new()),
+        regex: RefCell::new(None),
+        regex_mode: Cell::new(false),
+        cancel_flag: RefCell::new(None),
+        generation: Cell::new(0),
+        file_count: Cell::new(0),
+        match_count: Cell::new(0),
+    });
+
+    // Start a search when Enter is pressed in the query entry
+    let state_for_search = state.clone();
+    let current_dir_for_search = deps.current_dir.clone();
+    let regex_toggle_for_search = regex_toggle.clone();
+    let case_toggle_for_search = case_toggle.clone();
+    let word_toggle_for_search = word_toggle.clone();
+    query_entry.connect_activate(move |entry| {
+        let options = SearchOptions {
+            regex: regex_toggle_for_search.is_active(),
+            case_sensitive: case_toggle_for_search.is_active(),
+            whole_word: word_toggle_for_search.is_active(),
+        };
+        let root = current_dir_for_search.borrow().clone();
+        start_search(&state_for_search, &entry.text(), options, root);
+    });
+
+    // Cancel the running search
+    let state_for_stop = state.clone();
+    stop_button.connect_clicked(move |_| {
+        if let Some(flag) = state_for_stop.cancel_flag.borrow().as_ref() {
+            flag.store(true, Ordering::Relaxed);
+        }
+    });
+
+    // Show or hide the replace row, checkboxes and previews
+    let state_for_toggle = state.clone();
+    let replace_revealer_clone = replace_revealer.clone();
+    replace_toggle.connect_toggled(move |toggle| {
+        replace_revealer_clone.set_reveal_child(toggle.is_active());
+        refresh_previews(&state_for_toggle);
+        if toggle.is_active() {
+            state_for_toggle.replace_entry.grab_focus();
+        }
+    });
+
+    // Keep previews in sync with the replacement text
+    let state_for_preview = state.clone();
+    replace_entry.connect_changed(move |_| {
+        refresh_previews(&state_for_preview);
+    });
+
+    // Replace the checked matches
+    let state_for_apply = state.clone();
+    let deps_for_apply = deps.clone();
+    apply_button.connect_clicked(move |_| {
+        apply_replacements(&state_for_apply, &deps_for_apply);
+    });
+    let apply_button_clone = apply_button.clone();
+    replace_entry.connect_activate(move |_| {
+        if apply_button_clone.is_sensitive() {
+            apply_button_clone.emit_clicked();
+        }
+    });
+
+    // Open the file at the match when a result is activated
+    let state_for_activate = state.clone();
+    let deps_for_activate = deps.clone();
+    results_list.connect_row_activated(move |_, row| {
+        let target = state_for_activate
+            .targets
+            .borrow()
+            .get(row.index() as usize)
+            .cloned()
+            .flatten();
+        if let Some(target) = target {
+            crate::handlers::open_file_at_position(
+                &deps_for_activate,
+                &target.path,
+                target.line as i32,
+                target.column as i32,
+            );
+        }
+    });
+
+    panel
+}
+
+/// Cancels the running search (if any) and removes all results from the list
+fn clear_results(state: &PanelState) {
+    if let Some(flag) = state.cancel_flag.borrow_mut().take() {
+        flag.store(true, Ordering::Relaxed);
+    }
+    state.generation.set(state.generation.get() + 1);
+
+    while let Some(child) = state.results_list.first_child() {
+        state.results_list.remove(&child);
+    }
+    state.targets.borrow_mut().clear();
+    state.files.borrow_mut().clear();
+    state.file_count.set(0);
+    state.match_count.set(0);
+    state.stop_button.set_sensitive(false);
+    state.apply_button.set_sensitive(false);
+}
+
+/// Clears the results and starts a new background search
+fn start_search(state: &Rc<PanelState>, query: &str, options: SearchOptions, root: PathBuf) {
+    // Stop the previous search and forget its pending results
+    clear_results(state);
+    let generation = state.generation.get();
+
+    if query.is_empty() {
+        state.status_label.set_text("");
+        return;
+    }
+
+    let regex = match build_regex(query, options) {
+        Ok(regex) => regex,
+        Err(e) => {
+            state.status_label.set_text(&format!("Invalid pattern: {}", e));
+            return;
+        }
+    };
+    *state.regex.borrow_mut() = Some(regex.clone());
+    state.regex_mode.set(options.regex);
+
+    state.status_label.set_text(&format!("Searching in {}…", root.display()));
+    state.stop_button.set_sensitive(true);
+
+    let cancelled = Arc::new(AtomicBool::new(false));
+    *state.cancel_flag.borrow_mut() = Some(cancelled.clone());
+
+    // Scan the tree off the GTK main thread
+    let (sender, receiver) = mpsc::channel();
+    let root_for_thread = root.clone();
+    std::thread::spawn(move || run_search(root_for_thread, regex, cancelled, sender));
+
+    // Drain streamed results on the main loop
+    let state = state.clone();
+    glib::timeout_add_local(Duration::from_millis(50), move || {
+        if state.generation.get() != generation {
+            return glib::ControlFlow::Break; // A newer search replaced this one
+        }
+
+        loop {
+            match receiver.try_recv() {
+                Ok(SearchMessage::FileMatches(path, matches)) => {
+                    append_file_results(&state, &root, &path, matches);
+                    state.status_label.set_text(&format!(
+                        "Searching… {} matches in {} files",
+                        state.match_count.get(),
+                        state.file_count.get()
+                    ));
+                }
+                Ok(SearchMessage::Finished { files_scanned, cancelled, truncated }) => {
+                    let mut summary = format!(
+                        "{} matches in {} files ({} files searched)",
+                        state.match_count.get(),
+                        state.file_count.get(),
+                        files_scanned
+                    );
+                    if truncated {
+                        summary.push_str(" - results truncated");
+                    } else if cancelled {
+                        summary.push_str(" - stopped");
+                    }
+                    state.status_label.set_text(&summary);
+                    state.stop_button.set_sensitive(false);
+                    state.apply_button.set_sensitive(state.match_count.get() > 0);
+                    *state.cancel_flag.borrow_mut() = None;
+                    return glib::ControlFlow::Break;
+                }
+                Err(mpsc::TryRecvError::Empty) => return glib::ControlFlow::Continue,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    state.stop_button.set_sensitive(false);
+                    return glib::ControlFlow::Break;
+                }
+            }
+        }
+    });
+}
+
+/// Appends a file header row followed by one row per match
+fn append_file_results(state: &PanelState, root: &Path, path: &Path, matches: Vec<LineMatch>) {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let replace_mode = state.replace_toggle.is_active();
+
+    // File header: checkbox, bold relative path and match count
+    let header_row = ListBoxRow::new();
+    header_row.set_activatable(false);
+    header_row.set_selectable(false);
+    header_row.add_css_class("find-in-files-file");
+
+    let header_check = CheckButton::new();
+    header_check.set_active(true);
+    header_check.set_visible(replace_mode);
+    header_check.set_tooltip_text(Some("Include every match in this file"));
+
+    let header_label = Label::new(None);
+    header_label.set_halign(gtk4::Align::Start);
+    header_label.set_hexpand(true);
+    header_label.set_ellipsize(pango::EllipsizeMode::Start);
+    header_label.set_markup(&format!(
+        "<span weight=\"bold\">{}</span>  <span alpha=\"60%\">{}</span>",
+        glib::markup_escape_text(&relative.display().to_string()),
+        matches.len()
+    ));
+    header_label.set_tooltip_text(Some(&path.display().to_string()));
+
+    let header_box = GtkBox::new(Orientation::Horizontal, 4);
+    header_box.append(&header_check);
+    header_box.append(&header_label);
+    header_row.set_child(Some(&header_box));
+    state.results_list.append(&header_row);
+    state.targets.borrow_mut().push(None);
+
+    // One row per match: checkbox, line number and highlighted preview
+    let mut entries = Vec::with_capacity(matches.len());
+    for line_match in matches {
+        let row = ListBoxRow::new();
+
+        let check = CheckButton::new();
+        check.set_active(true);
+        check.set_visible(replace_mode);
+
+        let label = Label::new(None);
+        label.set_halign(gtk4::Align::Start);
+        label.set_hexpand(true);
+        label.set_ellipsize(pango::EllipsizeMode::End);
+
+        let row_box = GtkBox::new(Orientation::Horizontal, 4);
+        row_box.set_margin_start(12);
+        row_box.append(&check);
+        row_box.append(&label);
+        row.set_child(Some(&row_box));
+        state.results_list.append(&row);
+
+        state.targets.borrow_mut().push(Some(ResultTarget {
+            path: path.to_path_buf(),
+            line: line_match.line,
+            column: line_match.column,
+        }));
+
+        let entry = MatchEntry { line_match, check, label };
+        update_match_label(state, &entry);
+        entries.push(entry);
+    }
+
+    // The header checkbox toggles all matches of the file
+    let match_checks: Vec<CheckButton> = entries.iter().map(|entry| entry.check.clone()).collect();
+    header_check.connect_toggled(move |header| {
+        for check in &match_checks {
+            check.set_active(header.is_active());
+        }
+    });
+
+    state.file_count.set(state.file_count.get() + 1);
+    state.match_count.set(state.match_count.get() + entries.len());
+    state.files.borrow_mut().push(FileResult {
+        path: path.to_path_buf(),
+        header_check,
+        matches: entries,
+    });
+}
+
+/// Renders a match row, with the replacement preview when replace mode is on
+fn update_match_label(state: &PanelState, entry: &MatchEntry) {
+    let line_match = &entry.line_match;
+    let replacement = if state.replace_toggle.is_active() {
+        state.regex.borrow().as_ref().map(|regex| {
+            expand_replacement(regex, line_match, &state.replace_entry.text(), state.regex_mode.get())
+        })
+    } else {
+        None
+    };
+
+    entry.label.set_markup(&format!(
+        "<span alpha=\"60%\">{}:</span> {}",
+        line_match.line + 1,
+        preview_markup(&line_match.line_text, line_match.start, line_match.end, replacement.as_deref())
+    ));
+}
+
+/// Updates checkbox visibility and previews of every result after a mode or text change
+fn refresh_previews(state: &PanelState) {
+    let replace_mode = state.replace_toggle.is_active();
+    for file in state.files.borrow().iter() {
+        file.header_check.set_visible(replace_mode);
+        for entry in &file.matches {
+            entry.check.set_visible(replace_mode);
+            update_match_label(state, entry);
+        }
+    }
+}
+
+/// A checked match with its computed replacement text
+struct PendingReplacement {
+    line_match: LineMatch,
+    replacement: String,
+}
+
+/// Replaces every checked match and reports the outcome
+///
+/// Files open in a tab are edited through their buffer (one undo step per file),
+/// other files are rewritten on disk. Matches whose line changed since the search
+/// are skipped. Files that cannot be read or written are listed in an error dialog.
+fn apply_replacements(state: &PanelState, deps: &NewTabDependencies) {
+    let Some(regex) = state.regex.borrow().clone() else {
+        return;
+    };
+    let template = state.replace_entry.text().to_string();
+    let use_captures = state.regex_mode.get();
+
+    let mut replaced = 0;
+    let mut skipped = 0;
+    let mut files_changed = 0;
+    let mut failures: Vec<(PathBuf, String)> = Vec::new();
+
+    for file in state.files.borrow().iter() {
+        let pending: Vec<PendingReplacement> = file
+            .matches
+            .iter()
+            .filter(|entry| entry.check.is_active())
+            .map(|entry| PendingReplacement {
+                line_match: entry.line_match.clone(),
+                replacement: expand_replacement(&regex, &entry.line_match, &template, use_captures),
+            })
+            .collect();
+        if pending.is_empty() {
+            continue;
+        }
+
+        let result = match find_open_buffer(deps, &file.path) {
+            Some(buffer) => Ok(replace_in_buffer(&buffer, &pending)),
+            None => replace_in_file(&file.path, &pending),
+        };
+
+        match result {
+            Ok(count) => {
+                replaced += count;
+                skipped += pending.len() - count;
+                if count > 0 {
+                    files_changed += 1;
+                }
+            }
+            Err(e) => {
+                eprintln!("Failed to replace in {:?}: {}", file.path, e);
+                failures.push((file.path.clone(), e));
+            }
+        }
+    }
+
+    // The results no longer match the files, so start over from a clean list
+    clear_results(state);
+
+    let mut summary = format!("Replaced {} occurrences in {} files", replaced, files_changed);
+    if skipped > 0 {
+        summary.push_str(&format!(" - {} skipped because the file changed", skipped));
+    }
+    if !failures.is_empty() {
+        summary.push_str(&format!(" - {} files failed", failures.len()));
+    }
+    state.status_label.set_text(&summary);
+    println!("{}", summary);
+
+    if !failures.is_empty() {
+        let details: Vec<String> = failures
+            .iter()
+            .map(|(path, error)| format!("{}: {}", path.display(), error))
+            .collect();
+        let error_dialog = MessageDialog::new(
+            Some(&deps.window),
+            DialogFlags::MODAL | DialogFlags::DESTROY_WITH_PARENT,
+            MessageType::Error,
+            ButtonsType::Ok,
+            &format!("Could not replace in {} files", failures.len()),
+        );
+        error_dialog.set_secondary_text(Some(&details.join("\n")));
+        error_dialog.connect_response(|dialog, _| dialog.close());
+        error_dialog.show();
+    }
+}
+
+/// Returns the buffer of the tab showing `path`, if the file is open
+fn find_open_buffer(deps: &NewTabDependencies, path: &Path) -> Option<TextBuffer> {
+    let page_num = deps
+        .file_path_manager
+        .borrow()
+        .iter()
+        .find(|(_, open_path)| open_path.as_path() == path)
+        .map(|(page_num, _)| *page_num)?;
+    let page = deps.editor_notebook.nth_page(Some(page_num))?;
+    crate::handlers::find_text_view_in_page(&page).map(|text_view| text_view.buffer())
+}
+
+/// Groups replacements by line, last line first, each line's matches last first
+///
+/// Applying edits in this order keeps the positions of the remaining edits valid.
+fn group_by_line_reversed(pending: &[PendingReplacement]) -> Vec<Vec<&PendingReplacement>> {
+    let mut sorted: Vec<&PendingReplacement> = pending.iter().collect();
+    sorted.sort_by(|a, b| {
+        (b.line_match.line, b.line_match.start).cmp(&(a.line_match.line, a.line_match.start))
+    });
+
+    let mut groups: Vec<Vec<&PendingReplacement>> = Vec::new();
+    for item in sorted {
+        match groups.last_mut() {
+            Some(group) if group[0].line_match.line == item.line_match.line => group.push(item),
+            _ => groups.push(vec![item]),
+        }
+    }
+    groups
+}
+
+/// Applies replacements to an open buffer as a single undoable action
+///
+/// Returns the number of matches replaced.
+fn replace_in_buffer(buffer: &TextBuffer, pending: &[PendingReplacement]) -> usize {
+    let mut count = 0;
+    buffer.begin_user_action();
+
+    for group in group_by_line_reversed(pending) {
+        let line = group[0].line_match.line as i32;
+        let Some(line_start) = buffer.iter_at_line(line) else {
+            continue;
+        };
+        let mut line_end = line_start.clone();
+        if !line_end.ends_line() {
+            line_end.forward_to_line_end();
+        }
+
+        // Only touch lines that still look the way they did during the search
+        if buffer.text(&line_start, &line_end, true).as_str() != group[0].line_match.line_text {
+            continue;
+        }
+
+        for item in group {
+            let line_match = &item.line_match;
+            let end_column = line_match.column + line_match.line_text[line_match.start..line_match.end].chars().count();
+            let (Some(mut start), Some(mut end)) = (
+                buffer.iter_at_line_offset(line, line_match.column as i32),
+                buffer.iter_at_line_offset(line, end_column as i32),
+            ) else {
+                continue;
+            };
+            buffer.delete(&mut start, &mut end);
+            buffer.insert(&mut start, &item.replacement);
+            count += 1;
+        }
+    }
+
+    buffer.end_user_action();
+    count
+}
+
+/// Applies replacements to a file on disk
+///
+/// Returns the number of matches replaced, or a description of the read/write error.
+fn replace_in_file(path: &Path, pending: &[PendingReplacement]) -> Result<usize, String> {
+    let mut content = std::fs::read_to_string(path).map_err(|e| format!("read failed: {}", e))?;
+
+    // Byte offset and text (without "\n" or "\r\n") of every line, like str::lines()
+    let mut lines: Vec<(usize, &str)> = Vec::new();
+    let mut offset = 0;
+    for raw_line in content.split_inclusive('\n') {
+        let text = raw_line.strip_suffix('\n').unwrap_or(raw_line);
+        let text = text.strip_suffix('\r').unwrap_or(text);
+        lines.push((offset, text));
+        offset += raw_line.len();
+    }
+
+    // Collect byte-range edits for lines that did not change since the search
+    let mut edits: Vec<(usize, usize, &str)> = Vec::new();
+    for group in group_by_line_reversed(pending) {
+        let line_match = &group[0].line_match;
+        match lines.get(line_match.line) {
+            Some((line_offset, text)) if *text == line_match.line_text => {
+                for item in group {
+                    edits.push((
+                        line_offset + item.line_match.start,
+                        line_offset + item.line_match.end,
+                        item.replacement.as_str(),
+                    ));
+                }
+            }
+            _ => continue,
+        }
+    }
+
+    if edits.is_empty() {
+        return Ok(0);
+    }
+
+    // Edits are ordered from the end of the file, so earlier offsets stay valid
+    let count = edits.len();
+    for (start, end, replacement) in edits {
+        content.replace_range(start..end, replacement);
+    }
+
+    std::fs::write(path, content).map_err(|e| format!("write failed: {}", e))?;
+    Ok(count)
+}
+
+/// Shows the Find in Files page of the sidebar and focuses its query entry
+///
+/// If the active editor has a single-line selection, it becomes the query.
+pub fn show_find_in_files(sidebar_stack: &Stack, editor_notebook: Option<&Notebook>) {
+    sidebar_stack.set_visible_child_name(SIDEBAR_PAGE_NAME);
+
+    let Some(page) = sidebar_stack.child_by_name(SIDEBAR_PAGE_NAME) else {
+        return;
+    };
+    let Some(entry) = crate::search::first_entry(&page) else {
+        return;
+    };
+
+    if let Some(notebook) = editor_notebook {
+        if let Some((_, buffer)) = crate::handlers::get_active_text_view_and_buffer(notebook) {
+            if let Some((start, end)) = buffer.selection_bounds() {
+                let selected = buffer.text(&start, &end, false);
+                if !selected.contains('\n') {
+                    entry.set_text(&selected);
+                }
+            }
+        }
+    }
+
+    entry.grab_focus();
+    entry.select_region(0, -1);
+}
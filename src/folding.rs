@@ -0,0 +1,615 @@
+// Code folding for the text editor
+// Computes fold regions from brackets, indentation and comments and shows fold markers in the gutter
+
+use gtk4::prelude::*;
+use gtk4::{gio, glib, TextBuffer, TextIter, TextMark, TextTag};
+use sourceview5::{prelude::*, Buffer, GutterRendererText, View};
+use std::cell::{Cell, RefCell};
+use std::rc::{Rc, Weak};
+use std::time::Duration;
+
+/// Marker shown next to an expanded fold region
+const EXPANDED_MARKER: &str = "▾";
+
+/// Marker shown next to a collapsed fold region
+const COLLAPSED_MARKER: &str = "▸";
+
+/// Delay before fold regions are recomputed after an edit
+const RECOMPUTE_DELAY: Duration = Duration::from_millis(300);
+
+/// Languages whose blocks are defined by indentation rather than brackets
+const INDENTATION_LANGUAGES: &[&str] = &["python", "python3", "yaml", "nim", "coffee", "haml", "pug"];
+
+/// A foldable range of lines
+///
+/// `start_line` stays visible; lines `start_line + 1 ..= end_line` are hidden when folded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FoldRegion {
+    pub start_line: i32,
+    pub end_line: i32,
+    /// Nesting depth, 1 for top-level regions
+    pub depth: usize,
+}
+
+/// A collapsed region: marks around the hidden text and the tag hiding it
+///
+/// Marks move with the text, so folds stay in place when the buffer is edited elsewhere.
+struct Fold {
+    start: TextMark,
+    end: TextMark,
+    tag: TextTag,
+}
+
+/// Folding state of one view
+struct FoldState {
+    view: glib::WeakRef<View>,
+    renderer: GutterRendererText,
+    regions: RefCell<Vec<FoldRegion>>,
+    folds: RefCell<Vec<Fold>>,
+    /// Incremented on every edit so only the last scheduled recompute runs
+    generation: Cell<u64>,
+}
+
+/// Adds fold markers to the gutter of a source view and enables the fold shortcuts
+///
+/// - Click a marker to fold or unfold its region
+/// - Ctrl+Shift+[ / Ctrl+Shift+] fold / unfold the region at the cursor
+/// - Ctrl+Alt+[ / Ctrl+Alt+] fold / unfold everything
+/// - Ctrl+Alt+1..9 fold to level N
+///
+/// The keys are the defaults of the "fold" actions and can be changed in keybindings.conf.
+pub fn attach(view: &View) {
+    let renderer = GutterRendererText::new();
+    renderer.set_xpad(4);
+    renderer.add_css_class("fold-markers");
+
+    let state = Rc::new(FoldState {
+        view: view.downgrade(),
+        renderer: renderer.clone(),
+        regions: RefCell::new(Vec::new()),
+        folds: RefCell::new(Vec::new()),
+        generation: Cell::new(0),
+    });
+
+    // Show a marker on every line that starts a region
+    let weak_state = Rc::downgrade(&state);
+    renderer.connect_query_data(move |renderer, _, line| {
+        let marker = weak_state
+            .upgrade()
+            .and_then(|state| marker_for_line(&state, line as i32))
+            .unwrap_or("");
+        renderer.set_text(marker, -1);
+    });
+
+    // Only lines with a marker react to clicks
+    let weak_state = Rc::downgrade(&state);
+    renderer.connect_query_activatable(move |_, iter, _| {
+        weak_state
+            .upgrade()
+            .map(|state| marker_for_line(&state, iter.line()).is_some())
+            .unwrap_or(false)
+    });
+
+    let weak_state = Rc::downgrade(&state);
+    renderer.connect_activate(move |_, iter, _, _, _, _| {
+        if let Some(state) = weak_state.upgrade() {
+            toggle_fold_at_line(&state, iter.line());
+        }
+    });
+
+    view.gutter(gtk4::TextWindowType::Left).insert(&renderer, 10);
+
+    // Recompute regions after edits and once highlighting has classified strings and comments
+    let buffer = view.buffer();
+    let weak_state = Rc::downgrade(&state);
+    buffer.connect_changed(move |_| {
+        if let Some(state) = weak_state.upgrade() {
+            schedule_recompute(&state);
+        }
+    });
+    if let Ok(source_buffer) = buffer.clone().downcast::<Buffer>() {
+        let weak_state = Rc::downgrade(&state);
+        source_buffer.connect_highlight_updated(move |_, _, _| {
+            if let Some(state) = weak_state.upgrade() {
+                schedule_recompute(&state);
+            }
+        });
+    }
+
+    // Moving the cursor into hidden text (search, go to line) unfolds it
+    let weak_state = Rc::downgrade(&state);
+    buffer.connect_notify_local(Some("cursor-position"), move |buffer, _| {
+        if let Some(state) = weak_state.upgrade() {
+            let cursor = buffer.iter_at_mark(&buffer.get_insert());
+            unfold_containing(&state, &cursor);
+        }
+    });
+
+    // Fold commands, also offered in the view's context menu
+    view.insert_action_group("fold", Some(&create_fold_actions(&state)));
+    let fold_menu = gio::Menu::new();
+    fold_menu.append(Some("Toggle Fold"), Some("fold.toggle"));
+    fold_menu.append(Some("Fold All"), Some("fold.fold-all"));
+    fold_menu.append(Some("Unfold All"), Some("fold.unfold-all"));
+    let level_menu = gio::Menu::new();
+    for level in 1..=5 {
+        level_menu.append(Some(&format!("Level {}", level)), Some(&format!("fold.fold-level({})", level)));
+    }
+    fold_menu.append_submenu(Some("Fold to Level"), &level_menu);
+    crate::syntax::add_context_menu_section(view, &fold_menu);
+
+    schedule_recompute(&state);
+}
+
+/// Creates the "fold" action group of a view
+///
+/// Actions: toggle, fold and unfold (region at the cursor), fold-all, unfold-all
+/// and fold-level(int32).
+/// The group is owned by the view, so its actions keep the folding state alive
+/// exactly as long as the view; every other handler only holds a weak reference.
+fn create_fold_actions(state: &Rc<FoldState>) -> gio::SimpleActionGroup {
+    let action_group = gio::SimpleActionGroup::new();
+
+    let toggle_action = gio::SimpleAction::new("toggle", None);
+    let state_for_toggle = state.clone();
+    toggle_action.connect_activate(move |_, _| {
+        crate::macros::record_action("fold.toggle");
+        toggle_fold_at_cursor(&state_for_toggle);
+    });
+    action_group.add_action(&toggle_action);
+
+    // Fold or unfold the region at the cursor, leaving it alone when it already is
+    for (name, fold) in [("fold", true), ("unfold", false)] {
+        let action = gio::SimpleAction::new(name, None);
+        let state = state.clone();
+        action.connect_activate(move |_, _| {
+            crate::macros::record_action(&format!("fold.{}", name));
+            set_fold_at_cursor(&state, fold);
+        });
+        action_group.add_action(&action);
+    }
+
+    let fold_all_action = gio::SimpleAction::new("fold-all", None);
+    let state_for_fold_all = state.clone();
+    fold_all_action.connect_activate(move |_, _| {
+        crate::macros::record_action("fold.fold-all");
+        fold_to_level(&state_for_fold_all, 1);
+    });
+    action_group.add_action(&fold_all_action);
+
+    let unfold_all_action = gio::SimpleAction::new("unfold-all", None);
+    let state_for_unfold_all = state.clone();
+    unfold_all_action.connect_activate(move |_, _| {
+        crate::macros::record_action("fold.unfold-all");
+        unfold_all_regions(&state_for_unfold_all);
+    });
+    action_group.add_action(&unfold_all_action);
+
+    let fold_level_action = gio::SimpleAction::new("fold-level", Some(glib::VariantTy::INT32));
+    let state_for_fold_level = state.clone();
+    fold_level_action.connect_activate(move |_, parameter| {
+        let level = parameter.and_then(|p| p.get::<i32>()).unwrap_or(1).max(1);
+        crate::macros::record_action(&format!("fold.fold-level({})", level));
+        fold_to_level(&state_for_fold_level, level as usize);
+    });
+    action_group.add_action(&fold_level_action);
+
+    action_group
+}
+
+/// Computes the fold regions of a buffer
+///
+/// Brackets are used for most languages, skipping those inside strings and comments
+/// when syntax highlighting is available. Indentation is used for indentation-based
+/// languages and as a fallback when no bracket regions exist. Multi-line comments
+/// are foldable too.
+pub fn compute_fold_regions(buffer: &TextBuffer, tab_width: usize) -> Vec<FoldRegion> {
+    let source_buffer = buffer.downcast_ref::<Buffer>();
+    let text = buffer.text(&buffer.start_iter(), &buffer.end_iter(), true).to_string();
+
+    let language_id = source_buffer
+        .and_then(|b| b.language())
+        .map(|language| language.id().to_string());
+    let indentation_language = language_id
+        .as_deref()
+        .map(|id| INDENTATION_LANGUAGES.contains(&id))
+        .unwrap_or(false);
+
+    let mut ranges = if indentation_language {
+        Vec::new()
+    } else {
+        bracket_ranges(buffer, source_buffer, &text)
+    };
+    if ranges.is_empty() {
+        ranges = indentation_ranges(&text, tab_width);
+    }
+    if let Some(source_buffer) = source_buffer {
+        ranges.extend(comment_ranges(source_buffer));
+    }
+
+    assign_depths(ranges)
+}
+
+/// Finds multi-line bracket pairs; the closing bracket's line stays visible
+fn bracket_ranges(buffer: &TextBuffer, source_buffer: Option<&Buffer>, text: &str) -> Vec<(i32, i32)> {
+    let mut ranges = Vec::new();
+    let mut stack: Vec<(char, i32)> = Vec::new();
+    let mut line = 0;
+
+    for (offset, character) in text.chars().enumerate() {
+        match character {
+            '\n' => line += 1,
+            '{' | '[' | '(' | ')' | ']' | '}' => {
+                // Brackets inside strings and comments do not delimit blocks
+                if let Some(source_buffer) = source_buffer {
+                    let iter = buffer.iter_at_offset(offset as i32);
+                    if source_buffer.iter_has_context_class(&iter, "string")
+                        || source_buffer.iter_has_context_class(&iter, "comment")
+                    {
+                        continue;
+                    }
+                }
+
+                match character {
+                    '{' | '[' | '(' => stack.push((character, line)),
+                    _ => {
+                        let expected = match character {
+                            ')' => '(',
+                            ']' => '[',
+                            _ => '{',
+                        };
+                        if let Some(position) = stack.iter().rposition(|(open, _)| *open == expected) {
+                            let (_, open_line) = stack[position];
+                            stack.truncate(position);
+                            if line - open_line >= 2 {
+                                ranges.push((open_line, line - 1));
+                            }
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    ranges
+}
+
+/// Finds blocks of lines indented deeper than the line before them
+fn indentation_ranges(text: &str, tab_width: usize) -> Vec<(i32, i32)> {
+    // Indentation width of each line, None for blank lines
+    let indents: Vec<Option<usize>> = text
+        .lines()
+        .map(|line| {
+            if line.trim().is_empty() {
+                return None;
+            }
+            let width = line
+                .chars()
+                .take_while(|c| c.is_whitespace())
+                .map(|c| if c == '\t' { tab_width } else { 1 })
+                .sum();
+            Some(width)
+        })
+        .collect();
+
+    let mut ranges = Vec::new();
+    for (line, indent) in indents.iter().enumerate() {
+        let Some(indent) = *indent else {
+            continue;
+        };
+
+        // The block runs until the next non-blank line that is not indented deeper
+        let mut end = None;
+        for (next_line, next_indent) in indents.iter().enumerate().skip(line + 1) {
+            match next_indent {
+                None => continue,
+                Some(next_indent) if *next_indent > indent => end = Some(next_line),
+                Some(_) => break,
+            }
+        }
+
+        if let Some(end) = end {
+            ranges.push((line as i32, end as i32));
+        }
+    }
+
+    ranges
+}
+
+/// Finds comments that span several lines
+fn comment_ranges(buffer: &Buffer) -> Vec<(i32, i32)> {
+    let mut ranges = Vec::new();
+    let mut iter = buffer.start_iter();
+
+    loop {
+        if !buffer.iter_has_context_class(&iter, "comment")
+            && !buffer.iter_forward_to_context_class_toggle(&mut iter, "comment")
+        {
+            break;
+        }
+        let start_line = iter.line();
+        if !buffer.iter_forward_to_context_class_toggle(&mut iter, "comment") {
+            iter = buffer.end_iter();
+        }
+
+        // The toggle is just after the comment, which may be the start of the next line
+        let mut last = iter.clone();
+        last.backward_char();
+        if last.line() > start_line {
+            ranges.push((start_line, last.line()));
+        }
+        if iter.is_end() {
+            break;
+        }
+    }
+
+    ranges
+}
+
+/// Removes duplicate start lines (keeping the largest region) and computes nesting depths
+fn assign_depths(mut ranges: Vec<(i32, i32)>) -> Vec<FoldRegion> {
+    ranges.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+    ranges.dedup_by_key(|range| range.0);
+
+    let mut regions = Vec::with_capacity(ranges.len());
+    let mut open_ends: Vec<i32> = Vec::new();
+    for (start_line, end_line) in ranges {
+        while open_ends.last().map(|end| *end < start_line).unwrap_or(false) {
+            open_ends.pop();
+        }
+        regions.push(FoldRegion { start_line, end_line, depth: open_ends.len() + 1 });
+        open_ends.push(end_line);
+    }
+    regions
+}
+
+/// Recomputes the regions shortly after the last edit
+fn schedule_recompute(state: &Rc<FoldState>) {
+    let generation = state.generation.get() + 1;
+    state.generation.set(generation);
+
+    let weak_state: Weak<FoldState> = Rc::downgrade(state);
+    glib::timeout_add_local_once(RECOMPUTE_DELAY, move || {
+        let Some(state) = weak_state.upgrade() else {
+            return;
+        };
+        if state.generation.get() != generation {
+            return; // A later edit scheduled another recompute
+        }
+        let Some(view) = state.view.upgrade() else {
+            return;
+        };
+
+        let regions = compute_fold_regions(&view.buffer(), view.tab_width() as usize);
+        *state.regions.borrow_mut() = regions;
+        state.renderer.queue_draw();
+    });
+}
+
+/// Returns the gutter marker for a line, if a region starts there
+fn marker_for_line(state: &FoldState, line: i32) -> Option<&'static str> {
+    if find_fold_at_line(state, line).is_some() {
+        return Some(COLLAPSED_MARKER);
+    }
+    state
+        .regions
+        .borrow()
+        .iter()
+        .any(|region| region.start_line == line)
+        .then_some(EXPANDED_MARKER)
+}
+
+/// Returns the innermost region that starts at or contains a line
+fn innermost_region_at(state: &FoldState, line: i32) -> Option<FoldRegion> {
+    state
+        .regions
+        .borrow()
+        .iter()
+        .filter(|region| region.start_line <= line && line <= region.end_line)
+        .max_by_key(|region| region.depth)
+        .copied()
+}
+
+/// Returns the index of the fold whose visible line is `line`
+fn find_fold_at_line(state: &FoldState, line: i32) -> Option<usize> {
+    let view = state.view.upgrade()?;
+    let buffer = view.buffer();
+    state
+        .folds
+        .borrow()
+        .iter()
+        .position(|fold| buffer.iter_at_mark(&fold.start).line() == line)
+}
+
+/// Folds the innermost region at the cursor, or unfolds it when it is folded
+fn toggle_fold_at_cursor(state: &FoldState) {
+    let Some(view) = state.view.upgrade() else {
+        return;
+    };
+    let buffer = view.buffer();
+    let line = buffer.iter_at_mark(&buffer.get_insert()).line();
+    if let Some(region) = innermost_region_at(state, line) {
+        toggle_fold_at_line(state, region.start_line);
+    }
+}
+
+/// Folds (or unfolds) the innermost region containing the cursor
+fn set_fold_at_cursor(state: &FoldState, fold: bool) {
+    let Some(view) = state.view.upgrade() else {
+        return;
+    };
+    let buffer = view.buffer();
+    let line = buffer.iter_at_mark(&buffer.get_insert()).line();
+    if let Some(region) = innermost_region_at(state, line) {
+        let folded = find_fold_at_line(state, region.start_line).is_some();
+        if folded != fold {
+            toggle_fold_at_line(state, region.start_line);
+        }
+    }
+}
+
+/// Folds the region starting at a line, or unfolds it when it is folded
+fn toggle_fold_at_line(state: &FoldState, line: i32) {
+    if let Some(index) = find_fold_at_line(state, line) {
+        remove_fold(state, index);
+    } else {
+        let region = state.regions.borrow().iter().find(|region| region.start_line == line).copied();
+        if let Some(region) = region {
+            add_fold(state, &region);
+        }
+    }
+    state.renderer.queue_draw();
+}
+
+/// Hides the lines of a region
+fn add_fold(state: &FoldState, region: &FoldRegion) {
+    let Some(view) = state.view.upgrade() else {
+        return;
+    };
+    let buffer = view.buffer();
+
+    // Hide from the end of the first line to the end of the last hidden line,
+    // so the first line is directly followed by the line after the region
+    let (Some(mut start), Some(mut end)) = (buffer.iter_at_line(region.start_line), buffer.iter_at_line(region.end_line)) else {
+        return;
+    };
+    if !start.ends_line() {
+        start.forward_to_line_end();
+    }
+    if !end.ends_line() {
+        end.forward_to_line_end();
+    }
+    if start.offset() >= end.offset() {
+        return;
+    }
+
+    // Keep the cursor visible by moving it out of the hidden text
+    let cursor = buffer.iter_at_mark(&buffer.get_insert());
+    if cursor.offset() > start.offset() && cursor.offset() <= end.offset() {
+        buffer.place_cursor(&start);
+    }
+
+    let Some(tag) = buffer.create_tag(None, &[("invisible", &true)]) else {
+        return;
+    };
+    buffer.apply_tag(&tag, &start, &end);
+
+    state.folds.borrow_mut().push(Fold {
+        start: buffer.create_mark(None, &start, true),
+        end: buffer.create_mark(None, &end, false),
+        tag,
+    });
+}
+
+/// Shows the lines of a fold again
+fn remove_fold(state: &FoldState, index: usize) {
+    let Some(view) = state.view.upgrade() else {
+        return;
+    };
+    let buffer = view.buffer();
+
+    let fold = state.folds.borrow_mut().remove(index);
+    let (start, end) = (buffer.iter_at_mark(&fold.start), buffer.iter_at_mark(&fold.end));
+    buffer.remove_tag(&fold.tag, &start, &end);
+    buffer.tag_table().remove(&fold.tag);
+    buffer.delete_mark(&fold.start);
+    buffer.delete_mark(&fold.end);
+}
+
+/// Unfolds every fold hiding the given position
+fn unfold_containing(state: &FoldState, iter: &TextIter) {
+    let Some(view) = state.view.upgrade() else {
+        return;
+    };
+    let buffer = view.buffer();
+
+    let mut changed = false;
+    loop {
+        let index = state.folds.borrow().iter().position(|fold| {
+            let (start, end) = (buffer.iter_at_mark(&fold.start), buffer.iter_at_mark(&fold.end));
+            iter.offset() > start.offset() && iter.offset() < end.offset()
+        });
+        match index {
+            Some(index) => {
+                remove_fold(state, index);
+                changed = true;
+            }
+            None => break,
+        }
+    }
+
+    if changed {
+        state.renderer.queue_draw();
+    }
+}
+
+/// Unfolds every fold
+fn unfold_all_regions(state: &FoldState) {
+    while !state.folds.borrow().is_empty() {
+        remove_fold(state, 0);
+    }
+    state.renderer.queue_draw();
+}
+
+/// Unfolds everything, then folds the regions at `level` and deeper
+fn fold_to_level(state: &FoldState, level: usize) {
+    unfold_all_regions(state);
+
+    let regions: Vec<FoldRegion> = state
+        .regions
+        .borrow()
+        .iter()
+        .filter(|region| region.depth >= level)
+        .copied()
+        .collect();
+    for region in &regions {
+        add_fold(state, region);
+    }
+    state.renderer.queue_draw();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "fn main() {\n    let a = 1;\n    let b = 2;\n}\n";
+
+    /// Returns a view showing SOURCE with the body of `main` folded
+    fn folded_view() -> View {
+        sourceview5::init();
+        let view = View::new();
+        view.buffer().set_text(SOURCE);
+        let state = FoldState {
+            view: view.downgrade(),
+            renderer: GutterRendererText::new(),
+            regions: RefCell::new(Vec::new()),
+            folds: RefCell::new(Vec::new()),
+            generation: Cell::new(0),
+        };
+        add_fold(&state, &FoldRegion { start_line: 0, end_line: 2, depth: 1 });
+        view
+    }
+
+    #[gtk4::test]
+    fn saving_writes_the_folded_lines() {
+        let buffer = folded_view().buffer();
+        assert_eq!(buffer.text(&buffer.start_iter(), &buffer.end_iter(), false), "fn main() {\n}\n");
+
+        let path = std::env::temp_dir().join(format!("folding-save-test-{}.rs", std::process::id()));
+        crate::editorconfig::save_buffer(&buffer, &path).unwrap();
+        let saved = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(saved, SOURCE);
+    }
+
+    #[gtk4::test]
+    fn folding_keeps_the_regions() {
+        let folded = folded_view().buffer();
+        let unfolded = TextBuffer::new(None);
+        unfolded.set_text(SOURCE);
+        let regions = compute_fold_regions(&folded, 4);
+        assert!(!regions.is_empty());
+        assert_eq!(regions, compute_fold_regions(&unfolded, 4));
+    }
+}
@@ -0,0 +1,143 @@
+// Go to line/column for the text editor
+// Ctrl+G opens a small dialog that accepts "line[:column]" and jumps there in the active tab
+
+use gtk4::prelude::*;
+use gtk4::{glib, ApplicationWindow, Box as GtkBox, Dialog, Entry, Label, Notebook, Orientation, ResponseType, TextBuffer, TextView};
+use std::time::Duration;
+
+use crate::handlers;
+
+/// Name of the text tag used to flash the target line
+const HIGHLIGHT_TAG_NAME: &str = "goto-line-highlight";
+
+/// How long the target line stays highlighted
+const HIGHLIGHT_DURATION: Duration = Duration::from_millis(1500);
+
+/// Parses a "line[:column]" position
+///
+/// Both numbers are one-based as typed by the user (and as printed by compilers).
+/// Trailing text after the column (e.g. "12:5: error") is ignored. Returns the
+/// zero-based line and column.
+pub fn parse_line_column(text: &str) -> Option<(i32, i32)> {
+    let mut parts = text.trim().split(':').map(str::trim);
+
+    let line: i32 = parts.next()?.parse().ok()?;
+    if line < 1 {
+        return None;
+    }
+
+    let column: i32 = match parts.next() {
+        None | Some("") => 1,
+        Some(column) => column.parse().ok().filter(|c| *c >= 1)?,
+    };
+
+    Some((line - 1, column - 1))
+}
+
+/// Shows the Go to Line dialog for the active tab
+///
+/// Does nothing when the active tab has no text view (e.g. an image).
+pub fn show_goto_line_dialog(window: &ApplicationWindow, notebook: &Notebook) {
+    let Some((text_view, buffer)) = handlers::get_active_text_view_and_buffer(notebook) else {
+        return;
+    };
+
+    let dialog = Dialog::builder()
+        .title("Go to Line")
+        .transient_for(window)
+        .modal(true)
+        .destroy_with_parent(true)
+        .use_header_bar(1)
+        .build();
+    dialog.add_css_class("goto-line");
+    dialog.add_button("Cancel", ResponseType::Cancel);
+    dialog.add_button("Go", ResponseType::Accept);
+    dialog.set_default_response(ResponseType::Accept);
+
+    let content_area = dialog.content_area();
+    content_area.set_margin_top(10);
+    content_area.set_margin_bottom(10);
+    content_area.set_margin_start(10);
+    content_area.set_margin_end(10);
+
+    let cursor = buffer.iter_at_mark(&buffer.get_insert());
+    let hint_label = Label::new(Some(&format!(
+        "Line (1 - {}), optionally followed by :column",
+        buffer.line_count()
+    )));
+    hint_label.set_halign(gtk4::Align::Start);
+    hint_label.add_css_class("caption");
+
+    // Prefill with the current position so small adjustments are quick
+    let entry = Entry::new();
+    entry.set_placeholder_text(Some("line[:column]"));
+    entry.set_text(&format!("{}:{}", cursor.line() + 1, cursor.line_offset() + 1));
+    entry.set_activates_default(true);
+    entry.set_width_chars(24);
+
+    let container = GtkBox::new(Orientation::Vertical, 6);
+    container.append(&hint_label);
+    container.append(&entry);
+    content_area.append(&container);
+
+    // Flag invalid input while typing
+    entry.connect_changed(|entry| {
+        if entry.text().is_empty() || parse_line_column(&entry.text()).is_some() {
+            entry.remove_css_class("error");
+        } else {
+            entry.add_css_class("error");
+        }
+    });
+
+    let entry_for_response = entry.clone();
+    dialog.connect_response(move |dialog, response| {
+        if response == ResponseType::Accept {
+            match parse_line_column(&entry_for_response.text()) {
+                Some((line, column)) => go_to_position(&text_view, &buffer, line, column),
+                None => {
+                    // Keep the dialog open so the input can be corrected
+                    entry_for_response.add_css_class("error");
+                    entry_for_response.grab_focus();
+                    return;
+                }
+            }
+        }
+        dialog.close();
+    });
+
+    dialog.present();
+    entry.grab_focus();
+    entry.select_region(0, -1);
+}
+
+/// Moves the cursor to a zero-based position, scrolls to it and flashes the line
+pub fn go_to_position(text_view: &TextView, buffer: &TextBuffer, line: i32, column: i32) {
+    handlers::move_cursor_to_line_column(text_view, buffer, line, column);
+    highlight_current_line(buffer);
+}
+
+/// Briefly highlights the line holding the cursor
+fn highlight_current_line(buffer: &TextBuffer) {
+    let tag_table = buffer.tag_table();
+    let tag = match tag_table.lookup(HIGHLIGHT_TAG_NAME) {
+        Some(tag) => tag,
+        None => match buffer.create_tag(Some(HIGHLIGHT_TAG_NAME), &[("background", &"rgba(246, 211, 45, 0.35)")]) {
+            Some(tag) => tag,
+            None => return,
+        },
+    };
+
+    // Clear any earlier highlight before flashing the new line
+    buffer.remove_tag(&tag, &buffer.start_iter(), &buffer.end_iter());
+
+    let mut line_start = buffer.iter_at_mark(&buffer.get_insert());
+    line_start.set_line_offset(0);
+    let mut line_end = line_start.clone();
+    line_end.forward_line();
+    buffer.apply_tag(&tag, &line_start, &line_end);
+
+    let buffer_clone = buffer.clone();
+    glib::timeout_add_local_once(HIGHLIGHT_DURATION, move || {
+        buffer_clone.remove_tag(&tag, &buffer_clone.start_iter(), &buffer_clone.end_iter());
+    });
+}
@@ -19,9 +19,7 @@ use gtk4::{
 use std::collections::HashMap;  // For mapping tab indices to file paths
 use std::rc::Rc;                // Reference counting for shared ownership
 use std::cell::RefCell;         // Interior mutability pattern
-use std::path::PathBuf;         // File system path representation
-use std::fs::File;              // File operations
-use std::io::Write;             // File writing capabilities
+use std::path::{Path, PathBuf}; // File system path representation
 
 // Internal imports
 use crate::utils;               // Utility functions
@@ -32,29 +30,8 @@ use crate::utils;               // Utility functions
 /// Returns None if there is no active tab or if the tab doesn't contain a text view
 /// (e.g., if it's showing an image instead).
 pub fn get_active_text_view_and_buffer(notebook: &Notebook) -> Option<(TextView, TextBuffer)> {
-    // Get the current page number, then use it to find the page widget
-    notebook.current_page().and_then(|page_num| {
-        notebook.nth_page(Some(page_num)).and_then(|page_widget| {
-            // Check if the page contains a ScrolledWindow (typical for text content)
-            if let Some(scrolled_window) = page_widget.downcast_ref::<ScrolledWindow>() {
-                // Get the child of the ScrolledWindow
-                scrolled_window.child().and_then(|child| {
-                    // Try to cast the child to a TextView
-                    if let Some(text_view) = child.downcast_ref::<TextView>() {
-                        // Return the TextView and its associated TextBuffer
-                        Some((text_view.clone(), text_view.buffer()))
-                    } else {
-                        // Child exists but is not a TextView
-                        None
-                    }
-                })
-            } else {
-                // Page widget is not a ScrolledWindow
-                // This happens for non-text content like images
-                None
-            }
-        })
-    })
+    // Get the current page number, then look up the text view on that page
+    notebook.current_page().and_then(|page_num| get_text_view_and_buffer_for_page(notebook, page_num))
 }
 
 /// Gets the TextView and TextBuffer for a specific notebook tab by index
@@ -64,24 +41,41 @@ pub fn get_active_text_view_and_buffer(notebook: &Notebook) -> Option<(TextView,
 pub fn get_text_view_and_buffer_for_page(notebook: &Notebook, page_num: u32) -> Option<(TextView, TextBuffer)> {
     // Get the page widget for the specified page number
     notebook.nth_page(Some(page_num)).and_then(|page_widget| {
-        // Check if the page contains a ScrolledWindow
-        if let Some(scrolled_window) = page_widget.downcast_ref::<ScrolledWindow>() {
-            // Get the child of the ScrolledWindow
-            scrolled_window.child().and_then(|child| {
-                // Try to cast the child to a TextView
-                if let Some(text_view) = child.downcast_ref::<TextView>() {
-                    // Return the TextView and its associated TextBuffer
-                    Some((text_view.clone(), text_view.buffer()))
-                } else {
-                    // Child exists but is not a TextView
-                    None
+        find_text_view_in_page(&page_widget).map(|text_view| {
+            // Return the TextView and its associated TextBuffer
+            let buffer = text_view.buffer();
+            (text_view, buffer)
+        })
+    })
+}
+
+/// Finds the editing TextView inside a notebook page widget
+///
+/// Text tabs are editor pages (a vertical box with the find bar above a ScrolledWindow),
+/// while older or non-text tabs are a bare ScrolledWindow. Returns None for pages that
+/// don't contain a text view (e.g., images).
+pub fn find_text_view_in_page(page_widget: &gtk4::Widget) -> Option<TextView> {
+    // A bare ScrolledWindow: the text view, if any, is its direct child
+    if let Some(scrolled_window) = page_widget.downcast_ref::<ScrolledWindow>() {
+        return scrolled_window.child().and_then(|child| child.downcast::<TextView>().ok());
+    }
+
+    // An editor page: look for the ScrolledWindow among the page's children
+    // (possibly inside a row that also holds the minimap, under the guides overlay)
+    if page_widget.is::<gtk4::Box>() || page_widget.is::<gtk4::Overlay>() {
+        let mut child = page_widget.first_child();
+        while let Some(current_child) = child {
+            if current_child.is::<ScrolledWindow>() || current_child.is::<gtk4::Box>() || current_child.is::<gtk4::Overlay>() {
+                if let Some(text_view) = find_text_view_in_page(&current_child) {
+                    return Some(text_view);
                 }
-            })
-        } else {
-            // Page widget is not a ScrolledWindow
-            None
+            }
+            child = current_child.next_sibling();
         }
-    })
+    }
+
+    // Page widget holds no text content
+    None
 }
 
 
@@ -122,14 +116,14 @@ fn create_new_empty_tab(deps: &NewTabDependencies) {
     let new_text_view = source_view.clone().upcast::<TextView>();
     let new_text_buffer = source_buffer.upcast::<TextBuffer>();
     
-    // Place the source view in a scrollable container
-    let new_scrolled_window = crate::syntax::create_source_view_scrolled(&source_view);
+    // Place the source view in an editor page (find bar above a scrollable container)
+    let (new_editor_page, _new_scrolled_window) = crate::ui::create_editor_page(&source_view);
     
     // Create a custom tab widget with label and close button
     let (tab_widget, tab_actual_label, tab_close_button) = crate::ui::create_tab_widget("Untitled");
     
     // Add the new tab to the notebook and switch to it
-    let new_page_num = deps.editor_notebook.append_page(&new_scrolled_window, Some(&tab_widget));
+    let new_page_num = deps.editor_notebook.append_page(&new_editor_page, Some(&tab_widget));
     // Setting current page after append ensures the switch_page signal is emitted properly
     deps.editor_notebook.set_current_page(Some(new_page_num));
     
@@ -177,10 +171,10 @@ fn create_new_empty_tab(deps: &NewTabDependencies) {
 
     // Connect close button for this new tab
     let deps_clone_for_close = deps.clone();
-    let new_scrolled_window_clone = new_scrolled_window.clone();
+    let new_editor_page_clone = new_editor_page.clone();
     tab_close_button.connect_clicked(move |_| {
         // Find the current page number of this tab using the correct widget reference
-        if let Some(current_idx_for_this_tab) = deps_clone_for_close.editor_notebook.page_num(&new_scrolled_window_clone) {
+        if let Some(current_idx_for_this_tab) = deps_clone_for_close.editor_notebook.page_num(&new_editor_page_clone) {
             handle_close_tab_request(
                 &deps_clone_for_close.editor_notebook,
                 current_idx_for_this_tab,
@@ -195,6 +189,22 @@ fn create_new_empty_tab(deps: &NewTabDependencies) {
     });
 }
 
+/// Reports a failed save in the terminal and in an error dialog
+pub fn show_save_error(notebook: &Notebook, path: &Path, error: &std::io::Error) {
+    eprintln!("Error saving file {:?}: {}", path, error);
+    let window = notebook.root().and_downcast::<gtk4::Window>();
+    let dialog = MessageDialog::new(
+        window.as_ref(),
+        DialogFlags::MODAL | DialogFlags::DESTROY_WITH_PARENT,
+        MessageType::Error,
+        ButtonsType::Ok,
+        &format!("Could not save {}", path.file_name().unwrap_or_default().to_string_lossy()),
+    );
+    dialog.set_secondary_text(Some(&error.to_string()));
+    dialog.connect_response(|dialog, _| dialog.close());
+    dialog.show();
+}
+
 // Helper function to update tab label after save or name change
 pub fn update_tab_label_after_save(notebook: &Notebook, page_num: u32, new_name_opt: Option<&str>, is_now_dirty: bool) {
     if let Some(page_widget) = notebook.nth_page(Some(page_num)) {
@@ -283,21 +293,12 @@ pub fn handle_close_tab_request(
                         if let Some((_tv, buffer)) = get_text_view_and_buffer_for_page(&notebook_clone, page_num_to_close) {
                             let path_opt = file_path_manager_clone.borrow().get(&page_num_to_close).cloned();
                             if let Some(path) = path_opt { // Existing file
-                                let text = buffer.text(&buffer.start_iter(), &buffer.end_iter(), false);
-                                match File::create(&path) {
-                                    Ok(mut file) => {
-                                        if file.write_all(text.as_bytes()).is_ok() {
-                                            update_tab_label_after_save(&notebook_clone, page_num_to_close, Some(&path.file_name().unwrap_or_default().to_string_lossy()), false);
-                                            actually_close_tab(&notebook_clone, page_num_to_close, &file_path_manager_clone, &active_tab_path_clone, new_tab_deps_clone.as_ref());
-                                        } else {
-                                            eprintln!("Error writing to file: {:?}", path);
-                                            // Optionally show error dialog to user
-                                        }
-                                    }
-                                    Err(e) => {
-                                        eprintln!("Error creating file for writing: {:?}, error: {}", path, e);
-                                        // Optionally show error dialog
+                                match crate::editorconfig::save_buffer(&buffer, &path) {
+                                    Ok(()) => {
+                                        update_tab_label_after_save(&notebook_clone, page_num_to_close, Some(&path.file_name().unwrap_or_default().to_string_lossy()), false);
+                                        actually_close_tab(&notebook_clone, page_num_to_close, &file_path_manager_clone, &active_tab_path_clone, new_tab_deps_clone.as_ref());
                                     }
+                                    Err(e) => show_save_error(&notebook_clone, &path, &e),
                                 }
                             } else { // Untitled file, need to "Save As"
                                 let save_as_dialog = gtk4::FileChooserDialog::new(
@@ -336,10 +337,9 @@ pub fn handle_close_tab_request(
                                 save_as_dialog.connect_response(move |d_sa, resp_sa| {
                                     if resp_sa == gtk4::ResponseType::Accept {
                                         if let Some(file_to_save) = d_sa.file().and_then(|f| f.path()) {
-                                            let text_to_save = buffer_clone_for_save_as.text(&buffer_clone_for_save_as.start_iter(), &buffer_clone_for_save_as.end_iter(), false);
-                                            match File::create(&file_to_save) {
-                                                Ok(mut f_obj) => {
-                                                    if f_obj.write_all(text_to_save.as_bytes()).is_ok() {
+                                            match crate::editorconfig::save_buffer(&buffer_clone_for_save_as, &file_to_save) {
+                                                Ok(()) => {
+                                                    {
                                                         fpm_save_as.borrow_mut().insert(page_num_to_close, file_to_save.clone());
                                                         if nc_save_as.current_page() == Some(page_num_to_close) {
                                                             *atp_save_as.borrow_mut() = Some(file_to_save.clone());
@@ -350,9 +350,9 @@ pub fn handle_close_tab_request(
                                                         }
                                                         utils::update_file_list(&flb_save_as, &cd_save_as.borrow(), &atp_save_as.borrow(), utils::FileSelectionSource::TabSwitch);
                                                         actually_close_tab(&nc_save_as, page_num_to_close, &fpm_save_as, &atp_save_as, ntd_save_as.as_ref());
-                                                    } else { eprintln!("Error writing to new file: {:?}", file_to_save); }
+                                                    }
                                                 }
-                                                Err(e) => { eprintln!("Error creating new file: {:?}, error: {}", file_to_save, e); }
+                                                Err(e) => show_save_error(&nc_save_as, &file_to_save, &e),
                                             }
                                         }
                                     }
@@ -389,6 +389,12 @@ fn actually_close_tab(
 ) {
     let n_pages_before_close = notebook.n_pages();
     
+    // Keep the undo history so it can be restored when the file is reopened
+    let closed_path = file_path_manager_rc.borrow().get(&page_num_to_close).cloned();
+    if let (Some(path), Some((_, buffer))) = (closed_path, get_text_view_and_buffer_for_page(notebook, page_num_to_close)) {
+        crate::undo_history::store(&buffer, &path);
+    }
+    
     notebook.remove_page(Some(page_num_to_close));
     
     { // Scope for mutable borrow of file_path_manager
@@ -499,6 +505,9 @@ pub fn open_or_focus_tab(
             .vexpand(true)
             .hexpand(true)
             .build();
+
+        // The widget added to the notebook: text files replace it with an editor page
+        let mut page_widget = new_scrolled_window.clone().upcast::<gtk4::Widget>();
             
         // Handle different file types
         if mime_type.type_() == "image" {
@@ -519,15 +528,28 @@ pub fn open_or_focus_tab(
             let (source_view, source_buffer) = crate::syntax::create_source_view();
             source_buffer.set_text(content);
             
+            // Bring back the undo/redo history from the last session if the file is unchanged
+            crate::undo_history::restore(source_buffer.upcast_ref(), file_to_open, content);
+            
             // Apply syntax highlighting based on file extension
             crate::syntax::set_language_for_file(&source_buffer, file_to_open);
             
+            // Match the file's indentation (tabs or spaces, and the width)
+            crate::indentation::detect_and_apply(&source_view);
+            
+            // .editorconfig settings take precedence over detection and global settings
+            crate::editorconfig::apply_to_view(&source_view, file_to_open);
+            
             // Get TextBuffer interfaces for compatibility with the rest of the code
             // Clone source_view to avoid ownership move
             let new_text_buffer = source_buffer.upcast::<TextBuffer>();
             
-            // Set the source view as the child of the scrolled window
-            new_scrolled_window.set_child(Some(&source_view));
+            // Host the source view in an editor page with its own find bar
+            let (editor_page, _editor_scrolled_window) = crate::ui::create_editor_page(&source_view);
+            page_widget = editor_page.upcast();
+            
+            // Warn when the file mixes tab and space indentation
+            crate::whitespace::check_mixed_indentation(&page_widget);
 
             // Dirty tracking
             let tab_actual_label_clone = tab_actual_label.clone();
@@ -545,7 +567,7 @@ pub fn open_or_focus_tab(
         }
 
         // Add the new tab to the notebook and make it the current page
-        let new_page_num = notebook.append_page(&new_scrolled_window, Some(&tab_widget));
+        let new_page_num = notebook.append_page(&page_widget, Some(&tab_widget));
         notebook.set_current_page(Some(new_page_num));
 
         // Focus the text area of the newly opened file if it's a text file
@@ -580,8 +602,8 @@ pub fn open_or_focus_tab(
         tab_close_button.connect_clicked(move |_| {
             // Need to find the current page number of this tab when button is clicked
             // The new_page_num captured at creation might be stale if other tabs were manipulated.
-            // Find the page by its child (new_scrolled_window)
-            if let Some(current_idx_for_this_tab) = notebook_clone.page_num(&new_scrolled_window) {
+            // Find the page by its child (page_widget)
+            if let Some(current_idx_for_this_tab) = notebook_clone.page_num(&page_widget) {
                 handle_close_tab_request(
                     &notebook_clone,
                     current_idx_for_this_tab,
@@ -604,6 +626,93 @@ pub fn open_or_focus_tab(
         }
     }
 }
+
+/// Opens a file in the editor like a click in the file list, or focuses its tab
+///
+/// Images and other files that can't be edited get their own kind of tab. The
+/// cursor of an already open file stays where it was. Returns false when the file
+/// couldn't be read.
+pub fn open_file(deps: &NewTabDependencies, file_path: &PathBuf) -> bool {
+    // Close any empty untitled tabs before opening the file
+    close_empty_untitled_tabs(&deps.editor_notebook, &deps.file_path_manager);
+
+    let mime_type = mime_guess::from_path(file_path).first_or_octet_stream();
+    let content = if utils::is_allowed_mime_type(&mime_type) {
+        match crate::editorconfig::read_file(file_path) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("Error reading file {:?}: {}", file_path, e);
+                return false;
+            }
+        }
+    } else {
+        String::new()
+    };
+
+    open_or_focus_tab(
+        &deps.editor_notebook,
+        file_path,
+        &content,
+        &deps.active_tab_path,
+        &deps.file_path_manager,
+        &deps.save_button,
+        &deps.save_as_button,
+        &mime_type,
+        &deps.window,
+        &deps.file_list_box,
+        &deps.current_dir,
+        deps._save_menu_button.as_ref(),
+    );
+    utils::update_file_list(
+        &deps.file_list_box,
+        &deps.current_dir.borrow(),
+        &deps.active_tab_path.borrow(),
+        utils::FileSelectionSource::DirectClick,
+    );
+    true
+}
+
+/// Opens a text file in the editor (or focuses its tab) and places the cursor at a position
+///
+/// Used by panels that link to locations inside files, such as search results.
+/// `line` and `column` are zero-based; the column is counted in characters and is
+/// clamped to the length of the line.
+pub fn open_file_at_position(deps: &NewTabDependencies, file_path: &PathBuf, line: i32, column: i32) {
+    let mime_type = mime_guess::from_path(file_path).first_or_octet_stream();
+    if !utils::is_allowed_mime_type(&mime_type) || !open_file(deps, file_path) {
+        return;
+    }
+
+    if let Some((text_view, buffer)) = get_active_text_view_and_buffer(&deps.editor_notebook) {
+        move_cursor_to_line_column(&text_view, &buffer, line, column);
+    }
+}
+
+/// Moves the cursor to a zero-based line and column and scrolls it into view
+///
+/// Out-of-range positions are clamped to the last line and the end of the line.
+pub fn move_cursor_to_line_column(text_view: &TextView, buffer: &TextBuffer, line: i32, column: i32) {
+    let line = line.clamp(0, (buffer.line_count() - 1).max(0));
+    let mut iter = buffer.iter_at_line(line).unwrap_or_else(|| buffer.end_iter());
+
+    // Find the length of the line (without its newline) to clamp the column
+    let mut line_end = iter.clone();
+    if !line_end.ends_line() {
+        line_end.forward_to_line_end();
+    }
+    iter.set_line_offset(column.clamp(0, line_end.line_offset()));
+
+    buffer.place_cursor(&iter);
+    text_view.grab_focus();
+
+    // Newly created views may not be allocated yet, so scroll once the main loop is idle
+    let text_view_clone = text_view.clone();
+    let buffer_clone = buffer.clone();
+    glib::idle_add_local_once(move || {
+        text_view_clone.scroll_to_mark(&buffer_clone.get_insert(), 0.0, true, 0.0, 0.5);
+    });
+}
+
 pub fn setup_button_handlers(
     new_button: &Button,
     open_button: &Button,
@@ -816,7 +925,7 @@ fn setup_open_button_handler(
                     
                     let mime_type = mime_guess::from_path(&file_to_open).first_or_octet_stream();
                     if utils::is_allowed_mime_type(&mime_type) {
-                        if let Ok(content) = std::fs::read_to_string(&file_to_open) {                        open_or_focus_tab(
+                        if let Ok(content) = crate::editorconfig::read_file(&file_to_open) {                        open_or_focus_tab(
                             &editor_notebook_clone,
                             &file_to_open,
                             &content,
@@ -905,65 +1014,7 @@ fn setup_save_button_handler(
     let current_dir = current_dir.clone();
 
     save_button.connect_clicked(move |_| {
-        if let Some((_active_text_view, active_buffer)) = get_active_text_view_and_buffer(&editor_notebook) { // Prefixed active_text_view
-            let current_page_num_opt = editor_notebook.current_page();
-            if current_page_num_opt.is_none() { return; }
-            let current_page_num = current_page_num_opt.unwrap();
-
-            let path_to_save_opt = file_path_manager.borrow().get(&current_page_num).cloned();
-
-            if let Some(path_to_save) = path_to_save_opt {
-                let mime_type = mime_guess::from_path(&path_to_save).first_or_octet_stream();
-                if utils::is_allowed_mime_type(&mime_type) {
-                    if let Ok(mut file) = File::create(&path_to_save) {
-                        let text = active_buffer.text(&active_buffer.start_iter(), &active_buffer.end_iter(), false);
-                        if file.write_all(text.as_bytes()).is_ok() {
-                            // Update tab label (remove *)
-                            update_tab_label_after_save(&editor_notebook, current_page_num, Some(&path_to_save.file_name().unwrap_or_default().to_string_lossy()), false);
-                        }
-                    }
-                }
-            } else { // No path associated, treat as "Save As"
-                // This logic should ideally call a shared "save_as" function
-                let dialog = gtk4::FileChooserDialog::new(
-                    Some("Save File"),
-                    Some(&window),
-                    gtk4::FileChooserAction::Save,
-                    &[("Cancel", gtk4::ResponseType::Cancel), ("Save", gtk4::ResponseType::Accept)],
-                );
-                
-                dialog.set_default_response(gtk4::ResponseType::Cancel);
-                // ... (rest of save_as logic, simplified here) ...
-                let editor_notebook_clone = editor_notebook.clone();
-                let active_tab_path_ref_clone = active_tab_path_ref.clone();
-                let file_path_manager_clone = file_path_manager.clone();
-                let file_list_box_clone = file_list_box.clone();
-                let current_dir_clone = current_dir.clone();
-
-                dialog.connect_response(move |d, resp| {
-                    if resp == gtk4::ResponseType::Accept {
-                        if let Some(file) = d.file().and_then(|f| f.path()) {
-                             if let Ok(mut f_obj) = File::create(&file) {
-                                let text = active_buffer.text(&active_buffer.start_iter(), &active_buffer.end_iter(), false);
-                                if f_obj.write_all(text.as_bytes()).is_ok() {
-                                    file_path_manager_clone.borrow_mut().insert(current_page_num, file.clone());
-                                    *active_tab_path_ref_clone.borrow_mut() = Some(file.clone());
-                                     // Update tab label
-                                    update_tab_label_after_save(&editor_notebook_clone, current_page_num, Some(&file.file_name().unwrap_or_default().to_string_lossy()), false);
-                                    // Update main window title potentially
-                                    if let Some(parent) = file.parent() {
-                                        *current_dir_clone.borrow_mut() = parent.to_path_buf();
-                                    }
-                                    utils::update_file_list(&file_list_box_clone, &current_dir_clone.borrow(), &active_tab_path_ref_clone.borrow(), utils::FileSelectionSource::TabSwitch);
-                                }
-                            }
-                        }
-                    }
-                    d.close();
-                });
-                dialog.show();
-            }
-        }
+        save_active_tab(&editor_notebook, &active_tab_path_ref, &file_path_manager, &window, &file_list_box, &current_dir, || {});
     });
 }
 
@@ -984,76 +1035,167 @@ fn setup_save_as_button_handler(
     let file_list_box = file_list_box.clone();
 
     save_as_button.connect_clicked(move |_| {
-        if let Some((_active_text_view, active_buffer)) = get_active_text_view_and_buffer(&editor_notebook) { // Prefixed active_text_view
-            let current_page_num_opt = editor_notebook.current_page();
-            if current_page_num_opt.is_none() { return; }
-            let current_page_num = current_page_num_opt.unwrap();
-
-            let dialog = gtk4::FileChooserDialog::new(
-                Some("Save File As"),
-                Some(&window),
-                gtk4::FileChooserAction::Save,
-                &[("Cancel", gtk4::ResponseType::Cancel), ("Save As", gtk4::ResponseType::Accept)],
-            );
+        save_active_tab_as(&editor_notebook, &active_tab_path_ref, &file_path_manager, &window, &file_list_box, &current_dir, || {});
+    });
+}
 
-            dialog.set_default_response(gtk4::ResponseType::Cancel);
-
-            let current_dialog_dir_path = current_dir.borrow().clone();
-            // Explicitly type annotation for gio_file_result and wrap the call in Ok()
-            let gio_file_result: Result<gtk4::gio::File, glib::Error> = Ok(gtk4::gio::File::for_path(&current_dialog_dir_path));
-            match gio_file_result {
-                Ok(gfile) => {
-                    if current_dialog_dir_path.is_dir() {
-                        let _ = dialog.set_current_folder(Some(&gfile));
-                    } else if let Some(parent_gfile) = gfile.parent() {
-                        let _ = dialog.set_current_folder(Some(&parent_gfile));
-                    }
-                }
-                Err(e) => { 
-                    eprintln!("Failed to create GFile for path {:?}: {}", current_dialog_dir_path, e);
-                }
-            }
-            // Suggest current file name if available
-            if let Some(p) = file_path_manager.borrow().get(&current_page_num) {
-                if let Some(name) = p.file_name() {
-                    dialog.set_current_name(&name.to_string_lossy());
-                }
-            }
+/// Saves the active tab to its file, or asks for a path when it has none
+///
+/// `on_saved` runs once the file is written; for an untitled tab that is after
+/// the save dialog is accepted. It doesn't run when saving fails or is cancelled.
+pub fn save_active_tab(
+    editor_notebook: &Notebook,
+    active_tab_path_ref: &Rc<RefCell<Option<PathBuf>>>,
+    file_path_manager: &Rc<RefCell<HashMap<u32, PathBuf>>>,
+    window: &ApplicationWindow,
+    file_list_box: &ListBox,
+    current_dir: &Rc<RefCell<PathBuf>>,
+    on_saved: impl FnOnce() + 'static,
+) {
+    let Some((_active_text_view, active_buffer)) = get_active_text_view_and_buffer(editor_notebook) else {
+        return;
+    };
+    let Some(current_page_num) = editor_notebook.current_page() else {
+        return;
+    };
+
+    let path_to_save_opt = file_path_manager.borrow().get(&current_page_num).cloned();
+    let Some(path_to_save) = path_to_save_opt else {
+        // No path associated, treat as "Save As"
+        show_save_dialog(
+            editor_notebook, current_page_num, &active_buffer, "Save File", "Save",
+            active_tab_path_ref, file_path_manager, window, file_list_box, current_dir, on_saved,
+        );
+        return;
+    };
 
+    let mime_type = mime_guess::from_path(&path_to_save).first_or_octet_stream();
+    if !utils::is_allowed_mime_type(&mime_type) {
+        return;
+    }
+    match crate::editorconfig::save_buffer(&active_buffer, &path_to_save) {
+        Ok(()) => {
+            // Update tab label (remove *)
+            update_tab_label_after_save(editor_notebook, current_page_num, Some(&path_to_save.file_name().unwrap_or_default().to_string_lossy()), false);
+            on_saved();
+        }
+        Err(e) => show_save_error(editor_notebook, &path_to_save, &e),
+    }
+}
 
-            let editor_notebook_clone = editor_notebook.clone();
-            let active_tab_path_ref_clone = active_tab_path_ref.clone();
-            let file_path_manager_clone = file_path_manager.clone();
-            let current_dir_clone = current_dir.clone();
-            let file_list_box_clone = file_list_box.clone();
+/// Asks for a new path and saves the active tab there, like the Save As button
+///
+/// `on_saved` runs once the file is written.
+pub fn save_active_tab_as(
+    editor_notebook: &Notebook,
+    active_tab_path_ref: &Rc<RefCell<Option<PathBuf>>>,
+    file_path_manager: &Rc<RefCell<HashMap<u32, PathBuf>>>,
+    window: &ApplicationWindow,
+    file_list_box: &ListBox,
+    current_dir: &Rc<RefCell<PathBuf>>,
+    on_saved: impl FnOnce() + 'static,
+) {
+    let Some((_active_text_view, active_buffer)) = get_active_text_view_and_buffer(editor_notebook) else {
+        return;
+    };
+    let Some(current_page_num) = editor_notebook.current_page() else {
+        return;
+    };
+    show_save_dialog(
+        editor_notebook, current_page_num, &active_buffer, "Save File As", "Save As",
+        active_tab_path_ref, file_path_manager, window, file_list_box, current_dir, on_saved,
+    );
+}
 
-            dialog.connect_response(move |d, resp| {
-                if resp == gtk4::ResponseType::Accept {
-                    if let Some(file_to_save) = d.file().and_then(|f| f.path()) {
-                        let mime_type = mime_guess::from_path(&file_to_save).first_or_octet_stream();
-                        if utils::is_allowed_mime_type(&mime_type) {
-                            if let Ok(mut f_obj) = File::create(&file_to_save) {
-                                let text = active_buffer.text(&active_buffer.start_iter(), &active_buffer.end_iter(), false);
-                                if f_obj.write_all(text.as_bytes()).is_ok() {
-                                    file_path_manager_clone.borrow_mut().insert(current_page_num, file_to_save.clone());
-                                    *active_tab_path_ref_clone.borrow_mut() = Some(file_to_save.clone());
-
-                                    // Update tab label
-                                    update_tab_label_after_save(&editor_notebook_clone, current_page_num, Some(&file_to_save.file_name().unwrap_or_default().to_string_lossy()), false);
-                                    if let Some(parent) = file_to_save.parent() {
-                                        *current_dir_clone.borrow_mut() = parent.to_path_buf();
-                                    }
-                                     utils::update_file_list(&file_list_box_clone, &current_dir_clone.borrow(), &active_tab_path_ref_clone.borrow(), utils::FileSelectionSource::TabSwitch);
-                                }
-                            }
-                        }
+/// Shows the file chooser for saving a tab under a new path
+fn show_save_dialog(
+    editor_notebook: &Notebook,
+    page_num: u32,
+    buffer: &TextBuffer,
+    title: &str,
+    accept_label: &str,
+    active_tab_path_ref: &Rc<RefCell<Option<PathBuf>>>,
+    file_path_manager: &Rc<RefCell<HashMap<u32, PathBuf>>>,
+    window: &ApplicationWindow,
+    file_list_box: &ListBox,
+    current_dir: &Rc<RefCell<PathBuf>>,
+    on_saved: impl FnOnce() + 'static,
+) {
+    let dialog = gtk4::FileChooserDialog::new(
+        Some(title),
+        Some(window),
+        gtk4::FileChooserAction::Save,
+        &[("Cancel", gtk4::ResponseType::Cancel), (accept_label, gtk4::ResponseType::Accept)],
+    );
+    dialog.set_default_response(gtk4::ResponseType::Cancel);
+
+    let current_dialog_dir_path = current_dir.borrow().clone();
+    let gfile = gtk4::gio::File::for_path(&current_dialog_dir_path);
+    if current_dialog_dir_path.is_dir() {
+        let _ = dialog.set_current_folder(Some(&gfile));
+    } else if let Some(parent_gfile) = gfile.parent() {
+        let _ = dialog.set_current_folder(Some(&parent_gfile));
+    }
+    // Suggest current file name if available
+    if let Some(name) = file_path_manager.borrow().get(&page_num).and_then(|p| p.file_name()) {
+        dialog.set_current_name(&name.to_string_lossy());
+    }
+
+    let editor_notebook = editor_notebook.clone();
+    let buffer = buffer.clone();
+    let active_tab_path_ref = active_tab_path_ref.clone();
+    let file_path_manager = file_path_manager.clone();
+    let file_list_box = file_list_box.clone();
+    let current_dir = current_dir.clone();
+    let on_saved = RefCell::new(Some(on_saved));
+    dialog.connect_response(move |d, resp| {
+        if resp == gtk4::ResponseType::Accept {
+            if let Some(file_to_save) = d.file().and_then(|f| f.path()) {
+                let mime_type = mime_guess::from_path(&file_to_save).first_or_octet_stream();
+                if utils::is_allowed_mime_type(&mime_type)
+                    && save_tab_as(&editor_notebook, page_num, &buffer, &file_to_save, &active_tab_path_ref, &file_path_manager, &file_list_box, &current_dir)
+                {
+                    if let Some(on_saved) = on_saved.take() {
+                        on_saved();
                     }
                 }
-                d.close();
-            });
-            dialog.show();
+            }
         }
+        d.close();
     });
+    dialog.show();
+}
+
+/// Saves a tab's buffer to a new path, and makes the tab show that file
+///
+/// Shared by Save As, saving an untitled tab and Vim's `:w <path>`. Returns
+/// whether the file was written; failures are shown on the tab.
+pub fn save_tab_as(
+    editor_notebook: &Notebook,
+    page_num: u32,
+    buffer: &TextBuffer,
+    file_to_save: &Path,
+    active_tab_path_ref: &Rc<RefCell<Option<PathBuf>>>,
+    file_path_manager: &Rc<RefCell<HashMap<u32, PathBuf>>>,
+    file_list_box: &ListBox,
+    current_dir: &Rc<RefCell<PathBuf>>,
+) -> bool {
+    if let Err(e) = crate::editorconfig::save_buffer(buffer, file_to_save) {
+        show_save_error(editor_notebook, file_to_save, &e);
+        return false;
+    }
+
+    file_path_manager.borrow_mut().insert(page_num, file_to_save.to_path_buf());
+    if editor_notebook.current_page() == Some(page_num) {
+        *active_tab_path_ref.borrow_mut() = Some(file_to_save.to_path_buf());
+    }
+    // Update tab label
+    update_tab_label_after_save(editor_notebook, page_num, Some(&file_to_save.file_name().unwrap_or_default().to_string_lossy()), false);
+    if let Some(parent) = file_to_save.parent() {
+        *current_dir.borrow_mut() = parent.to_path_buf();
+    }
+    utils::update_file_list(file_list_box, &current_dir.borrow(), &active_tab_path_ref.borrow(), utils::FileSelectionSource::TabSwitch);
+    true
 }
 
 
@@ -1219,7 +1361,7 @@ fn setup_file_selection_handler(
             } else if path_from_list.is_file() {
                 let mime_type = mime_guess::from_path(&path_from_list).first_or_octet_stream();
                 if utils::is_allowed_mime_type(&mime_type) {
-                    if let Ok(content) = std::fs::read_to_string(&path_from_list) {                            open_or_focus_tab(
+                    if let Ok(content) = crate::editorconfig::read_file(&path_from_list) {                            open_or_focus_tab(
                             &editor_notebook_for_handler, 
                             &path_from_list,
                             &content,
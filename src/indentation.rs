@@ -0,0 +1,198 @@
+// Indentation detection for the text editor
+// Guesses tabs vs. spaces and the indent width of each file, with a per-buffer override menu
+
+use gtk4::prelude::*;
+use gtk4::{gio, glib, MenuButton, Notebook};
+use sourceview5::{prelude::*, View};
+
+/// Indent width used for new buffers and when a file gives no hint
+pub const DEFAULT_INDENT_WIDTH: u32 = 4;
+
+/// Widths offered in the status bar menu
+const MENU_WIDTHS: [u32; 4] = [2, 3, 4, 8];
+
+/// Indentation style of a buffer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IndentStyle {
+    /// Insert spaces (true) or tab characters (false) when indenting
+    pub use_spaces: bool,
+    /// Columns per indentation level (also the tab width)
+    pub width: u32,
+}
+
+impl Default for IndentStyle {
+    fn default() -> Self {
+        IndentStyle { use_spaces: true, width: DEFAULT_INDENT_WIDTH }
+    }
+}
+
+impl IndentStyle {
+    /// Short description for the status bar, e.g. "Spaces: 4" or "Tabs: 8"
+    pub fn label(&self) -> String {
+        format!("{}: {}", if self.use_spaces { "Spaces" } else { "Tabs" }, self.width)
+    }
+}
+
+/// Guesses the indentation style from a file's leading whitespace
+///
+/// Tabs win when more lines start with a tab than with spaces. For spaces the width
+/// is the most common change of indentation between consecutive lines (ties go to
+/// the smaller width). Returns None when no line is indented.
+pub fn detect_indentation(text: &str) -> Option<IndentStyle> {
+    let mut tab_lines = 0;
+    let mut space_lines = 0;
+    let mut width_votes = [0usize; 9];
+    let mut previous_indent = 0;
+
+    for line in text.lines() {
+        let indent: String = line.chars().take_while(|c| *c == ' ' || *c == '\t').collect();
+        let rest = &line[indent.len()..];
+        if rest.is_empty() {
+            continue;
+        }
+
+        if indent.starts_with('\t') {
+            tab_lines += 1;
+            continue;
+        }
+        // Block comment continuations (" * ") are aligned, not indented
+        if rest.starts_with('*') && indent.len() % 2 == 1 {
+            continue;
+        }
+
+        let columns = indent.len();
+        if columns > 0 {
+            space_lines += 1;
+        }
+        let change = columns.abs_diff(previous_indent);
+        if (2..=8).contains(&change) {
+            width_votes[change] += 1;
+        }
+        previous_indent = columns;
+    }
+
+    if tab_lines == 0 && space_lines == 0 {
+        return None;
+    }
+    if tab_lines > space_lines {
+        return Some(IndentStyle { use_spaces: false, width: DEFAULT_INDENT_WIDTH });
+    }
+
+    let width = (2..=8)
+        .filter(|width| width_votes[*width] > 0)
+        .max_by(|a, b| width_votes[*a].cmp(&width_votes[*b]).then(b.cmp(a)))
+        .map(|width| width as u32)
+        .unwrap_or(DEFAULT_INDENT_WIDTH);
+    Some(IndentStyle { use_spaces: true, width })
+}
+
+/// Returns the indentation style a view currently uses
+pub fn current_indent_style(view: &View) -> IndentStyle {
+    let width = match view.indent_width() {
+        width if width > 0 => width as u32,
+        _ => view.tab_width(),
+    };
+    IndentStyle { use_spaces: view.is_insert_spaces_instead_of_tabs(), width }
+}
+
+/// Applies an indentation style to a view
+pub fn apply_indent_style(view: &View, style: IndentStyle) {
+    view.set_tab_width(style.width);
+    // -1 makes the indent width follow the tab width
+    view.set_indent_width(-1);
+    view.set_insert_spaces_instead_of_tabs(style.use_spaces);
+}
+
+/// Detects the indentation of a view's buffer and applies it
+///
+/// Buffers without indented lines keep their current style.
+pub fn detect_and_apply(view: &View) {
+    let buffer = view.buffer();
+    let text = buffer.text(&buffer.start_iter(), &buffer.end_iter(), false);
+    if let Some(style) = detect_indentation(&text) {
+        println!("Detected indentation: {}", style.label());
+        apply_indent_style(view, style);
+    }
+}
+
+/// Builds the indentation menu shown by the status bar button
+pub fn create_indentation_menu() -> gio::Menu {
+    let menu = gio::Menu::new();
+
+    let kind_section = gio::Menu::new();
+    kind_section.append(Some("Indent Using Spaces"), Some("indent.use-spaces(true)"));
+    kind_section.append(Some("Indent Using Tabs"), Some("indent.use-spaces(false)"));
+    menu.append_section(None, &kind_section);
+
+    let width_section = gio::Menu::new();
+    for width in MENU_WIDTHS {
+        width_section.append(Some(&format!("Width: {}", width)), Some(&format!("indent.width(uint32 {})", width)));
+    }
+    menu.append_section(None, &width_section);
+
+    let tools_section = gio::Menu::new();
+    tools_section.append(Some("Detect from Content"), Some("indent.detect"));
+    tools_section.append(Some("Convert Indentation to Current Style"), Some("indent.convert"));
+    menu.append_section(None, &tools_section);
+
+    menu
+}
+
+/// Adds the "indent" actions used by the status bar menu
+///
+/// Actions: use-spaces(bool), width(uint32), detect and convert. They act on the
+/// view of the active tab only, so each buffer keeps its own override.
+pub fn install_actions(menu_button: &MenuButton, notebook: &Notebook) {
+    let action_group = gio::SimpleActionGroup::new();
+
+    let use_spaces_action = gio::SimpleAction::new("use-spaces", Some(glib::VariantTy::BOOLEAN));
+    let notebook_clone = notebook.clone();
+    use_spaces_action.connect_activate(move |_, parameter| {
+        let Some(use_spaces) = parameter.and_then(|p| p.get::<bool>()) else {
+            return;
+        };
+        if let Some(view) = active_source_view(&notebook_clone) {
+            apply_indent_style(&view, IndentStyle { use_spaces, ..current_indent_style(&view) });
+        }
+    });
+    action_group.add_action(&use_spaces_action);
+
+    let width_action = gio::SimpleAction::new("width", Some(glib::VariantTy::UINT32));
+    let notebook_clone = notebook.clone();
+    width_action.connect_activate(move |_, parameter| {
+        let Some(width) = parameter.and_then(|p| p.get::<u32>()).filter(|w| *w > 0) else {
+            return;
+        };
+        if let Some(view) = active_source_view(&notebook_clone) {
+            apply_indent_style(&view, IndentStyle { width, ..current_indent_style(&view) });
+        }
+    });
+    action_group.add_action(&width_action);
+
+    let detect_action = gio::SimpleAction::new("detect", None);
+    let notebook_clone = notebook.clone();
+    detect_action.connect_activate(move |_, _| {
+        if let Some(view) = active_source_view(&notebook_clone) {
+            detect_and_apply(&view);
+        }
+    });
+    action_group.add_action(&detect_action);
+
+    let convert_action = gio::SimpleAction::new("convert", None);
+    let notebook_clone = notebook.clone();
+    convert_action.connect_activate(move |_, _| {
+        if let Some(view) = active_source_view(&notebook_clone) {
+            let style = current_indent_style(&view);
+            crate::whitespace::convert_indentation(&view.buffer(), !style.use_spaces, view.tab_width() as usize);
+        }
+    });
+    action_group.add_action(&convert_action);
+
+    menu_button.insert_action_group("indent", Some(&action_group));
+}
+
+/// Returns the source view of the active tab
+pub fn active_source_view(notebook: &Notebook) -> Option<View> {
+    crate::handlers::get_active_text_view_and_buffer(notebook)
+        .and_then(|(text_view, _)| text_view.downcast::<View>().ok())
+}
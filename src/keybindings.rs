@@ -0,0 +1,692 @@
+// Rebindable keyboard shortcuts for the text editor
+// Every command is a named gio action; keybindings.conf maps the actions to accelerators
+
+use gtk4::prelude::*;
+use gtk4::{gdk, gio, glib, Box as GtkBox, Button, Dialog, EventControllerKey, Label, ListBox, Orientation, ScrolledWindow};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use crate::settings::Keymap;
+
+/// A command that can be bound to keys
+pub struct Command {
+    /// Detailed action name, such as "win.save" or "fold.fold-level(2)"
+    pub action: &'static str,
+    pub title: &'static str,
+    /// Group shown in the shortcuts window and on the keybindings page
+    pub section: &'static str,
+    /// Accelerators used when keybindings.conf doesn't set the action
+    pub default_accels: &'static [&'static str],
+}
+
+const fn command(action: &'static str, title: &'static str, section: &'static str, default_accels: &'static [&'static str]) -> Command {
+    Command { action, title, section, default_accels }
+}
+
+/// Every bindable command, in the order they are listed to the user
+///
+/// "win" actions belong to the main window; the other groups are added to each
+/// source view and run on the focused one.
+pub const COMMANDS: &[Command] = &[
+    command("win.new-file", "New File", "Files", &["<Control>n"]),
+    command("win.open", "Open File", "Files", &["<Control>o"]),
+    command("win.save", "Save", "Files", &["<Control>s"]),
+    command("win.save-as", "Save As", "Files", &["<Control><Shift>s"]),
+    command("win.close-tab", "Close Tab", "Files", &["<Control>w"]),
+    command("win.edit-path", "Edit Path", "Files", &["<Control>l"]),
+    command("win.quick-open", "Go to File", "Files", &["<Control>p"]),
+    command("win.quit", "Quit", "Files", &["<Control>q"]),
+    command("win.next-tab", "Next Tab", "Tabs", &["<Control>Tab", "<Control>Page_Down"]),
+    command("win.previous-tab", "Previous Tab", "Tabs", &["<Control><Shift>Tab", "<Control>Page_Up"]),
+    command("win.find", "Find", "Search", &["<Control>f"]),
+    command("win.replace", "Replace", "Search", &["<Control>h"]),
+    command("win.find-next", "Find Next", "Search", &["F3"]),
+    command("win.find-previous", "Find Previous", "Search", &["<Shift>F3"]),
+    command("win.find-in-files", "Find in Files", "Search", &["<Control><Shift>f"]),
+    command("win.goto-line", "Go to Line", "Search", &["<Control>g"]),
+    command("comments.toggle-line", "Toggle Line Comment", "Editing", &["<Control>slash", "<Control>KP_Divide"]),
+    command("comments.toggle-block", "Toggle Block Comment", "Editing", &["<Control><Shift>slash", "<Control>question"]),
+    command("brackets.jump-to-match", "Jump to Matching Bracket", "Editing", &["<Control>m"]),
+    command("brackets.select-enclosing", "Select Inside Brackets", "Editing", &["<Control><Shift>m"]),
+    command("lines.duplicate", "Duplicate Lines", "Editing", &["<Control><Shift>d"]),
+    command("lines.delete", "Delete Lines", "Editing", &["<Control><Shift>k"]),
+    command("lines.move-up", "Move Lines Up", "Editing", &["<Alt>Up"]),
+    command("lines.move-down", "Move Lines Down", "Editing", &["<Alt>Down"]),
+    command("lines.join", "Join Lines", "Editing", &["<Control>j"]),
+    command("lines.sort-ascending", "Sort Lines Ascending", "Editing", &["F9"]),
+    command("lines.sort-descending", "Sort Lines Descending", "Editing", &["<Shift>F9"]),
+    command("lines.unique", "Remove Duplicate Lines", "Editing", &[]),
+    command("lines.reverse", "Reverse Lines", "Editing", &[]),
+    command("lines.shuffle", "Shuffle Lines", "Editing", &[]),
+    command("wrap.toggle", "Toggle Word Wrap", "View", &["<Alt>z"]),
+    command("wrap.mode::none", "No Wrap", "View", &[]),
+    command("wrap.mode::word", "Wrap at Words", "View", &[]),
+    command("wrap.mode::char", "Wrap at Characters", "View", &[]),
+    command("wrap.mode::margin", "Wrap at Margin Column", "View", &[]),
+    command("cursors.add-next-occurrence", "Add Caret at Next Occurrence", "Multiple Cursors", &["<Control>d"]),
+    command("cursors.add-caret-above", "Add Caret Above", "Multiple Cursors", &["<Control><Alt>Up"]),
+    command("cursors.add-caret-below", "Add Caret Below", "Multiple Cursors", &["<Control><Alt>Down"]),
+    command("fold.toggle", "Toggle Fold", "Folding", &[]),
+    command("fold.fold", "Fold", "Folding", &["<Control><Shift>bracketleft", "<Control><Shift>braceleft"]),
+    command("fold.unfold", "Unfold", "Folding", &["<Control><Shift>bracketright", "<Control><Shift>braceright"]),
+    command("fold.fold-all", "Fold All", "Folding", &["<Control><Alt>bracketleft"]),
+    command("fold.unfold-all", "Unfold All", "Folding", &["<Control><Alt>bracketright", "<Control><Alt>0"]),
+    command("fold.fold-level(1)", "Fold to Level 1", "Folding", &["<Control><Alt>1"]),
+    command("fold.fold-level(2)", "Fold to Level 2", "Folding", &["<Control><Alt>2"]),
+    command("fold.fold-level(3)", "Fold to Level 3", "Folding", &["<Control><Alt>3"]),
+    command("fold.fold-level(4)", "Fold to Level 4", "Folding", &["<Control><Alt>4"]),
+    command("fold.fold-level(5)", "Fold to Level 5", "Folding", &["<Control><Alt>5"]),
+    command("fold.fold-level(6)", "Fold to Level 6", "Folding", &["<Control><Alt>6"]),
+    command("fold.fold-level(7)", "Fold to Level 7", "Folding", &["<Control><Alt>7"]),
+    command("fold.fold-level(8)", "Fold to Level 8", "Folding", &["<Control><Alt>8"]),
+    command("fold.fold-level(9)", "Fold to Level 9", "Folding", &["<Control><Alt>9"]),
+    command("macros.toggle-recording", "Start/Stop Recording", "Macros", &["<Control><Shift>r"]),
+    command("macros.play", "Play Last Macro", "Macros", &["<Control><Shift>e"]),
+    command("macros.play-dialog", "Play Macro…", "Macros", &[]),
+    command("win.command-palette", "Command Palette", "Help", &["<Control><Shift>p"]),
+    command("win.show-shortcuts", "Keyboard Shortcuts", "Help", &["F1"]),
+    command("win.edit-keybindings", "Edit Keyboard Shortcuts", "Help", &[]),
+];
+
+/// Accelerators of each action, by detailed action name
+pub type Bindings = HashMap<String, Vec<String>>;
+
+/// Kind of the focused widget, which decides the keys it keeps for itself
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Focus {
+    Editor,
+    Terminal,
+    Other,
+}
+
+thread_local! {
+    // Bindings in use, loaded from keybindings.conf
+    static BINDINGS: RefCell<Bindings> = RefCell::new(Bindings::new());
+    // Focus and keymap the accelerators were last registered for
+    static REGISTERED_FOR: Cell<Option<(Focus, Keymap)>> = Cell::new(None);
+}
+
+/// Returns the path of the keybindings file in the config directory
+pub fn keybindings_path() -> PathBuf {
+    crate::settings::get_config_dir().join("keybindings.conf")
+}
+
+/// Loads keybindings.conf and makes its bindings active
+///
+/// The file is written with the default bindings the first time, so there is
+/// something to edit.
+pub fn initialize() {
+    let path = keybindings_path();
+    if !path.exists() {
+        if let Err(e) = save_bindings(&default_bindings()) {
+            eprintln!("Failed to write {}: {}", path.display(), e);
+        }
+    }
+    apply_bindings(load_bindings());
+}
+
+/// Returns the default accelerators of every command
+pub fn default_bindings() -> Bindings {
+    COMMANDS
+        .iter()
+        .map(|command| (command.action.to_string(), command.default_accels.iter().map(|accel| accel.to_string()).collect()))
+        .collect()
+}
+
+/// Reads keybindings.conf on top of the defaults
+///
+/// Each line is `action=accelerator, accelerator`; an empty value unbinds the
+/// action. Unknown actions and invalid accelerators are reported and skipped.
+pub fn load_bindings() -> Bindings {
+    let mut bindings = default_bindings();
+    let path = keybindings_path();
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                eprintln!("Failed to load {}: {}", path.display(), e);
+            }
+            return bindings;
+        }
+    };
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((action, value)) = line.split_once('=') else {
+            eprintln!("Ignoring keybinding line without '=': {}", line);
+            continue;
+        };
+        let action = action.trim();
+        if !COMMANDS.iter().any(|command| command.action == action) {
+            eprintln!("Ignoring keybinding for unknown action '{}'", action);
+            continue;
+        }
+        let accels = value
+            .split(',')
+            .map(str::trim)
+            .filter(|accel| !accel.is_empty())
+            .filter(|accel| {
+                let valid = gtk4::accelerator_parse(*accel).is_some();
+                if !valid {
+                    eprintln!("Ignoring invalid accelerator '{}' for '{}'", accel, action);
+                }
+                valid
+            })
+            .map(str::to_string)
+            .collect();
+        bindings.insert(action.to_string(), accels);
+    }
+    bindings
+}
+
+/// Writes every command's accelerators to keybindings.conf
+pub fn save_bindings(bindings: &Bindings) -> std::io::Result<()> {
+    let mut contents = String::new();
+    contents.push_str("# Text Editor Keyboard Shortcuts\n");
+    contents.push_str("# action=accelerator, accelerator (leave empty to unbind), e.g. win.save=<Control>s\n\n");
+    for command in COMMANDS {
+        let accels = bindings.get(command.action).map(|accels| accels.join(", ")).unwrap_or_default();
+        contents.push_str(&format!("{}={}\n", command.action, accels));
+    }
+    fs::write(keybindings_path(), contents)
+}
+
+/// Makes a set of bindings active and reports conflicting ones
+pub fn apply_bindings(bindings: Bindings) {
+    for (action, others) in find_conflicts(&bindings) {
+        eprintln!("Keybinding conflict: {} shares a shortcut with {}", action, others.join(", "));
+    }
+
+    BINDINGS.with(|current| *current.borrow_mut() = bindings);
+    REGISTERED_FOR.with(|registered| registered.set(None));
+    register_accels();
+}
+
+/// Returns the bindings in use
+pub fn current_bindings() -> Bindings {
+    BINDINGS.with(|bindings| bindings.borrow().clone())
+}
+
+/// Finds the actions that share an accelerator with another action
+///
+/// Returns, for each action involved, the other actions bound to the same keys.
+pub fn find_conflicts(bindings: &Bindings) -> Vec<(String, Vec<String>)> {
+    conflicts_with(bindings, |accel| gtk4::accelerator_parse(accel))
+}
+
+/// Finds the conflicts, reading accelerators with `parse`
+///
+/// GTK's parser needs an initialized display, which the tests don't have.
+fn conflicts_with(
+    bindings: &Bindings,
+    parse: impl Fn(&str) -> Option<(gdk::Key, gdk::ModifierType)>,
+) -> Vec<(String, Vec<String>)> {
+    let mut by_key: HashMap<(gdk::Key, gdk::ModifierType), Vec<&'static str>> = HashMap::new();
+    for command in COMMANDS {
+        for accel in bindings.get(command.action).into_iter().flatten() {
+            if let Some((key, modifiers)) = parse(accel.as_str()) {
+                let actions = by_key.entry(normalize(key, modifiers)).or_default();
+                if !actions.contains(&command.action) {
+                    actions.push(command.action);
+                }
+            }
+        }
+    }
+
+    let mut conflicts: Vec<(String, Vec<String>)> = Vec::new();
+    for command in COMMANDS {
+        let mut others: Vec<String> = Vec::new();
+        for actions in by_key.values().filter(|actions| actions.contains(&command.action)) {
+            for other in actions.iter().filter(|other| **other != command.action) {
+                if !others.iter().any(|known| known.as_str() == *other) {
+                    others.push(other.to_string());
+                }
+            }
+        }
+        if !others.is_empty() {
+            conflicts.push((command.action.to_string(), others));
+        }
+    }
+    conflicts
+}
+
+/// Registers the bindings as the application's accelerators
+///
+/// Keys the focused widget needs for itself are left out: the Emacs keys while
+/// the Emacs keymap is selected, the Vim keys in a source view with Vim input and
+/// Ctrl+letter in the terminal. Call this again when the focus or the keymap
+/// changes; nothing is re-registered if neither did.
+pub fn register_accels() {
+    let Some(app) = gio::Application::default().and_downcast::<gtk4::Application>() else {
+        return;
+    };
+    let focus = app
+        .active_window()
+        .and_then(|window| gtk4::prelude::GtkWindowExt::focus(&window))
+        .map_or(Focus::Other, |widget| focus_kind(&widget));
+    let keymap = crate::settings::with_settings(|settings| settings.get_keymap());
+    if REGISTERED_FOR.with(|registered| registered.replace(Some((focus, keymap)))) == Some((focus, keymap)) {
+        return;
+    }
+
+    let bindings = current_bindings();
+    for command in COMMANDS {
+        let accels: Vec<&str> = bindings
+            .get(command.action)
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .filter(|accel| {
+                gtk4::accelerator_parse(*accel).is_some_and(|(key, modifiers)| !is_reserved(focus, keymap, key, modifiers))
+            })
+            .collect();
+        app.set_accels_for_action(command.action, &accels);
+    }
+}
+
+/// Runs the command bound to a key press in a source view, returning whether one ran
+///
+/// Macro playback uses this for recorded keys, which GTK can't send through the
+/// window's accelerators.
+pub fn activate_accel(view: &sourceview5::View, keyval: gdk::Key, state: gdk::ModifierType) -> bool {
+    let keymap = crate::settings::with_settings(|settings| settings.get_keymap());
+    let pressed = normalize(keyval, state);
+    let action = BINDINGS.with(|bindings| {
+        let bindings = bindings.borrow();
+        COMMANDS
+            .iter()
+            .find(|command| {
+                bindings
+                    .get(command.action)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|accel| gtk4::accelerator_parse(accel.as_str()))
+                    .any(|(key, modifiers)| {
+                        normalize(key, modifiers) == pressed && !is_reserved(Focus::Editor, keymap, key, modifiers)
+                    })
+            })
+            .map(|command| command.action)
+    });
+    action.is_some_and(|action| activate_action(view, action))
+}
+
+/// Returns what kind of widget has the focus, as far as shortcuts are concerned
+fn focus_kind(widget: &gtk4::Widget) -> Focus {
+    if widget.is::<sourceview5::View>() {
+        Focus::Editor
+    } else if widget.is::<vte4::Terminal>() {
+        Focus::Terminal
+    } else {
+        Focus::Other
+    }
+}
+
+/// Checks whether an accelerator's keys belong to the keymap or the focused widget
+fn is_reserved(focus: Focus, keymap: Keymap, key: gdk::Key, modifiers: gdk::ModifierType) -> bool {
+    // The keymaps see Shift+letter as the capital letter, as in a key press
+    let keyval = if modifiers.contains(gdk::ModifierType::SHIFT_MASK) { key.to_upper() } else { key };
+    match (keymap, focus) {
+        (Keymap::Emacs, _) if crate::emacs::is_reserved_key(keyval, modifiers) => true,
+        (Keymap::Vim, Focus::Editor) => crate::vim::is_reserved_key(keyval, modifiers),
+        (_, Focus::Terminal) => {
+            let others = gdk::ModifierType::SHIFT_MASK | gdk::ModifierType::ALT_MASK | gdk::ModifierType::SUPER_MASK;
+            modifiers.contains(gdk::ModifierType::CONTROL_MASK)
+                && !modifiers.intersects(others)
+                && key.to_unicode().is_some_and(|c| c.is_ascii_alphabetic())
+        }
+        _ => false,
+    }
+}
+
+/// Activates a detailed action name from a widget, returning whether it ran
+pub fn activate_action(widget: &impl IsA<gtk4::Widget>, detailed_action: &str) -> bool {
+    match gio::Action::parse_detailed_name(detailed_action) {
+        Ok((name, target)) => widget.activate_action(&name, target.as_ref()).is_ok(),
+        Err(e) => {
+            eprintln!("Invalid action name '{}': {}", detailed_action, e);
+            false
+        }
+    }
+}
+
+/// Brings a key press and an accelerator to the same form for comparison
+fn normalize(keyval: gdk::Key, modifiers: gdk::ModifierType) -> (gdk::Key, gdk::ModifierType) {
+    // Shift+Tab arrives as ISO_Left_Tab
+    let keyval = if keyval == gdk::Key::ISO_Left_Tab { gdk::Key::Tab } else { keyval.to_lower() };
+    let mask = gdk::ModifierType::CONTROL_MASK
+        | gdk::ModifierType::SHIFT_MASK
+        | gdk::ModifierType::ALT_MASK
+        | gdk::ModifierType::SUPER_MASK;
+    (keyval, modifiers & mask)
+}
+
+/// Returns the label of an accelerator as shown to the user ("Ctrl+Shift+S")
+pub fn accel_label(accel: &str) -> String {
+    match gtk4::accelerator_parse(accel) {
+        Some((key, modifiers)) => gtk4::accelerator_get_label(key, modifiers).to_string(),
+        None => accel.to_string(),
+    }
+}
+
+/// Returns the sections of the command list in order
+fn sections() -> Vec<&'static str> {
+    let mut sections: Vec<&'static str> = Vec::new();
+    for command in COMMANDS {
+        if !sections.contains(&command.section) {
+            sections.push(command.section);
+        }
+    }
+    sections
+}
+
+/// Shows a shortcuts window generated from the current bindings
+pub fn show_shortcuts_window(parent: &impl IsA<gtk4::Window>) {
+    let builder = gtk4::Builder::from_string(&shortcuts_window_ui(&current_bindings()));
+    let Some(window) = builder.object::<gtk4::ShortcutsWindow>("shortcuts_window") else {
+        eprintln!("Failed to build the shortcuts window");
+        return;
+    };
+    window.set_transient_for(Some(parent));
+    window.present();
+}
+
+/// Builds the UI definition of the shortcuts window
+///
+/// Unbound commands are left out; sections of the command list become groups.
+fn shortcuts_window_ui(bindings: &Bindings) -> String {
+    let mut groups = String::new();
+    for section in sections() {
+        let mut shortcuts = String::new();
+        for command in COMMANDS.iter().filter(|command| command.section == section) {
+            let accels = bindings.get(command.action).cloned().unwrap_or_default();
+            if accels.is_empty() {
+                continue;
+            }
+            shortcuts.push_str(&format!(
+                "<child><object class=\"GtkShortcutsShortcut\">\
+                 <property name=\"title\">{}</property>\
+                 <property name=\"accelerator\">{}</property>\
+                 </object></child>",
+                escape_markup(command.title),
+                escape_markup(&accels.join(" "))
+            ));
+        }
+        if shortcuts.is_empty() {
+            continue;
+        }
+        groups.push_str(&format!(
+            "<child><object class=\"GtkShortcutsGroup\"><property name=\"title\">{}</property>{}</object></child>",
+            escape_markup(section),
+            shortcuts
+        ));
+    }
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+         <interface>\
+         <object class=\"GtkShortcutsWindow\" id=\"shortcuts_window\">\
+         <property name=\"modal\">1</property>\
+         <child><object class=\"GtkShortcutsSection\">\
+         <property name=\"section-name\">editor</property>\
+         <property name=\"max-height\">12</property>\
+         {}\
+         </object></child>\
+         </object>\
+         </interface>",
+        groups
+    )
+}
+
+/// Escapes text for a UI definition
+fn escape_markup(text: &str) -> String {
+    glib::markup_escape_text(text).to_string()
+}
+
+/// Shows the keybindings page: every command with its keys, conflicts and a way to record new keys
+///
+/// Click a command's shortcut, then press the new keys; Backspace removes the
+/// binding and Escape cancels. Changes are written to keybindings.conf on Save.
+pub fn show_keybindings_dialog(parent: &impl IsA<gtk4::Window>) {
+    let dialog = Dialog::builder()
+        .title("Keyboard Shortcuts")
+        .transient_for(parent)
+        .modal(true)
+        .destroy_with_parent(true)
+        .use_header_bar(1)
+        .default_width(640)
+        .default_height(560)
+        .build();
+
+    let content_area = dialog.content_area();
+    content_area.set_margin_top(10);
+    content_area.set_margin_bottom(10);
+    content_area.set_margin_start(10);
+    content_area.set_margin_end(10);
+    content_area.set_spacing(8);
+
+    let help_label = Label::new(Some(
+        "Click a shortcut, then press the new keys. Backspace removes the shortcut, Escape cancels.",
+    ));
+    help_label.set_wrap(true);
+    help_label.set_halign(gtk4::Align::Start);
+    help_label.add_css_class("caption");
+    content_area.append(&help_label);
+
+    let bindings = Rc::new(RefCell::new(current_bindings()));
+    // Action whose keys are being recorded
+    let recording: Rc<RefCell<Option<String>>> = Rc::new(RefCell::new(None));
+
+    let list = ListBox::new();
+    list.set_selection_mode(gtk4::SelectionMode::None);
+    let rows: Rc<RefCell<Vec<(&'static str, Button, Label)>>> = Rc::new(RefCell::new(Vec::new()));
+
+    // Redraws every row from the working copy of the bindings
+    let refresh = {
+        let bindings = bindings.clone();
+        let recording = recording.clone();
+        let rows = rows.clone();
+        Rc::new(move || {
+            let bindings = bindings.borrow();
+            let conflicts = find_conflicts(&bindings);
+            for (action, accel_button, conflict_label) in rows.borrow().iter() {
+                let label = if recording.borrow().as_deref() == Some(*action) {
+                    "Press keys…".to_string()
+                } else {
+                    let accels = bindings.get(*action).cloned().unwrap_or_default();
+                    if accels.is_empty() {
+                        "Disabled".to_string()
+                    } else {
+                        accels.iter().map(|accel| accel_label(accel)).collect::<Vec<_>>().join(", ")
+                    }
+                };
+                accel_button.set_label(&label);
+
+                match conflicts.iter().find(|(conflicting, _)| conflicting == action) {
+                    Some((_, others)) => {
+                        let titles: Vec<&str> = others
+                            .iter()
+                            .filter_map(|other| COMMANDS.iter().find(|command| command.action == other))
+                            .map(|command| command.title)
+                            .collect();
+                        conflict_label.set_text("Conflict");
+                        conflict_label.set_tooltip_text(Some(&format!("Also bound to: {}", titles.join(", "))));
+                    }
+                    None => {
+                        conflict_label.set_text("");
+                        conflict_label.set_tooltip_text(None);
+                    }
+                }
+            }
+        })
+    };
+
+    for section in sections() {
+        let heading = Label::new(Some(section));
+        heading.set_halign(gtk4::Align::Start);
+        heading.set_margin_top(8);
+        heading.add_css_class("heading");
+        list.append(&heading);
+
+        for command in COMMANDS.iter().filter(|command| command.section == section) {
+            let row = GtkBox::new(Orientation::Horizontal, 8);
+            let title = Label::new(Some(command.title));
+            title.set_halign(gtk4::Align::Start);
+            title.set_hexpand(true);
+            title.set_xalign(0.0);
+            let conflict_label = Label::new(None);
+            conflict_label.add_css_class("error");
+            conflict_label.add_css_class("caption");
+            let accel_button = Button::new();
+            accel_button.set_width_request(180);
+            let reset_button = Button::from_icon_name("edit-undo-symbolic");
+            reset_button.add_css_class("flat");
+            reset_button.set_tooltip_text(Some("Reset to default"));
+            row.append(&title);
+            row.append(&conflict_label);
+            row.append(&accel_button);
+            row.append(&reset_button);
+            list.append(&row);
+
+            let action = command.action;
+            let recording_for_button = recording.clone();
+            let refresh_for_button = refresh.clone();
+            accel_button.connect_clicked(move |_| {
+                *recording_for_button.borrow_mut() = Some(action.to_string());
+                refresh_for_button();
+            });
+
+            let bindings_for_reset = bindings.clone();
+            let refresh_for_reset = refresh.clone();
+            reset_button.connect_clicked(move |_| {
+                let default_accels = command.default_accels.iter().map(|accel| accel.to_string()).collect();
+                bindings_for_reset.borrow_mut().insert(action.to_string(), default_accels);
+                refresh_for_reset();
+            });
+
+            rows.borrow_mut().push((command.action, accel_button, conflict_label));
+        }
+    }
+    refresh();
+
+    let scrolled = ScrolledWindow::builder().child(&list).vexpand(true).build();
+    content_area.append(&scrolled);
+
+    // Record the next key press for the chosen command
+    let key_controller = EventControllerKey::new();
+    key_controller.set_propagation_phase(gtk4::PropagationPhase::Capture);
+    let bindings_for_keys = bindings.clone();
+    let recording_for_keys = recording.clone();
+    let refresh_for_keys = refresh.clone();
+    key_controller.connect_key_pressed(move |_, keyval, _, state| {
+        let Some(action) = recording_for_keys.borrow().clone() else {
+            return glib::Propagation::Proceed;
+        };
+        if crate::multi_cursor::is_modifier_key(keyval) {
+            return glib::Propagation::Stop;
+        }
+        let (key, modifiers) = normalize(keyval, state);
+        if modifiers.is_empty() && key == gdk::Key::BackSpace {
+            bindings_for_keys.borrow_mut().insert(action, Vec::new());
+        } else if !(modifiers.is_empty() && key == gdk::Key::Escape) {
+            let accel = gtk4::accelerator_name(key, modifiers).to_string();
+            bindings_for_keys.borrow_mut().insert(action, vec![accel]);
+        }
+        *recording_for_keys.borrow_mut() = None;
+        refresh_for_keys();
+        glib::Propagation::Stop
+    });
+    dialog.add_controller(key_controller);
+
+    dialog.add_button("Reset All", gtk4::ResponseType::Reject);
+    dialog.add_button("Cancel", gtk4::ResponseType::Cancel);
+    dialog.add_button("Save", gtk4::ResponseType::Accept);
+
+    dialog.connect_response(move |dialog, response| {
+        match response {
+            gtk4::ResponseType::Reject => {
+                *bindings.borrow_mut() = default_bindings();
+                *recording.borrow_mut() = None;
+                refresh();
+                return;
+            }
+            gtk4::ResponseType::Accept => {
+                let bindings = bindings.borrow().clone();
+                if let Err(e) = save_bindings(&bindings) {
+                    eprintln!("Failed to save keybindings: {}", e);
+                }
+                apply_bindings(bindings);
+            }
+            _ => {}
+        }
+        dialog.close();
+    });
+
+    dialog.present();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses "<Control><Shift>s"-style accelerators like GTK, without a display
+    fn parse(accel: &str) -> Option<(gdk::Key, gdk::ModifierType)> {
+        let mut modifiers = gdk::ModifierType::empty();
+        let mut rest = accel;
+        while let Some(stripped) = rest.strip_prefix('<') {
+            let (name, after) = stripped.split_once('>')?;
+            modifiers |= match name {
+                "Control" | "Primary" => gdk::ModifierType::CONTROL_MASK,
+                "Shift" => gdk::ModifierType::SHIFT_MASK,
+                "Alt" => gdk::ModifierType::ALT_MASK,
+                _ => return None,
+            };
+            rest = after;
+        }
+        gdk::Key::from_name(rest).map(|key| (key, modifiers))
+    }
+
+    fn bind(bindings: &mut Bindings, action: &str, accels: &[&str]) {
+        bindings.insert(action.to_string(), accels.iter().map(|accel| accel.to_string()).collect());
+    }
+
+    #[test]
+    fn default_bindings_do_not_conflict() {
+        assert!(conflicts_with(&default_bindings(), parse).is_empty());
+    }
+
+    #[test]
+    fn shared_keys_are_reported_for_every_action() {
+        let mut bindings = default_bindings();
+        bind(&mut bindings, "lines.join", &["<Control>s"]);
+        bind(&mut bindings, "wrap.toggle", &["<Primary>S"]);
+
+        let conflicts = conflicts_with(&bindings, parse);
+        let others = |action: &str| conflicts.iter().find(|(conflicting, _)| conflicting == action).map(|(_, others)| others.clone());
+        assert_eq!(others("win.save"), Some(vec!["lines.join".to_string(), "wrap.toggle".to_string()]));
+        assert_eq!(others("lines.join"), Some(vec!["win.save".to_string(), "wrap.toggle".to_string()]));
+        assert_eq!(conflicts.len(), 3);
+    }
+
+    #[test]
+    fn shift_tab_matches_iso_left_tab() {
+        let mut bindings = default_bindings();
+        bind(&mut bindings, "lines.join", &["<Control><Shift>ISO_Left_Tab"]);
+        let conflicts = conflicts_with(&bindings, parse);
+        assert_eq!(conflicts.len(), 2);
+        assert!(conflicts.iter().any(|(action, others)| action == "win.previous-tab" && others == &["lines.join".to_string()]));
+    }
+
+    #[test]
+    fn repeated_and_invalid_keys_do_not_conflict() {
+        let mut bindings = default_bindings();
+        bind(&mut bindings, "win.save", &["<Control>s", "<Control>S"]);
+        bind(&mut bindings, "lines.unique", &["garbage", ""]);
+        assert!(conflicts_with(&bindings, parse).is_empty());
+    }
+}
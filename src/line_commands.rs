@@ -0,0 +1,459 @@
+// Line editing commands for the text editor
+// Duplicate, delete, move, join, sort, unique, reverse and shuffle the current or selected lines
+
+use gtk4::prelude::*;
+use gtk4::{gio, TextBuffer};
+use sourceview5::View;
+use std::cmp::Ordering;
+
+use crate::utils::selected_lines;
+
+/// Options for sorting lines
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SortOptions {
+    pub descending: bool,
+    /// Compare runs of digits by their numeric value ("file2" before "file10")
+    pub natural: bool,
+    pub case_insensitive: bool,
+}
+
+/// Returns the lines a block command works on, or `None` without a selection
+///
+/// Reordering the cursor line alone does nothing, so sorting and similar
+/// commands only apply to selected lines.
+fn block_lines(buffer: &TextBuffer) -> Option<(i32, i32)> {
+    buffer.has_selection().then(|| selected_lines(buffer))
+}
+
+/// Returns the text of lines `first..=last`, one string per line, without line breaks
+fn lines_text(buffer: &TextBuffer, first: i32, last: i32) -> Vec<String> {
+    let (start, end) = lines_bounds(buffer, first, last);
+    buffer
+        .text(&start, &end, true)
+        .split('\n')
+        .map(str::to_string)
+        .collect()
+}
+
+/// Returns the start of `first` and the end of `last` (before its line break)
+fn lines_bounds(buffer: &TextBuffer, first: i32, last: i32) -> (gtk4::TextIter, gtk4::TextIter) {
+    let start = buffer.iter_at_line(first).unwrap_or_else(|| buffer.start_iter());
+    let mut end = buffer.iter_at_line(last).unwrap_or_else(|| buffer.end_iter());
+    if !end.ends_line() {
+        end.forward_to_line_end();
+    }
+    (start, end)
+}
+
+/// Replaces lines `first..=last` with new lines as a single undo step
+///
+/// When the lines were selected, the new lines are selected afterwards.
+fn replace_lines(buffer: &TextBuffer, first: i32, last: i32, lines: &[String]) {
+    let had_selection = buffer.has_selection();
+    let (mut start, mut end) = lines_bounds(buffer, first, last);
+    let new_text = lines.join("\n");
+    if buffer.text(&start, &end, true) == new_text {
+        return;
+    }
+
+    let start_offset = start.offset();
+    buffer.begin_user_action();
+    buffer.delete(&mut start, &mut end);
+    buffer.insert(&mut start, &new_text);
+    buffer.end_user_action();
+
+    let new_start = buffer.iter_at_offset(start_offset);
+    if had_selection {
+        buffer.select_range(&new_start, &buffer.iter_at_offset(start_offset + new_text.chars().count() as i32));
+    } else {
+        buffer.place_cursor(&new_start);
+    }
+}
+
+/// Duplicates the current or selected lines below themselves
+///
+/// The cursor (or selection) moves to the copy, so repeating the command keeps copying.
+pub fn duplicate_lines(buffer: &TextBuffer) {
+    let (first, last) = selected_lines(buffer);
+    let (start, end) = lines_bounds(buffer, first, last);
+    let text = buffer.text(&start, &end, true).to_string();
+    let block_length = text.chars().count() as i32 + 1;
+
+    let insert_offset = buffer.iter_at_mark(&buffer.get_insert()).offset();
+    let bound_offset = buffer.iter_at_mark(&buffer.selection_bound()).offset();
+
+    buffer.begin_user_action();
+    let mut position = end.clone();
+    buffer.insert(&mut position, &format!("\n{}", text));
+    buffer.end_user_action();
+
+    buffer.select_range(
+        &buffer.iter_at_offset(insert_offset + block_length),
+        &buffer.iter_at_offset(bound_offset + block_length),
+    );
+}
+
+/// Deletes the current or selected lines, including their line breaks
+pub fn delete_lines(buffer: &TextBuffer) {
+    let (first, last) = selected_lines(buffer);
+    let mut start = buffer.iter_at_line(first).unwrap_or_else(|| buffer.start_iter());
+    let mut end = match buffer.iter_at_line(last + 1) {
+        Some(next_line) if last + 1 < buffer.line_count() => next_line,
+        _ => {
+            // The last line has no line break after it: take the one before it instead
+            if first > 0 {
+                start.backward_char();
+            }
+            buffer.end_iter()
+        }
+    };
+
+    let column = buffer.iter_at_mark(&buffer.get_insert()).line_offset();
+    buffer.begin_user_action();
+    buffer.delete(&mut start, &mut end);
+    buffer.end_user_action();
+
+    // Keep the cursor in the same column on the line that moved up
+    let mut cursor = buffer.iter_at_line(first.min(buffer.line_count() - 1)).unwrap_or_else(|| buffer.end_iter());
+    let line_length = {
+        let mut line_end = cursor.clone();
+        if !line_end.ends_line() {
+            line_end.forward_to_line_end();
+        }
+        line_end.line_offset()
+    };
+    cursor.set_line_offset(column.min(line_length));
+    buffer.place_cursor(&cursor);
+}
+
+/// Moves the current or selected lines up or down by one line
+///
+/// The cursor and selection move with the lines. Returns false at the start or end
+/// of the buffer.
+pub fn move_lines(buffer: &TextBuffer, up: bool) -> bool {
+    let (first, last) = selected_lines(buffer);
+    if (up && first == 0) || (!up && last >= buffer.line_count() - 1) {
+        return false;
+    }
+
+    let (other_line, range_first, range_last) = if up { (first - 1, first - 1, last) } else { (last + 1, first, last + 1) };
+    let block = lines_text(buffer, first, last);
+    let other = lines_text(buffer, other_line, other_line).remove(0);
+    let other_length = other.chars().count() as i32 + 1;
+
+    let insert_offset = buffer.iter_at_mark(&buffer.get_insert()).offset();
+    let bound_offset = buffer.iter_at_mark(&buffer.selection_bound()).offset();
+
+    let mut new_lines = block;
+    if up {
+        new_lines.push(other);
+    } else {
+        new_lines.insert(0, other);
+    }
+
+    let (mut start, mut end) = lines_bounds(buffer, range_first, range_last);
+    buffer.begin_user_action();
+    buffer.delete(&mut start, &mut end);
+    buffer.insert(&mut start, &new_lines.join("\n"));
+    buffer.end_user_action();
+
+    // The block moved by the length of the line it swapped with
+    let shift = if up { -other_length } else { other_length };
+    buffer.select_range(
+        &buffer.iter_at_offset(insert_offset + shift),
+        &buffer.iter_at_offset(bound_offset + shift),
+    );
+    true
+}
+
+/// Joins the selected lines, or the current line with the next one
+///
+/// Leading whitespace of the joined lines is replaced by a single space.
+pub fn join_lines(buffer: &TextBuffer) {
+    let (first, mut last) = selected_lines(buffer);
+    if first == last {
+        if last >= buffer.line_count() - 1 {
+            return;
+        }
+        last += 1;
+    }
+
+    let lines = lines_text(buffer, first, last);
+    let mut joined = lines[0].trim_end().to_string();
+    for line in &lines[1..] {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if !joined.is_empty() {
+            joined.push(' ');
+        }
+        joined.push_str(line);
+    }
+
+    let had_selection = buffer.has_selection();
+    let (mut start, mut end) = lines_bounds(buffer, first, last);
+    let cursor_offset = start.offset() + lines[0].trim_end().chars().count() as i32;
+    buffer.begin_user_action();
+    buffer.delete(&mut start, &mut end);
+    buffer.insert(&mut start, &joined);
+    buffer.end_user_action();
+
+    if !had_selection {
+        // Leave the cursor at the join point
+        buffer.place_cursor(&buffer.iter_at_offset(cursor_offset));
+    }
+}
+
+/// Sorts the selected lines
+pub fn sort_lines(buffer: &TextBuffer, options: SortOptions) {
+    let Some((first, last)) = block_lines(buffer) else {
+        return;
+    };
+    let mut lines = lines_text(buffer, first, last);
+    lines.sort_by(|a, b| {
+        let ordering = compare_lines(a, b, options);
+        if options.descending { ordering.reverse() } else { ordering }
+    });
+    replace_lines(buffer, first, last, &lines);
+}
+
+/// Compares two lines with the given sort options
+fn compare_lines(a: &str, b: &str, options: SortOptions) -> Ordering {
+    let (a, b) = if options.case_insensitive {
+        (a.to_lowercase(), b.to_lowercase())
+    } else {
+        (a.to_string(), b.to_string())
+    };
+    if options.natural {
+        natural_compare(&a, &b)
+    } else {
+        a.cmp(&b)
+    }
+}
+
+/// Compares strings treating runs of digits as numbers
+pub fn natural_compare(a: &str, b: &str) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+
+    loop {
+        match (a_chars.peek().copied(), b_chars.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a_char), Some(b_char)) if a_char.is_ascii_digit() && b_char.is_ascii_digit() => {
+                let mut a_number = String::new();
+                while let Some(c) = a_chars.next_if(char::is_ascii_digit) {
+                    a_number.push(c);
+                }
+                let mut b_number = String::new();
+                while let Some(c) = b_chars.next_if(char::is_ascii_digit) {
+                    b_number.push(c);
+                }
+                // Compare by value (length after leading zeros, then digits), then by length
+                let a_trimmed = a_number.trim_start_matches('0');
+                let b_trimmed = b_number.trim_start_matches('0');
+                let ordering = a_trimmed
+                    .len()
+                    .cmp(&b_trimmed.len())
+                    .then_with(|| a_trimmed.cmp(b_trimmed))
+                    .then_with(|| a_number.len().cmp(&b_number.len()));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(a_char), Some(b_char)) => {
+                if a_char != b_char {
+                    return a_char.cmp(&b_char);
+                }
+                a_chars.next();
+                b_chars.next();
+            }
+        }
+    }
+}
+
+/// Removes repeated lines from the selection, keeping the first
+pub fn unique_lines(buffer: &TextBuffer) {
+    let Some((first, last)) = block_lines(buffer) else {
+        return;
+    };
+    let mut seen = std::collections::HashSet::new();
+    let lines: Vec<String> = lines_text(buffer, first, last)
+        .into_iter()
+        .filter(|line| seen.insert(line.clone()))
+        .collect();
+    replace_lines(buffer, first, last, &lines);
+}
+
+/// Reverses the order of the selected lines
+pub fn reverse_lines(buffer: &TextBuffer) {
+    let Some((first, last)) = block_lines(buffer) else {
+        return;
+    };
+    let mut lines = lines_text(buffer, first, last);
+    lines.reverse();
+    replace_lines(buffer, first, last, &lines);
+}
+
+/// Shuffles the selected lines
+pub fn shuffle_lines(buffer: &TextBuffer) {
+    let Some((first, last)) = block_lines(buffer) else {
+        return;
+    };
+    let mut lines = lines_text(buffer, first, last);
+
+    // Fisher-Yates with a xorshift generator seeded from the clock
+    let mut state = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_nanos() as u64)
+        .unwrap_or(0x2545_F491_4F6C_DD1D)
+        | 1;
+    for i in (1..lines.len()).rev() {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        let j = (state % (i as u64 + 1)) as usize;
+        lines.swap(i, j);
+    }
+    replace_lines(buffer, first, last, &lines);
+}
+
+/// Adds the line commands to a source view
+///
+/// Shortcuts: Ctrl+Shift+D duplicate, Ctrl+Shift+K delete, Alt+Up/Down move,
+/// Ctrl+J join, F9 / Shift+F9 sort ascending / descending. Every command is also
+/// in the "Lines" submenu of the view's context menu, with the sort options.
+pub fn attach(view: &View) {
+    view.insert_action_group("lines", Some(&create_line_actions(view)));
+
+    // Add a "Lines" submenu next to the existing context menu entries
+    let lines_menu = gio::Menu::new();
+    let edit_section = gio::Menu::new();
+    edit_section.append(Some("Duplicate"), Some("lines.duplicate"));
+    edit_section.append(Some("Delete"), Some("lines.delete"));
+    edit_section.append(Some("Move Up"), Some("lines.move-up"));
+    edit_section.append(Some("Move Down"), Some("lines.move-down"));
+    edit_section.append(Some("Join"), Some("lines.join"));
+    lines_menu.append_section(None, &edit_section);
+    let order_section = gio::Menu::new();
+    order_section.append(Some("Sort Ascending"), Some("lines.sort-ascending"));
+    order_section.append(Some("Sort Descending"), Some("lines.sort-descending"));
+    order_section.append(Some("Natural Sort"), Some("lines.sort-natural"));
+    order_section.append(Some("Ignore Case When Sorting"), Some("lines.sort-case-insensitive"));
+    lines_menu.append_section(None, &order_section);
+    let other_section = gio::Menu::new();
+    other_section.append(Some("Remove Duplicates"), Some("lines.unique"));
+    other_section.append(Some("Reverse"), Some("lines.reverse"));
+    other_section.append(Some("Shuffle"), Some("lines.shuffle"));
+    lines_menu.append_section(None, &other_section);
+
+    let section = gio::Menu::new();
+    section.append_submenu(Some("Lines"), &lines_menu);
+    crate::syntax::add_context_menu_section(view, &section);
+}
+
+/// Creates the "lines" action group of a view
+///
+/// sort-natural and sort-case-insensitive are toggles read by the sort actions.
+fn create_line_actions(view: &View) -> gio::SimpleActionGroup {
+    let action_group = gio::SimpleActionGroup::new();
+
+    let simple_commands: [(&str, fn(&TextBuffer)); 8] = [
+        ("duplicate", duplicate_lines),
+        ("delete", delete_lines),
+        ("move-up", |buffer| {
+            move_lines(buffer, true);
+        }),
+        ("move-down", |buffer| {
+            move_lines(buffer, false);
+        }),
+        ("join", join_lines),
+        ("unique", unique_lines),
+        ("reverse", reverse_lines),
+        ("shuffle", shuffle_lines),
+    ];
+    for (name, command) in simple_commands {
+        let action = gio::SimpleAction::new(name, None);
+        let view = view.clone();
+        action.connect_activate(move |_, _| {
+            if !can_run(&view) {
+                return;
+            }
+            crate::macros::record_action(&format!("lines.{}", name));
+            let buffer = view.buffer();
+            command(&buffer);
+            view.scroll_mark_onscreen(&buffer.get_insert());
+        });
+        action_group.add_action(&action);
+    }
+
+    let natural_action = gio::SimpleAction::new_stateful("sort-natural", None, &false.to_variant());
+    let case_action = gio::SimpleAction::new_stateful("sort-case-insensitive", None, &false.to_variant());
+    for toggle in [&natural_action, &case_action] {
+        toggle.connect_activate(|action, _| {
+            let enabled = action.state().and_then(|state| state.get::<bool>()).unwrap_or(false);
+            action.set_state(&(!enabled).to_variant());
+        });
+        action_group.add_action(toggle);
+    }
+
+    for (name, descending) in [("sort-ascending", false), ("sort-descending", true)] {
+        let action = gio::SimpleAction::new(name, None);
+        let view = view.clone();
+        let natural_action = natural_action.clone();
+        let case_action = case_action.clone();
+        action.connect_activate(move |_, _| {
+            if !can_run(&view) {
+                return;
+            }
+            crate::macros::record_action(&format!("lines.{}", name));
+            let is_on = |action: &gio::SimpleAction| action.state().and_then(|state| state.get::<bool>()).unwrap_or(false);
+            let options = SortOptions {
+                descending,
+                natural: is_on(&natural_action),
+                case_insensitive: is_on(&case_action),
+            };
+            sort_lines(&view.buffer(), options);
+        });
+        action_group.add_action(&action);
+    }
+
+    action_group
+}
+
+/// Checks whether the line commands can run in a view: it is editable and has a single caret
+fn can_run(view: &View) -> bool {
+    view.is_editable() && !crate::multi_cursor::has_extra_carets(view)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digits_compare_by_value() {
+        assert_eq!(natural_compare("file2", "file10"), Ordering::Less);
+        assert_eq!(natural_compare("file10", "file9"), Ordering::Greater);
+        assert_eq!(natural_compare("x9y", "x10a"), Ordering::Less);
+        assert_eq!(natural_compare("v1.10", "v1.9"), Ordering::Greater);
+    }
+
+    #[test]
+    fn leading_zeros_only_break_ties() {
+        assert_eq!(natural_compare("007", "8"), Ordering::Less);
+        assert_eq!(natural_compare("a1", "a01"), Ordering::Less);
+        assert_eq!(natural_compare("a01b", "a1c"), Ordering::Greater);
+        assert_eq!(natural_compare("0", "00"), Ordering::Less);
+        assert_eq!(natural_compare("a01", "a01"), Ordering::Equal);
+    }
+
+    #[test]
+    fn text_compares_by_character() {
+        assert_eq!(natural_compare("abc", "abd"), Ordering::Less);
+        assert_eq!(natural_compare("ab", "abc"), Ordering::Less);
+        assert_eq!(natural_compare("", ""), Ordering::Equal);
+        assert_eq!(natural_compare("B", "a"), Ordering::Less);
+    }
+}
@@ -0,0 +1,661 @@
+// Keyboard macros for the text editor
+// Records key presses and editor commands in a view and plays them back; named macros live in the config dir
+
+use gtk4::prelude::*;
+use gtk4::{
+    gdk, gio, glib, Box as GtkBox, CheckButton, Dialog, DropDown, Entry, EventControllerKey, Label, Orientation,
+    ResponseType, SpinButton, StringList,
+};
+use sourceview5::{prelude::*, View};
+use std::cell::{Cell, RefCell};
+use std::fs;
+use std::path::PathBuf;
+
+/// File extension of saved macros
+const MACRO_EXTENSION: &str = "macro";
+
+/// Upper bound for "until end of buffer", in case a macro never reaches the end
+const MAX_REPETITIONS: u32 = 100_000;
+
+/// Label of the unsaved, most recently recorded macro in the play dialog
+const LAST_MACRO_LABEL: &str = "Last recorded macro";
+
+/// How long a macro message stays in the status bar
+const MESSAGE_SECONDS: u32 = 3;
+
+/// One recorded step of a macro
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MacroStep {
+    /// A key press with its Ctrl/Shift/Alt/Super modifiers
+    Key { keyval: gdk::Key, modifiers: gdk::ModifierType },
+    /// An editor command run from a shortcut or a menu, e.g. "lines.sort-ascending"
+    Action(String),
+}
+
+/// How often a macro is played
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Repeat {
+    Times(u32),
+    /// Until the cursor reaches the end of the buffer or the macro stops changing anything
+    UntilEnd,
+}
+
+thread_local! {
+    // Steps recorded so far, while recording
+    static RECORDING: RefCell<Option<Vec<MacroStep>>> = RefCell::new(None);
+    // The macro recorded last, played by Ctrl+Shift+E
+    static LAST_MACRO: RefCell<Vec<MacroStep>> = RefCell::new(Vec::new());
+    // Set while a macro plays so its own keys and commands are not recorded
+    static PLAYING: Cell<bool> = Cell::new(false);
+    // Status bar label showing the recording state and macro messages
+    static STATUS_LABEL: RefCell<Option<glib::WeakRef<Label>>> = RefCell::new(None);
+    // Counts the messages shown, so only the latest one is cleared
+    static MESSAGE_SERIAL: Cell<u32> = Cell::new(0);
+}
+
+/// Returns the directory holding the saved macros
+pub fn macros_dir() -> PathBuf {
+    crate::settings::get_config_dir().join("macros")
+}
+
+/// Converts macro steps to the saved text format, one step per line
+///
+/// Keys are written as "key <name> [ctrl] [shift] [alt] [super]" and commands as
+/// "action <name>".
+pub fn format_steps(steps: &[MacroStep]) -> String {
+    let mut text = String::new();
+    for step in steps {
+        match step {
+            MacroStep::Key { keyval, modifiers } => {
+                let Some(name) = keyval.name() else {
+                    continue;
+                };
+                text.push_str("key ");
+                text.push_str(&name);
+                for (mask, word) in modifier_words() {
+                    if modifiers.contains(mask) {
+                        text.push(' ');
+                        text.push_str(word);
+                    }
+                }
+            }
+            MacroStep::Action(name) => {
+                text.push_str("action ");
+                text.push_str(name);
+            }
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/// Parses the saved text format; unknown lines and comments (#) are skipped
+pub fn parse_steps(contents: &str) -> Vec<MacroStep> {
+    let mut steps = Vec::new();
+    for line in contents.lines() {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("key") => {
+                let Some(keyval) = words.next().and_then(gdk::Key::from_name) else {
+                    continue;
+                };
+                let mut modifiers = gdk::ModifierType::empty();
+                for word in words {
+                    if let Some((mask, _)) = modifier_words().into_iter().find(|(_, name)| *name == word) {
+                        modifiers |= mask;
+                    }
+                }
+                steps.push(MacroStep::Key { keyval, modifiers });
+            }
+            Some("action") => {
+                if let Some(name) = words.next() {
+                    steps.push(MacroStep::Action(name.to_string()));
+                }
+            }
+            _ => {}
+        }
+    }
+    steps
+}
+
+/// Modifiers kept in recorded key steps, with their names in saved files
+fn modifier_words() -> [(gdk::ModifierType, &'static str); 4] {
+    [
+        (gdk::ModifierType::CONTROL_MASK, "ctrl"),
+        (gdk::ModifierType::SHIFT_MASK, "shift"),
+        (gdk::ModifierType::ALT_MASK, "alt"),
+        (gdk::ModifierType::SUPER_MASK, "super"),
+    ]
+}
+
+/// Returns the names of the saved macros, sorted
+pub fn saved_macro_names() -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(macros_dir())
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().and_then(|e| e.to_str()) == Some(MACRO_EXTENSION))
+                .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().to_string()))
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+/// Loads a saved macro by name
+pub fn load_macro(name: &str) -> Option<Vec<MacroStep>> {
+    let path = macros_dir().join(format!("{}.{}", name, MACRO_EXTENSION));
+    match fs::read_to_string(&path) {
+        Ok(contents) => Some(parse_steps(&contents)),
+        Err(e) => {
+            eprintln!("Failed to read macro {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Saves a macro under a name, replacing any macro with the same name
+pub fn save_macro(name: &str, steps: &[MacroStep]) -> std::io::Result<()> {
+    let dir = macros_dir();
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join(format!("{}.{}", name, MACRO_EXTENSION)), format_steps(steps))
+}
+
+/// Deletes a saved macro
+pub fn delete_macro(name: &str) -> std::io::Result<()> {
+    fs::remove_file(macros_dir().join(format!("{}.{}", name, MACRO_EXTENSION)))
+}
+
+/// Checks whether a macro is being recorded
+pub fn is_recording() -> bool {
+    RECORDING.with(|recording| recording.borrow().is_some())
+}
+
+/// Starts recording, or stops and keeps the recording as the last macro
+pub fn toggle_recording() {
+    let finished = RECORDING.with(|recording| recording.borrow_mut().take());
+    match finished {
+        // An empty recording keeps the previous macro
+        Some(steps) if steps.is_empty() => show_message("Recorded nothing; the last macro is kept"),
+        Some(steps) => {
+            show_message(&format!("Recorded macro ({} steps)", steps.len()));
+            LAST_MACRO.with(|last| *last.borrow_mut() = steps);
+        }
+        None => {
+            RECORDING.with(|recording| *recording.borrow_mut() = Some(Vec::new()));
+            update_status_label();
+        }
+    }
+}
+
+/// Adds a step to the recording, if one is running
+fn record_step(step: MacroStep) {
+    if PLAYING.with(|playing| playing.get()) {
+        return;
+    }
+    RECORDING.with(|recording| {
+        if let Some(steps) = recording.borrow_mut().as_mut() {
+            steps.push(step);
+        }
+    });
+}
+
+/// Records an editor command, run from a shortcut or a menu
+///
+/// Shortcuts run their commands before the view sees the key, so commands
+/// record themselves; `name` is the detailed action name.
+pub fn record_action(name: &str) {
+    record_step(MacroStep::Action(name.to_string()));
+}
+
+/// Creates the status bar label showing when a macro is being recorded
+///
+/// The label also shows macro messages for a few seconds, such as how often a
+/// macro played.
+pub fn create_status_label() -> Label {
+    let label = Label::new(None);
+    label.add_css_class("status-item");
+    STATUS_LABEL.with(|status_label| *status_label.borrow_mut() = Some(label.downgrade()));
+    update_status_label();
+    label
+}
+
+/// Shows the recording state on the status bar label
+fn update_status_label() {
+    let Some(label) = STATUS_LABEL.with(|status_label| status_label.borrow().as_ref().and_then(|weak| weak.upgrade())) else {
+        return;
+    };
+    let recording = is_recording();
+    label.set_text("● Recording macro");
+    label.set_tooltip_text(Some("Stop recording with Ctrl+Shift+R"));
+    label.add_css_class("error");
+    label.set_visible(recording);
+}
+
+/// Shows a macro message on the status bar label for a few seconds
+fn show_message(message: &str) {
+    let Some(label) = STATUS_LABEL.with(|status_label| status_label.borrow().as_ref().and_then(|weak| weak.upgrade())) else {
+        return;
+    };
+    label.set_text(message);
+    label.set_tooltip_text(None);
+    label.remove_css_class("error");
+    label.set_visible(true);
+
+    let serial = MESSAGE_SERIAL.with(|serial| {
+        serial.set(serial.get().wrapping_add(1));
+        serial.get()
+    });
+    glib::timeout_add_seconds_local_once(MESSAGE_SECONDS, move || {
+        if MESSAGE_SERIAL.with(|current| current.get()) == serial {
+            update_status_label();
+        }
+    });
+}
+
+/// Adds macro recording and playback to a source view
+///
+/// Ctrl+Shift+R starts or stops recording, Ctrl+Shift+E plays the last macro once
+/// (the defaults of the "macros" actions in keybindings.conf).
+/// The "Macros" submenu of the context menu also opens the play dialog. Attach this
+/// before the other key handlers (only Vim input comes earlier) so it sees every
+/// key press the shortcuts leave to the view; commands record themselves.
+pub fn attach(view: &View) {
+    let key_controller = EventControllerKey::new();
+    key_controller.set_propagation_phase(gtk4::PropagationPhase::Capture);
+    key_controller.connect_key_pressed(move |_, keyval, _, state| {
+        if is_recording() && !crate::multi_cursor::is_modifier_key(keyval) {
+            let modifiers = modifier_words()
+                .into_iter()
+                .fold(gdk::ModifierType::empty(), |mask, (modifier, _)| mask | (state & modifier));
+            record_step(MacroStep::Key { keyval, modifiers });
+        }
+        glib::Propagation::Proceed
+    });
+    view.add_controller(key_controller);
+
+    let action_group = gio::SimpleActionGroup::new();
+    let toggle_action = gio::SimpleAction::new("toggle-recording", None);
+    toggle_action.connect_activate(|_, _| toggle_recording());
+    action_group.add_action(&toggle_action);
+
+    let play_action = gio::SimpleAction::new("play", None);
+    let view_clone = view.clone();
+    play_action.connect_activate(move |_, _| play_last_macro(&view_clone, Repeat::Times(1)));
+    action_group.add_action(&play_action);
+
+    let dialog_action = gio::SimpleAction::new("play-dialog", None);
+    let view_clone = view.clone();
+    dialog_action.connect_activate(move |_, _| show_play_macro_dialog(&view_clone));
+    action_group.add_action(&dialog_action);
+    view.insert_action_group("macros", Some(&action_group));
+
+    let macros_menu = gio::Menu::new();
+    macros_menu.append(Some("Start/Stop Recording"), Some("macros.toggle-recording"));
+    macros_menu.append(Some("Play Last Macro"), Some("macros.play"));
+    macros_menu.append(Some("Play Macro…"), Some("macros.play-dialog"));
+    let section = gio::Menu::new();
+    section.append_submenu(Some("Macros"), &macros_menu);
+    crate::syntax::add_context_menu_section(view, &section);
+}
+
+/// Plays the most recently recorded macro
+pub fn play_last_macro(view: &View, repeat: Repeat) {
+    let steps = LAST_MACRO.with(|last| last.borrow().clone());
+    play_macro(view, &steps, repeat);
+}
+
+/// Plays macro steps in a view as a single undo step
+///
+/// Playing is ignored while recording, so a macro can't record itself.
+pub fn play_macro(view: &View, steps: &[MacroStep], repeat: Repeat) {
+    if steps.is_empty() {
+        show_message("No macro to play");
+        return;
+    }
+    if is_recording() {
+        show_message("Stop recording before playing a macro");
+        return;
+    }
+
+    let buffer = view.buffer();
+    PLAYING.with(|playing| playing.set(true));
+    buffer.begin_user_action();
+
+    let mut repetitions = 0;
+    match repeat {
+        Repeat::Times(count) => {
+            for _ in 0..count {
+                play_steps(view, steps);
+                repetitions += 1;
+            }
+        }
+        Repeat::UntilEnd => {
+            while repetitions < MAX_REPETITIONS {
+                let before = (buffer.iter_at_mark(&buffer.get_insert()).offset(), buffer.char_count());
+                play_steps(view, steps);
+                repetitions += 1;
+
+                let cursor = buffer.iter_at_mark(&buffer.get_insert());
+                if cursor.is_end() || (cursor.offset(), buffer.char_count()) == before {
+                    break;
+                }
+            }
+        }
+    }
+
+    buffer.end_user_action();
+    PLAYING.with(|playing| playing.set(false));
+    view.scroll_mark_onscreen(&buffer.get_insert());
+    if repetitions == 1 {
+        show_message("Played macro once");
+    } else {
+        show_message(&format!("Played macro {} times", repetitions));
+    }
+}
+
+/// Plays every step of a macro once
+fn play_steps(view: &View, steps: &[MacroStep]) {
+    for step in steps {
+        match step {
+            MacroStep::Key { keyval, modifiers } => play_key(view, *keyval, *modifiers),
+            MacroStep::Action(name) => {
+                if !crate::keybindings::activate_action(view, name) {
+                    eprintln!("Macro command {} failed", name);
+                }
+            }
+        }
+    }
+}
+
+/// Replays a key press through the handlers a real key press reaches
+///
+/// GTK can't synthesize key events, so the key goes to the bound command first,
+/// as the window's accelerators would, then to the view's key controllers in
+/// propagation order (capture phase first), its keybindings (those of GtkTextView
+/// and GtkSourceView) and the window's Emacs chords. A printable key that nothing
+/// handled is typed, as the input method would.
+fn play_key(view: &View, keyval: gdk::Key, modifiers: gdk::ModifierType) {
+    if crate::keybindings::activate_accel(view, keyval, modifiers) {
+        return;
+    }
+
+    let controllers: Vec<gtk4::EventController> = {
+        let model = view.observe_controllers();
+        (0..model.n_items()).filter_map(|position| model.item(position).and_downcast()).collect()
+    };
+    let dispatch = |controller: &gtk4::EventController| {
+        if let Some(key_controller) = controller.downcast_ref::<EventControllerKey>() {
+            key_controller.emit_by_name::<bool>("key-pressed", &[&keyval, &0u32, &modifiers])
+        } else if let Some(shortcut_controller) = controller.downcast_ref::<gtk4::ShortcutController>() {
+            activate_shortcut(view, shortcut_controller, keyval, modifiers)
+        } else {
+            false
+        }
+    };
+    let phase = |controller: &&gtk4::EventController| controller.propagation_phase();
+
+    if controllers.iter().filter(|controller| phase(controller) == gtk4::PropagationPhase::Capture).any(|controller| dispatch(controller)) {
+        return;
+    }
+    // The view's own paste is asynchronous and would land after the following steps
+    if is_paste_key(keyval, modifiers) {
+        paste_now(view);
+        return;
+    }
+    if controllers
+        .iter()
+        .filter(|controller| matches!(phase(controller), gtk4::PropagationPhase::Target | gtk4::PropagationPhase::Bubble))
+        .any(|controller| dispatch(controller))
+    {
+        return;
+    }
+
+    if crate::utils::handle_window_key(keyval, modifiers) == glib::Propagation::Stop {
+        return;
+    }
+
+    let ctrl_or_alt = modifiers.intersects(gdk::ModifierType::CONTROL_MASK | gdk::ModifierType::ALT_MASK);
+    if let Some(character) = keyval.to_unicode().filter(|c| !c.is_control() && !ctrl_or_alt) {
+        view.emit_insert_at_cursor(&character.to_string());
+    }
+}
+
+/// Runs the shortcut of a shortcut controller that matches a key, returning whether one ran
+fn activate_shortcut(
+    view: &View,
+    shortcut_controller: &gtk4::ShortcutController,
+    keyval: gdk::Key,
+    modifiers: gdk::ModifierType,
+) -> bool {
+    (0..shortcut_controller.n_items())
+        .filter_map(|position| shortcut_controller.item(position).and_downcast::<gtk4::Shortcut>())
+        .filter(|shortcut| shortcut.trigger().is_some_and(|trigger| trigger_matches(&trigger, keyval, modifiers)))
+        .any(|shortcut| {
+            shortcut.action().is_some_and(|action| {
+                action.activate(gtk4::ShortcutActionFlags::EXCLUSIVE, view, shortcut.arguments().as_ref())
+            })
+        })
+}
+
+/// Checks whether a shortcut trigger fires for a key with exactly these modifiers
+fn trigger_matches(trigger: &gtk4::ShortcutTrigger, keyval: gdk::Key, modifiers: gdk::ModifierType) -> bool {
+    if let Some(keyval_trigger) = trigger.downcast_ref::<gtk4::KeyvalTrigger>() {
+        keyval_trigger.keyval().to_lower() == keyval.to_lower() && keyval_trigger.modifiers() == modifiers
+    } else if let Some(alternative) = trigger.downcast_ref::<gtk4::AlternativeTrigger>() {
+        trigger_matches(&alternative.first(), keyval, modifiers) || trigger_matches(&alternative.second(), keyval, modifiers)
+    } else {
+        false
+    }
+}
+
+/// Checks whether a key is plain Ctrl+V
+fn is_paste_key(keyval: gdk::Key, modifiers: gdk::ModifierType) -> bool {
+    keyval.to_lower() == gdk::Key::v && modifiers == gdk::ModifierType::CONTROL_MASK
+}
+
+/// Types text at the cursor, replacing the selection
+fn insert_text(view: &View, text: &str) {
+    let buffer = view.buffer();
+    buffer.delete_selection(true, view.is_editable());
+    buffer.insert_interactive_at_cursor(text, view.is_editable());
+    view.scroll_mark_onscreen(&buffer.get_insert());
+}
+
+/// Pastes the clipboard synchronously when it holds text copied in this editor
+///
+/// The view's own paste is asynchronous and would land after the following steps,
+/// so it is only used for clipboards owned by other applications.
+fn paste_now(view: &View) {
+    let local_text = view
+        .clipboard()
+        .content()
+        .and_then(|content| content.value(glib::Type::STRING).ok())
+        .and_then(|value| value.get::<String>().ok());
+    match local_text {
+        Some(text) => insert_text(view, &text),
+        None => view.emit_paste_clipboard(),
+    }
+}
+
+/// Shows the dialog to play, save and delete macros
+///
+/// The macro plays in the view that opened the dialog.
+pub fn show_play_macro_dialog(view: &View) {
+    let dialog = Dialog::builder()
+        .title("Play Macro")
+        .modal(true)
+        .destroy_with_parent(true)
+        .use_header_bar(1)
+        .build();
+    if let Some(window) = view.root().and_then(|root| root.downcast::<gtk4::Window>().ok()) {
+        dialog.set_transient_for(Some(&window));
+    }
+    dialog.add_css_class("play-macro");
+    dialog.add_button("Close", ResponseType::Close);
+    dialog.add_button("Play", ResponseType::Accept);
+    dialog.set_default_response(ResponseType::Accept);
+
+    let content_area = dialog.content_area();
+    content_area.set_margin_top(10);
+    content_area.set_margin_bottom(10);
+    content_area.set_margin_start(10);
+    content_area.set_margin_end(10);
+    content_area.set_spacing(8);
+
+    // The last recording comes first, then the saved macros
+    let macro_names = StringList::new(&[LAST_MACRO_LABEL]);
+    for name in saved_macro_names() {
+        macro_names.append(&name);
+    }
+    let macro_dropdown = DropDown::new(Some(macro_names.clone()), None::<gtk4::Expression>);
+    let macro_row = GtkBox::new(Orientation::Horizontal, 6);
+    macro_row.append(&Label::new(Some("Macro:")));
+    macro_dropdown.set_hexpand(true);
+    macro_row.append(&macro_dropdown);
+    let delete_button = gtk4::Button::from_icon_name("user-trash-symbolic");
+    delete_button.set_tooltip_text(Some("Delete the selected saved macro"));
+    macro_row.append(&delete_button);
+    content_area.append(&macro_row);
+
+    let times_check = CheckButton::with_label("Repeat");
+    times_check.set_active(true);
+    let times_spin = SpinButton::with_range(1.0, 10_000.0, 1.0);
+    let times_row = GtkBox::new(Orientation::Horizontal, 6);
+    times_row.append(&times_check);
+    times_row.append(&times_spin);
+    times_row.append(&Label::new(Some("times")));
+    content_area.append(&times_row);
+
+    let until_end_check = CheckButton::with_label("Repeat until the end of the buffer");
+    until_end_check.set_group(Some(&times_check));
+    content_area.append(&until_end_check);
+
+    // Saving names the last recording so it survives restarts
+    let save_row = GtkBox::new(Orientation::Horizontal, 6);
+    let name_entry = Entry::new();
+    name_entry.set_placeholder_text(Some("Name for the last recorded macro"));
+    name_entry.set_hexpand(true);
+    let save_button = gtk4::Button::with_label("Save");
+    save_row.append(&name_entry);
+    save_row.append(&save_button);
+    content_area.append(&save_row);
+
+    let selected_name = {
+        let macro_dropdown = macro_dropdown.clone();
+        move || -> Option<String> {
+            macro_dropdown
+                .selected_item()
+                .and_then(|item| item.downcast::<gtk4::StringObject>().ok())
+                .map(|item| item.string().to_string())
+                .filter(|name| name != LAST_MACRO_LABEL)
+        }
+    };
+
+    let selected_name_for_delete = selected_name.clone();
+    let macro_names_for_delete = macro_names.clone();
+    let macro_dropdown_for_delete = macro_dropdown.clone();
+    delete_button.connect_clicked(move |_| {
+        let Some(name) = selected_name_for_delete() else {
+            return;
+        };
+        match delete_macro(&name) {
+            Ok(()) => {
+                show_message(&format!("Deleted macro {}", name));
+                macro_names_for_delete.remove(macro_dropdown_for_delete.selected());
+                macro_dropdown_for_delete.set_selected(0);
+            }
+            Err(e) => eprintln!("Failed to delete macro {}: {}", name, e),
+        }
+    });
+
+    let macro_names_for_save = macro_names.clone();
+    let macro_dropdown_for_save = macro_dropdown.clone();
+    let name_entry_for_save = name_entry.clone();
+    save_button.connect_clicked(move |_| {
+        // The name becomes a file name, so keep it to a safe character set
+        let name: String = name_entry_for_save
+            .text()
+            .trim()
+            .chars()
+            .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        let steps = LAST_MACRO.with(|last| last.borrow().clone());
+        if name.is_empty() || steps.is_empty() {
+            return;
+        }
+        match save_macro(&name, &steps) {
+            Ok(()) => {
+                show_message(&format!("Saved macro {}", name));
+                let existing = (0..macro_names_for_save.n_items()).find(|i| macro_names_for_save.string(*i).as_deref() == Some(name.as_str()));
+                let position = existing.unwrap_or_else(|| {
+                    macro_names_for_save.append(&name);
+                    macro_names_for_save.n_items() - 1
+                });
+                macro_dropdown_for_save.set_selected(position);
+                name_entry_for_save.set_text("");
+            }
+            Err(e) => eprintln!("Failed to save macro {}: {}", name, e),
+        }
+    });
+
+    let view = view.clone();
+    dialog.connect_response(move |dialog, response| {
+        if response == ResponseType::Accept {
+            let repeat = if until_end_check.is_active() {
+                Repeat::UntilEnd
+            } else {
+                Repeat::Times(times_spin.value() as u32)
+            };
+            let steps = match selected_name() {
+                Some(name) => load_macro(&name).unwrap_or_default(),
+                None => LAST_MACRO.with(|last| last.borrow().clone()),
+            };
+            play_macro(&view, &steps, repeat);
+        }
+        dialog.close();
+    });
+
+    dialog.present();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_steps_read_back_the_same() {
+        let steps = vec![
+            MacroStep::Key { keyval: gdk::Key::a, modifiers: gdk::ModifierType::empty() },
+            MacroStep::Key { keyval: gdk::Key::Return, modifiers: gdk::ModifierType::empty() },
+            MacroStep::Key {
+                keyval: gdk::Key::Right,
+                modifiers: gdk::ModifierType::CONTROL_MASK | gdk::ModifierType::SHIFT_MASK,
+            },
+            MacroStep::Key {
+                keyval: gdk::Key::z,
+                modifiers: gdk::ModifierType::ALT_MASK | gdk::ModifierType::SUPER_MASK,
+            },
+            MacroStep::Action("lines.sort-ascending".to_string()),
+            MacroStep::Action("fold.fold-level(2)".to_string()),
+        ];
+        let text = format_steps(&steps);
+        assert_eq!(text.lines().nth(2), Some("key Right ctrl shift"));
+        assert_eq!(parse_steps(&text), steps);
+    }
+
+    #[test]
+    fn unknown_and_comment_lines_are_skipped() {
+        let text = "# recorded by hand\n\nkey b\nkey NoSuchKey ctrl\nkey\naction\nmove left\nkey c hyper ctrl\naction wrap.toggle\n";
+        assert_eq!(
+            parse_steps(text),
+            vec![
+                MacroStep::Key { keyval: gdk::Key::b, modifiers: gdk::ModifierType::empty() },
+                MacroStep::Key { keyval: gdk::Key::c, modifiers: gdk::ModifierType::CONTROL_MASK },
+                MacroStep::Action("wrap.toggle".to_string()),
+            ]
+        );
+    }
+}
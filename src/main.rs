@@ -4,6 +4,32 @@ mod handlers;  // Event handlers and business logic
 mod utils;     // Utility functions used across the application
 mod syntax;    // Syntax highlighting functionality
 mod settings;  // User settings and preferences
+mod search;    // In-buffer find and replace
+mod project;   // Project tree walking shared by project-wide features
+mod find_in_files; // Project-wide search panel
+mod status_bar; // Cursor position display in the status bar
+mod goto_line; // Go to line/column dialog
+mod multi_cursor; // Multiple carets and selections in source views
+mod folding;   // Code folding in the source view gutter
+mod brackets;  // Auto-closing pairs and bracket navigation
+mod comments;  // Line and block comment toggling
+mod whitespace; // Visible whitespace, indentation guides and mixed-indent warnings
+mod indentation; // Per-file indentation detection and override
+mod editorconfig; // .editorconfig lookup for opened and saved files
+mod completion; // Word, path and keyword completion providers
+mod snippets;  // User snippet files, Tab expansion and the snippet manager
+mod line_commands; // Duplicate, move, join, sort and other line editing commands
+mod macros;    // Keyboard macro recording and playback
+mod undo_history; // Undo/redo history saved between sessions
+mod vim;       // Optional Vim keybindings
+mod emacs;     // Optional Emacs keybindings
+mod commands;  // Editor commands shared by the keymaps
+mod keybindings; // Rebindable shortcuts loaded from keybindings.conf
+mod command_palette; // Ctrl+Shift+P list of every command
+mod quick_open;  // Ctrl+P fuzzy file finder over a background project index
+mod dictionary;  // Hunspell dictionaries for the spell checker
+mod spell_check; // Spell checking of prose, comments and strings
+mod wrap;        // Wrap modes, Alt+Z and right-margin rulers
 
 // GTK and standard library imports
 use gtk4::prelude::*;   // GTK trait imports for widget functionality
@@ -15,7 +41,6 @@ use std::rc::Rc;        // Reference counting for shared ownership
 use std::cell::RefCell; // Interior mutability pattern
 use std::collections::HashMap; // For mapping tab indices to file paths
 use std::path::PathBuf;        // File system path representation
-use std::io::Write;            // File writing capabilities
 use clap::Parser;       // Command line argument parsing
 
 /// Command line arguments for the Basado Text Editor
@@ -172,10 +197,22 @@ pub fn update_all_buffer_themes(window: &gtk4::ApplicationWindow) {
                 println!("Found {} source views in page {}", source_views.len(), page_num);
                 
                 for (view_idx, source_view) in source_views.iter().enumerate() {
+                    // Minimaps share their editor's buffer, only their visibility needs updating
+                    if let Some(minimap) = source_view.downcast_ref::<sourceview5::Map>() {
+                        syntax::update_minimap(minimap);
+                        continue;
+                    }
+                    
                     let buffer = source_view.buffer();
                     if let Some(source_buffer) = buffer.dynamic_cast_ref::<sourceview5::Buffer>() {
                         println!("Updating source buffer {} in page {}", view_idx, page_num);
                         syntax::update_buffer_style_scheme(source_buffer);
+                        whitespace::apply_whitespace_settings(source_view);
+                        completion::apply_completion_settings(source_view);
+                        vim::apply_vim_settings(source_view);
+                        emacs::apply_emacs_settings(source_view);
+                        spell_check::apply_spell_check_settings(source_view);
+                        wrap::apply_wrap_settings(source_view);
                         source_view.queue_draw();
                     }
                 }
@@ -189,6 +226,9 @@ pub fn update_all_buffer_themes(window: &gtk4::ApplicationWindow) {
         notebook.queue_draw();
     }
 
+    // The Vim and Emacs keymaps keep some keys from the shortcuts
+    keybindings::register_accels();
+
     // Let's also print the current dark mode setting to help with debugging
     if let Some(settings) = gtk4::Settings::default() {
         let is_dark = settings.is_gtk_application_prefer_dark_theme();
@@ -370,6 +410,8 @@ fn build_ui(app: &Application, file_to_open: Option<PathBuf>) {
             dialog.set_default_response(gtk4::ResponseType::Cancel);
             
             let window_clone_for_dialog = window_clone_for_close.clone();
+            let notebook_for_dialog = notebook.clone();
+            let file_path_manager_for_dialog = file_path_manager_clone_for_close.clone();
             
             dialog.connect_response(move |d, response| {
                 d.close();
@@ -377,6 +419,7 @@ fn build_ui(app: &Application, file_to_open: Option<PathBuf>) {
                     gtk4::ResponseType::Yes => {
                         // User chose "Close Anyway" - allow the close to proceed
                         // We need to temporarily disconnect the close handler to avoid recursion
+                        store_undo_histories(&notebook_for_dialog, &file_path_manager_for_dialog);
                         window_clone_for_dialog.destroy();
                     }
                     _ => {
@@ -391,6 +434,7 @@ fn build_ui(app: &Application, file_to_open: Option<PathBuf>) {
         }
         
         // No unsaved changes, allow normal close
+        store_undo_histories(notebook, &file_path_manager_clone_for_close);
         glib::Propagation::Proceed
     });
 
@@ -398,14 +442,37 @@ fn build_ui(app: &Application, file_to_open: Option<PathBuf>) {
     let (file_list_box, file_list_scrolled_window) =
         ui::create_file_manager_panel();
         
+    // Dependencies used by sidebar panels that open files in new tabs
+    let deps_for_sidebar_panels = handlers::NewTabDependencies {
+        editor_notebook: editor_notebook.clone(),
+        active_tab_path: active_tab_path.clone(),
+        file_path_manager: file_path_manager.clone(),
+        window: window.clone(),
+        file_list_box: file_list_box.clone(),
+        current_dir: current_dir.clone(),
+        save_button: save_button.clone(),
+        save_as_button: save_as_button.clone(),
+        _save_menu_button: Some(save_menu_button.clone()),
+    };
+
+    // Let the Vim and Emacs keymaps use the same save, close and open paths as the buttons
+    commands::set_dependencies(deps_for_sidebar_panels.clone(), &open_button);
+
+    // Index the project in the background so Ctrl+P has results right away
+    quick_open::start_indexing(&deps_for_sidebar_panels.current_dir.borrow());
+
+    // Create the project-wide search panel shown next to the file list
+    let find_in_files_panel = find_in_files::create_find_in_files_panel(&deps_for_sidebar_panels);
+
     // Assemble the file manager panel from its components
-    let file_manager_panel =
-        ui::create_file_manager_panel_container(file_list_scrolled_window);
+    let (file_manager_panel, sidebar_stack) =
+        ui::create_file_manager_panel_container(file_list_scrolled_window, &find_in_files_panel);
 
     // Create the path bar with navigation buttons and path segments
     let (path_bar, path_box, up_button, _refresh_button, terminal_button) = ui::create_path_bar();
     
-    // Set up keyboard shortcuts for common operations (including Ctrl+L for path editing)
+    // Load the user's shortcuts, then register the window commands they run
+    keybindings::initialize();
     utils::setup_keyboard_shortcuts(
         &window, 
         &save_button, 
@@ -416,38 +483,15 @@ fn build_ui(app: &Application, file_to_open: Option<PathBuf>) {
         Some(&path_box),
         Some(&current_dir),
         Some(&file_list_box),
-        Some(&active_tab_path)
+        Some(&active_tab_path),
+        Some(&sidebar_stack)
     );
 
     // Create the main container that will hold the path bar and paned content
     let main_container = GtkBox::new(gtk4::Orientation::Vertical, 0);
     main_container.append(&path_bar);
 
-    // Define GIO actions for save operations to be used by the menu
-    let save_action = gio::SimpleAction::new("save", None);
-    let save_as_action = gio::SimpleAction::new("save-as", None);
-    
-    // Prepare button references for the action handlers
-    let save_button_clone = save_button.clone();
-    let save_as_button_clone = save_as_button.clone();
-    
-    // Connect the save action to trigger the save button's click event
-    // This allows menu items to reuse existing save functionality
-    let save_button_clone_for_action = save_button_clone.clone();
-    save_action.connect_activate(move |_, _| {
-        save_button_clone_for_action.emit_clicked();
-    });
-    
-    // Connect the save-as action to trigger the save-as button's click event
-    let save_as_button_clone_for_action = save_as_button_clone.clone();
-    save_as_action.connect_activate(move |_, _| {
-        save_as_button_clone_for_action.emit_clicked();
-    });
-    
-    // Register the actions with the application window
-    // This makes them available to be triggered by menu items
-    window.add_action(&save_action);
-    window.add_action(&save_as_action);
+    // The save menu uses the "win.save" and "win.save-as" actions registered with the shortcuts above
     
     // Set up direct save functionality for the main save button
     // Instead of circular references between buttons, implement the save logic directly here
@@ -476,16 +520,11 @@ fn build_ui(app: &Application, file_to_open: Option<PathBuf>) {
                 // Check if this is a supported file type for saving
                 let mime_type = mime_guess::from_path(&path_to_save).first_or_octet_stream();
                 if utils::is_allowed_mime_type(&mime_type) {
-                    // Attempt to save the file
-                    if let Ok(mut file) = std::fs::File::create(&path_to_save) {
-                        // Extract the text content from the buffer
-                        let text = active_buffer.text(&active_buffer.start_iter(), &active_buffer.end_iter(), false);
-                        
-                        // Write the content to the file and update UI if successful
-                        if file.write_all(text.as_bytes()).is_ok() {
-                            // Update tab label to remove the modified indicator (*)
-                            handlers::update_tab_label_after_save(&editor_notebook_clone, current_page_num, Some(&path_to_save.file_name().unwrap_or_default().to_string_lossy()), false);
-                        }
+                    // Write the buffer to the file, formatted per .editorconfig, and update UI if successful
+                    match editorconfig::save_buffer(&active_buffer, &path_to_save) {
+                        // Update tab label to remove the modified indicator (*)
+                        Ok(()) => handlers::update_tab_label_after_save(&editor_notebook_clone, current_page_num, Some(&path_to_save.file_name().unwrap_or_default().to_string_lossy()), false),
+                        Err(e) => handlers::show_save_error(&editor_notebook_clone, &path_to_save, &e),
                     }
                 }
             } else {
@@ -637,6 +676,24 @@ fn build_ui(app: &Application, file_to_open: Option<PathBuf>) {
     // Add the main paned content
     main_container.append(&paned_content);
 
+    // Add the status bar showing the indentation and cursor position of the active tab
+    let (status_bar, position_label, indentation_button) = ui::create_status_bar();
+    main_container.append(&status_bar);
+    status_bar::connect_notebook(&editor_notebook, &position_label, &indentation_button);
+    indentation::install_actions(&indentation_button, &editor_notebook);
+    
+    // Offer words from every open tab in completion
+    completion::connect_notebook(&editor_notebook);
+
+    // Clicking the cursor position opens the Go to Line dialog
+    let position_click = gtk4::GestureClick::new();
+    let window_clone_for_goto = window.clone();
+    let editor_notebook_clone_for_goto = editor_notebook.clone();
+    position_click.connect_released(move |_, _, _, _| {
+        goto_line::show_goto_line_dialog(&window_clone_for_goto, &editor_notebook_clone_for_goto);
+    });
+    position_label.add_controller(position_click);
+
     // Set the custom header bar as the window's titlebar
     window.set_titlebar(Some(&header));
 
@@ -651,6 +708,9 @@ fn build_ui(app: &Application, file_to_open: Option<PathBuf>) {
     // Set the main container (with paned layout and status bar) as the window's content
     window.set_child(Some(&main_container));
 
+    // Keep the find bar open on the newly selected tab when switching tabs
+    search::connect_notebook(&editor_notebook);
+
     // Set up the tab switching handler to update UI state when changing tabs
     // Clone all required references for use in the closure
     let file_path_manager_clone_for_switch = file_path_manager.clone();
@@ -804,7 +864,7 @@ fn build_ui(app: &Application, file_to_open: Option<PathBuf>) {
                 
                 if utils::is_allowed_mime_type(&mime_type) {
                     // Try to read the file content
-                    match std::fs::read_to_string(&file_path) {
+                    match editorconfig::read_file(&file_path) {
                         Ok(content) => {
                             // Open the file in a new tab
                             handlers::open_or_focus_tab(
@@ -915,6 +975,15 @@ fn build_ui(app: &Application, file_to_open: Option<PathBuf>) {
     });
 }
 
+/// Saves the undo history of every open file before the window closes
+fn store_undo_histories(notebook: &gtk4::Notebook, file_path_manager: &Rc<RefCell<HashMap<u32, PathBuf>>>) {
+    for (page_num, path) in file_path_manager.borrow().iter() {
+        if let Some((_, buffer)) = handlers::get_text_view_and_buffer_for_page(notebook, *page_num) {
+            undo_history::store(&buffer, path);
+        }
+    }
+}
+
 /// Sets up a GSettings monitor to detect Ubuntu/GNOME theme changes
 /// This provides better integration with system theme switching on Ubuntu
 fn setup_gsettings_monitor(window: &ApplicationWindow, terminal_notebook: &gtk4::Notebook) {
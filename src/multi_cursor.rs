@@ -0,0 +1,812 @@
+// Multi-cursor editing for the text editor
+// Extra carets (with optional selections) that receive the same edits as the main cursor
+// Rectangular (block) selections are made of one caret per row
+
+use gtk4::prelude::*;
+use gtk4::{gdk, gio, glib, EventControllerKey, GestureClick, GestureDrag, TextBuffer, TextIter, TextMark, TextSearchFlags, TextWindowType};
+use sourceview5::{prelude::*, View};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use crate::utils::ObjectState;
+
+/// Tag used to show the selections of the extra carets
+const SELECTION_TAG_NAME: &str = "multi-cursor-selection";
+
+/// Tag used to show the position of the extra carets (on the character after them)
+const CARET_TAG_NAME: &str = "multi-cursor-caret";
+
+
+/// An extra caret: a cursor mark and an anchor mark, with the selection in between
+struct Caret {
+    anchor: TextMark,
+    cursor: TextMark,
+}
+
+impl Caret {
+    /// Creates a caret with right-gravity marks, so inserted text ends up before it
+    fn new(buffer: &TextBuffer, anchor: &TextIter, cursor: &TextIter) -> Self {
+        Caret {
+            anchor: buffer.create_mark(None, anchor, false),
+            cursor: buffer.create_mark(None, cursor, false),
+        }
+    }
+
+    /// The main cursor expressed as a caret (its marks belong to the buffer)
+    fn primary(buffer: &TextBuffer) -> Self {
+        Caret {
+            anchor: buffer.selection_bound(),
+            cursor: buffer.get_insert(),
+        }
+    }
+
+    /// Returns the selection bounds in document order (equal when nothing is selected)
+    fn bounds(&self, buffer: &TextBuffer) -> (TextIter, TextIter) {
+        let anchor = buffer.iter_at_mark(&self.anchor);
+        let cursor = buffer.iter_at_mark(&self.cursor);
+        if anchor.offset() <= cursor.offset() {
+            (anchor, cursor)
+        } else {
+            (cursor, anchor)
+        }
+    }
+
+    /// Moves the caret and clears its selection
+    fn move_to(&self, buffer: &TextBuffer, iter: &TextIter) {
+        buffer.move_mark(&self.cursor, iter);
+        buffer.move_mark(&self.anchor, iter);
+    }
+
+    /// Removes the marks from the buffer
+    fn remove(&self, buffer: &TextBuffer) {
+        buffer.delete_mark(&self.cursor);
+        buffer.delete_mark(&self.anchor);
+    }
+}
+
+/// Extra carets of one view
+type Carets = Rc<RefCell<Vec<Caret>>>;
+
+/// The extra carets stored on their view, for `has_extra_carets`
+const VIEW_CARETS: ObjectState<Carets> = ObjectState::new("multi-cursor-carets");
+
+/// Corners of a rectangular selection, as lines and visual columns (tabs expanded)
+///
+/// Columns may lie past the end of short lines; those rows then select nothing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct BlockSelection {
+    anchor_line: i32,
+    anchor_column: u32,
+    cursor_line: i32,
+    cursor_column: u32,
+}
+
+/// The rectangular selection of one view, while it is being extended
+type Block = Rc<Cell<Option<BlockSelection>>>;
+
+thread_local! {
+    // Set while edits are applied at the carets, so they are not typed at the extra carets again
+    static APPLYING_EDITS: Cell<bool> = Cell::new(false);
+}
+
+/// An edit applied at every caret
+enum Edit {
+    Insert(String),
+    Backspace,
+    Delete,
+}
+
+/// Adds multi-cursor support to a source view
+///
+/// - Ctrl+D selects the word under the cursor, then adds the next occurrence of the selection
+/// - Ctrl+Alt+Up/Down adds a caret on the line above/below
+/// - Alt+click adds (or removes) a caret
+/// - Alt+drag and Alt+Shift+arrows make a rectangular selection, one caret per row
+/// - Ctrl+C / Ctrl+X copy or cut the selections of all carets, one per line
+/// - Esc or a plain click goes back to a single caret
+pub fn attach(view: &View) {
+    let carets: Carets = Rc::new(RefCell::new(Vec::new()));
+    let block: Block = Rc::new(Cell::new(None));
+    let buffer = view.buffer();
+    VIEW_CARETS.set(view, carets.clone());
+
+    buffer.create_tag(Some(SELECTION_TAG_NAME), &[("background", &"rgba(53, 132, 228, 0.3)")]);
+    buffer.create_tag(Some(CARET_TAG_NAME), &[("background", &"rgba(53, 132, 228, 0.7)")]);
+
+    // Keys are handled in the capture phase so they are seen before the view's own bindings
+    let key_controller = EventControllerKey::new();
+    key_controller.set_propagation_phase(gtk4::PropagationPhase::Capture);
+    let view_for_keys = view.clone();
+    let carets_for_keys = carets.clone();
+    let block_for_keys = block.clone();
+    key_controller.connect_key_pressed(move |_, keyval, _, state| {
+        handle_key(&view_for_keys, &carets_for_keys, &block_for_keys, keyval, state)
+    });
+    view.add_controller(key_controller);
+
+    // Typed text goes through the view and its input method (so dead keys, compose
+    // sequences and IMEs work). Text the view inserts at the main cursor goes in at
+    // the extra carets first, inside the view's user action, so it is one undo step
+    let in_user_action = Rc::new(Cell::new(false));
+    let in_user_action_for_begin = in_user_action.clone();
+    buffer.connect_begin_user_action(move |_| in_user_action_for_begin.set(true));
+    let in_user_action_for_end = in_user_action.clone();
+    buffer.connect_end_user_action(move |_| in_user_action_for_end.set(false));
+    let view_for_typing = view.clone();
+    let carets_for_typing = carets.clone();
+    buffer.connect_insert_text(move |buffer, location, text| {
+        if !in_user_action.get()
+            || APPLYING_EDITS.with(|flag| flag.get())
+            || carets_for_typing.borrow().is_empty()
+            || location.offset() != buffer.iter_at_mark(&buffer.get_insert()).offset()
+        {
+            return;
+        }
+        // The edits invalidate the location, so it is found again through a mark
+        let main_insert = buffer.create_mark(None, location, true);
+        type_at_extra_carets(&view_for_typing, &carets_for_typing, text);
+        let offset = buffer.iter_at_mark(&main_insert).offset();
+        buffer.delete_mark(&main_insert);
+        *location = buffer.iter_at_offset(offset);
+    });
+
+    // Caret commands as named actions, so their keys can be rebound
+    let action_group = gio::SimpleActionGroup::new();
+    let next_occurrence_action = gio::SimpleAction::new("add-next-occurrence", None);
+    let view_for_action = view.clone();
+    let carets_for_action = carets.clone();
+    next_occurrence_action.connect_activate(move |_, _| {
+        crate::macros::record_action("cursors.add-next-occurrence");
+        add_next_occurrence(&view_for_action, &carets_for_action);
+    });
+    action_group.add_action(&next_occurrence_action);
+    for (name, above) in [("add-caret-above", true), ("add-caret-below", false)] {
+        let action = gio::SimpleAction::new(name, None);
+        let view_for_action = view.clone();
+        let carets_for_action = carets.clone();
+        action.connect_activate(move |_, _| {
+            crate::macros::record_action(&format!("cursors.{}", name));
+            add_caret_on_adjacent_line(&view_for_action, &carets_for_action, above);
+        });
+        action_group.add_action(&action);
+    }
+    view.insert_action_group("cursors", Some(&action_group));
+
+    // A plain click collapses to the main cursor
+    let click = GestureClick::new();
+    click.set_button(gdk::BUTTON_PRIMARY);
+    click.set_propagation_phase(gtk4::PropagationPhase::Capture);
+    let view_for_click = view.clone();
+    let carets_for_click = carets.clone();
+    let block_for_click = block.clone();
+    click.connect_pressed(move |gesture, _, _, _| {
+        if gesture.current_event_state().contains(gdk::ModifierType::ALT_MASK) {
+            return; // Handled by the Alt drag gesture below
+        }
+        block_for_click.set(None);
+        if !carets_for_click.borrow().is_empty() {
+            clear_carets(&view_for_click, &carets_for_click);
+        }
+    });
+    view.add_controller(click);
+
+    // Alt+drag makes a rectangular selection, Alt+click without moving toggles a caret
+    let drag = GestureDrag::new();
+    drag.set_button(gdk::BUTTON_PRIMARY);
+    drag.set_propagation_phase(gtk4::PropagationPhase::Capture);
+    let drag_anchor: Rc<Cell<Option<(i32, u32)>>> = Rc::new(Cell::new(None));
+    let dragged = Rc::new(Cell::new(false));
+
+    let view_for_drag = view.clone();
+    let drag_anchor_for_begin = drag_anchor.clone();
+    let dragged_for_begin = dragged.clone();
+    drag.connect_drag_begin(move |gesture, x, y| {
+        if !gesture.current_event_state().contains(gdk::ModifierType::ALT_MASK) {
+            drag_anchor_for_begin.set(None);
+            gesture.set_state(gtk4::EventSequenceState::Denied);
+            return;
+        }
+        gesture.set_state(gtk4::EventSequenceState::Claimed);
+        drag_anchor_for_begin.set(Some(position_at_point(&view_for_drag, x, y)));
+        dragged_for_begin.set(false);
+    });
+
+    let view_for_drag = view.clone();
+    let carets_for_drag = carets.clone();
+    let block_for_drag = block.clone();
+    let drag_anchor_for_update = drag_anchor.clone();
+    let dragged_for_update = dragged.clone();
+    drag.connect_drag_update(move |gesture, offset_x, offset_y| {
+        let (Some((anchor_line, anchor_column)), Some((start_x, start_y))) = (drag_anchor_for_update.get(), gesture.start_point()) else {
+            return;
+        };
+        // Small movements still count as a click
+        if !dragged_for_update.get() && offset_x.abs() < 4.0 && offset_y.abs() < 4.0 {
+            return;
+        }
+        dragged_for_update.set(true);
+
+        let (cursor_line, cursor_column) = position_at_point(&view_for_drag, start_x + offset_x, start_y + offset_y);
+        let selection = BlockSelection { anchor_line, anchor_column, cursor_line, cursor_column };
+        if block_for_drag.get() != Some(selection) {
+            block_for_drag.set(Some(selection));
+            select_block(&view_for_drag, &carets_for_drag, selection);
+        }
+    });
+
+    let view_for_drag = view.clone();
+    let carets_for_drag = carets.clone();
+    drag.connect_drag_end(move |gesture, _, _| {
+        if drag_anchor.take().is_none() || dragged.get() {
+            return;
+        }
+        if let Some((x, y)) = gesture.start_point() {
+            let (buffer_x, buffer_y) = view_for_drag.window_to_buffer_coords(TextWindowType::Widget, x as i32, y as i32);
+            if let Some(iter) = view_for_drag.iter_at_location(buffer_x, buffer_y) {
+                toggle_caret_at(&view_for_drag, &carets_for_drag, &iter);
+            }
+        }
+    });
+    view.add_controller(drag);
+}
+
+/// Handles a key press for the rectangular selection and for editing at every caret
+fn handle_key(view: &View, carets: &Carets, block: &Block, keyval: gdk::Key, state: gdk::ModifierType) -> glib::Propagation {
+    let ctrl = state.contains(gdk::ModifierType::CONTROL_MASK);
+    let alt = state.contains(gdk::ModifierType::ALT_MASK);
+    let shift = state.contains(gdk::ModifierType::SHIFT_MASK);
+    let only_ctrl = ctrl && !alt && !shift && !state.contains(gdk::ModifierType::SUPER_MASK);
+
+    // Alt+Shift+arrows grow or shrink the rectangular selection
+    if alt && shift && !ctrl && matches!(keyval, gdk::Key::Up | gdk::Key::Down | gdk::Key::Left | gdk::Key::Right) {
+        extend_block(view, carets, block, keyval);
+        return glib::Propagation::Stop;
+    }
+    // Any other key ends the rectangle; its rows stay as ordinary carets
+    if !is_modifier_key(keyval) {
+        block.set(None);
+    }
+
+    if carets.borrow().is_empty() {
+        return glib::Propagation::Proceed;
+    }
+
+    match keyval {
+        gdk::Key::Escape => {
+            clear_carets(view, carets);
+            return glib::Propagation::Stop;
+        }
+        gdk::Key::BackSpace if !ctrl && !alt => {
+            edit_at_all_carets(view, carets, Edit::Backspace);
+            return glib::Propagation::Stop;
+        }
+        gdk::Key::Delete | gdk::Key::KP_Delete if !ctrl && !alt => {
+            edit_at_all_carets(view, carets, Edit::Delete);
+            return glib::Propagation::Stop;
+        }
+        gdk::Key::Return | gdk::Key::KP_Enter if !ctrl && !alt => {
+            edit_at_all_carets(view, carets, Edit::Insert("\n".to_string()));
+            return glib::Propagation::Stop;
+        }
+        gdk::Key::Tab if !ctrl && !alt && !shift => {
+            let indent = if view.is_insert_spaces_instead_of_tabs() {
+                " ".repeat(view.tab_width() as usize)
+            } else {
+                "\t".to_string()
+            };
+            edit_at_all_carets(view, carets, Edit::Insert(indent));
+            return glib::Propagation::Stop;
+        }
+        gdk::Key::Left | gdk::Key::Right | gdk::Key::Up | gdk::Key::Down | gdk::Key::Home | gdk::Key::End => {
+            if ctrl || alt || shift {
+                // Word/selection movement is only supported for the main cursor
+                clear_carets(view, carets);
+            } else {
+                move_extra_carets(view, carets, keyval);
+            }
+            // Let the view move the main cursor
+            return glib::Propagation::Proceed;
+        }
+        _ => {}
+    }
+
+    if only_ctrl && keyval.to_lower() == gdk::Key::v {
+        paste_at_all_carets(view, carets);
+        return glib::Propagation::Stop;
+    }
+    if only_ctrl && matches!(keyval.to_lower(), gdk::Key::c | gdk::Key::x) {
+        copy_from_all_carets(view, carets, keyval.to_lower() == gdk::Key::x);
+        return glib::Propagation::Stop;
+    }
+
+    // Undo/redo move text around the carets, so fall back to a single caret first
+    if ctrl && matches!(keyval, gdk::Key::z | gdk::Key::Z | gdk::Key::y | gdk::Key::Y) {
+        clear_carets(view, carets);
+        return glib::Propagation::Proceed;
+    }
+
+    // Printable characters are typed by the view, and then at the extra carets
+    glib::Propagation::Proceed
+}
+
+/// Applies an edit at the main cursor and every extra caret as one undoable action
+fn edit_at_all_carets(view: &View, carets: &Carets, edit: Edit) {
+    let texts = match &edit {
+        Edit::Insert(text) => vec![text.clone(); carets.borrow().len() + 1],
+        _ => Vec::new(),
+    };
+    apply_edits(view, carets, &edit, &texts);
+}
+
+/// Applies an edit at each caret in document order, inserting `texts[i]` at the i-th caret
+fn apply_edits(view: &View, carets: &Carets, edit: &Edit, texts: &[String]) {
+    let buffer = view.buffer();
+    let primary = Caret::primary(&buffer);
+
+    // Visit carets in document order so per-caret texts line up with what the user sees
+    let mut order: Vec<(i32, Option<usize>)> = carets
+        .borrow()
+        .iter()
+        .enumerate()
+        .map(|(index, caret)| (caret.bounds(&buffer).0.offset(), Some(index)))
+        .collect();
+    order.push((primary.bounds(&buffer).0.offset(), None));
+    order.sort_by_key(|(offset, _)| *offset);
+
+    APPLYING_EDITS.with(|flag| flag.set(true));
+    buffer.begin_user_action();
+    let extra_carets = carets.borrow();
+    for (position, (_, index)) in order.iter().enumerate() {
+        let caret = match index {
+            Some(index) => &extra_carets[*index],
+            None => &primary,
+        };
+
+        let (mut start, mut end) = caret.bounds(&buffer);
+        if start.offset() != end.offset() {
+            // Typing over a selection replaces it; deleting removes it
+            buffer.delete(&mut start, &mut end);
+            if let Edit::Insert(_) = edit {
+                buffer.insert(&mut start, &texts[position]);
+            }
+        } else {
+            match edit {
+                Edit::Insert(_) => buffer.insert(&mut start, &texts[position]),
+                Edit::Backspace => {
+                    let mut previous = start.clone();
+                    if previous.backward_cursor_position() {
+                        buffer.delete(&mut previous, &mut start);
+                    }
+                }
+                Edit::Delete => {
+                    let mut next = start.clone();
+                    if next.forward_cursor_position() {
+                        buffer.delete(&mut start, &mut next);
+                    }
+                }
+            }
+        }
+
+        // Collapse the caret's selection onto the cursor
+        let cursor = buffer.iter_at_mark(&caret.cursor);
+        buffer.move_mark(&caret.anchor, &cursor);
+    }
+    drop(extra_carets);
+    buffer.end_user_action();
+    APPLYING_EDITS.with(|flag| flag.set(false));
+
+    remove_duplicate_carets(&buffer, carets);
+    refresh_caret_tags(view, carets);
+    view.scroll_mark_onscreen(&buffer.get_insert());
+}
+
+/// Types text the view is inserting at the main cursor at every extra caret, replacing their selections
+///
+/// Runs inside the view's user action, which makes the main cursor's insert and
+/// these one undo step.
+fn type_at_extra_carets(view: &View, carets: &Carets, text: &str) {
+    let buffer = view.buffer();
+    APPLYING_EDITS.with(|flag| flag.set(true));
+    for caret in carets.borrow().iter() {
+        let (mut start, mut end) = caret.bounds(&buffer);
+        if start.offset() != end.offset() {
+            buffer.delete(&mut start, &mut end);
+        }
+        buffer.insert(&mut start, text);
+        let cursor = buffer.iter_at_mark(&caret.cursor);
+        buffer.move_mark(&caret.anchor, &cursor);
+    }
+    APPLYING_EDITS.with(|flag| flag.set(false));
+
+    remove_duplicate_carets(&buffer, carets);
+    refresh_caret_tags(view, carets);
+}
+
+/// Pastes the clipboard at every caret
+///
+/// When the clipboard has as many lines as there are carets, each caret gets one
+/// line (in document order); otherwise the whole text is pasted at every caret.
+fn paste_at_all_carets(view: &View, carets: &Carets) {
+    let clipboard = view.clipboard();
+    let view = view.clone();
+    let carets = carets.clone();
+    clipboard.read_text_async(None::<&gtk4::gio::Cancellable>, move |result| {
+        let Ok(Some(text)) = result else {
+            return;
+        };
+        let caret_count = carets.borrow().len() + 1;
+        let lines: Vec<&str> = text.trim_end_matches('\n').split('\n').collect();
+        let texts: Vec<String> = if caret_count > 1 && lines.len() == caret_count {
+            lines.iter().map(|line| line.to_string()).collect()
+        } else {
+            vec![text.to_string(); caret_count]
+        };
+        apply_edits(&view, &carets, &Edit::Insert(String::new()), &texts);
+    });
+}
+
+/// Copies the selections of every caret to the clipboard, one per line
+///
+/// With `cut`, the selections are then deleted as one undoable action.
+fn copy_from_all_carets(view: &View, carets: &Carets, cut: bool) {
+    let buffer = view.buffer();
+    let mut selections: Vec<(i32, String)> = carets
+        .borrow()
+        .iter()
+        .chain(std::iter::once(&Caret::primary(&buffer)))
+        .map(|caret| {
+            let (start, end) = caret.bounds(&buffer);
+            (start.offset(), buffer.text(&start, &end, true).to_string())
+        })
+        .collect();
+    selections.sort_by_key(|(offset, _)| *offset);
+
+    let text = selections.into_iter().map(|(_, text)| text).collect::<Vec<_>>().join("\n");
+    view.clipboard().set_text(&text);
+
+    if cut {
+        // Inserting nothing replaces each selection and leaves empty carets alone
+        let texts = vec![String::new(); carets.borrow().len() + 1];
+        apply_edits(view, carets, &Edit::Insert(String::new()), &texts);
+    }
+}
+
+/// Moves the cursor corner of the rectangular selection with an arrow key
+///
+/// Without a rectangle yet, it starts from the main cursor's selection.
+fn extend_block(view: &View, carets: &Carets, block: &Block, keyval: gdk::Key) {
+    let buffer = view.buffer();
+    let mut selection = block.get().unwrap_or_else(|| {
+        let anchor = buffer.iter_at_mark(&buffer.selection_bound());
+        let cursor = buffer.iter_at_mark(&buffer.get_insert());
+        BlockSelection {
+            anchor_line: anchor.line(),
+            anchor_column: visual_column(view, &anchor),
+            cursor_line: cursor.line(),
+            cursor_column: visual_column(view, &cursor),
+        }
+    });
+
+    match keyval {
+        gdk::Key::Up if selection.cursor_line > 0 => selection.cursor_line -= 1,
+        gdk::Key::Down if selection.cursor_line < buffer.line_count() - 1 => selection.cursor_line += 1,
+        gdk::Key::Left => selection.cursor_column = selection.cursor_column.saturating_sub(1),
+        gdk::Key::Right => selection.cursor_column += 1,
+        _ => {}
+    }
+
+    block.set(Some(selection));
+    select_block(view, carets, selection);
+}
+
+/// Replaces all carets with one caret per row of a rectangular selection
+///
+/// The row of the cursor corner uses the main cursor, so scrolling follows it.
+fn select_block(view: &View, carets: &Carets, selection: BlockSelection) {
+    let buffer = view.buffer();
+    for caret in carets.borrow_mut().drain(..) {
+        caret.remove(&buffer);
+    }
+
+    let first_line = selection.anchor_line.min(selection.cursor_line);
+    let last_line = selection.anchor_line.max(selection.cursor_line);
+    for line in first_line..=last_line {
+        let anchor = iter_at_visual_column(view, line, selection.anchor_column);
+        let cursor = iter_at_visual_column(view, line, selection.cursor_column);
+        if line == selection.cursor_line {
+            buffer.select_range(&cursor, &anchor);
+        } else {
+            carets.borrow_mut().push(Caret::new(&buffer, &anchor, &cursor));
+        }
+    }
+
+    refresh_caret_tags(view, carets);
+    view.scroll_mark_onscreen(&buffer.get_insert());
+}
+
+/// Returns the line and visual column under a point in widget coordinates
+///
+/// Past the end of a line the column keeps growing with x, so rectangles can
+/// extend beyond short lines.
+fn position_at_point(view: &View, x: f64, y: f64) -> (i32, u32) {
+    let (buffer_x, buffer_y) = view.window_to_buffer_coords(TextWindowType::Widget, x as i32, y as i32);
+    let (line_start, _) = view.line_at_y(buffer_y);
+    let line = line_start.line();
+
+    if let Some(iter) = view.iter_at_location(buffer_x, buffer_y).filter(|iter| iter.line() == line) {
+        return (line, visual_column(view, &iter));
+    }
+
+    let char_width = view.create_pango_layout(Some("M")).pixel_size().0.max(1);
+    let column = ((buffer_x - view.left_margin()).max(0) as f64 / char_width as f64).round() as u32;
+    (line, column)
+}
+
+/// Returns the visual column of an iterator, with tabs expanded to the view's tab width
+fn visual_column(view: &View, iter: &TextIter) -> u32 {
+    let tab_width = view.tab_width().max(1);
+    let mut position = iter.clone();
+    position.set_line_offset(0);
+    let mut column = 0;
+    while position.offset() < iter.offset() {
+        column += if position.char() == '\t' { tab_width - column % tab_width } else { 1 };
+        position.forward_char();
+    }
+    column
+}
+
+/// Returns the iterator at a visual column of a line, clamped to the end of the line
+///
+/// A column in the middle of a tab resolves to the position before the tab.
+fn iter_at_visual_column(view: &View, line: i32, column: u32) -> TextIter {
+    let buffer = view.buffer();
+    let tab_width = view.tab_width().max(1);
+    let mut iter = buffer.iter_at_line(line).unwrap_or_else(|| buffer.end_iter());
+    let mut current = 0;
+    while current < column && !iter.ends_line() {
+        let width = if iter.char() == '\t' { tab_width - current % tab_width } else { 1 };
+        if current + width > column {
+            break;
+        }
+        current += width;
+        iter.forward_char();
+    }
+    iter
+}
+
+/// Checks whether a key is a modifier on its own (pressed while building a shortcut)
+pub fn is_modifier_key(keyval: gdk::Key) -> bool {
+    matches!(
+        keyval,
+        gdk::Key::Shift_L
+            | gdk::Key::Shift_R
+            | gdk::Key::Control_L
+            | gdk::Key::Control_R
+            | gdk::Key::Alt_L
+            | gdk::Key::Alt_R
+            | gdk::Key::Meta_L
+            | gdk::Key::Meta_R
+            | gdk::Key::Super_L
+            | gdk::Key::Super_R
+            | gdk::Key::ISO_Level3_Shift
+    )
+}
+
+/// Selects the word at the cursor, or adds the next occurrence of the selection as a new caret
+fn add_next_occurrence(view: &View, carets: &Carets) {
+    let buffer = view.buffer();
+
+    // Without a selection, Ctrl+D first selects the word under the cursor
+    let Some((selection_start, selection_end)) = buffer.selection_bounds() else {
+        let cursor = buffer.iter_at_mark(&buffer.get_insert());
+        if !cursor.inside_word() && !cursor.ends_word() {
+            return;
+        }
+        let mut start = cursor.clone();
+        if !start.starts_word() {
+            start.backward_word_start();
+        }
+        let mut end = start.clone();
+        end.forward_word_end();
+        buffer.select_range(&start, &end);
+        return;
+    };
+
+    let needle = buffer.text(&selection_start, &selection_end, true).to_string();
+    if needle.is_empty() {
+        return;
+    }
+
+    // Start searching after the most recently added occurrence
+    let search_from = carets
+        .borrow()
+        .last()
+        .map(|caret| caret.bounds(&buffer).1)
+        .unwrap_or(selection_end);
+
+    let is_taken = |start: &TextIter| {
+        start.offset() == selection_start.offset()
+            || carets.borrow().iter().any(|caret| caret.bounds(&buffer).0.offset() == start.offset())
+    };
+
+    // Search forward, wrapping around once at the end of the buffer
+    let mut from = search_from.clone();
+    let mut wrapped = false;
+    let found = loop {
+        match from.forward_search(&needle, TextSearchFlags::TEXT_ONLY, None) {
+            Some((start, end)) if !is_taken(&start) => break Some((start, end)),
+            Some((_, end)) => from = end,
+            None if !wrapped => {
+                wrapped = true;
+                from = buffer.start_iter();
+            }
+            None => break None,
+        }
+        if wrapped && from.offset() >= search_from.offset() {
+            break None; // Every occurrence already has a caret
+        }
+    };
+
+    if let Some((start, end)) = found {
+        let caret = Caret::new(&buffer, &start, &end);
+        view.scroll_mark_onscreen(&caret.cursor);
+        carets.borrow_mut().push(caret);
+        refresh_caret_tags(view, carets);
+    }
+}
+
+/// Adds a caret on the line above the topmost caret or below the bottommost one
+fn add_caret_on_adjacent_line(view: &View, carets: &Carets, above: bool) {
+    let buffer = view.buffer();
+
+    // Find the outermost caret in the requested direction
+    let mut outermost = buffer.iter_at_mark(&buffer.get_insert());
+    for caret in carets.borrow().iter() {
+        let cursor = buffer.iter_at_mark(&caret.cursor);
+        if (above && cursor.line() < outermost.line()) || (!above && cursor.line() > outermost.line()) {
+            outermost = cursor;
+        }
+    }
+
+    let target_line = if above { outermost.line() - 1 } else { outermost.line() + 1 };
+    if target_line < 0 || target_line >= buffer.line_count() {
+        return;
+    }
+
+    let target = iter_at_line_clamped(&buffer, target_line, outermost.line_offset());
+    let caret = Caret::new(&buffer, &target, &target);
+    view.scroll_mark_onscreen(&caret.cursor);
+    carets.borrow_mut().push(caret);
+    refresh_caret_tags(view, carets);
+}
+
+/// Adds a caret at a position, or removes the extra caret already there
+fn toggle_caret_at(view: &View, carets: &Carets, iter: &TextIter) {
+    let buffer = view.buffer();
+
+    let existing = carets
+        .borrow()
+        .iter()
+        .position(|caret| buffer.iter_at_mark(&caret.cursor).offset() == iter.offset());
+    match existing {
+        Some(index) => {
+            let caret = carets.borrow_mut().remove(index);
+            caret.remove(&buffer);
+        }
+        None => {
+            // Clicking the main cursor itself does not add a caret
+            if buffer.iter_at_mark(&buffer.get_insert()).offset() != iter.offset() {
+                carets.borrow_mut().push(Caret::new(&buffer, iter, iter));
+            }
+        }
+    }
+    refresh_caret_tags(view, carets);
+}
+
+/// Moves the extra carets with the arrow, Home and End keys
+fn move_extra_carets(view: &View, carets: &Carets, keyval: gdk::Key) {
+    let buffer = view.buffer();
+    for caret in carets.borrow().iter() {
+        let (start, end) = caret.bounds(&buffer);
+        let mut iter = buffer.iter_at_mark(&caret.cursor);
+        let has_selection = start.offset() != end.offset();
+
+        match keyval {
+            // Left/Right on a selection collapse it to the matching side
+            gdk::Key::Left if has_selection => iter = start,
+            gdk::Key::Right if has_selection => iter = end,
+            gdk::Key::Left => {
+                iter.backward_cursor_position();
+            }
+            gdk::Key::Right => {
+                iter.forward_cursor_position();
+            }
+            gdk::Key::Up | gdk::Key::Down => {
+                let line = if keyval == gdk::Key::Up { iter.line() - 1 } else { iter.line() + 1 };
+                if line >= 0 && line < buffer.line_count() {
+                    iter = iter_at_line_clamped(&buffer, line, iter.line_offset());
+                }
+            }
+            gdk::Key::Home => iter.set_line_offset(0),
+            gdk::Key::End => {
+                if !iter.ends_line() {
+                    iter.forward_to_line_end();
+                }
+            }
+            _ => {}
+        }
+        caret.move_to(&buffer, &iter);
+    }
+
+    remove_duplicate_carets(&buffer, carets);
+    refresh_caret_tags(view, carets);
+}
+
+/// Returns the iterator at a line and character offset, clamped to the end of the line
+fn iter_at_line_clamped(buffer: &TextBuffer, line: i32, offset: i32) -> TextIter {
+    let mut iter = buffer.iter_at_line(line).unwrap_or_else(|| buffer.end_iter());
+    let mut line_end = iter.clone();
+    if !line_end.ends_line() {
+        line_end.forward_to_line_end();
+    }
+    iter.set_line_offset(offset.min(line_end.line_offset()));
+    iter
+}
+
+/// Drops extra carets that ended up on the main cursor or on another caret
+fn remove_duplicate_carets(buffer: &TextBuffer, carets: &Carets) {
+    let mut seen = vec![buffer.iter_at_mark(&buffer.get_insert()).offset()];
+    carets.borrow_mut().retain(|caret| {
+        let offset = buffer.iter_at_mark(&caret.cursor).offset();
+        if seen.contains(&offset) {
+            caret.remove(buffer);
+            false
+        } else {
+            seen.push(offset);
+            true
+        }
+    });
+}
+
+/// Removes every extra caret, keeping only the main cursor
+fn clear_carets(view: &View, carets: &Carets) {
+    let buffer = view.buffer();
+    for caret in carets.borrow_mut().drain(..) {
+        caret.remove(&buffer);
+    }
+    refresh_caret_tags(view, carets);
+}
+
+/// Checks whether a view currently has extra carets
+///
+/// Other key handlers use this to leave typing to the multi-cursor code.
+pub fn has_extra_carets(view: &View) -> bool {
+    VIEW_CARETS.get(view).is_some_and(|carets| !carets.borrow().is_empty())
+}
+
+/// Redraws the caret and selection highlights of the extra carets
+fn refresh_caret_tags(view: &View, carets: &Carets) {
+    let buffer = &view.buffer();
+    let tag_table = buffer.tag_table();
+    let (Some(selection_tag), Some(caret_tag)) = (tag_table.lookup(SELECTION_TAG_NAME), tag_table.lookup(CARET_TAG_NAME)) else {
+        return;
+    };
+
+    let (buffer_start, buffer_end) = buffer.bounds();
+    buffer.remove_tag(&selection_tag, &buffer_start, &buffer_end);
+    buffer.remove_tag(&caret_tag, &buffer_start, &buffer_end);
+
+    for caret in carets.borrow().iter() {
+        let (start, end) = caret.bounds(buffer);
+        if start.offset() != end.offset() {
+            buffer.apply_tag(&selection_tag, &start, &end);
+        }
+
+        // Highlight the character right after the caret
+        let cursor = buffer.iter_at_mark(&caret.cursor);
+        let mut next = cursor.clone();
+        if next.forward_char() {
+            buffer.apply_tag(&caret_tag, &cursor, &next);
+        }
+    }
+}
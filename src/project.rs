@@ -0,0 +1,204 @@
+// Project tree walking for the text editor
+// Shared by the project-wide features that need to visit every file under a directory
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Directory names that are never worth descending into
+const DEFAULT_IGNORED_DIRS: &[&str] = &[
+    "target", "node_modules", "build", "dist", "__pycache__", "venv",
+];
+
+/// Files larger than this are skipped by content scans
+pub const MAX_SCANNED_FILE_SIZE: u64 = 4 * 1024 * 1024;
+
+/// A single ignore pattern from a .gitignore file
+#[derive(Clone, Debug)]
+struct IgnorePattern {
+    /// Glob pattern (supports * and ?)
+    glob: String,
+    /// Pattern only applies to directories (it ended with '/')
+    dir_only: bool,
+    /// Pattern contains a '/' and must match the path relative to the root
+    anchored: bool,
+}
+
+/// Ignore rules for a project tree
+///
+/// Combines a built-in list of build/dependency directories with the simple
+/// patterns from the root `.gitignore`. Negated patterns (`!pattern`) are not supported.
+#[derive(Clone, Debug)]
+pub struct IgnoreRules {
+    patterns: Vec<IgnorePattern>,
+}
+
+impl IgnoreRules {
+    /// Loads the ignore rules for the given project root
+    pub fn load(root: &Path) -> Self {
+        let mut patterns: Vec<IgnorePattern> = DEFAULT_IGNORED_DIRS
+            .iter()
+            .map(|name| IgnorePattern { glob: name.to_string(), dir_only: true, anchored: false })
+            .collect();
+
+        if let Ok(contents) = fs::read_to_string(root.join(".gitignore")) {
+            for line in contents.lines() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') || line.starts_with('!') {
+                    continue; // Skip comments, blank lines and unsupported negations
+                }
+
+                let dir_only = line.ends_with('/');
+                let trimmed = line.trim_end_matches('/');
+                let anchored = trimmed.contains('/');
+                let glob = trimmed.trim_start_matches('/').to_string();
+                if !glob.is_empty() {
+                    patterns.push(IgnorePattern { glob, dir_only, anchored });
+                }
+            }
+        }
+
+        Self { patterns }
+    }
+
+    /// Checks whether an entry should be skipped
+    ///
+    /// `relative_path` is the entry's path relative to the project root.
+    pub fn is_ignored(&self, relative_path: &Path, is_dir: bool) -> bool {
+        let name = relative_path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        // Hidden files and directories are always skipped
+        if name.starts_with('.') {
+            return true;
+        }
+
+        let relative_str = relative_path.to_string_lossy().replace('\\', "/");
+        self.patterns.iter().any(|pattern| {
+            if pattern.dir_only && !is_dir {
+                return false;
+            }
+            if pattern.anchored {
+                glob_match(&pattern.glob, &relative_str)
+            } else {
+                glob_match(&pattern.glob, &name)
+            }
+        })
+    }
+}
+
+/// Matches text against a glob pattern supporting `*` and `?`
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<usize> = None;
+    let mut star_text = 0;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some(p);
+            star_text = t;
+            p += 1;
+        } else if let Some(star_pos) = star {
+            // Let the last star absorb one more character and retry
+            p = star_pos + 1;
+            star_text += 1;
+            t = star_text;
+        } else {
+            return false;
+        }
+    }
+
+    while p < pattern.len() && pattern[p] == '*' {
+        p += 1;
+    }
+    p == pattern.len()
+}
+
+/// Walks every non-ignored file under `root`
+///
+/// Directories are visited in alphabetical order. Symlinked directories are not
+/// followed to avoid cycles. The walk stops early when `cancelled` is set or when
+/// `visit` returns false.
+pub fn walk_files(root: &Path, cancelled: &AtomicBool, visit: &mut dyn FnMut(&Path) -> bool) {
+    let rules = IgnoreRules::load(root);
+    walk_tree(root, root, &rules, cancelled, &mut |path, is_dir| is_dir || visit(path));
+}
+
+/// Walks every non-ignored directory and file under `start`, a directory inside `root`
+///
+/// Ignore rules apply to paths relative to `root`, so a subdirectory can be walked
+/// with the rules of its project. `visit` gets each entry and whether it is a
+/// directory; directories are reported before their contents.
+pub fn walk_tree(
+    root: &Path,
+    start: &Path,
+    rules: &IgnoreRules,
+    cancelled: &AtomicBool,
+    visit: &mut dyn FnMut(&Path, bool) -> bool,
+) {
+    let mut pending_dirs = vec![start.to_path_buf()];
+
+    while let Some(dir) = pending_dirs.pop() {
+        if cancelled.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let mut entries: Vec<(PathBuf, fs::FileType)> = match fs::read_dir(&dir) {
+            Ok(read_dir) => read_dir
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| entry.file_type().ok().map(|ft| (entry.path(), ft)))
+                .collect(),
+            Err(_) => continue, // Unreadable directory, skip it
+        };
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut subdirs = Vec::new();
+        for (path, file_type) in entries {
+            let relative = path.strip_prefix(root).unwrap_or(&path);
+            if file_type.is_dir() {
+                if !rules.is_ignored(relative, true) {
+                    if !visit(&path, true) {
+                        return;
+                    }
+                    subdirs.push(path);
+                }
+            } else if file_type.is_file() || (file_type.is_symlink() && path.is_file()) {
+                if rules.is_ignored(relative, false) {
+                    continue;
+                }
+                if cancelled.load(Ordering::Relaxed) || !visit(&path, false) {
+                    return;
+                }
+            }
+        }
+
+        // Push in reverse so the alphabetically first directory is walked next
+        pending_dirs.extend(subdirs.into_iter().rev());
+    }
+}
+
+/// Reads a file as UTF-8 text for scanning, skipping large and binary files
+pub fn read_text_file(path: &Path) -> Option<String> {
+    let metadata = fs::metadata(path).ok()?;
+    if metadata.len() > MAX_SCANNED_FILE_SIZE {
+        return None;
+    }
+
+    let bytes = fs::read(path).ok()?;
+
+    // A NUL byte near the start is a reliable sign of binary content
+    let probe_len = bytes.len().min(8192);
+    if bytes[..probe_len].contains(&0) {
+        return None;
+    }
+
+    String::from_utf8(bytes).ok()
+}
@@ -0,0 +1,469 @@
+// Quick open for the text editor
+// Ctrl+P finds any file of the project by fuzzy search over a background index kept up to date by directory monitors
+
+use gtk4::prelude::*;
+use gtk4::{gio, glib, pango, ApplicationWindow, Box as GtkBox, Label, ListBoxRow, Orientation};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::{Rc, Weak};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+use crate::command_palette::{create_popup, fuzzy_score};
+use crate::project::{self, IgnoreRules};
+use crate::utils::ObjectState;
+
+/// Stop indexing after this many files to bound memory and search time
+const MAX_INDEXED_FILES: usize = 200_000;
+
+/// Directories watched for changes; deeper trees are still indexed but not watched past this
+const MAX_MONITORED_DIRS: usize = 4_000;
+
+/// Number of files sent from the indexing thread at a time
+const BATCH_SIZE: usize = 500;
+
+/// Number of rows shown for a query
+const MAX_RESULTS: usize = 50;
+
+/// Number of files opened through quick open remembered between sessions
+const MAX_RECENT_FILES: usize = 50;
+
+/// File shown by each row
+const ROW_PATH: ObjectState<PathBuf> = ObjectState::new("quick-open-path");
+
+/// Messages streamed from an indexing thread to the UI
+enum IndexMessage {
+    /// A directory to watch for changes
+    Directory(PathBuf),
+    /// Files found since the last batch
+    Files(Vec<PathBuf>),
+    /// The scan ended
+    Finished,
+}
+
+/// Files of a project tree, filled by background scans and updated by directory monitors
+struct FileIndex {
+    root: PathBuf,
+    rules: IgnoreRules,
+    files: RefCell<BTreeSet<PathBuf>>,
+    /// Counts the changes to `files`, so results of an earlier query can be reused
+    revision: Cell<usize>,
+    monitors: RefCell<HashMap<PathBuf, gio::FileMonitor>>,
+    /// Scans still running; the index is complete when this is zero
+    scans_running: Cell<usize>,
+    /// Stops the scans of an index that was replaced
+    cancelled: Arc<AtomicBool>,
+}
+
+thread_local! {
+    // Index of the current project, replaced when the file list moves to another project
+    static INDEX: RefCell<Option<Rc<FileIndex>>> = RefCell::new(None);
+}
+
+/// Returns the project root of a directory: the closest ancestor holding a `.git`, or the directory itself
+pub fn project_root(dir: &Path) -> PathBuf {
+    dir.ancestors()
+        .find(|ancestor| ancestor.join(".git").exists())
+        .unwrap_or(dir)
+        .to_path_buf()
+}
+
+/// Starts indexing the project containing `dir`, unless it is already indexed
+pub fn start_indexing(dir: &Path) {
+    let root = project_root(dir);
+    let current = INDEX.with(|index| index.borrow().clone());
+    if let Some(current) = current {
+        if current.root == root {
+            return;
+        }
+        // Drop the old project's scans and monitors
+        current.cancelled.store(true, Ordering::Relaxed);
+        for monitor in current.monitors.borrow().values() {
+            monitor.cancel();
+        }
+    }
+
+    println!("Indexing files under {}", root.display());
+    let index = Rc::new(FileIndex {
+        rules: IgnoreRules::load(&root),
+        root: root.clone(),
+        files: RefCell::new(BTreeSet::new()),
+        revision: Cell::new(0),
+        monitors: RefCell::new(HashMap::new()),
+        scans_running: Cell::new(0),
+        cancelled: Arc::new(AtomicBool::new(false)),
+    });
+    INDEX.with(|current| *current.borrow_mut() = Some(index.clone()));
+
+    watch_directory(&index, &root);
+    scan_directory(&index, root);
+}
+
+/// Checks whether an index is still the current one
+fn is_current(index: &Rc<FileIndex>) -> bool {
+    INDEX.with(|current| current.borrow().as_ref().is_some_and(|current| Rc::ptr_eq(current, index)))
+}
+
+/// Indexes a directory of the project in a background thread
+fn scan_directory(index: &Rc<FileIndex>, dir: PathBuf) {
+    index.scans_running.set(index.scans_running.get() + 1);
+
+    let (sender, receiver) = mpsc::channel();
+    let root = index.root.clone();
+    let rules = index.rules.clone();
+    let cancelled = index.cancelled.clone();
+    std::thread::spawn(move || {
+        let mut batch = Vec::new();
+        let mut file_count = 0;
+        project::walk_tree(&root, &dir, &rules, &cancelled, &mut |path, is_dir| {
+            if is_dir {
+                return sender.send(IndexMessage::Directory(path.to_path_buf())).is_ok();
+            }
+            batch.push(path.to_path_buf());
+            file_count += 1;
+            if batch.len() >= BATCH_SIZE && sender.send(IndexMessage::Files(std::mem::take(&mut batch))).is_err() {
+                return false; // The UI stopped listening
+            }
+            file_count < MAX_INDEXED_FILES
+        });
+        let _ = sender.send(IndexMessage::Files(batch));
+        let _ = sender.send(IndexMessage::Finished);
+    });
+
+    // Merge streamed results into the index on the main loop
+    let index = index.clone();
+    glib::timeout_add_local(Duration::from_millis(100), move || {
+        if !is_current(&index) {
+            return glib::ControlFlow::Break; // Another project replaced this one
+        }
+
+        loop {
+            match receiver.try_recv() {
+                Ok(IndexMessage::Directory(dir)) => watch_directory(&index, &dir),
+                Ok(IndexMessage::Files(paths)) => {
+                    let mut files = index.files.borrow_mut();
+                    for path in paths {
+                        if files.len() >= MAX_INDEXED_FILES {
+                            break;
+                        }
+                        files.insert(path);
+                    }
+                    index.revision.set(index.revision.get() + 1);
+                }
+                Ok(IndexMessage::Finished) | Err(mpsc::TryRecvError::Disconnected) => {
+                    index.scans_running.set(index.scans_running.get().saturating_sub(1));
+                    if index.scans_running.get() == 0 {
+                        println!("Indexed {} files under {}", index.files.borrow().len(), index.root.display());
+                    }
+                    return glib::ControlFlow::Break;
+                }
+                Err(mpsc::TryRecvError::Empty) => return glib::ControlFlow::Continue,
+            }
+        }
+    });
+}
+
+/// Watches a directory so files created, deleted or moved in it update the index
+fn watch_directory(index: &Rc<FileIndex>, dir: &Path) {
+    let mut monitors = index.monitors.borrow_mut();
+    if monitors.contains_key(dir) || monitors.len() >= MAX_MONITORED_DIRS {
+        return;
+    }
+
+    let monitor = match gio::File::for_path(dir).monitor_directory(gio::FileMonitorFlags::WATCH_MOVES, None::<&gio::Cancellable>) {
+        Ok(monitor) => monitor,
+        Err(e) => {
+            eprintln!("Failed to watch {}: {}", dir.display(), e);
+            return;
+        }
+    };
+
+    let weak_index: Weak<FileIndex> = Rc::downgrade(index);
+    monitor.connect_changed(move |_, file, other_file, event| {
+        let Some(index) = weak_index.upgrade() else {
+            return;
+        };
+        if !is_current(&index) {
+            return;
+        }
+        let (Some(path), other_path) = (file.path(), other_file.and_then(|other| other.path())) else {
+            return;
+        };
+        match event {
+            gio::FileMonitorEvent::Created | gio::FileMonitorEvent::MovedIn => add_path(&index, &path),
+            gio::FileMonitorEvent::Deleted | gio::FileMonitorEvent::MovedOut => remove_path(&index, &path),
+            gio::FileMonitorEvent::Renamed => {
+                remove_path(&index, &path);
+                if let Some(new_path) = other_path {
+                    add_path(&index, &new_path);
+                }
+            }
+            _ => {}
+        }
+    });
+    monitors.insert(dir.to_path_buf(), monitor);
+}
+
+/// Adds a created or moved-in file to the index, scanning it if it is a directory
+fn add_path(index: &Rc<FileIndex>, path: &Path) {
+    let Ok(relative) = path.strip_prefix(&index.root) else {
+        return;
+    };
+    if path.is_dir() {
+        if !index.rules.is_ignored(relative, true) {
+            watch_directory(index, path);
+            scan_directory(index, path.to_path_buf());
+        }
+    } else if path.is_file() && !index.rules.is_ignored(relative, false) {
+        let mut files = index.files.borrow_mut();
+        if files.len() < MAX_INDEXED_FILES {
+            files.insert(path.to_path_buf());
+            index.revision.set(index.revision.get() + 1);
+        }
+    }
+}
+
+/// Removes a deleted or moved-out file, or everything under a directory, from the index
+fn remove_path(index: &FileIndex, path: &Path) {
+    index.files.borrow_mut().retain(|file| !file.starts_with(path));
+    index.revision.set(index.revision.get() + 1);
+    index.monitors.borrow_mut().retain(|dir, monitor| {
+        let keep = !dir.starts_with(path);
+        if !keep {
+            monitor.cancel();
+        }
+        keep
+    });
+}
+
+/// Returns the path of the file remembering files opened through quick open
+fn recent_files_path() -> PathBuf {
+    crate::settings::get_config_dir().join("recent_files")
+}
+
+/// Loads the files recently opened through quick open, most recent first
+fn load_recent_files() -> Vec<PathBuf> {
+    fs::read_to_string(recent_files_path())
+        .map(|contents| contents.lines().filter(|line| !line.trim().is_empty()).map(PathBuf::from).collect())
+        .unwrap_or_default()
+}
+
+/// Moves a file to the front of the recently opened list
+fn remember_file(path: &Path) {
+    let mut recent = load_recent_files();
+    recent.retain(|known| known != path);
+    recent.insert(0, path.to_path_buf());
+    recent.truncate(MAX_RECENT_FILES);
+    let contents: Vec<String> = recent.iter().map(|path| path.to_string_lossy().to_string()).collect();
+    if let Err(e) = fs::write(recent_files_path(), contents.join("\n") + "\n") {
+        eprintln!("Failed to save recent files: {}", e);
+    }
+}
+
+/// Scores how well a query matches a file, or None when it doesn't match
+///
+/// Matches within the file name count double; a query containing '/' is matched
+/// against the path relative to the project root only.
+fn score_file(query: &str, relative: &str) -> Option<i32> {
+    let path_score = fuzzy_score(query, relative);
+    if query.contains('/') {
+        return path_score;
+    }
+    let name = relative.rsplit('/').next().unwrap_or(relative);
+    let name_score = fuzzy_score(query, name).map(|score| score * 2);
+    name_score.max(path_score)
+}
+
+/// Files that matched the last query
+///
+/// A file matching a query also matches every prefix of it, so while the query
+/// grows only these files are scored again instead of the whole index.
+#[derive(Default)]
+struct PreviousMatches {
+    query: String,
+    /// Revision of the index the files were matched in
+    revision: usize,
+    files: Vec<PathBuf>,
+}
+
+/// Orders the indexed files for a query: best matches first, then recently opened and open files
+///
+/// An empty query lists the recently opened files, then the files open in tabs.
+fn rank_files(
+    index: &FileIndex,
+    query: &str,
+    recent: &[PathBuf],
+    open: &[PathBuf],
+    previous: &mut PreviousMatches,
+) -> Vec<PathBuf> {
+    let recency_bonus = |path: &Path| {
+        let recent_bonus = recent
+            .iter()
+            .position(|known| known == path)
+            .map(|rank| (MAX_RECENT_FILES - rank) as i32)
+            .unwrap_or(0);
+        let open_bonus = if open.iter().any(|known| known == path) { 10 } else { 0 };
+        recent_bonus + open_bonus
+    };
+
+    if query.trim().is_empty() {
+        let mut ranked: Vec<PathBuf> = Vec::new();
+        for path in recent.iter().chain(open.iter()) {
+            if path.starts_with(&index.root) && path.is_file() && !ranked.contains(path) {
+                ranked.push(path.clone());
+            }
+        }
+        ranked.truncate(MAX_RESULTS);
+        return ranked;
+    }
+
+    let score = |path: &PathBuf| {
+        let relative = path.strip_prefix(&index.root).unwrap_or(path).to_string_lossy().replace('\\', "/");
+        let score = score_file(query, &relative)?;
+        Some((score * 4 + recency_bonus(path), path.clone()))
+    };
+    let narrowing = previous.revision == index.revision.get()
+        && !previous.query.trim().is_empty()
+        && query.starts_with(previous.query.as_str());
+    let mut ranked: Vec<(i32, PathBuf)> = if narrowing {
+        previous.files.iter().filter_map(score).collect()
+    } else {
+        index.files.borrow().iter().filter_map(score).collect()
+    };
+    ranked.sort_by(|(score_a, a), (score_b, b)| {
+        score_b
+            .cmp(score_a)
+            .then_with(|| a.as_os_str().len().cmp(&b.as_os_str().len()))
+            .then_with(|| a.cmp(b))
+    });
+    let results = ranked.iter().take(MAX_RESULTS).map(|(_, path)| path.clone()).collect();
+
+    *previous = PreviousMatches {
+        query: query.to_string(),
+        revision: index.revision.get(),
+        files: ranked.into_iter().map(|(_, path)| path).collect(),
+    };
+    results
+}
+
+/// Creates the row showing one file: its name, then its folder relative to the project root
+fn create_file_row(index: &FileIndex, path: &Path) -> ListBoxRow {
+    let row_box = GtkBox::new(Orientation::Horizontal, 8);
+    row_box.set_margin_top(4);
+    row_box.set_margin_bottom(4);
+    row_box.set_margin_start(8);
+    row_box.set_margin_end(8);
+
+    let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    let name_label = Label::new(Some(&name));
+    name_label.set_halign(gtk4::Align::Start);
+    row_box.append(&name_label);
+
+    let folder = path
+        .parent()
+        .and_then(|parent| parent.strip_prefix(&index.root).ok())
+        .map(|parent| parent.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let folder_label = Label::new(Some(&folder));
+    folder_label.set_halign(gtk4::Align::Start);
+    folder_label.set_hexpand(true);
+    folder_label.set_ellipsize(pango::EllipsizeMode::Start);
+    folder_label.add_css_class("dim-label");
+    row_box.append(&folder_label);
+
+    let row = ListBoxRow::new();
+    row.set_child(Some(&row_box));
+    ROW_PATH.set(&row, path.to_path_buf());
+    row
+}
+
+/// Shows the quick open popup over the main window
+///
+/// Results refresh while the index is still being built, so files show up as soon
+/// as the background scan reaches them.
+pub fn show_quick_open(window: &ApplicationWindow) {
+    let Some(dir) = crate::commands::current_dir() else {
+        return;
+    };
+    start_indexing(&dir);
+    let Some(index) = INDEX.with(|index| index.borrow().clone()) else {
+        return;
+    };
+
+    let open_file = |row: &ListBoxRow| {
+        let Some(path) = ROW_PATH.get(row) else {
+            return;
+        };
+        remember_file(&path);
+        crate::commands::open_path(&path);
+    };
+    let (popup, container, search_entry, list) = create_popup(window, "Go to file", open_file);
+    popup.add_css_class("quick-open");
+
+    let status_label = Label::new(None);
+    status_label.set_halign(gtk4::Align::Start);
+    status_label.add_css_class("caption");
+    status_label.add_css_class("dim-label");
+    container.append(&status_label);
+
+    let recent = load_recent_files();
+    let open = crate::commands::open_file_paths();
+
+    // Fills the list for the current query and selects the best match
+    let fill_list = {
+        let index = index.clone();
+        let previous = RefCell::new(PreviousMatches::default());
+        Rc::new(move |query: &str| {
+            while let Some(child) = list.first_child() {
+                list.remove(&child);
+            }
+            for path in rank_files(&index, query, &recent, &open, &mut previous.borrow_mut()) {
+                list.append(&create_file_row(&index, &path));
+            }
+            if let Some(first) = list.row_at_index(0) {
+                list.select_row(Some(&first));
+            }
+        })
+    };
+    fill_list("");
+    let fill_list_for_search = fill_list.clone();
+    search_entry.connect_search_changed(move |entry| fill_list_for_search(&entry.text()));
+
+    // Show the indexing progress, and pick up files indexed since the last refresh
+    let update_status = {
+        let index = index.clone();
+        let status_label = status_label.clone();
+        move || {
+            let count = index.files.borrow().len();
+            let text = if index.scans_running.get() > 0 {
+                format!("Indexing {}… {} files", index.root.display(), count)
+            } else {
+                format!("{} files in {}", count, index.root.display())
+            };
+            status_label.set_text(&text);
+            count
+        }
+    };
+    let last_count = Cell::new(update_status());
+    let popup_weak = popup.downgrade();
+    let search_entry_weak = search_entry.downgrade();
+    glib::timeout_add_local(Duration::from_millis(300), move || {
+        let (Some(popup), Some(search_entry)) = (popup_weak.upgrade(), search_entry_weak.upgrade()) else {
+            return glib::ControlFlow::Break;
+        };
+        if !popup.is_visible() {
+            return glib::ControlFlow::Break;
+        }
+        let count = update_status();
+        if count != last_count.replace(count) && !search_entry.text().is_empty() {
+            fill_list(&search_entry.text());
+        }
+        glib::ControlFlow::Continue
+    });
+
+    popup.present();
+    search_entry.grab_focus();
+}
@@ -0,0 +1,490 @@
+// In-buffer find and replace functionality for the text editor
+// This module builds the find/replace bar shown above each source view
+
+use gtk4::prelude::*;
+use gtk4::{gio, glib, Box as GtkBox, Button, Entry, EventControllerKey, Label, Notebook, Orientation, Revealer, ToggleButton};
+use sourceview5::{prelude::*, Buffer, SearchContext, SearchSettings, View};
+use std::cell::Cell;
+
+/// Widget name used to locate the find bar inside an editor page
+const FIND_BAR_NAME: &str = "find-bar";
+
+// Search settings are shared by every tab so the query and options survive tab switches
+thread_local! {
+    static SEARCH_SETTINGS: SearchSettings = {
+        let settings = SearchSettings::new();
+        settings.set_wrap_around(true);
+        settings
+    };
+    // Whether the find bar (and its replace row) was open when the user last used it
+    static BAR_VISIBLE: Cell<bool> = Cell::new(false);
+    static REPLACE_VISIBLE: Cell<bool> = Cell::new(false);
+}
+
+/// Returns the search settings shared by all editor tabs
+pub fn shared_search_settings() -> SearchSettings {
+    SEARCH_SETTINGS.with(|settings| settings.clone())
+}
+
+/// Creates the find/replace bar for a source view
+///
+/// The bar lives in a Revealer so it can slide in above the view. It searches the view's
+/// buffer with a SearchContext bound to the shared search settings, and exposes its
+/// commands as a "search" action group (search.next, search.previous, search.replace,
+/// search.replace-all, search.close) so keyboard shortcuts can drive it.
+pub fn create_search_bar(source_view: &View) -> Revealer {
+    let buffer = source_view.buffer().downcast::<Buffer>().expect("Source views always use a sourceview5 Buffer");
+    let settings = shared_search_settings();
+    let context = SearchContext::new(&buffer, Some(&settings));
+    context.set_highlight(false);
+
+    let revealer = Revealer::new();
+    revealer.set_widget_name(FIND_BAR_NAME);
+    revealer.set_transition_type(gtk4::RevealerTransitionType::SlideDown);
+    revealer.set_reveal_child(false);
+
+    let bar = GtkBox::new(Orientation::Vertical, 4);
+    bar.add_css_class("find-bar");
+
+    // First row: search entry, options, match counter and navigation
+    let find_row = GtkBox::new(Orientation::Horizontal, 4);
+
+    let search_entry = Entry::new();
+    search_entry.set_placeholder_text(Some("Find"));
+    search_entry.set_hexpand(true);
+    search_entry.set_text(&settings.search_text().map(|s| s.to_string()).unwrap_or_default());
+
+    let regex_toggle = create_option_toggle(".*", "Use regular expressions");
+    let case_toggle = create_option_toggle("Aa", "Match case");
+    let word_toggle = create_option_toggle("W", "Match whole words only");
+
+    // Keep the option toggles in sync with the shared settings (and thus with every other tab)
+    settings.bind_property("regex-enabled", &regex_toggle, "active").bidirectional().sync_create().build();
+    settings.bind_property("case-sensitive", &case_toggle, "active").bidirectional().sync_create().build();
+    settings.bind_property("at-word-boundaries", &word_toggle, "active").bidirectional().sync_create().build();
+
+    let options_box = GtkBox::new(Orientation::Horizontal, 0);
+    options_box.add_css_class("linked");
+    options_box.append(&regex_toggle);
+    options_box.append(&case_toggle);
+    options_box.append(&word_toggle);
+
+    let match_label = Label::new(None);
+    match_label.set_width_chars(12);
+    match_label.add_css_class("dim-label");
+
+    let previous_button = Button::from_icon_name("go-up-symbolic");
+    previous_button.set_tooltip_text(Some("Previous match (Shift+F3)"));
+    previous_button.set_action_name(Some("search.previous"));
+    let next_button = Button::from_icon_name("go-down-symbolic");
+    next_button.set_tooltip_text(Some("Next match (F3)"));
+    next_button.set_action_name(Some("search.next"));
+
+    let replace_toggle = ToggleButton::new();
+    replace_toggle.set_icon_name("edit-find-replace-symbolic");
+    replace_toggle.set_tooltip_text(Some("Toggle replace (Ctrl+H)"));
+
+    let close_button = Button::from_icon_name("window-close-symbolic");
+    close_button.add_css_class("flat");
+    close_button.set_tooltip_text(Some("Close (Esc)"));
+    close_button.set_action_name(Some("search.close"));
+
+    find_row.append(&search_entry);
+    find_row.append(&options_box);
+    find_row.append(&match_label);
+    find_row.append(&previous_button);
+    find_row.append(&next_button);
+    find_row.append(&replace_toggle);
+    find_row.append(&close_button);
+
+    // Second row: replacement entry and replace buttons, only shown in replace mode
+    let replace_row = GtkBox::new(Orientation::Horizontal, 4);
+    let replace_entry = Entry::new();
+    replace_entry.set_placeholder_text(Some("Replace"));
+    replace_entry.set_hexpand(true);
+    let replace_button = Button::with_label("Replace");
+    replace_button.set_action_name(Some("search.replace"));
+    let replace_all_button = Button::with_label("Replace All");
+    replace_all_button.set_action_name(Some("search.replace-all"));
+    replace_row.append(&replace_entry);
+    replace_row.append(&replace_button);
+    replace_row.append(&replace_all_button);
+    replace_row.set_visible(false);
+
+    replace_toggle.bind_property("active", &replace_row, "visible").sync_create().build();
+
+    bar.append(&find_row);
+    bar.append(&replace_row);
+    revealer.set_child(Some(&bar));
+
+    // Push the query into the shared settings as the user types
+    let settings_for_entry = settings.clone();
+    search_entry.connect_changed(move |entry| {
+        let text = entry.text();
+        let current = settings_for_entry.search_text().map(|s| s.to_string()).unwrap_or_default();
+        if current != text.as_str() {
+            settings_for_entry.set_search_text(if text.is_empty() { None } else { Some(text.as_str()) });
+        }
+    });
+
+    // Reflect query changes made from other tabs in this bar's entry
+    // A weak reference keeps the shared settings from holding closed tabs alive
+    let search_entry_weak = search_entry.downgrade();
+    settings.connect_notify_local(Some("search-text"), move |settings, _| {
+        if let Some(entry) = search_entry_weak.upgrade() {
+            let text = settings.search_text().map(|s| s.to_string()).unwrap_or_default();
+            if entry.text().as_str() != text {
+                entry.set_text(&text);
+            }
+        }
+    });
+
+    // Keep the match counter up to date as the search runs and as the cursor moves
+    let update_counter = {
+        let context = context.clone();
+        let buffer = buffer.clone();
+        let match_label = match_label.clone();
+        let search_entry = search_entry.clone();
+        move || update_match_label(&context, &buffer, &match_label, &search_entry)
+    };
+    let update_counter_for_count = update_counter.clone();
+    context.connect_notify_local(Some("occurrences-count"), move |_, _| update_counter_for_count());
+    let update_counter_for_regex = update_counter.clone();
+    context.connect_notify_local(Some("regex-error"), move |_, _| update_counter_for_regex());
+    let update_counter_for_marks = update_counter.clone();
+    buffer.connect_mark_set(move |buffer, _, mark| {
+        if mark == &buffer.get_insert() {
+            update_counter_for_marks();
+        }
+    });
+
+    // Only highlight matches while the bar is open
+    let context_for_reveal = context.clone();
+    revealer.connect_reveal_child_notify(move |revealer| {
+        context_for_reveal.set_highlight(revealer.reveals_child());
+    });
+
+    // Register the bar's commands so buttons and shortcuts share one implementation
+    let actions = gio::SimpleActionGroup::new();
+
+    let next_action = gio::SimpleAction::new("next", None);
+    let context_for_next = context.clone();
+    let view_for_next = source_view.clone();
+    next_action.connect_activate(move |_, _| {
+        find_next(&context_for_next, &view_for_next);
+    });
+    actions.add_action(&next_action);
+
+    let previous_action = gio::SimpleAction::new("previous", None);
+    let context_for_previous = context.clone();
+    let view_for_previous = source_view.clone();
+    previous_action.connect_activate(move |_, _| {
+        find_previous(&context_for_previous, &view_for_previous);
+    });
+    actions.add_action(&previous_action);
+
+    let replace_action = gio::SimpleAction::new("replace", None);
+    let context_for_replace = context.clone();
+    let view_for_replace = source_view.clone();
+    let replace_entry_for_replace = replace_entry.clone();
+    replace_action.connect_activate(move |_, _| {
+        replace_current(&context_for_replace, &view_for_replace, &replace_entry_for_replace.text());
+    });
+    actions.add_action(&replace_action);
+
+    let replace_all_action = gio::SimpleAction::new("replace-all", None);
+    let context_for_replace_all = context.clone();
+    let replace_entry_for_replace_all = replace_entry.clone();
+    replace_all_action.connect_activate(move |_, _| {
+        replace_all(&context_for_replace_all, &replace_entry_for_replace_all.text());
+    });
+    actions.add_action(&replace_all_action);
+
+    let close_action = gio::SimpleAction::new("close", None);
+    let revealer_for_close = revealer.clone();
+    let view_for_close = source_view.clone();
+    close_action.connect_activate(move |_, _| {
+        revealer_for_close.set_reveal_child(false);
+        BAR_VISIBLE.with(|flag| flag.set(false));
+        view_for_close.grab_focus();
+    });
+    actions.add_action(&close_action);
+
+    // Toggling replace mode is exposed as an action so show_find_bar() can drive it
+    let replace_mode_action = gio::SimpleAction::new_stateful("replace-mode", Some(glib::VariantTy::BOOLEAN), &false.to_variant());
+    let replace_toggle_for_action = replace_toggle.clone();
+    replace_mode_action.connect_activate(move |_, value| {
+        if let Some(enabled) = value.and_then(|v| v.get::<bool>()) {
+            replace_toggle_for_action.set_active(enabled);
+        }
+    });
+    actions.add_action(&replace_mode_action);
+
+    let replace_mode_action_for_toggle = replace_mode_action.clone();
+    replace_toggle.connect_toggled(move |toggle| {
+        REPLACE_VISIBLE.with(|flag| flag.set(toggle.is_active()));
+        replace_mode_action_for_toggle.set_state(&toggle.is_active().to_variant());
+    });
+
+    revealer.insert_action_group("search", Some(&actions));
+
+    // Enter searches forward, Shift+Enter backward
+    let revealer_for_activate = revealer.clone();
+    search_entry.connect_activate(move |_| {
+        let _ = revealer_for_activate.activate_action("search.next", None);
+    });
+    let revealer_for_replace_activate = revealer.clone();
+    replace_entry.connect_activate(move |_| {
+        let _ = revealer_for_replace_activate.activate_action("search.replace", None);
+    });
+
+    // Escape closes the bar, Shift+Enter goes to the previous match
+    let key_controller = EventControllerKey::new();
+    let revealer_for_keys = revealer.clone();
+    key_controller.connect_key_pressed(move |_controller, keyval, _keycode, state| {
+        match keyval {
+            gtk4::gdk::Key::Escape => {
+                let _ = revealer_for_keys.activate_action("search.close", None);
+                glib::Propagation::Stop
+            }
+            gtk4::gdk::Key::Return | gtk4::gdk::Key::KP_Enter
+                if state.contains(gtk4::gdk::ModifierType::SHIFT_MASK) =>
+            {
+                let _ = revealer_for_keys.activate_action("search.previous", None);
+                glib::Propagation::Stop
+            }
+            // Emacs keymap: C-s and C-r step through the matches, C-g closes the bar
+            gtk4::gdk::Key::s | gtk4::gdk::Key::r | gtk4::gdk::Key::g
+                if state.contains(gtk4::gdk::ModifierType::CONTROL_MASK) && crate::emacs::is_enabled() =>
+            {
+                let action = match keyval {
+                    gtk4::gdk::Key::s => "search.next",
+                    gtk4::gdk::Key::r => "search.previous",
+                    _ => "search.close",
+                };
+                let _ = revealer_for_keys.activate_action(action, None);
+                glib::Propagation::Stop
+            }
+            _ => glib::Propagation::Proceed,
+        }
+    });
+    bar.add_controller(key_controller);
+
+    update_counter();
+
+    revealer
+}
+
+/// Creates a small toggle button for a search option
+///
+/// Shared with the Find in Files panel, whose options look the same.
+pub fn create_option_toggle(label: &str, tooltip: &str) -> ToggleButton {
+    let toggle = ToggleButton::with_label(label);
+    toggle.set_tooltip_text(Some(tooltip));
+    toggle.add_css_class("find-option");
+    toggle
+}
+
+/// Updates the "N of M" counter and flags invalid regular expressions
+fn update_match_label(context: &SearchContext, buffer: &Buffer, match_label: &Label, search_entry: &Entry) {
+    if let Some(error) = context.regex_error() {
+        search_entry.add_css_class("error");
+        search_entry.set_tooltip_text(Some(&error.to_string()));
+        match_label.set_text("Invalid pattern");
+        return;
+    }
+    search_entry.remove_css_class("error");
+    search_entry.set_tooltip_text(None);
+
+    if context.settings().search_text().is_none() {
+        match_label.set_text("");
+        return;
+    }
+
+    let count = context.occurrences_count();
+    if count < 0 {
+        // The buffer has not been fully scanned yet
+        match_label.set_text("…");
+        return;
+    }
+    if count == 0 {
+        match_label.set_text("No results");
+        return;
+    }
+
+    let position = buffer
+        .selection_bounds()
+        .map(|(start, end)| context.occurrence_position(&start, &end))
+        .unwrap_or(0);
+
+    if position > 0 {
+        match_label.set_text(&format!("{} of {}", position, count));
+    } else if count == 1 {
+        match_label.set_text("1 match");
+    } else {
+        match_label.set_text(&format!("{} matches", count));
+    }
+}
+
+/// Selects the next occurrence after the cursor and scrolls it into view
+fn find_next(context: &SearchContext, view: &View) -> bool {
+    let buffer = context.buffer();
+    let from = match buffer.selection_bounds() {
+        Some((_, end)) => end,
+        None => buffer.iter_at_mark(&buffer.get_insert()),
+    };
+
+    if let Some((start, end, _wrapped)) = context.forward(&from) {
+        select_and_reveal(&buffer, view, &start, &end);
+        true
+    } else {
+        false
+    }
+}
+
+/// Selects the previous occurrence before the cursor and scrolls it into view
+fn find_previous(context: &SearchContext, view: &View) -> bool {
+    let buffer = context.buffer();
+    let from = match buffer.selection_bounds() {
+        Some((start, _)) => start,
+        None => buffer.iter_at_mark(&buffer.get_insert()),
+    };
+
+    if let Some((start, end, _wrapped)) = context.backward(&from) {
+        select_and_reveal(&buffer, view, &start, &end);
+        true
+    } else {
+        false
+    }
+}
+
+/// Selects a match and scrolls the view so it is visible
+fn select_and_reveal(buffer: &Buffer, view: &View, start: &gtk4::TextIter, end: &gtk4::TextIter) {
+    buffer.select_range(start, end);
+    view.scroll_to_mark(&buffer.get_insert(), 0.25, false, 0.0, 0.0);
+}
+
+/// Replaces the selected occurrence (if the selection is one) and moves to the next match
+fn replace_current(context: &SearchContext, view: &View, replacement: &str) {
+    let buffer = context.buffer();
+
+    if let Some((mut start, mut end)) = buffer.selection_bounds() {
+        if context.occurrence_position(&start, &end) > 0 {
+            if let Err(e) = context.replace(&mut start, &mut end, replacement) {
+                eprintln!("Failed to replace match: {}", e);
+                return;
+            }
+            buffer.place_cursor(&end);
+        }
+    }
+
+    find_next(context, view);
+}
+
+/// Replaces every occurrence in the buffer as a single undoable action
+fn replace_all(context: &SearchContext, replacement: &str) {
+    let buffer = context.buffer();
+    buffer.begin_user_action();
+    if let Err(e) = context.replace_all(replacement) {
+        eprintln!("Failed to replace all matches: {}", e);
+    }
+    buffer.end_user_action();
+}
+
+/// Finds the find bar revealer inside an editor page
+pub fn find_search_bar(page: &gtk4::Widget) -> Option<Revealer> {
+    let mut child = page.first_child();
+    while let Some(current_child) = child {
+        if let Some(revealer) = current_child.downcast_ref::<Revealer>() {
+            if revealer.widget_name() == FIND_BAR_NAME {
+                return Some(revealer.clone());
+            }
+        }
+        child = current_child.next_sibling();
+    }
+    None
+}
+
+/// Returns the find bar of the notebook's current page, if that page is an editor
+fn current_search_bar(notebook: &Notebook) -> Option<Revealer> {
+    notebook
+        .current_page()
+        .and_then(|page_num| notebook.nth_page(Some(page_num)))
+        .and_then(|page| find_search_bar(&page))
+}
+
+/// Opens the find bar on the current tab and focuses its search entry
+///
+/// The current single-line selection, if any, becomes the search text.
+/// When `with_replace` is true the replace row is shown as well (Ctrl+H).
+pub fn show_find_bar(notebook: &Notebook, with_replace: bool) {
+    let Some(revealer) = current_search_bar(notebook) else {
+        return;
+    };
+
+    // Seed the query with the selected text when it fits on one line
+    if let Some((_, buffer)) = crate::handlers::get_active_text_view_and_buffer(notebook) {
+        if let Some((start, end)) = buffer.selection_bounds() {
+            let selected = buffer.text(&start, &end, false);
+            if !selected.contains('\n') {
+                shared_search_settings().set_search_text(Some(selected.as_str()));
+            }
+        }
+    }
+
+    let _ = revealer.activate_action("search.replace-mode", Some(&with_replace.to_variant()));
+    revealer.set_reveal_child(true);
+    BAR_VISIBLE.with(|flag| flag.set(true));
+
+    if let Some(entry) = first_entry(revealer.upcast_ref::<gtk4::Widget>()) {
+        entry.grab_focus();
+        entry.select_region(0, -1);
+    }
+}
+
+/// Checks whether the find bar is open on the current tab
+pub fn is_find_bar_open(notebook: &Notebook) -> bool {
+    current_search_bar(notebook).map(|revealer| revealer.reveals_child()).unwrap_or(false)
+}
+
+/// Moves to the next (or previous) match in the current tab
+pub fn find_in_current_tab(notebook: &Notebook, backwards: bool) {
+    if let Some(revealer) = current_search_bar(notebook) {
+        let action = if backwards { "search.previous" } else { "search.next" };
+        let _ = revealer.activate_action(action, None);
+    }
+}
+
+/// Keeps the find bar open across tab switches
+///
+/// When the user switches tabs with the bar open, the bar is opened on the new tab
+/// too, in the same mode, so the shared query stays visible and usable.
+pub fn connect_notebook(notebook: &Notebook) {
+    notebook.connect_switch_page(|_notebook, page, _page_num| {
+        let Some(revealer) = find_search_bar(page) else {
+            return;
+        };
+        if BAR_VISIBLE.with(|flag| flag.get()) {
+            let replace_visible = REPLACE_VISIBLE.with(|flag| flag.get());
+            let _ = revealer.activate_action("search.replace-mode", Some(&replace_visible.to_variant()));
+            revealer.set_reveal_child(true);
+        } else {
+            revealer.set_reveal_child(false);
+        }
+    });
+}
+
+/// Finds the first Entry below a widget (the search entry of a find bar or panel)
+pub fn first_entry(widget: &gtk4::Widget) -> Option<Entry> {
+    if let Some(entry) = widget.downcast_ref::<Entry>() {
+        return Some(entry.clone());
+    }
+    let mut child = widget.first_child();
+    while let Some(current_child) = child {
+        if let Some(entry) = first_entry(&current_child) {
+            return Some(entry);
+        }
+        child = current_child.next_sibling();
+    }
+    None
+}
@@ -10,6 +10,82 @@ use home::home_dir;
 const DEFAULT_LIGHT_THEME: &str = "solarized-light";
 const DEFAULT_DARK_THEME: &str = "solarized-dark";
 
+/// Keybinding profile used in the editor views
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Keymap {
+    Default,
+    Vim,
+    Emacs,
+}
+
+impl Keymap {
+    /// All profiles, in the order they are offered in the settings dialog
+    pub const ALL: [Keymap; 3] = [Keymap::Default, Keymap::Vim, Keymap::Emacs];
+
+    /// Returns the value stored in the settings file
+    pub fn key(self) -> &'static str {
+        match self {
+            Keymap::Default => "default",
+            Keymap::Vim => "vim",
+            Keymap::Emacs => "emacs",
+        }
+    }
+
+    /// Returns the name shown in the settings dialog
+    pub fn label(self) -> &'static str {
+        match self {
+            Keymap::Default => "Default",
+            Keymap::Vim => "Vim",
+            Keymap::Emacs => "Emacs",
+        }
+    }
+
+    /// Parses a value stored in the settings file
+    pub fn from_key(key: &str) -> Option<Keymap> {
+        Keymap::ALL.into_iter().find(|keymap| keymap.key() == key)
+    }
+}
+
+/// How long lines are wrapped in the editor views
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WrapMode {
+    None,
+    Word,
+    Char,
+    /// Wrap at word boundaries, no further right than the margin column
+    Margin,
+}
+
+impl WrapMode {
+    /// All modes, in the order they are offered in the settings dialog
+    pub const ALL: [WrapMode; 4] = [WrapMode::None, WrapMode::Word, WrapMode::Char, WrapMode::Margin];
+
+    /// Returns the value stored in the settings file
+    pub fn key(self) -> &'static str {
+        match self {
+            WrapMode::None => "none",
+            WrapMode::Word => "word",
+            WrapMode::Char => "char",
+            WrapMode::Margin => "margin",
+        }
+    }
+
+    /// Returns the name shown in the settings dialog and the context menu
+    pub fn label(self) -> &'static str {
+        match self {
+            WrapMode::None => "No Wrap",
+            WrapMode::Word => "Wrap at Words",
+            WrapMode::Char => "Wrap at Characters",
+            WrapMode::Margin => "Wrap at Margin Column",
+        }
+    }
+
+    /// Parses a value stored in the settings file
+    pub fn from_key(key: &str) -> Option<WrapMode> {
+        WrapMode::ALL.into_iter().find(|mode| mode.key() == key)
+    }
+}
+
 /// Represents user-configurable settings for the application
 #[derive(Clone)]
 pub struct EditorSettings {
@@ -50,6 +126,29 @@ impl EditorSettings {
     fn set_defaults(&mut self) {
         self.values.insert("light_theme".to_string(), DEFAULT_LIGHT_THEME.to_string());
         self.values.insert("dark_theme".to_string(), DEFAULT_DARK_THEME.to_string());
+        self.values.insert("show_minimap".to_string(), "false".to_string());
+        for key in ["draw_spaces", "draw_tabs", "draw_trailing_whitespace", "show_indent_guides"] {
+            self.values.insert(key.to_string(), "false".to_string());
+        }
+        for key in ["completion.words", "completion.paths", "completion.keywords", "completion.snippets"] {
+            self.values.insert(key.to_string(), "true".to_string());
+        }
+        self.values.insert("completion.min_chars".to_string(), "3".to_string());
+        self.values.insert("persistent_undo".to_string(), "true".to_string());
+        self.values.insert("keymap".to_string(), Keymap::Default.key().to_string());
+        // Wrapping, with per-language overrides as wrap_mode.<language id>; no rulers by default
+        self.values.insert("wrap_mode".to_string(), WrapMode::None.key().to_string());
+        self.values.insert("wrap_mode.markdown".to_string(), WrapMode::Word.key().to_string());
+        self.values.insert("right_margin.columns".to_string(), String::new());
+        // An empty language means the locale's, see spell_check::selected_language
+        self.values.insert("spell_check".to_string(), "false".to_string());
+        self.values.insert("spell_check.language".to_string(), String::new());
+        self.values.insert("persistent_undo.max_kb".to_string(), crate::undo_history::DEFAULT_MAX_KB.to_string());
+        
+        // Comment tokens for file types without language metadata (see comments.rs)
+        for (file_type, token) in [("conf", "#"), ("cfg", "#"), ("env", "#"), ("properties", "#"), ("ini", ";"), ("text", "#")] {
+            self.values.insert(format!("comment.line.{}", file_type), token.to_string());
+        }
         // Add more default settings here as needed
     }
 
@@ -121,10 +220,67 @@ impl EditorSettings {
     pub fn set_dark_theme(&mut self, theme: &str) {
         self.set("dark_theme", theme);
     }
+
+    /// Gets a boolean setting, falling back to `default` when missing or invalid
+    pub fn get_bool(&self, key: &str, default: bool) -> bool {
+        self.get(key).and_then(|value| value.parse().ok()).unwrap_or(default)
+    }
+
+    /// Sets a boolean setting
+    pub fn set_bool(&mut self, key: &str, value: bool) {
+        self.set(key, if value { "true" } else { "false" });
+    }
+
+    /// Gets whether the minimap is shown next to each editor
+    pub fn get_show_minimap(&self) -> bool {
+        self.get_bool("show_minimap", false)
+    }
+
+    /// Sets whether the minimap is shown next to each editor
+    pub fn set_show_minimap(&mut self, show: bool) {
+        self.set_bool("show_minimap", show);
+    }
+
+    /// Gets the keybinding profile of the editor views
+    pub fn get_keymap(&self) -> Keymap {
+        self.get("keymap").and_then(|value| Keymap::from_key(value)).unwrap_or(Keymap::Default)
+    }
+
+    /// Sets the keybinding profile of the editor views
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        self.set("keymap", keymap.key());
+    }
+
+    /// Gets the wrap mode for a language: `wrap_mode.<language id>` if set, else `wrap_mode`
+    pub fn get_wrap_mode(&self, language: Option<&str>) -> WrapMode {
+        language
+            .and_then(|language| self.get(&format!("wrap_mode.{}", language)))
+            .and_then(|value| WrapMode::from_key(value))
+            .or_else(|| self.get("wrap_mode").and_then(|value| WrapMode::from_key(value)))
+            .unwrap_or(WrapMode::None)
+    }
+
+    /// Sets the default wrap mode of the editor views
+    pub fn set_wrap_mode(&mut self, mode: WrapMode) {
+        self.set("wrap_mode", mode.key());
+    }
+
+    /// Gets the columns of the right-margin rulers, e.g. `80, 100`; empty when none are shown
+    pub fn get_ruler_columns(&self) -> Vec<u32> {
+        self.get("right_margin.columns")
+            .map(|value| {
+                value
+                    .split(|c: char| c == ',' || c.is_whitespace())
+                    .filter_map(|column| column.parse::<u32>().ok())
+                    .filter(|column| *column > 0)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
 }
 
 /// Returns the configuration directory path
-fn get_config_dir() -> PathBuf {
+pub fn get_config_dir() -> PathBuf {
     // First try to use XDG_CONFIG_HOME
     if let Ok(xdg_config) = std::env::var("XDG_CONFIG_HOME") {
         let path = Path::new(&xdg_config).join("basado-text-editor");
@@ -171,6 +327,14 @@ pub fn get_settings() -> EditorSettings {
     SETTINGS_INSTANCE.lock().unwrap().clone()
 }
 
+/// Runs a closure with the settings under the lock, without cloning them
+///
+/// For code that reads a few keys on every key press.
+pub fn with_settings<R>(f: impl FnOnce(&EditorSettings) -> R) -> R {
+    initialize_settings();
+    f(&SETTINGS_INSTANCE.lock().unwrap())
+}
+
 /// Updates and returns the mutable settings
 /// 
 /// This function locks the mutex to perform changes and returns a mutable
@@ -212,3 +376,30 @@ pub fn refresh_settings() {
     // Reset the refreshing flag
     REFRESHING.with(|flag| flag.set(false));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings_from(contents: &str) -> EditorSettings {
+        let mut settings = EditorSettings { values: HashMap::new(), config_path: PathBuf::new() };
+        settings.set_defaults();
+        for (key, value) in contents.lines().filter_map(|line| line.split_once('=')) {
+            settings.set(key.trim(), value.trim());
+        }
+        settings
+    }
+
+    #[test]
+    fn ruler_columns_are_read_from_a_list() {
+        assert!(settings_from("").get_ruler_columns().is_empty());
+        assert_eq!(settings_from("right_margin.columns=80\n").get_ruler_columns(), vec![80]);
+        assert_eq!(settings_from("right_margin.columns=80, 100 120\n").get_ruler_columns(), vec![80, 100, 120]);
+    }
+
+    #[test]
+    fn invalid_ruler_columns_are_skipped() {
+        assert_eq!(settings_from("right_margin.columns=0,abc,,72\n").get_ruler_columns(), vec![72]);
+        assert!(settings_from("right_margin.columns=-1\n").get_ruler_columns().is_empty());
+    }
+}
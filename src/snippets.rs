@@ -0,0 +1,787 @@
+// Snippets for the text editor
+// User-defined snippet files per language, expanded with Tab and offered in completion
+
+use gtk4::prelude::*;
+use gtk4::{
+    gdk, gio, glib, Box as GtkBox, Button, Dialog, Entry, EventControllerKey, Label, ListBox, Orientation,
+    ScrolledWindow, TextBuffer, TextIter,
+};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use sourceview5::subclass::prelude::*;
+use sourceview5::{
+    prelude::*, Buffer, CompletionActivation, CompletionCell, CompletionColumn, CompletionContext,
+    CompletionProposal, CompletionProvider, View,
+};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::rc::Rc;
+
+use crate::utils::ObjectState;
+
+/// Extension of snippet files (`<language>.snippets`)
+const SNIPPET_FILE_EXTENSION: &str = "snippets";
+
+/// Snippet file whose snippets are offered in every language
+const GLOBAL_SNIPPET_FILE: &str = "all";
+
+/// Snippets written when the snippets directory is first created
+const DEFAULT_RUST_SNIPPETS: &str = "\
+# Snippets for Rust files
+#
+# Each snippet starts with \"snippet <trigger> <description>\" and its body is
+# indented with one tab. $1, $2... are tab stops, ${1:text} a placeholder,
+# repeating $1 mirrors it, and $0 is where the cursor ends.
+# Variables: $FILENAME $FILEPATH $DIRECTORY $CLIPBOARD $DATE $TIME $YEAR
+
+snippet derive Struct with derives
+\t#[derive(${1:Debug, Clone})]
+\tpub struct ${2:Name} {
+\t    $0
+\t}
+
+snippet test Test module
+\t#[cfg(test)]
+\tmod tests {
+\t    use super::*;
+
+\t    #[test]
+\t    fn ${1:it_works}() {
+\t        $0
+\t    }
+\t}
+
+snippet impl Impl block
+\timpl ${1:Type} {
+\t    $0
+\t}
+";
+
+const DEFAULT_GLOBAL_SNIPPETS: &str = "\
+# Snippets offered in every file
+
+snippet date Today's date
+\t$DATE
+
+snippet header File header
+\t${1:$FILENAME} - ${2:description}
+\tCreated on $DATE
+";
+
+/// Editor variables in a snippet body: `$NAME` or `${NAME}`
+static VARIABLE_PATTERN: Lazy<Regex> = Lazy::new(|| Regex::new(r"\$(?:\{([A-Z_]+)\}|([A-Z_]+))").unwrap());
+
+/// Snippet file shown by a row of the snippet manager's file list
+const ROW_FILE: ObjectState<PathBuf> = ObjectState::new("snippet-file");
+
+thread_local! {
+    // Snippets loaded for each language key, until a snippet file changes
+    static SNIPPET_CACHE: RefCell<HashMap<String, Rc<Vec<SnippetDefinition>>>> = RefCell::new(HashMap::new());
+    // Watches the snippets directory to empty the cache
+    static SNIPPET_MONITOR: RefCell<Option<gio::FileMonitor>> = RefCell::new(None);
+}
+
+/// A snippet from a snippet file
+#[derive(Clone, Debug, PartialEq)]
+pub struct SnippetDefinition {
+    /// Text typed before pressing Tab
+    pub trigger: String,
+    /// Shown in completion
+    pub description: String,
+    /// Body in snippet syntax ($1, ${1:placeholder}, $0, $VARIABLE)
+    pub body: String,
+}
+
+/// Returns the directory holding the snippet files (next to settings.conf)
+///
+/// Created with example snippets the first time it is needed.
+pub fn snippets_dir() -> PathBuf {
+    let dir = crate::settings::get_config_dir().join("snippets");
+    if !dir.exists() {
+        if let Err(e) = fs::create_dir_all(&dir) {
+            eprintln!("Failed to create snippets directory: {}", e);
+            return dir;
+        }
+        for (name, contents) in [("rust", DEFAULT_RUST_SNIPPETS), (GLOBAL_SNIPPET_FILE, DEFAULT_GLOBAL_SNIPPETS)] {
+            if let Err(e) = fs::write(snippet_file_path(&dir, name), contents) {
+                eprintln!("Failed to write default snippets: {}", e);
+            }
+        }
+    }
+    dir
+}
+
+/// Returns the path of the snippet file for a language key
+fn snippet_file_path(dir: &Path, language_key: &str) -> PathBuf {
+    dir.join(format!("{}.{}", language_key, SNIPPET_FILE_EXTENSION))
+}
+
+/// Parses a snippet file
+///
+/// The format follows SnipMate: a `snippet <trigger> [description]` line starts a
+/// snippet, and the tab-indented lines after it are its body. Lines starting with
+/// `#` outside a body are comments.
+pub fn parse_snippet_file(contents: &str) -> Vec<SnippetDefinition> {
+    let mut snippets: Vec<SnippetDefinition> = Vec::new();
+    let mut body_lines: Vec<&str> = Vec::new();
+
+    let finish = |snippets: &mut Vec<SnippetDefinition>, body_lines: &mut Vec<&str>| {
+        if let Some(snippet) = snippets.last_mut() {
+            // Blank lines between snippets are not part of the body
+            while body_lines.last().is_some_and(|line| line.trim().is_empty()) {
+                body_lines.pop();
+            }
+            snippet.body = body_lines.join("\n");
+        }
+        body_lines.clear();
+    };
+
+    let mut in_snippet = false;
+    for line in contents.lines() {
+        if let Some(header) = line.strip_prefix("snippet ") {
+            if in_snippet {
+                finish(&mut snippets, &mut body_lines);
+            }
+            let header = header.trim();
+            let (trigger, description) = header.split_once(char::is_whitespace).unwrap_or((header, ""));
+            snippets.push(SnippetDefinition {
+                trigger: trigger.to_string(),
+                description: description.trim().to_string(),
+                body: String::new(),
+            });
+            in_snippet = true;
+        } else if in_snippet && (line.starts_with('\t') || line.trim().is_empty()) {
+            body_lines.push(line.strip_prefix('\t').unwrap_or(""));
+        } else if in_snippet && !line.starts_with('#') {
+            // Any other unindented line ends the body
+            finish(&mut snippets, &mut body_lines);
+            in_snippet = false;
+        }
+    }
+    if in_snippet {
+        finish(&mut snippets, &mut body_lines);
+    }
+
+    snippets.retain(|snippet| !snippet.trigger.is_empty());
+    snippets
+}
+
+/// Loads the snippets for a language key, plus the global ones
+///
+/// Language snippets come first so they win over global snippets with the same trigger.
+/// The files are read once per language; a change in the snippets directory (or a
+/// save in the snippet manager) empties the cache.
+pub fn load_snippets(language_key: &str) -> Rc<Vec<SnippetDefinition>> {
+    if let Some(snippets) = SNIPPET_CACHE.with(|cache| cache.borrow().get(language_key).cloned()) {
+        return snippets;
+    }
+
+    let dir = snippets_dir();
+    watch_snippets_dir(&dir);
+    let mut snippets = Vec::new();
+    for name in [language_key, GLOBAL_SNIPPET_FILE] {
+        if let Ok(contents) = fs::read_to_string(snippet_file_path(&dir, name)) {
+            snippets.extend(parse_snippet_file(&contents));
+        }
+    }
+
+    let snippets = Rc::new(snippets);
+    SNIPPET_CACHE.with(|cache| cache.borrow_mut().insert(language_key.to_string(), snippets.clone()));
+    snippets
+}
+
+/// Forgets the loaded snippets, so they are read again from their files
+fn clear_snippet_cache() {
+    SNIPPET_CACHE.with(|cache| cache.borrow_mut().clear());
+}
+
+/// Starts watching the snippets directory for edited, added or removed files
+fn watch_snippets_dir(dir: &Path) {
+    if SNIPPET_MONITOR.with(|monitor| monitor.borrow().is_some()) {
+        return;
+    }
+    match gio::File::for_path(dir).monitor_directory(gio::FileMonitorFlags::WATCH_MOVES, None::<&gio::Cancellable>) {
+        Ok(monitor) => {
+            monitor.connect_changed(|_, _, _, _| clear_snippet_cache());
+            SNIPPET_MONITOR.with(|slot| *slot.borrow_mut() = Some(monitor));
+        }
+        Err(e) => eprintln!("Failed to watch the snippets directory: {}", e),
+    }
+}
+
+/// Returns the snippets for the language of a buffer
+fn snippets_for_buffer(buffer: &TextBuffer) -> Rc<Vec<SnippetDefinition>> {
+    let language_key = buffer
+        .downcast_ref::<Buffer>()
+        .map(crate::syntax::language_key)
+        .unwrap_or_else(|| "text".to_string());
+    load_snippets(&language_key)
+}
+
+/// Replaces the editor variables ($FILENAME, ${DATE}...) in a snippet body
+///
+/// Unknown names are left for the snippet parser. Values are escaped so they are
+/// inserted literally.
+pub fn expand_variables(body: &str, buffer: &TextBuffer, clipboard: Option<&str>) -> String {
+    let file_path = buffer.downcast_ref::<Buffer>().and_then(crate::syntax::buffer_file_path);
+    let now = glib::DateTime::now_local().ok();
+    let format_now = |format: &str| {
+        now.as_ref()
+            .and_then(|date| date.format(format).ok())
+            .map(|text| text.to_string())
+            .unwrap_or_default()
+    };
+
+    replace_variables(body, |name| {
+        let value = match name {
+            "FILENAME" => file_path
+                .as_ref()
+                .and_then(|path| path.file_name())
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| "Untitled".to_string()),
+            "FILEPATH" => file_path
+                .as_ref()
+                .map(|path| path.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            "DIRECTORY" => file_path
+                .as_ref()
+                .and_then(|path| path.parent())
+                .map(|dir| dir.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            "CLIPBOARD" => clipboard.unwrap_or("").to_string(),
+            "DATE" => format_now("%Y-%m-%d"),
+            "TIME" => format_now("%H:%M:%S"),
+            "YEAR" => format_now("%Y"),
+            _ => return None,
+        };
+        Some(value)
+    })
+}
+
+/// Replaces each variable that `value` knows with its escaped value
+fn replace_variables(body: &str, value: impl Fn(&str) -> Option<String>) -> String {
+    VARIABLE_PATTERN
+        .replace_all(body, |captures: &regex::Captures| {
+            let name = captures.get(1).or_else(|| captures.get(2)).map_or("", |m| m.as_str());
+            match value(name) {
+                Some(value) => escape_snippet_text(&value),
+                None => captures[0].to_string(),
+            }
+        })
+        .to_string()
+}
+
+/// Escapes text so the snippet parser inserts it literally
+fn escape_snippet_text(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('$', "\\$").replace('}', "\\}")
+}
+
+/// Adds snippet expansion with Tab to a source view
+///
+/// Tab after a trigger (with no selection) replaces the trigger with the snippet.
+/// Tab and Shift+Tab then move between the tab stops.
+pub fn attach(view: &View) {
+    let key_controller = EventControllerKey::new();
+    key_controller.set_propagation_phase(gtk4::PropagationPhase::Capture);
+    let view_clone = view.clone();
+    key_controller.connect_key_pressed(move |_, keyval, _, state| handle_key(&view_clone, keyval, state));
+    view.add_controller(key_controller);
+}
+
+/// Handles Tab for snippet expansion
+pub fn handle_key(view: &View, keyval: gdk::Key, state: gdk::ModifierType) -> glib::Propagation {
+    let modifiers = gdk::ModifierType::CONTROL_MASK | gdk::ModifierType::ALT_MASK | gdk::ModifierType::SHIFT_MASK;
+    if keyval != gdk::Key::Tab || state.intersects(modifiers) {
+        return glib::Propagation::Proceed;
+    }
+    if !view.is_editable() || crate::multi_cursor::has_extra_carets(view) {
+        return glib::Propagation::Proceed;
+    }
+    if expand_trigger_at_cursor(view) {
+        glib::Propagation::Stop
+    } else {
+        glib::Propagation::Proceed
+    }
+}
+
+/// Expands the snippet whose trigger ends at the cursor
+///
+/// The longest trigger wins, e.g. "#[test" before "test", and triggers only
+/// expand at the start of a word. Returns false when nothing matches.
+fn expand_trigger_at_cursor(view: &View) -> bool {
+    let buffer = view.buffer();
+    if buffer.has_selection() {
+        return false;
+    }
+
+    let cursor = buffer.iter_at_mark(&buffer.get_insert());
+    let mut word_start = cursor.clone();
+    while word_start.backward_char() {
+        if word_start.char().is_whitespace() {
+            word_start.forward_char();
+            break;
+        }
+    }
+    let typed = buffer.text(&word_start, &cursor, false).to_string();
+    if typed.is_empty() {
+        return false;
+    }
+
+    let snippets = snippets_for_buffer(&buffer);
+    let Some(snippet) = snippets
+        .iter()
+        .filter(|snippet| trigger_matches(&typed, &snippet.trigger))
+        .max_by_key(|snippet| snippet.trigger.chars().count())
+    else {
+        return false;
+    };
+
+    let end_offset = cursor.offset();
+    let start_offset = end_offset - snippet.trigger.chars().count() as i32;
+    insert_snippet(view, start_offset, end_offset, snippet);
+    true
+}
+
+/// Checks whether the text before the cursor ends with a trigger that starts a word
+///
+/// A trigger starting with a letter, digit or `_` doesn't expand at the end of a
+/// longer word: "fn" expands after a space or "(", but not at the end of "often".
+fn trigger_matches(typed: &str, trigger: &str) -> bool {
+    let Some(before) = typed.strip_suffix(trigger) else {
+        return false;
+    };
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+    let starts_word = trigger.chars().next().is_some_and(is_word_char);
+    !(starts_word && before.chars().next_back().is_some_and(is_word_char))
+}
+
+/// Replaces a range of the buffer with a snippet
+///
+/// Reads the clipboard first when the snippet uses $CLIPBOARD.
+pub fn insert_snippet(view: &View, start_offset: i32, end_offset: i32, snippet: &SnippetDefinition) {
+    let body = snippet.body.clone();
+    if !body.contains("CLIPBOARD") {
+        push_snippet(view, start_offset, end_offset, &body, None);
+        return;
+    }
+
+    let view = view.clone();
+    view.clipboard().read_text_async(None::<&gio::Cancellable>, move |result| {
+        let clipboard = result.ok().flatten().map(|text| text.to_string());
+        push_snippet(&view, start_offset, end_offset, &body, clipboard.as_deref());
+    });
+}
+
+/// Deletes the trigger and starts the snippet in the view
+fn push_snippet(view: &View, start_offset: i32, end_offset: i32, body: &str, clipboard: Option<&str>) {
+    let buffer = view.buffer();
+    let expanded = expand_variables(body, &buffer, clipboard);
+
+    buffer.begin_user_action();
+    let mut start = buffer.iter_at_offset(start_offset);
+    let mut end = buffer.iter_at_offset(end_offset);
+    buffer.delete(&mut start, &mut end);
+
+    match sourceview5::Snippet::new_parsed(&expanded) {
+        Ok(parsed) => view.push_snippet(&parsed, Some(&mut start)),
+        Err(e) => {
+            // Insert the text as-is so the trigger is not lost
+            eprintln!("Failed to parse snippet: {}", e);
+            buffer.insert(&mut start, &expanded);
+        }
+    }
+    buffer.end_user_action();
+}
+
+/// Returns the word (letters, digits, `_`, `-`, `#`) ending at the cursor and its start
+fn completion_prefix(buffer: &TextBuffer) -> (String, TextIter) {
+    let cursor = buffer.iter_at_mark(&buffer.get_insert());
+    let mut start = cursor.clone();
+    while start.backward_char() {
+        let character = start.char();
+        if !(character.is_alphanumeric() || matches!(character, '_' | '-' | '#')) {
+            start.forward_char();
+            break;
+        }
+    }
+    (buffer.text(&start, &cursor, false).to_string(), start)
+}
+
+mod imp {
+    use super::*;
+
+    /// A snippet offered in completion
+    #[derive(Default)]
+    pub struct SnippetProposal {
+        pub snippet: RefCell<Option<SnippetDefinition>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for SnippetProposal {
+        const NAME: &'static str = "BasadoSnippetProposal";
+        type Type = super::SnippetProposal;
+        type ParentType = glib::Object;
+        type Interfaces = (CompletionProposal,);
+    }
+
+    impl ObjectImpl for SnippetProposal {}
+    impl CompletionProposalImpl for SnippetProposal {}
+
+    /// Offers the snippets whose trigger starts with the word at the cursor
+    #[derive(Default)]
+    pub struct SnippetProvider;
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for SnippetProvider {
+        const NAME: &'static str = "BasadoSnippetCompletionProvider";
+        type Type = super::SnippetProvider;
+        type ParentType = glib::Object;
+        type Interfaces = (CompletionProvider,);
+    }
+
+    impl ObjectImpl for SnippetProvider {}
+
+    impl CompletionProviderImpl for SnippetProvider {
+        fn title(&self) -> Option<glib::GString> {
+            Some("Snippets".into())
+        }
+
+        fn populate_future(
+            &self,
+            context: &CompletionContext,
+        ) -> Pin<Box<dyn Future<Output = Result<gio::ListModel, glib::Error>> + 'static>> {
+            let store = gio::ListStore::new::<super::SnippetProposal>();
+
+            if let Some(buffer) = context.buffer() {
+                let buffer = buffer.upcast::<TextBuffer>();
+                let (prefix, _) = completion_prefix(&buffer);
+                let interactive = context.activation() == CompletionActivation::Interactive;
+                if !(interactive && prefix.is_empty()) {
+                    let proposals: Vec<super::SnippetProposal> = snippets_for_buffer(&buffer)
+                        .iter()
+                        .filter(|snippet| snippet.trigger.starts_with(&prefix))
+                        .map(|snippet| super::SnippetProposal::new(snippet.clone()))
+                        .collect();
+                    store.extend_from_slice(&proposals);
+                }
+            }
+
+            Box::pin(async move { Ok(store.upcast::<gio::ListModel>()) })
+        }
+
+        fn refilter(&self, context: &CompletionContext, model: &gio::ListModel) {
+            let (Some(buffer), Some(store)) = (context.buffer(), model.downcast_ref::<gio::ListStore>()) else {
+                return;
+            };
+            let (prefix, _) = completion_prefix(buffer.upcast_ref());
+            store.retain(|item| {
+                item.downcast_ref::<super::SnippetProposal>()
+                    .and_then(|proposal| proposal.snippet())
+                    .is_some_and(|snippet| snippet.trigger.starts_with(&prefix))
+            });
+        }
+
+        fn display(&self, _context: &CompletionContext, proposal: &CompletionProposal, cell: &CompletionCell) {
+            let Some(snippet) = proposal.downcast_ref::<super::SnippetProposal>().and_then(|p| p.snippet()) else {
+                return;
+            };
+            match cell.column() {
+                CompletionColumn::Icon => cell.set_icon_name("insert-text-symbolic"),
+                CompletionColumn::TypedText => cell.set_text(Some(&snippet.trigger)),
+                CompletionColumn::Comment => cell.set_text(Some(&snippet.description)),
+                _ => cell.set_text(None),
+            }
+        }
+
+        fn activate(&self, context: &CompletionContext, proposal: &CompletionProposal) {
+            let (Some(view), Some(snippet)) = (
+                context.view(),
+                proposal.downcast_ref::<super::SnippetProposal>().and_then(|p| p.snippet()),
+            ) else {
+                return;
+            };
+            let buffer = view.buffer();
+            let (_, start) = completion_prefix(&buffer);
+            let end = buffer.iter_at_mark(&buffer.get_insert());
+            insert_snippet(&view, start.offset(), end.offset(), &snippet);
+        }
+    }
+}
+
+glib::wrapper! {
+    /// A snippet offered in completion
+    pub struct SnippetProposal(ObjectSubclass<imp::SnippetProposal>) @implements CompletionProposal;
+}
+
+impl SnippetProposal {
+    fn new(snippet: SnippetDefinition) -> Self {
+        let proposal: Self = glib::Object::new();
+        proposal.imp().snippet.replace(Some(snippet));
+        proposal
+    }
+
+    fn snippet(&self) -> Option<SnippetDefinition> {
+        self.imp().snippet.borrow().clone()
+    }
+}
+
+glib::wrapper! {
+    /// Completion provider listing the snippets of the buffer's language
+    pub struct SnippetProvider(ObjectSubclass<imp::SnippetProvider>) @implements CompletionProvider;
+}
+
+impl SnippetProvider {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+}
+
+impl Default for SnippetProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Shows the snippet manager
+///
+/// Lists the snippet files of the config dir on the left and edits the selected
+/// one on the right. New files are named after a language id (e.g. "python") or
+/// "all" for snippets available everywhere.
+pub fn show_snippet_manager(parent: &impl IsA<gtk4::Window>) {
+    let dir = snippets_dir();
+
+    let dialog = Dialog::builder()
+        .title("Snippets")
+        .transient_for(parent)
+        .modal(true)
+        .destroy_with_parent(true)
+        .use_header_bar(1)
+        .default_width(760)
+        .default_height(480)
+        .build();
+
+    let content_area = dialog.content_area();
+    content_area.set_margin_top(10);
+    content_area.set_margin_bottom(10);
+    content_area.set_margin_start(10);
+    content_area.set_margin_end(10);
+    content_area.set_spacing(8);
+
+    let help_label = Label::new(Some(
+        "snippet <trigger> <description>, then the body indented with one tab. \
+         $1 ${1:placeholder} $0 are tab stops; variables: $FILENAME $CLIPBOARD $DATE $TIME $YEAR",
+    ));
+    help_label.set_wrap(true);
+    help_label.set_halign(gtk4::Align::Start);
+    help_label.add_css_class("caption");
+    content_area.append(&help_label);
+
+    let main_box = GtkBox::new(Orientation::Horizontal, 10);
+    main_box.set_vexpand(true);
+
+    // File list with a row to create a new file
+    let side_box = GtkBox::new(Orientation::Vertical, 6);
+    let file_list = ListBox::new();
+    let file_list_scrolled = ScrolledWindow::builder()
+        .child(&file_list)
+        .vexpand(true)
+        .min_content_width(160)
+        .build();
+    side_box.append(&file_list_scrolled);
+
+    let new_file_box = GtkBox::new(Orientation::Horizontal, 4);
+    let new_file_entry = Entry::new();
+    new_file_entry.set_placeholder_text(Some("language id"));
+    new_file_entry.set_width_chars(12);
+    let new_file_button = Button::from_icon_name("list-add-symbolic");
+    new_file_button.set_tooltip_text(Some("Create snippet file"));
+    new_file_box.append(&new_file_entry);
+    new_file_box.append(&new_file_button);
+    side_box.append(&new_file_box);
+    main_box.append(&side_box);
+
+    // Editor for the selected file
+    // A plain view: snippet bodies are indented with real tabs
+    let editor = View::new();
+    editor.set_monospace(true);
+    editor.set_show_line_numbers(true);
+    editor.set_insert_spaces_instead_of_tabs(false);
+    let editor_buffer = editor.buffer();
+    let editor_scrolled = ScrolledWindow::builder()
+        .child(&editor)
+        .hexpand(true)
+        .vexpand(true)
+        .build();
+    main_box.append(&editor_scrolled);
+    content_area.append(&main_box);
+
+    let status_label = Label::new(None);
+    status_label.set_halign(gtk4::Align::Start);
+    status_label.add_css_class("caption");
+    content_area.append(&status_label);
+
+    let delete_button = dialog.add_button("Delete File", gtk4::ResponseType::Reject);
+    delete_button.add_css_class("destructive-action");
+    dialog.add_button("Close", gtk4::ResponseType::Close);
+    dialog.add_button("Save", gtk4::ResponseType::Apply);
+
+    let selected_file: std::rc::Rc<RefCell<Option<PathBuf>>> = std::rc::Rc::new(RefCell::new(None));
+
+    // Load the chosen file into the editor
+    let editor_buffer_clone = editor_buffer.clone();
+    let selected_file_clone = selected_file.clone();
+    let status_label_clone = status_label.clone();
+    file_list.connect_row_selected(move |_, row| {
+        let Some(path) = row.and_then(|row| ROW_FILE.get(row)) else {
+            return;
+        };
+        let contents = fs::read_to_string(&path).unwrap_or_default();
+        editor_buffer_clone.set_text(&contents);
+        let snippet_count = parse_snippet_file(&contents).len();
+        status_label_clone.set_text(&format!("{} snippets", snippet_count));
+        *selected_file_clone.borrow_mut() = Some(path);
+    });
+
+    refresh_snippet_file_list(&file_list, &dir, None);
+
+    // Create a new file for a language
+    let file_list_clone = file_list.clone();
+    let dir_clone = dir.clone();
+    let create_file = move |entry: &Entry| {
+        let name = entry.text().trim().to_lowercase();
+        if name.is_empty() || name.contains(['/', '\\', '.']) {
+            entry.add_css_class("error");
+            return;
+        }
+        entry.remove_css_class("error");
+        let path = snippet_file_path(&dir_clone, &name);
+        if !path.exists() {
+            if let Err(e) = fs::write(&path, format!("# Snippets for {}\n", name)) {
+                eprintln!("Failed to create snippet file: {}", e);
+                return;
+            }
+            clear_snippet_cache();
+        }
+        entry.set_text("");
+        refresh_snippet_file_list(&file_list_clone, &dir_clone, Some(&path));
+    };
+    let create_file_clone = create_file.clone();
+    new_file_entry.connect_activate(move |entry| create_file_clone(entry));
+    let new_file_entry_clone = new_file_entry.clone();
+    new_file_button.connect_clicked(move |_| create_file(&new_file_entry_clone));
+
+    dialog.connect_response(move |dialog, response| {
+        match response {
+            gtk4::ResponseType::Apply => {
+                if let Some(path) = selected_file.borrow().as_ref() {
+                    let contents = editor_buffer.text(&editor_buffer.start_iter(), &editor_buffer.end_iter(), false);
+                    let result = fs::write(path, contents.as_str());
+                    clear_snippet_cache();
+                    match result {
+                        Ok(()) => status_label.set_text(&format!(
+                            "Saved {} snippets",
+                            parse_snippet_file(&contents).len()
+                        )),
+                        Err(e) => status_label.set_text(&format!("Failed to save: {}", e)),
+                    }
+                }
+            }
+            gtk4::ResponseType::Reject => {
+                let path = selected_file.borrow_mut().take();
+                if let Some(path) = path {
+                    if let Err(e) = fs::remove_file(&path) {
+                        eprintln!("Failed to delete snippet file: {}", e);
+                    }
+                    clear_snippet_cache();
+                    editor_buffer.set_text("");
+                    status_label.set_text("");
+                    refresh_snippet_file_list(&file_list, &dir, None);
+                }
+            }
+            _ => dialog.close(),
+        }
+    });
+
+    dialog.present();
+}
+
+/// Lists the snippet files in the manager, selecting `select` (or the first file)
+fn refresh_snippet_file_list(file_list: &ListBox, dir: &Path, select: Option<&Path>) {
+    while let Some(row) = file_list.row_at_index(0) {
+        file_list.remove(&row);
+    }
+
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == SNIPPET_FILE_EXTENSION))
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort();
+
+    for path in &files {
+        let name = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+        let label = Label::new(Some(&name));
+        label.set_halign(gtk4::Align::Start);
+        label.set_margin_start(6);
+        label.set_margin_top(4);
+        label.set_margin_bottom(4);
+        let row = gtk4::ListBoxRow::new();
+        row.set_child(Some(&label));
+        ROW_FILE.set(&row, path.clone());
+        file_list.append(&row);
+    }
+
+    let index = select
+        .and_then(|select| files.iter().position(|path| path == select))
+        .unwrap_or(0);
+    if let Some(row) = file_list.row_at_index(index as i32) {
+        file_list.select_row(Some(&row));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_snippets_with_descriptions_and_bodies() {
+        let snippets = parse_snippet_file("# comment\nsnippet fn Function\n\tfn ${1:name}() {\n\t\t$0\n\t}\n\nsnippet x\n\tx\n");
+        assert_eq!(snippets.len(), 2);
+        assert_eq!(snippets[0].trigger, "fn");
+        assert_eq!(snippets[0].description, "Function");
+        assert_eq!(snippets[0].body, "fn ${1:name}() {\n\t$0\n}");
+        assert_eq!(snippets[1].description, "");
+        assert_eq!(snippets[1].body, "x");
+    }
+
+    #[test]
+    fn replaces_known_variables_only() {
+        let value = |name: &str| match name {
+            "FILENAME" => Some("main.rs".to_string()),
+            "CLIPBOARD" => Some("a$b}".to_string()),
+            _ => None,
+        };
+        assert_eq!(replace_variables("// $FILENAME ${FILENAME}", value), "// main.rs main.rs");
+        assert_eq!(replace_variables("$CLIPBOARD", value), "a\\$b\\}");
+        assert_eq!(replace_variables("$OTHER ${1:x} $1", value), "$OTHER ${1:x} $1");
+    }
+
+    #[test]
+    fn triggers_expand_at_word_starts_only() {
+        assert!(trigger_matches("fn", "fn"));
+        assert!(trigger_matches("(fn", "fn"));
+        assert!(!trigger_matches("often", "fn"));
+        assert!(!trigger_matches("x_fn", "fn"));
+        assert!(trigger_matches("#[test", "#[test"));
+        assert!(trigger_matches("x->", "->"));
+        assert!(!trigger_matches("f", "fn"));
+    }
+}
@@ -0,0 +1,598 @@
+// Spell checking for the text editor
+// Underlines misspelled words in prose files and in the comments and strings of code, with corrections in the context menu
+
+use gtk4::prelude::*;
+use gtk4::{
+    gdk, gio, glib, pango, Adjustment, EventControllerKey, GestureClick, TextBuffer, TextIter, TextMark, TextTag,
+    TextWindowType,
+};
+use sourceview5::{prelude::*, Buffer, View};
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+use crate::dictionary::{self, Dictionary};
+use crate::utils::ObjectState;
+
+/// Delay before a view is checked again after an edit or a scroll
+const CHECK_DELAY: Duration = Duration::from_millis(400);
+
+/// How often the context menu looks for the suggestions being computed
+const SUGGESTIONS_POLL: Duration = Duration::from_millis(50);
+
+/// Languages checked as prose: the whole buffer rather than only comments and strings
+const PROSE_LANGUAGES: &[&str] = &["markdown", "rst", "latex", "asciidoc", "texinfo", "mallard", "docbook"];
+
+/// Language used when neither the settings nor the locale name an installed dictionary
+const FALLBACK_LANGUAGE: &str = "en_US";
+
+/// Spell checking state of one view
+struct SpellState {
+    view: glib::WeakRef<View>,
+    /// Red wavy underline on misspelled words
+    tag: TextTag,
+    /// Incremented on every edit so only the last scheduled check runs
+    generation: Cell<u64>,
+    /// Context menu section with the corrections of the word under the pointer
+    menu_section: gio::Menu,
+    /// Bounds of the word the context menu section is about
+    menu_word: RefCell<Option<(TextMark, TextMark)>>,
+    /// Incremented whenever the context menu changes word, so late suggestions are dropped
+    menu_request: Cell<u64>,
+    /// Scroll adjustment the view is checked along, with its handlers
+    scrolling: RefCell<Option<(Adjustment, Vec<glib::SignalHandlerId>)>>,
+}
+
+/// Spell checking state of each source view
+const SPELL_STATE: ObjectState<Rc<SpellState>> = ObjectState::new("spell-state");
+
+thread_local! {
+    // Dictionary of the selected language, loaded on first use; None when it isn't installed
+    static DICTIONARY: RefCell<Option<(String, Option<Arc<Dictionary>>)>> = RefCell::new(None);
+    // Words added with "Add to Dictionary", loaded from the config directory on first use
+    static PERSONAL_WORDS: RefCell<Option<HashSet<String>>> = RefCell::new(None);
+    // Words ignored with "Ignore All" until the editor is closed
+    static IGNORED_WORDS: RefCell<HashSet<String>> = RefCell::new(HashSet::new());
+}
+
+/// Checks whether spell checking is turned on in the settings
+pub fn is_enabled() -> bool {
+    crate::settings::get_settings().get_bool("spell_check", false)
+}
+
+/// Returns the spelling language: the one chosen in the settings, else the locale's, else en_US
+pub fn selected_language() -> String {
+    let chosen = crate::settings::get_settings().get("spell_check.language").cloned().unwrap_or_default();
+    if !chosen.is_empty() {
+        return chosen;
+    }
+    let available = dictionary::available_languages();
+    dictionary::locale_language()
+        .filter(|language| available.contains(language))
+        .or_else(|| available.iter().find(|language| language.as_str() == FALLBACK_LANGUAGE).cloned())
+        .or_else(|| available.first().cloned())
+        .unwrap_or_else(|| FALLBACK_LANGUAGE.to_string())
+}
+
+/// Returns the dictionary of the selected language, loading it when the language changed
+fn current_dictionary() -> Option<Arc<Dictionary>> {
+    let language = selected_language();
+    DICTIONARY.with(|cached| {
+        let mut cached = cached.borrow_mut();
+        if let Some((cached_language, dictionary)) = cached.as_ref() {
+            if *cached_language == language {
+                return dictionary.clone();
+            }
+        }
+        let dictionary = Dictionary::load(&language).map(Arc::new);
+        if dictionary.is_none() {
+            eprintln!("No {} dictionary found; install a Hunspell dictionary or put one in the config directory", language);
+        }
+        *cached = Some((language, dictionary.clone()));
+        dictionary
+    })
+}
+
+/// Returns the path of the personal dictionary in the config directory
+pub fn personal_dictionary_path() -> PathBuf {
+    crate::settings::get_config_dir().join("personal_dictionary.txt")
+}
+
+/// Runs a closure with the personal dictionary, loading it on first use
+fn with_personal_words<T>(f: impl FnOnce(&mut HashSet<String>) -> T) -> T {
+    PERSONAL_WORDS.with(|words| {
+        let mut words = words.borrow_mut();
+        let words = words.get_or_insert_with(|| {
+            fs::read_to_string(personal_dictionary_path())
+                .map(|contents| contents.lines().map(str::trim).filter(|word| !word.is_empty()).map(str::to_string).collect())
+                .unwrap_or_default()
+        });
+        f(words)
+    })
+}
+
+/// Adds a word to the personal dictionary file and re-checks every view
+fn add_to_personal_dictionary(word: &str) {
+    with_personal_words(|words| words.insert(word.to_string()));
+    let result = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(personal_dictionary_path())
+        .and_then(|mut file| writeln!(file, "{}", word));
+    if let Err(e) = result {
+        eprintln!("Failed to save the personal dictionary: {}", e);
+    }
+    recheck_all_views();
+}
+
+/// Checks a word against the dictionary, the personal dictionary and the ignored words
+fn is_known(dictionary: &Dictionary, word: &str) -> bool {
+    if dictionary.check(word) || IGNORED_WORDS.with(|ignored| ignored.borrow().contains(word)) {
+        return true;
+    }
+    with_personal_words(|words| words.contains(word) || words.contains(&word.to_lowercase()))
+}
+
+/// Checks whether a character can be part of a word
+fn is_word_char(character: char) -> bool {
+    character.is_alphabetic() || character == '\'' || character == '’'
+}
+
+/// Splits text into the words worth checking, as (start, end) character offsets
+///
+/// Leaves out words that look like code rather than prose: identifiers with
+/// underscores, digits or inner capitals, escapes and tags (`\n`, `@param`,
+/// `#include`), file names and URLs.
+fn words_to_check(text: &str) -> Vec<(i32, i32, String)> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut words = Vec::new();
+    let mut index = 0;
+    while index < chars.len() {
+        if !is_word_char(chars[index]) {
+            index += 1;
+            continue;
+        }
+        let mut start = index;
+        let mut end = index;
+        while end < chars.len() && is_word_char(chars[end]) {
+            end += 1;
+        }
+        index = end;
+
+        let previous = start.checked_sub(1).map(|previous| chars[previous]);
+        let next = chars.get(end).copied();
+        let after_next = chars.get(end + 1).copied();
+
+        // Quotes around a word are not part of it
+        while start < end && !chars[start].is_alphabetic() {
+            start += 1;
+        }
+        let mut word_end = end;
+        while word_end > start && !chars[word_end - 1].is_alphabetic() {
+            word_end -= 1;
+        }
+        if word_end - start < 2 {
+            continue;
+        }
+
+        let word: String = chars[start..word_end].iter().collect();
+        let looks_like_code = word.chars().skip(1).any(|character| character.is_uppercase())
+            || previous.is_some_and(|c| c.is_ascii_digit() || "_\\@#$%&/~".contains(c))
+            || (previous == Some('.') && start >= 2 && chars[start - 2].is_alphanumeric())
+            || next.is_some_and(|c| c.is_ascii_digit() || c == '_' || c == '/')
+            || (next == Some('.') && after_next.is_some_and(|c| c.is_alphanumeric()))
+            || (next == Some(':') && after_next == Some('/'));
+        if !looks_like_code {
+            words.push((start as i32, word_end as i32, word));
+        }
+    }
+    words
+}
+
+/// Checks whether the words at an iterator should be checked in this buffer
+///
+/// Prose buffers are checked everywhere but code blocks and URLs; code only in
+/// comments and strings. Both rely on the highlighting's context classes.
+fn is_checked_region(buffer: &Buffer, iter: &TextIter, is_prose: bool) -> bool {
+    if buffer.iter_has_context_class(iter, "no-spell-check") {
+        return false;
+    }
+    is_prose || buffer.iter_has_context_class(iter, "comment") || buffer.iter_has_context_class(iter, "string")
+}
+
+/// Checks whether a buffer holds prose, like plain text and Markdown
+fn is_prose_buffer(buffer: &Buffer) -> bool {
+    match buffer.language() {
+        Some(language) => PROSE_LANGUAGES.contains(&language.id().as_str()),
+        None => true,
+    }
+}
+
+/// Returns the lines worth checking in a view: the visible ones and a screenful either side
+///
+/// The rest of the buffer is checked as it scrolls into view, so underlines left
+/// outside this range by edits elsewhere are fixed before they are seen.
+fn checked_range(view: &View) -> (TextIter, TextIter) {
+    let visible = view.visible_rect();
+    let (mut start, _) = view.line_at_y(visible.y() - visible.height());
+    let (mut end, _) = view.line_at_y(visible.y() + 2 * visible.height());
+    start.set_line_offset(0);
+    if !end.ends_line() {
+        end.forward_to_line_end();
+    }
+    (start, end)
+}
+
+/// Underlines the misspelled words around the visible part of a view, clearing the old underlines there
+fn check_view(state: &SpellState) {
+    let Some(view) = state.view.upgrade() else {
+        return;
+    };
+    let Ok(buffer) = view.buffer().downcast::<Buffer>() else {
+        return;
+    };
+    let dictionary = if is_enabled() { current_dictionary() } else { None };
+    let Some(dictionary) = dictionary else {
+        let (start, end) = buffer.bounds();
+        buffer.remove_tag(&state.tag, &start, &end);
+        return;
+    };
+
+    let (start, end) = checked_range(&view);
+    buffer.remove_tag(&state.tag, &start, &end);
+    // Context classes are only known once the text is highlighted
+    buffer.ensure_highlight(&start, &end);
+    let is_prose = is_prose_buffer(&buffer);
+    let offset = start.offset();
+    let text = buffer.text(&start, &end, true);
+    for (word_start, word_end, word) in words_to_check(&text) {
+        let start_iter = buffer.iter_at_offset(offset + word_start);
+        if !is_checked_region(&buffer, &start_iter, is_prose) || is_known(&dictionary, &word) {
+            continue;
+        }
+        buffer.apply_tag(&state.tag, &start_iter, &buffer.iter_at_offset(offset + word_end));
+    }
+}
+
+/// Checks a view's buffer after a short delay, so typing isn't slowed down
+fn schedule_check(state: &Rc<SpellState>) {
+    let generation = state.generation.get() + 1;
+    state.generation.set(generation);
+
+    let weak_state = Rc::downgrade(state);
+    glib::timeout_add_local_once(CHECK_DELAY, move || {
+        let Some(state) = weak_state.upgrade() else {
+            return;
+        };
+        if state.generation.get() == generation {
+            check_view(&state);
+        }
+    });
+}
+
+/// Checks a view again whenever it scrolls or is resized, following its current scroll adjustment
+fn follow_scrolling(state: &Rc<SpellState>, view: &View) {
+    if let Some((adjustment, handlers)) = state.scrolling.borrow_mut().take() {
+        for handler in handlers {
+            adjustment.disconnect(handler);
+        }
+    }
+    let Some(adjustment) = view.vadjustment() else {
+        return;
+    };
+
+    let weak_state = Rc::downgrade(state);
+    let scrolled = adjustment.connect_value_changed(move |_| {
+        if let Some(state) = weak_state.upgrade() {
+            schedule_check(&state);
+        }
+    });
+    // The page size changes when the view is shown or resized
+    let weak_state = Rc::downgrade(state);
+    let resized = adjustment.connect_changed(move |_| {
+        if let Some(state) = weak_state.upgrade() {
+            schedule_check(&state);
+        }
+    });
+    *state.scrolling.borrow_mut() = Some((adjustment, vec![scrolled, resized]));
+}
+
+/// Re-checks every view, after the personal dictionary or the ignored words changed
+fn recheck_all_views() {
+    fn recheck_below(widget: &gtk4::Widget) {
+        if let Some(state) = widget.downcast_ref::<View>().and_then(|view| SPELL_STATE.get(view)) {
+            schedule_check(&state);
+        }
+        let mut child = widget.first_child();
+        while let Some(current_child) = child {
+            recheck_below(&current_child);
+            child = current_child.next_sibling();
+        }
+    }
+
+    for window in gtk4::Window::list_toplevels() {
+        recheck_below(&window);
+    }
+}
+
+/// Returns the bounds of the word around an iterator
+fn word_bounds(iter: &TextIter) -> (TextIter, TextIter) {
+    let mut start = iter.clone();
+    while start.backward_char() {
+        if !is_word_char(start.char()) {
+            start.forward_char();
+            break;
+        }
+    }
+    let mut end = iter.clone();
+    while !end.is_end() && is_word_char(end.char()) {
+        end.forward_char();
+    }
+    // Quotes around a word are not part of it
+    while start < end && !start.char().is_alphabetic() {
+        start.forward_char();
+    }
+    while end > start {
+        let mut last = end.clone();
+        last.backward_char();
+        if last.char().is_alphabetic() {
+            break;
+        }
+        end = last;
+    }
+    (start, end)
+}
+
+/// Fills the context menu section with corrections for the misspelled word at an iterator
+///
+/// The section is left empty when the word there is spelled correctly. Suggestions
+/// are looked up on a worker thread and replace a placeholder once found.
+fn update_menu(state: &Rc<SpellState>, buffer: &TextBuffer, iter: &TextIter) {
+    let request = state.menu_request.get() + 1;
+    state.menu_request.set(request);
+    state.menu_section.remove_all();
+    if let Some((start, end)) = state.menu_word.borrow_mut().take() {
+        buffer.delete_mark(&start);
+        buffer.delete_mark(&end);
+    }
+
+    let (start, end) = word_bounds(iter);
+    if start == end || !start.has_tag(&state.tag) {
+        return;
+    }
+    let Some(dictionary) = current_dictionary() else {
+        return;
+    };
+    let word = buffer.text(&start, &end, false).to_string();
+
+    state.menu_section.append(Some("Looking for Suggestions…"), None);
+    state.menu_section.append(Some(&format!("Add “{}” to Dictionary", word)), Some("spell.add-word"));
+    state.menu_section.append(Some("Ignore All"), Some("spell.ignore-word"));
+    *state.menu_word.borrow_mut() = Some((buffer.create_mark(None, &start, true), buffer.create_mark(None, &end, false)));
+
+    // Trying the edits of a long word takes a while, so it is kept off the main thread
+    let personal_words = with_personal_words(|words| words.clone());
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = sender.send(dictionary.suggest(&word, |candidate| personal_words.contains(candidate)));
+    });
+
+    let weak_state = Rc::downgrade(state);
+    glib::timeout_add_local(SUGGESTIONS_POLL, move || {
+        let Some(state) = weak_state.upgrade() else {
+            return glib::ControlFlow::Break;
+        };
+        if state.menu_request.get() != request {
+            return glib::ControlFlow::Break; // The menu moved on to another word
+        }
+        match receiver.try_recv() {
+            Ok(suggestions) => {
+                show_suggestions(&state, suggestions);
+                glib::ControlFlow::Break
+            }
+            Err(mpsc::TryRecvError::Empty) => glib::ControlFlow::Continue,
+            Err(mpsc::TryRecvError::Disconnected) => glib::ControlFlow::Break,
+        }
+    });
+}
+
+/// Replaces the context menu's placeholder with the suggestions found
+fn show_suggestions(state: &SpellState, suggestions: Vec<String>) {
+    state.menu_section.remove(0);
+    if suggestions.is_empty() {
+        state.menu_section.insert(0, Some("No Suggestions"), None);
+    }
+    for (index, suggestion) in suggestions.iter().enumerate() {
+        let item = gio::MenuItem::new(Some(suggestion), None);
+        item.set_action_and_target_value(Some("spell.replace"), Some(&suggestion.to_variant()));
+        state.menu_section.insert_item(index as i32, &item);
+    }
+}
+
+/// Returns the word the context menu is about, with its bounds
+fn menu_word(state: &SpellState, buffer: &TextBuffer) -> Option<(TextIter, TextIter, String)> {
+    let menu_word = state.menu_word.borrow();
+    let (start, end) = menu_word.as_ref()?;
+    let start = buffer.iter_at_mark(start);
+    let end = buffer.iter_at_mark(end);
+    let word = buffer.text(&start, &end, false).to_string();
+    Some((start, end, word))
+}
+
+/// Creates the "spell" actions run from the context menu
+fn create_spell_actions(state: &Rc<SpellState>) -> gio::SimpleActionGroup {
+    let group = gio::SimpleActionGroup::new();
+
+    let replace = gio::SimpleAction::new("replace", Some(glib::VariantTy::STRING));
+    let weak_state = Rc::downgrade(state);
+    replace.connect_activate(move |_, parameter| {
+        let (Some(state), Some(replacement)) = (weak_state.upgrade(), parameter.and_then(|p| p.get::<String>())) else {
+            return;
+        };
+        let Some(view) = state.view.upgrade() else {
+            return;
+        };
+        let buffer = view.buffer();
+        if let Some((mut start, mut end, _)) = menu_word(&state, &buffer) {
+            buffer.begin_user_action();
+            buffer.delete(&mut start, &mut end);
+            buffer.insert(&mut start, &replacement);
+            buffer.end_user_action();
+        }
+    });
+    group.add_action(&replace);
+
+    let add_word = gio::SimpleAction::new("add-word", None);
+    let weak_state = Rc::downgrade(state);
+    add_word.connect_activate(move |_, _| {
+        let Some(state) = weak_state.upgrade() else {
+            return;
+        };
+        if let Some((_, _, word)) = state.view.upgrade().and_then(|view| menu_word(&state, &view.buffer())) {
+            add_to_personal_dictionary(&word);
+        }
+    });
+    group.add_action(&add_word);
+
+    let ignore_word = gio::SimpleAction::new("ignore-word", None);
+    let weak_state = Rc::downgrade(state);
+    ignore_word.connect_activate(move |_, _| {
+        let Some(state) = weak_state.upgrade() else {
+            return;
+        };
+        if let Some((_, _, word)) = state.view.upgrade().and_then(|view| menu_word(&state, &view.buffer())) {
+            IGNORED_WORDS.with(|ignored| ignored.borrow_mut().insert(word));
+            recheck_all_views();
+        }
+    });
+    group.add_action(&ignore_word);
+
+    group
+}
+
+/// Adds spell checking to a source view
+///
+/// Misspelled words get a red wavy underline, and the context menu of a misspelled
+/// word offers corrections, "Add to Dictionary" and "Ignore All". Nothing is checked
+/// until spell checking is turned on in the settings.
+pub fn attach(view: &View) {
+    let buffer = view.buffer();
+    let tag = TextTag::builder()
+        .underline(pango::Underline::Error)
+        .underline_rgba(&gdk::RGBA::new(0.88, 0.11, 0.14, 1.0))
+        .build();
+    buffer.tag_table().add(&tag);
+
+    let state = Rc::new(SpellState {
+        view: view.downgrade(),
+        tag,
+        generation: Cell::new(0),
+        menu_section: gio::Menu::new(),
+        menu_word: RefCell::new(None),
+        menu_request: Cell::new(0),
+        scrolling: RefCell::new(None),
+    });
+
+    // Re-check after edits and when the file type, and so the checked regions, change
+    let weak_state = Rc::downgrade(&state);
+    buffer.connect_changed(move |_| {
+        if let Some(state) = weak_state.upgrade() {
+            schedule_check(&state);
+        }
+    });
+    let weak_state = Rc::downgrade(&state);
+    buffer.connect_notify_local(Some("language"), move |_, _| {
+        if let Some(state) = weak_state.upgrade() {
+            schedule_check(&state);
+        }
+    });
+
+    // Only the visible lines are checked, so check again as others come into view
+    follow_scrolling(&state, view);
+    let weak_state = Rc::downgrade(&state);
+    view.connect_vadjustment_notify(move |view| {
+        if let Some(state) = weak_state.upgrade() {
+            follow_scrolling(&state, view);
+        }
+    });
+
+    // Prepare the corrections before the context menu opens, for a right click or the menu keys
+    let click = GestureClick::new();
+    click.set_button(gdk::BUTTON_SECONDARY);
+    click.set_propagation_phase(gtk4::PropagationPhase::Capture);
+    let state_for_click = state.clone();
+    click.connect_pressed(move |_, _, x, y| {
+        let Some(view) = state_for_click.view.upgrade() else {
+            return;
+        };
+        let (buffer_x, buffer_y) = view.window_to_buffer_coords(TextWindowType::Widget, x as i32, y as i32);
+        if let Some(iter) = view.iter_at_location(buffer_x, buffer_y) {
+            update_menu(&state_for_click, &view.buffer(), &iter);
+        }
+    });
+    view.add_controller(click);
+
+    let key_controller = EventControllerKey::new();
+    key_controller.set_propagation_phase(gtk4::PropagationPhase::Capture);
+    let weak_state = Rc::downgrade(&state);
+    key_controller.connect_key_pressed(move |_, keyval, _, modifiers| {
+        let is_menu_key = keyval == gdk::Key::Menu
+            || (keyval == gdk::Key::F10 && modifiers.contains(gdk::ModifierType::SHIFT_MASK));
+        if let (true, Some(state)) = (is_menu_key, weak_state.upgrade()) {
+            if let Some(view) = state.view.upgrade() {
+                let buffer = view.buffer();
+                update_menu(&state, &buffer, &buffer.iter_at_mark(&buffer.get_insert()));
+            }
+        }
+        glib::Propagation::Proceed
+    });
+    view.add_controller(key_controller);
+
+    view.insert_action_group("spell", Some(&create_spell_actions(&state)));
+    crate::syntax::add_context_menu_section(view, &state.menu_section);
+
+    schedule_check(&state);
+    SPELL_STATE.set(view, state);
+}
+
+/// Re-checks a view after the spell checking settings changed
+pub fn apply_spell_check_settings(view: &View) {
+    if let Some(state) = SPELL_STATE.get(view) {
+        schedule_check(&state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(text: &str) -> Vec<String> {
+        words_to_check(text).into_iter().map(|(_, _, word)| word).collect()
+    }
+
+    #[test]
+    fn quotes_around_words_are_left_out() {
+        assert_eq!(words("It's 'quoted' “text”, don’t."), vec!["It's", "quoted", "text", "don’t"]);
+    }
+
+    #[test]
+    fn code_like_words_are_skipped() {
+        let text = "see main.rs and http://exmple.com @param foo_bar camelCase \\nfoo x2y #include ~/dir";
+        assert_eq!(words(text), vec!["see", "and"]);
+    }
+
+    #[test]
+    fn single_letters_are_skipped() {
+        assert_eq!(words("a b cd"), vec!["cd"]);
+    }
+
+    #[test]
+    fn offsets_count_characters() {
+        assert_eq!(words_to_check("café naïve"), vec![(0, 4, "café".to_string()), (5, 10, "naïve".to_string())]);
+    }
+}
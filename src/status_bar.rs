@@ -0,0 +1,136 @@
+// Status bar updates for the text editor
+// Keeps the indentation, cursor position and selection length of the active tab up to date
+
+use gtk4::prelude::*;
+use gtk4::{Label, MenuButton, Notebook, TextBuffer};
+use sourceview5::View;
+
+use crate::handlers;
+
+/// Formats the cursor position and selection length of a buffer
+///
+/// Lines and columns are shown one-based, matching the Go to Line dialog.
+pub fn format_cursor_position(buffer: &TextBuffer) -> String {
+    let cursor = buffer.iter_at_mark(&buffer.get_insert());
+    let mut text = format!("Ln {}, Col {}", cursor.line() + 1, cursor.line_offset() + 1);
+
+    if let Some((start, end)) = buffer.selection_bounds() {
+        let selected_chars = end.offset() - start.offset();
+        let selected_lines = end.line() - start.line() + 1;
+        if selected_lines > 1 {
+            text.push_str(&format!(" ({} selected, {} lines)", selected_chars, selected_lines));
+        } else {
+            text.push_str(&format!(" ({} selected)", selected_chars));
+        }
+    }
+
+    text
+}
+
+/// Refreshes the position label from the active tab
+///
+/// Tabs without a text view (images, unsupported files) clear the label.
+pub fn update_position_label(notebook: &Notebook, position_label: &Label) {
+    match handlers::get_active_text_view_and_buffer(notebook) {
+        Some((_, buffer)) => position_label.set_text(&format_cursor_position(&buffer)),
+        None => position_label.set_text(""),
+    }
+}
+
+/// Shows the indentation style of a view on the status bar button
+///
+/// Tabs without a source view hide the button.
+pub fn update_indentation_button(text_view: Option<&gtk4::TextView>, indentation_button: &MenuButton) {
+    match text_view.and_then(|view| view.downcast_ref::<View>()) {
+        Some(view) => {
+            indentation_button.set_label(&crate::indentation::current_indent_style(view).label());
+            indentation_button.set_visible(true);
+        }
+        None => indentation_button.set_visible(false),
+    }
+}
+
+/// Connects the status bar to the editor notebook
+///
+/// Every text tab added to the notebook updates the label when its cursor or
+/// selection moves and the indentation button when its indentation changes.
+/// Switching tabs shows the position and indentation of the new tab.
+pub fn connect_notebook(notebook: &Notebook, position_label: &Label, indentation_button: &MenuButton) {
+    // Track cursor, selection and indentation changes in each text tab
+    let notebook_for_added = notebook.clone();
+    let position_label_for_added = position_label.clone();
+    let indentation_button_for_added = indentation_button.clone();
+    notebook.connect_page_added(move |_, page, _| {
+        if let Some(text_view) = handlers::find_text_view_in_page(page) {
+            connect_buffer(&text_view.buffer(), &notebook_for_added, &position_label_for_added);
+            connect_view(&text_view, &notebook_for_added, &indentation_button_for_added);
+        }
+    });
+
+    // Tabs that already exist (e.g. the initial untitled tab)
+    for page_num in 0..notebook.n_pages() {
+        if let Some(page) = notebook.nth_page(Some(page_num)) {
+            if let Some(text_view) = handlers::find_text_view_in_page(&page) {
+                connect_buffer(&text_view.buffer(), notebook, position_label);
+                connect_view(&text_view, notebook, indentation_button);
+            }
+        }
+    }
+
+    // Show the position and indentation of the newly selected tab
+    let position_label_for_switch = position_label.clone();
+    let indentation_button_for_switch = indentation_button.clone();
+    notebook.connect_switch_page(move |_, page, _| {
+        let text_view = handlers::find_text_view_in_page(page);
+        match &text_view {
+            Some(text_view) => position_label_for_switch.set_text(&format_cursor_position(&text_view.buffer())),
+            None => position_label_for_switch.set_text(""),
+        }
+        update_indentation_button(text_view.as_ref(), &indentation_button_for_switch);
+    });
+
+    update_position_label(notebook, position_label);
+    let active_view = handlers::get_active_text_view_and_buffer(notebook).map(|(text_view, _)| text_view);
+    update_indentation_button(active_view.as_ref(), indentation_button);
+}
+
+/// Updates the indentation button whenever the indentation settings of a view change
+fn connect_view(text_view: &gtk4::TextView, notebook: &Notebook, indentation_button: &MenuButton) {
+    for property in ["tab-width", "indent-width", "insert-spaces-instead-of-tabs"] {
+        let notebook = notebook.clone();
+        let indentation_button = indentation_button.clone();
+        text_view.connect_notify_local(Some(property), move |text_view, _| {
+            if is_active_buffer(&notebook, &text_view.buffer()) {
+                update_indentation_button(Some(text_view), &indentation_button);
+            }
+        });
+    }
+}
+
+/// Updates the label whenever the cursor or selection of a buffer changes
+fn connect_buffer(buffer: &TextBuffer, notebook: &Notebook, position_label: &Label) {
+    // The cursor moved (typing, arrow keys, clicks)
+    let notebook_for_cursor = notebook.clone();
+    let position_label_for_cursor = position_label.clone();
+    buffer.connect_notify_local(Some("cursor-position"), move |buffer, _| {
+        if is_active_buffer(&notebook_for_cursor, buffer) {
+            position_label_for_cursor.set_text(&format_cursor_position(buffer));
+        }
+    });
+
+    // The selection bound moved without the cursor (e.g. select all, shift+click)
+    let notebook_for_mark = notebook.clone();
+    let position_label_for_mark = position_label.clone();
+    buffer.connect_mark_set(move |buffer, _, mark| {
+        if mark.name().as_deref() == Some("selection_bound") && is_active_buffer(&notebook_for_mark, buffer) {
+            position_label_for_mark.set_text(&format_cursor_position(buffer));
+        }
+    });
+}
+
+/// Checks whether a buffer belongs to the currently selected tab
+fn is_active_buffer(notebook: &Notebook, buffer: &TextBuffer) -> bool {
+    handlers::get_active_text_view_and_buffer(notebook)
+        .map(|(_, active_buffer)| &active_buffer == buffer)
+        .unwrap_or(false)
+}
@@ -1,10 +1,12 @@
 // Syntax highlighting functionality for the text editor
 // This module manages syntax highlighting based on file types
 
-use sourceview5::{prelude::*, Buffer, LanguageManager, StyleSchemeManager, View};
+use sourceview5::{prelude::*, Buffer, LanguageManager, Map, StyleSchemeManager, View};
 use gtk4::ScrolledWindow;
 use gtk4::Settings;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+use crate::utils::ObjectState;
 
 /// Determines whether the system is using a dark theme
 /// 
@@ -195,12 +197,71 @@ pub fn create_source_view() -> (View, Buffer) {
     source_view.set_cursor_visible(true);
     source_view.set_show_line_numbers(true);
     source_view.set_highlight_current_line(true);
-    source_view.set_tab_width(4);
     source_view.set_auto_indent(true);
     
+    // Default indentation; files opened from disk get their own detected style
+    crate::indentation::apply_indent_style(&source_view, crate::indentation::IndentStyle::default());
+    
+    // Log edits for the undo history kept between sessions
+    crate::undo_history::attach(&source_view);
+    
+    // Vim keybindings, when enabled; attached first so Vim sees keys before any shortcut
+    crate::vim::attach(&source_view);
+    crate::vim::apply_vim_settings(&source_view);
+    
+    // Record and play keyboard macros; attached early so it sees every key press
+    crate::macros::attach(&source_view);
+    
+    // Emacs keybindings, when enabled; after macros so recordings include Emacs keys
+    crate::emacs::attach(&source_view);
+    crate::emacs::apply_emacs_settings(&source_view);
+    
+    // Enable multiple carets (Ctrl+D, Ctrl+Alt+Up/Down, Alt+click)
+    crate::multi_cursor::attach(&source_view);
+    
+    // Show fold markers in the gutter and enable the fold commands
+    crate::folding::attach(&source_view);
+    
+    // Auto-close pairs, surround selections and bracket navigation (Ctrl+M)
+    crate::brackets::attach(&source_view);
+    
+    // Toggle line (Ctrl+/) and block (Ctrl+Shift+/) comments
+    crate::comments::attach(&source_view);
+    
+    // Visible whitespace and indentation guides, as configured
+    crate::whitespace::apply_whitespace_settings(&source_view);
+    
+    // Wrap long lines as configured for the file type, Alt+Z toggles
+    crate::wrap::attach(&source_view);
+    
+    // Word, path and keyword completion (Ctrl+Space)
+    crate::completion::attach(&source_view);
+    
+    // Expand snippets with Tab
+    crate::snippets::attach(&source_view);
+    
+    // Duplicate, delete, move, join and sort lines
+    crate::line_commands::attach(&source_view);
+    
+    // Underline misspelled words, when enabled, with corrections in the context menu
+    crate::spell_check::attach(&source_view);
+    
     (source_view, buffer)
 }
 
+/// Adds a section to a source view's context menu, after the sections added so far
+///
+/// Features attached in `create_source_view` each add their entries this way, so
+/// they don't replace each other's menus.
+pub fn add_context_menu_section(view: &View, section: &gtk4::gio::Menu) {
+    let extra_menu = view
+        .extra_menu()
+        .and_then(|menu| menu.downcast::<gtk4::gio::Menu>().ok())
+        .unwrap_or_else(gtk4::gio::Menu::new);
+    extra_menu.append_section(None, section);
+    view.set_extra_menu(Some(&extra_menu));
+}
+
 /// Updates the style scheme of an existing buffer based on user theme preference
 /// 
 /// This function can be called when the system theme changes to update
@@ -239,6 +300,7 @@ pub fn update_buffer_style_scheme(buffer: &Buffer) {
 /// and applies appropriate syntax highlighting to the buffer.
 pub fn set_language_for_file(buffer: &Buffer, file_path: &Path) -> bool {
     let language_manager = LanguageManager::new();
+    set_buffer_file(buffer, file_path);
     
     // Get the file extension
     let extension = file_path.extension()
@@ -295,6 +357,37 @@ pub fn set_language_for_file(buffer: &Buffer, file_path: &Path) -> bool {
     false
 }
 
+/// File each buffer was opened from, for features that depend on its location or type
+const BUFFER_FILE: ObjectState<PathBuf> = ObjectState::new("buffer-file");
+
+/// Records the file a buffer was opened from
+fn set_buffer_file(buffer: &Buffer, file_path: &Path) {
+    BUFFER_FILE.set(buffer, file_path.to_path_buf());
+}
+
+/// Returns the file a buffer was opened from (None for untitled buffers)
+pub fn buffer_file_path(buffer: &Buffer) -> Option<PathBuf> {
+    BUFFER_FILE.get(buffer)
+}
+
+/// Returns the key used for per-language settings of a buffer
+///
+/// This is the language id when the buffer has one, otherwise the extension (or name)
+/// of the file it was opened from, e.g. "conf" or "makefile". Untitled plain-text
+/// buffers use "text".
+pub fn language_key(buffer: &Buffer) -> String {
+    if let Some(language) = buffer.language() {
+        return language.id().to_string();
+    }
+    buffer_file_path(buffer)
+        .and_then(|path| {
+            path.extension()
+                .or_else(|| path.file_name())
+                .map(|key| key.to_string_lossy().to_lowercase())
+        })
+        .unwrap_or_else(|| "text".to_string())
+}
+
 /// Wraps a SourceView in a ScrolledWindow
 ///
 /// This function creates a scrollable container for the sourceview,
@@ -307,6 +400,30 @@ pub fn create_source_view_scrolled(source_view: &View) -> ScrolledWindow {
         .build()
 }
 
+/// Creates a minimap for a SourceView
+///
+/// The minimap shares the view's buffer, so it shows the same syntax highlighting
+/// and style scheme in a zoomed-out form. Clicking or dragging in it scrolls the view.
+/// It is only visible when enabled in the settings.
+pub fn create_minimap(source_view: &View) -> Map {
+    let map = Map::new();
+    map.set_view(source_view);
+    map.add_css_class("minimap");
+    update_minimap(&map);
+    map
+}
+
+/// Applies the minimap setting to an existing minimap
+///
+/// Called alongside `update_buffer_style_scheme` when settings or themes change.
+pub fn update_minimap(map: &Map) {
+    let show_minimap = crate::settings::get_settings().get_show_minimap();
+    map.set_visible(show_minimap);
+    if show_minimap {
+        map.queue_draw();
+    }
+}
+
 /// Debug function to print current theme detection status
 /// Useful for troubleshooting theme switching issues
 pub fn debug_theme_detection() {
@@ -204,12 +204,12 @@ pub fn create_text_view() -> (
     let text_view = source_view.clone().upcast::<TextView>();
     let buffer = source_buffer.upcast::<TextBuffer>();
 
-    // Place the source view in a scrolled window
-    let scrolled_window = syntax::create_source_view_scrolled(&source_view);
+    // Place the source view in an editor page (find bar above a scrolled window)
+    let (editor_page, scrolled_window) = create_editor_page(&source_view);
 
-    // Add the scrolled window as a page in the notebook with our custom tab widget
-    editor_notebook.append_page(&scrolled_window, Some(&tab_widget));
-    editor_notebook.set_tab_label(&scrolled_window, Some(&tab_widget));
+    // Add the editor page as a page in the notebook with our custom tab widget
+    editor_notebook.append_page(&editor_page, Some(&tab_widget));
+    editor_notebook.set_tab_label(&editor_page, Some(&tab_widget));
 
     // Initialize shared state objects
     let file_path = Rc::new(RefCell::new(None)); // No file associated with initial tab
@@ -235,6 +235,50 @@ pub fn create_text_view() -> (
     )
 }
 
+/// Creates the notebook page that hosts a source view
+///
+/// Each editor tab is a vertical box holding the find/replace bar (hidden until
+/// Ctrl+F or Ctrl+H) and the mixed indentation banner above the scrolled source
+/// view, its indentation guides and its optional minimap.
+///
+/// Returns a tuple of:
+/// - GtkBox: The page container to add to the editor notebook
+/// - ScrolledWindow: The scrollable container wrapping the source view
+pub fn create_editor_page(source_view: &sourceview5::View) -> (GtkBox, ScrolledWindow) {
+    let page = GtkBox::new(Orientation::Vertical, 0);
+    page.add_css_class("editor-page");
+
+    // The find bar sits above the view and slides down when opened
+    let search_bar = crate::search::create_search_bar(source_view);
+    page.append(&search_bar);
+
+    // Warning shown when a loaded file mixes tab and space indentation
+    let mixed_indent_banner = crate::whitespace::create_mixed_indent_banner(source_view);
+    page.append(&mixed_indent_banner);
+
+    // The scrolled view takes all remaining space, with the optional minimap on its right
+    let editor_row = GtkBox::new(Orientation::Horizontal, 0);
+    editor_row.set_vexpand(true);
+    
+    let scrolled_window = syntax::create_source_view_scrolled(source_view);
+    scrolled_window.set_vexpand(true);
+    scrolled_window.set_hexpand(true);
+    
+    // Indentation guides and margin rulers are drawn on layers above the scrolled view
+    let view_overlay = gtk4::Overlay::new();
+    view_overlay.set_child(Some(&scrolled_window));
+    view_overlay.add_overlay(&crate::whitespace::create_indent_guides(source_view, &scrolled_window));
+    view_overlay.add_overlay(&crate::wrap::create_margin_rulers(source_view, &scrolled_window));
+    editor_row.append(&view_overlay);
+    
+    let minimap = syntax::create_minimap(source_view);
+    editor_row.append(&minimap);
+    
+    page.append(&editor_row);
+
+    (page, scrolled_window)
+}
+
 /// Creates and initializes a terminal emulator
 /// 
 /// This function creates a VTE terminal widget and spawns the user's default shell in it
@@ -316,19 +360,37 @@ pub fn create_file_manager_panel() -> (ListBox, ScrolledWindow) {
 
 /// Assembles the file manager panel from its components
 /// 
-/// Takes the file list and creates a single container
-pub fn create_file_manager_panel_container(file_list_scrolled_window: ScrolledWindow) -> GtkBox {
+/// Takes the file list and the Find in Files panel and places them in a stack,
+/// with a switcher on top to move between the two sidebar pages.
+/// Returns the panel container and the stack so other code can switch pages.
+pub fn create_file_manager_panel_container(
+    file_list_scrolled_window: ScrolledWindow,
+    find_in_files_panel: &GtkBox,
+) -> (GtkBox, gtk4::Stack) {
     // Create a vertical box to hold all file manager components
     let file_manager_panel = GtkBox::new(Orientation::Vertical, 5);
     file_manager_panel.add_css_class("file-manager-panel"); // Add CSS class for styling
     
-    // Add the scrollable file list
-    file_manager_panel.append(&file_list_scrolled_window);
+    // Stack holding the sidebar pages
+    let sidebar_stack = gtk4::Stack::new();
+    sidebar_stack.set_transition_type(gtk4::StackTransitionType::Crossfade);
+    sidebar_stack.set_vexpand(true);
+    sidebar_stack.add_titled(&file_list_scrolled_window, Some("files"), "Files");
+    sidebar_stack.add_titled(find_in_files_panel, Some(crate::find_in_files::SIDEBAR_PAGE_NAME), "Search");
+    sidebar_stack.set_visible_child_name("files");
+    
+    // Switcher to move between the file list and the search panel
+    let sidebar_switcher = gtk4::StackSwitcher::new();
+    sidebar_switcher.set_stack(Some(&sidebar_stack));
+    sidebar_switcher.set_halign(gtk4::Align::Center);
+    
+    file_manager_panel.append(&sidebar_switcher);
+    file_manager_panel.append(&sidebar_stack);
     
     // Make the panel expand vertically to use available space
     file_manager_panel.set_vexpand(true);
     
-    file_manager_panel
+    (file_manager_panel, sidebar_stack)
 }
 
 /// Creates the main application layout using paned containers
@@ -527,36 +589,54 @@ pub fn create_terminal_notebook_box(terminal_notebook: &Notebook, add_terminal_b
 
 /// Creates a status bar for the bottom of the application
 ///
-/// This function creates a status bar with a horizontal box to display the current directory path
-/// as a series of clickable buttons, one for each directory level
+/// This function creates a status bar with a button showing the indentation style
+/// of the active editor and a label on the right that shows the cursor position
+/// (line and column) and the selection length.
 /// 
 /// Returns a tuple of:
 /// - GtkBox: The status bar container
-/// - GtkBox: The path box that will contain individual path segment buttons
-#[allow(dead_code)]
-pub fn create_status_bar() -> (GtkBox, GtkBox) {
+/// - Label: The cursor position label, kept up to date by `status_bar::connect_notebook`
+/// - MenuButton: The indentation button, kept up to date by `status_bar::connect_notebook`
+pub fn create_status_bar() -> (GtkBox, Label, MenuButton) {
     // Create a horizontal box for the status bar
     let status_bar = GtkBox::new(Orientation::Horizontal, 5);
     status_bar.set_margin_start(10);
     status_bar.set_margin_end(10);
-    status_bar.set_margin_top(5);
-    status_bar.set_margin_bottom(5);
-    
-    // Create a horizontal box to hold the path segment buttons
-    let path_box = GtkBox::new(Orientation::Horizontal, 2);
-    path_box.set_halign(gtk4::Align::Start); // Align to the left
-    path_box.set_hexpand(true); // Use all available horizontal space
-    
-    // Add some styling to make the path box visually distinct
-    path_box.add_css_class("path-box");
-    
-    // Add the path box to the status bar
-    status_bar.append(&path_box);
+    status_bar.set_margin_top(2);
+    status_bar.set_margin_bottom(2);
+    
+    // Spacer pushing the status items to the right
+    let spacer = GtkBox::new(Orientation::Horizontal, 0);
+    spacer.set_hexpand(true);
+    status_bar.append(&spacer);
+    
+    // Shown while a keyboard macro is being recorded
+    status_bar.append(&crate::macros::create_status_label());
+    
+    // Vim mode of the focused editor, when Vim keybindings are enabled
+    status_bar.append(&crate::vim::create_mode_label());
+    
+    // Indentation style of the active tab, with a menu to override it
+    let indentation_button = MenuButton::new();
+    indentation_button.set_label("Spaces: 4");
+    indentation_button.set_menu_model(Some(&crate::indentation::create_indentation_menu()));
+    indentation_button.set_direction(gtk4::ArrowType::Up);
+    indentation_button.add_css_class("flat");
+    indentation_button.add_css_class("status-item");
+    indentation_button.set_tooltip_text(Some("Indentation"));
+    status_bar.append(&indentation_button);
+    
+    // Cursor position and selection length
+    let position_label = Label::new(None);
+    position_label.set_halign(gtk4::Align::End);
+    position_label.add_css_class("status-item");
+    position_label.set_tooltip_text(Some("Go to line (Ctrl+G)"));
+    status_bar.append(&position_label);
     
     // Add a CSS class for custom styling
     status_bar.add_css_class("basado-status-bar");
     
-    (status_bar, path_box)
+    (status_bar, position_label, indentation_button)
 }
 
 /// Creates a path bar for displaying the current directory path with navigation buttons
@@ -643,11 +723,12 @@ pub fn apply_custom_css() {
 /// Builds the complete CSS string by combining all component styles
 fn build_complete_css() -> String {
     format!(
-        "{}{}{}{}",
+        "{}{}{}{}{}",
         get_notebook_tab_styles(),
         get_button_styles(),
         get_status_bar_styles(),
-        get_path_navigation_styles()
+        get_path_navigation_styles(),
+        get_find_bar_styles()
     )
 }
 
@@ -755,6 +836,18 @@ fn get_status_bar_styles() -> &'static str {
         border-top: 1px solid alpha(#999, 0.3);
     }
     
+    .basado-status-bar .status-item {
+        font-size: 0.9em;
+        padding: 0 6px;
+    }
+    
+    /* === GO TO LINE STYLES === */
+    
+    .goto-line entry.error {
+        border-color: #e74c3c;
+        background-color: alpha(#e74c3c, 0.1);
+    }
+    
     /* === PATH BAR STYLES === */
     
     .basado-path-bar {
@@ -896,6 +989,52 @@ fn get_path_navigation_styles() -> &'static str {
     "
 }
 
+/// Returns CSS styles for the in-buffer find/replace bar
+fn get_find_bar_styles() -> &'static str {
+    "
+    /* === FIND BAR STYLES === */
+    
+    .find-bar {
+        padding: 4px 6px;
+        background-color: shade(@theme_bg_color, 0.97);
+        border-bottom: 1px solid alpha(#999, 0.3);
+    }
+    
+    .find-bar entry.error {
+        border-color: #e74c3c;
+        background-color: alpha(#e74c3c, 0.1);
+    }
+    
+    .minimap {
+        border-left: 1px solid alpha(#999, 0.3);
+    }
+    
+    .fold-markers {
+        color: alpha(currentColor, 0.6);
+    }
+    
+    .mixed-indent-banner {
+        padding: 4px 8px;
+        background-color: alpha(#f39c12, 0.2);
+        border-bottom: 1px solid alpha(#f39c12, 0.5);
+    }
+    
+    button.find-option {
+        padding: 2px 6px;
+        min-width: 20px;
+        font-family: monospace;
+    }
+    
+    .find-in-files-results row.find-in-files-file {
+        padding-top: 6px;
+    }
+    
+    .find-in-files-results row label {
+        font-family: monospace;
+    }
+    "
+}
+
 /// Sets up the terminal color theme to match the editor's syntax highlighting theme
 ///
 /// This function configures the VTE terminal colors to match the editor's color scheme
@@ -1134,6 +1273,192 @@ pub fn create_settings_dialog(parent: &ApplicationWindow) -> Dialog {
     settings_box.append(&light_theme_box.0);
     settings_box.append(&dark_theme_box.0);
     
+    // Create a section for editor display options
+    let editor_label = Label::new(Some("Editor"));
+    editor_label.set_halign(gtk4::Align::Start);
+    editor_label.set_margin_top(10);
+    editor_label.set_margin_bottom(5);
+    editor_label.add_css_class("heading");
+    settings_box.append(&editor_label);
+    
+    let minimap_check = gtk4::CheckButton::with_label("Show minimap next to the editor");
+    minimap_check.set_active(settings_instance.get_show_minimap());
+    settings_box.append(&minimap_check);
+    
+    // Keybinding profile: the default shortcuts, Vim or Emacs
+    let keymap_box = GtkBox::new(Orientation::Horizontal, 10);
+    let keymap_label = Label::new(Some("Keybindings:"));
+    keymap_label.set_halign(gtk4::Align::Start);
+    keymap_label.set_hexpand(true);
+    let keymap_labels: Vec<&str> = settings::Keymap::ALL.iter().map(|keymap| keymap.label()).collect();
+    let keymap_dropdown = gtk4::DropDown::from_strings(&keymap_labels);
+    let current_keymap = settings_instance.get_keymap();
+    if let Some(position) = settings::Keymap::ALL.iter().position(|keymap| *keymap == current_keymap) {
+        keymap_dropdown.set_selected(position as u32);
+    }
+    keymap_box.append(&keymap_label);
+    keymap_box.append(&keymap_dropdown);
+    settings_box.append(&keymap_box);
+    
+    // Shortcuts are edited in their own dialog and saved to keybindings.conf there
+    let keybindings_button = Button::with_label("Keyboard Shortcuts…");
+    keybindings_button.set_halign(gtk4::Align::Start);
+    let dialog_for_keybindings = dialog.clone();
+    keybindings_button.connect_clicked(move |_| {
+        crate::keybindings::show_keybindings_dialog(&dialog_for_keybindings);
+    });
+    settings_box.append(&keybindings_button);
+    
+    // Whitespace display options, stored under their settings keys
+    let whitespace_checks: Vec<(&str, gtk4::CheckButton)> = [
+        ("draw_spaces", "Show spaces"),
+        ("draw_tabs", "Show tabs"),
+        ("draw_trailing_whitespace", "Show trailing whitespace"),
+        ("show_indent_guides", "Show indentation guides"),
+    ]
+    .into_iter()
+    .map(|(key, label)| {
+        let check = gtk4::CheckButton::with_label(label);
+        check.set_active(settings_instance.get_bool(key, false));
+        settings_box.append(&check);
+        (key, check)
+    })
+    .collect();
+    
+    // Default wrap mode; file types can have their own as wrap_mode.<language> in the settings file
+    let wrap_box = GtkBox::new(Orientation::Horizontal, 10);
+    let wrap_label = Label::new(Some("Line wrapping:"));
+    wrap_label.set_halign(gtk4::Align::Start);
+    wrap_label.set_hexpand(true);
+    let wrap_labels: Vec<&str> = settings::WrapMode::ALL.iter().map(|mode| mode.label()).collect();
+    let wrap_dropdown = gtk4::DropDown::from_strings(&wrap_labels);
+    let current_wrap_mode = settings_instance.get_wrap_mode(None);
+    if let Some(position) = settings::WrapMode::ALL.iter().position(|mode| *mode == current_wrap_mode) {
+        wrap_dropdown.set_selected(position as u32);
+    }
+    wrap_box.append(&wrap_label);
+    wrap_box.append(&wrap_dropdown);
+    settings_box.append(&wrap_box);
+    
+    let rulers_box = GtkBox::new(Orientation::Horizontal, 10);
+    let rulers_label = Label::new(Some("Rulers at columns (e.g. 80, 100):"));
+    rulers_label.set_halign(gtk4::Align::Start);
+    rulers_label.set_hexpand(true);
+    let rulers_entry = gtk4::Entry::new();
+    let current_rulers: Vec<String> = settings_instance.get_ruler_columns().iter().map(|column| column.to_string()).collect();
+    rulers_entry.set_text(&current_rulers.join(", "));
+    rulers_entry.set_placeholder_text(Some("None"));
+    rulers_box.append(&rulers_label);
+    rulers_box.append(&rulers_entry);
+    settings_box.append(&rulers_box);
+    
+    // Spell checking and the dictionary it uses
+    let spell_check_check = gtk4::CheckButton::with_label("Check spelling in text files and in code comments and strings");
+    spell_check_check.set_active(settings_instance.get_bool("spell_check", false));
+    settings_box.append(&spell_check_check);
+    
+    let spell_language_box = GtkBox::new(Orientation::Horizontal, 10);
+    let spell_language_label = Label::new(Some("Spelling language:"));
+    spell_language_label.set_halign(gtk4::Align::Start);
+    spell_language_label.set_hexpand(true);
+    let spell_languages = crate::dictionary::available_languages();
+    let spell_language_dropdown = if spell_languages.is_empty() {
+        let dropdown = gtk4::DropDown::from_strings(&["No dictionaries installed"]);
+        dropdown.set_sensitive(false);
+        dropdown
+    } else {
+        let labels: Vec<&str> = spell_languages.iter().map(|language| language.as_str()).collect();
+        let dropdown = gtk4::DropDown::from_strings(&labels);
+        let current_language = crate::spell_check::selected_language();
+        if let Some(position) = spell_languages.iter().position(|language| *language == current_language) {
+            dropdown.set_selected(position as u32);
+        }
+        dropdown
+    };
+    spell_language_box.append(&spell_language_label);
+    spell_language_box.append(&spell_language_dropdown);
+    settings_box.append(&spell_language_box);
+    
+    // Create a section for code completion
+    let completion_label = Label::new(Some("Completion"));
+    completion_label.set_halign(gtk4::Align::Start);
+    completion_label.set_margin_top(10);
+    completion_label.set_margin_bottom(5);
+    completion_label.add_css_class("heading");
+    settings_box.append(&completion_label);
+    
+    let completion_checks: Vec<(&str, gtk4::CheckButton)> = [
+        ("completion.words", "Complete words from open files"),
+        ("completion.paths", "Complete file paths inside strings"),
+        ("completion.keywords", "Complete language keywords"),
+        ("completion.snippets", "Offer snippets"),
+    ]
+    .into_iter()
+    .map(|(key, label)| {
+        let check = gtk4::CheckButton::with_label(label);
+        check.set_active(settings_instance.get_bool(key, true));
+        settings_box.append(&check);
+        (key, check)
+    })
+    .collect();
+    
+    // Word length at which completion appears while typing (0 = Ctrl+Space only)
+    let min_chars_box = GtkBox::new(Orientation::Horizontal, 10);
+    let min_chars_label = Label::new(Some("Show completion after typing (characters, 0 = only Ctrl+Space):"));
+    min_chars_label.set_halign(gtk4::Align::Start);
+    min_chars_label.set_hexpand(true);
+    let min_chars_spin = gtk4::SpinButton::with_range(0.0, 10.0, 1.0);
+    let current_min_chars = settings_instance
+        .get("completion.min_chars")
+        .and_then(|value| value.parse::<f64>().ok())
+        .unwrap_or(3.0);
+    min_chars_spin.set_value(current_min_chars);
+    min_chars_box.append(&min_chars_label);
+    min_chars_box.append(&min_chars_spin);
+    settings_box.append(&min_chars_box);
+    
+    // Snippet files are edited in their own dialog and saved there
+    let snippets_button = Button::with_label("Manage Snippets…");
+    snippets_button.set_halign(gtk4::Align::Start);
+    snippets_button.set_margin_top(5);
+    let dialog_for_snippets = dialog.clone();
+    snippets_button.connect_clicked(move |_| {
+        crate::snippets::show_snippet_manager(&dialog_for_snippets);
+    });
+    settings_box.append(&snippets_button);
+    
+    // Create a section for the undo history kept between sessions
+    let undo_label = Label::new(Some("Undo History"));
+    undo_label.set_halign(gtk4::Align::Start);
+    undo_label.set_margin_top(10);
+    undo_label.set_margin_bottom(5);
+    undo_label.add_css_class("heading");
+    settings_box.append(&undo_label);
+    
+    let persistent_undo_check = gtk4::CheckButton::with_label("Keep undo history of closed files");
+    persistent_undo_check.set_active(settings_instance.get_bool("persistent_undo", true));
+    settings_box.append(&persistent_undo_check);
+    
+    let undo_size_box = GtkBox::new(Orientation::Horizontal, 10);
+    let undo_size_label = Label::new(Some("Maximum history size per file (KB):"));
+    undo_size_label.set_halign(gtk4::Align::Start);
+    undo_size_label.set_hexpand(true);
+    let undo_size_spin = gtk4::SpinButton::with_range(64.0, 65536.0, 64.0);
+    let current_undo_size = settings_instance
+        .get("persistent_undo.max_kb")
+        .and_then(|value| value.parse::<f64>().ok())
+        .unwrap_or(crate::undo_history::DEFAULT_MAX_KB as f64);
+    undo_size_spin.set_value(current_undo_size);
+    undo_size_box.append(&undo_size_label);
+    undo_size_box.append(&undo_size_spin);
+    settings_box.append(&undo_size_box);
+    
+    let clear_undo_button = Button::with_label("Clear Saved Undo History");
+    clear_undo_button.set_halign(gtk4::Align::Start);
+    clear_undo_button.set_margin_top(5);
+    clear_undo_button.connect_clicked(|_| crate::undo_history::clear_cache());
+    settings_box.append(&clear_undo_button);
+    
     // Add the settings box to the content area
     content_area.append(&settings_box);
     
@@ -1165,6 +1490,26 @@ pub fn create_settings_dialog(parent: &ApplicationWindow) -> Dialog {
                 settings.set_dark_theme(&dark_theme);
             }
             
+            // Editor display options
+            settings::get_settings_mut().set_show_minimap(minimap_check.is_active());
+            if let Some(keymap) = settings::Keymap::ALL.get(keymap_dropdown.selected() as usize) {
+                settings::get_settings_mut().set_keymap(*keymap);
+            }
+            for (key, check) in whitespace_checks.iter().chain(&completion_checks) {
+                settings::get_settings_mut().set_bool(key, check.is_active());
+            }
+            if let Some(mode) = settings::WrapMode::ALL.get(wrap_dropdown.selected() as usize) {
+                settings::get_settings_mut().set_wrap_mode(*mode);
+            }
+            settings::get_settings_mut().set("right_margin.columns", rulers_entry.text().trim());
+            settings::get_settings_mut().set_bool("spell_check", spell_check_check.is_active());
+            if let Some(language) = spell_languages.get(spell_language_dropdown.selected() as usize) {
+                settings::get_settings_mut().set("spell_check.language", language);
+            }
+            settings::get_settings_mut().set("completion.min_chars", &min_chars_spin.value_as_int().to_string());
+            settings::get_settings_mut().set_bool("persistent_undo", persistent_undo_check.is_active());
+            settings::get_settings_mut().set("persistent_undo.max_kb", &undo_size_spin.value_as_int().to_string());
+            
             // Save settings to disk
             if let Err(e) = settings::get_settings_mut().save() {
                 eprintln!("Failed to save settings: {}", e);
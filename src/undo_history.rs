@@ -0,0 +1,576 @@
+// Persistent undo history for the text editor
+// Saves each file's undo/redo stack to a cache in the config dir and restores it when the unchanged file is reopened
+
+use gtk4::prelude::*;
+use gtk4::TextBuffer;
+use sourceview5::View;
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::utils::ObjectState;
+
+/// Default size limit of one file's saved history, in kilobytes
+pub const DEFAULT_MAX_KB: usize = 1024;
+
+/// Number of files whose history is kept; the least recently saved are removed
+const MAX_CACHED_FILES: usize = 200;
+
+/// Edit log of each buffer, kept as the buffer is edited
+const EDIT_LOG: ObjectState<Rc<RefCell<EditLog>>> = ObjectState::new("undo-history-edit-log");
+
+/// One change of the buffer, in character offsets before the change
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Edit {
+    Insert { offset: i32, text: String },
+    Delete { start: i32, end: i32 },
+}
+
+/// The undo/redo history of a buffer
+///
+/// Applying the groups to `base` in order replays every undo step; the first
+/// `position` groups lead to the text the history was saved with, the rest can be redone.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UndoHistory {
+    base: String,
+    groups: Vec<Vec<Edit>>,
+    position: usize,
+}
+
+/// Which way the buffer's undo manager is moving through the history
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Replay {
+    Undo,
+    Redo,
+}
+
+/// The history of a buffer as it is edited, with the edits still being grouped
+#[derive(Default)]
+struct EditLog {
+    history: UndoHistory,
+    /// Edits of the user action in progress
+    pending: Vec<Edit>,
+    in_user_action: bool,
+    /// Set while an undo or redo runs, with the change in length it made so far
+    replay: Option<(Replay, i32)>,
+    /// Cleared when an undo or redo didn't match the logged steps
+    consistent: bool,
+}
+
+impl EditLog {
+    fn new(history: UndoHistory) -> Self {
+        EditLog { history, consistent: true, ..Default::default() }
+    }
+
+    /// Adds an edit made to the buffer
+    fn record(&mut self, edit: Edit) {
+        if let Some((_, delta)) = self.replay.as_mut() {
+            *delta += edit_delta(&edit);
+        } else if self.in_user_action {
+            self.pending.push(edit);
+        } else {
+            self.push_group(vec![edit]);
+        }
+    }
+
+    /// Adds a new undo step, dropping the steps that could have been redone
+    fn push_group(&mut self, group: Vec<Edit>) {
+        let history = &mut self.history;
+        history.groups.truncate(history.position);
+        history.groups.push(group);
+        history.position = history.groups.len();
+    }
+
+    /// Moves the position over the steps an undo or redo applied
+    ///
+    /// The undo manager merges consecutive typing or deleting into one step, so
+    /// one undo can cover several logged steps; they are matched by the change
+    /// in length, which only grows (or shrinks) along such a run.
+    fn finish_replay(&mut self) {
+        let Some((replay, delta)) = self.replay.take() else {
+            return;
+        };
+        let history = &mut self.history;
+        let mut covered = 0;
+        let mut steps = 0;
+        loop {
+            let group = match replay {
+                Replay::Undo if steps < history.position => &history.groups[history.position - steps - 1],
+                Replay::Redo if history.position + steps < history.groups.len() => &history.groups[history.position + steps],
+                _ => {
+                    self.consistent = false;
+                    return;
+                }
+            };
+            covered += group.iter().map(edit_delta).sum::<i32>();
+            steps += 1;
+            let matched = match replay {
+                Replay::Undo => covered == -delta,
+                Replay::Redo => covered == delta,
+            };
+            if matched {
+                break;
+            }
+        }
+        match replay {
+            Replay::Undo => history.position -= steps,
+            Replay::Redo => history.position += steps,
+        }
+    }
+}
+
+/// Returns how many characters an edit adds (or removes, when negative)
+fn edit_delta(edit: &Edit) -> i32 {
+    match edit {
+        Edit::Insert { text, .. } => text.chars().count() as i32,
+        Edit::Delete { start, end } => start - end,
+    }
+}
+
+/// Checks whether undo history is saved and restored
+pub fn is_enabled() -> bool {
+    crate::settings::get_settings().get_bool("persistent_undo", true)
+}
+
+/// Returns the size limit of one file's saved history, in bytes
+fn max_history_bytes() -> usize {
+    crate::settings::get_settings()
+        .get("persistent_undo.max_kb")
+        .and_then(|value| value.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_MAX_KB)
+        * 1024
+}
+
+/// Returns the directory holding the saved histories
+pub fn cache_dir() -> PathBuf {
+    crate::settings::get_config_dir().join("undo")
+}
+
+/// Returns the cache file of a file's history, named after a hash of its path
+fn cache_file(path: &Path) -> PathBuf {
+    cache_dir().join(format!("{:016x}.undo", content_hash(&path.to_string_lossy())))
+}
+
+/// Hashes text with 64-bit FNV-1a
+///
+/// Unlike the standard library hasher, the result never changes between builds,
+/// so cache entries written by an older version still match.
+pub fn content_hash(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// Starts logging the edits of a view's buffer for its undo history
+///
+/// Edits are grouped per user action, like the buffer's undo steps, and undo and
+/// redo move through the logged steps. `restore` starts the log over when a file
+/// is loaded.
+pub fn attach(view: &View) {
+    let buffer = view.buffer();
+    let log = Rc::new(RefCell::new(EditLog::new(UndoHistory::default())));
+    EDIT_LOG.set(&buffer, log.clone());
+
+    let log_for_insert = log.clone();
+    buffer.connect_insert_text(move |_, iter, text| {
+        log_for_insert.borrow_mut().record(Edit::Insert { offset: iter.offset(), text: text.to_string() });
+    });
+    let log_for_delete = log.clone();
+    buffer.connect_delete_range(move |_, start, end| {
+        log_for_delete.borrow_mut().record(Edit::Delete { start: start.offset(), end: end.offset() });
+    });
+
+    let log_for_begin = log.clone();
+    buffer.connect_begin_user_action(move |_| {
+        let mut log = log_for_begin.borrow_mut();
+        if log.replay.is_none() {
+            log.in_user_action = true;
+        }
+    });
+    let log_for_end = log.clone();
+    buffer.connect_end_user_action(move |_| {
+        let mut log = log_for_end.borrow_mut();
+        if log.replay.is_some() {
+            return;
+        }
+        log.in_user_action = false;
+        let group = std::mem::take(&mut log.pending);
+        if !group.is_empty() {
+            log.push_group(group);
+        }
+    });
+
+    // The undo and redo signals run their default handler last, so the edits they
+    // make land between these handlers
+    for (signal, replay) in [("undo", Replay::Undo), ("redo", Replay::Redo)] {
+        let log_for_start = log.clone();
+        buffer.connect_local(signal, false, move |_| {
+            log_for_start.borrow_mut().replay = Some((replay, 0));
+            None
+        });
+        let log_for_finish = log.clone();
+        buffer.connect_local(signal, true, move |_| {
+            log_for_finish.borrow_mut().finish_replay();
+            None
+        });
+    }
+
+    // Irreversible changes (such as loading a file) clear the undo manager, and the
+    // log starts over from the current text
+    for property in ["can-undo", "can-redo"] {
+        let log = log.clone();
+        buffer.connect_notify_local(Some(property), move |buffer, _| {
+            if !buffer.can_undo() && !buffer.can_redo() && log.borrow().replay.is_none() {
+                reset_log(buffer);
+            }
+        });
+    }
+}
+
+/// Starts a buffer's edit log over from its current text
+fn reset_log(buffer: &TextBuffer) {
+    if let Some(log) = EDIT_LOG.get(buffer) {
+        let base = buffer.text(&buffer.start_iter(), &buffer.end_iter(), true).to_string();
+        *log.borrow_mut() = EditLog::new(UndoHistory { base, ..Default::default() });
+    }
+}
+
+/// Returns a copy of a buffer's logged undo/redo history
+///
+/// Returns None when nothing was logged, or when the log no longer leads to the
+/// buffer's text (an undo step it could not follow).
+fn logged_history(buffer: &TextBuffer) -> Option<UndoHistory> {
+    let log = EDIT_LOG.get(buffer)?;
+    let log = log.borrow();
+    if log.history.groups.is_empty() {
+        return None;
+    }
+
+    let mut text = log.history.base.clone();
+    for edit in log.history.groups[..log.history.position].iter().flatten() {
+        apply_to_text(&mut text, edit);
+    }
+    if !log.consistent || text != buffer.text(&buffer.start_iter(), &buffer.end_iter(), true).as_str() {
+        eprintln!("The undo history log does not match the buffer; it will not be saved");
+        return None;
+    }
+    Some(log.history.clone())
+}
+
+/// Saves the undo history of a buffer for a file
+///
+/// Called when a tab is closed. Oldest steps are dropped to stay under the size
+/// limit. Does nothing when persistent undo is disabled.
+pub fn store(buffer: &TextBuffer, path: &Path) {
+    if !is_enabled() {
+        return;
+    }
+    let cache_path = cache_file(path);
+    let Some(mut history) = logged_history(buffer) else {
+        let _ = fs::remove_file(&cache_path);
+        return;
+    };
+    if !trim_to_size(&mut history, max_history_bytes()) {
+        let _ = fs::remove_file(&cache_path);
+        return;
+    }
+
+    let text = buffer.text(&buffer.start_iter(), &buffer.end_iter(), true);
+    let contents = serialize(&history, path, content_hash(&text));
+    let result = fs::create_dir_all(cache_dir()).and_then(|_| fs::write(&cache_path, contents));
+    if let Err(e) = result {
+        eprintln!("Failed to save undo history of {}: {}", path.display(), e);
+    }
+    prune_cache();
+}
+
+/// Restores the saved undo history of a file into a freshly loaded buffer
+///
+/// `content` is the text just read from disk. When it differs from the text the
+/// history was saved with, the file changed outside the editor and the history
+/// is discarded. Returns true when a history was restored.
+pub fn restore(buffer: &TextBuffer, path: &Path, content: &str) -> bool {
+    // Loading the file is not an edit
+    reset_log(buffer);
+    let cache_path = cache_file(path);
+    if !is_enabled() {
+        return false;
+    }
+    let Ok(saved) = fs::read_to_string(&cache_path) else {
+        return false;
+    };
+
+    let history = match parse(&saved) {
+        Some((saved_path, hash, history)) if saved_path == path && hash == content_hash(content) => history,
+        Some(_) => {
+            // The file changed since the history was saved
+            let _ = fs::remove_file(&cache_path);
+            return false;
+        }
+        None => {
+            eprintln!("Dropping unreadable undo history {}", cache_path.display());
+            let _ = fs::remove_file(&cache_path);
+            return false;
+        }
+    };
+
+    // The base text is not undoable; every group becomes one undo step on top of it
+    buffer.begin_irreversible_action();
+    buffer.set_text(&history.base);
+    buffer.end_irreversible_action();
+    reset_log(buffer);
+    for group in &history.groups {
+        buffer.begin_user_action();
+        for edit in group {
+            apply_to_buffer(buffer, edit);
+        }
+        buffer.end_user_action();
+    }
+    for _ in history.position..history.groups.len() {
+        if buffer.text(&buffer.start_iter(), &buffer.end_iter(), true) == content {
+            break;
+        }
+        buffer.undo();
+    }
+
+    // Never show different text than the file holds
+    if buffer.text(&buffer.start_iter(), &buffer.end_iter(), true) != content {
+        eprintln!("Undo history of {} did not replay cleanly; dropping it", path.display());
+        buffer.begin_irreversible_action();
+        buffer.set_text(content);
+        buffer.end_irreversible_action();
+        reset_log(buffer);
+        let _ = fs::remove_file(&cache_path);
+        return false;
+    }
+
+    buffer.place_cursor(&buffer.start_iter());
+    true
+}
+
+/// Removes all saved histories
+pub fn clear_cache() {
+    if let Err(e) = fs::remove_dir_all(cache_dir()) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            eprintln!("Failed to clear the undo history cache: {}", e);
+        }
+    }
+}
+
+/// Applies an edit to a buffer
+fn apply_to_buffer(buffer: &TextBuffer, edit: &Edit) {
+    match edit {
+        Edit::Insert { offset, text } => {
+            let mut iter = buffer.iter_at_offset(*offset);
+            buffer.insert(&mut iter, text);
+        }
+        Edit::Delete { start, end } => {
+            let mut start = buffer.iter_at_offset(*start);
+            let mut end = buffer.iter_at_offset(*end);
+            buffer.delete(&mut start, &mut end);
+        }
+    }
+}
+
+/// Applies an edit to a string
+fn apply_to_text(text: &mut String, edit: &Edit) {
+    let byte_index = |text: &String, offset: i32| {
+        text.char_indices().nth(offset.max(0) as usize).map(|(index, _)| index).unwrap_or(text.len())
+    };
+    match edit {
+        Edit::Insert { offset, text: inserted } => {
+            let index = byte_index(text, *offset);
+            text.insert_str(index, inserted);
+        }
+        Edit::Delete { start, end } => {
+            let start = byte_index(text, *start);
+            let end = byte_index(text, *end);
+            text.replace_range(start..end.max(start), "");
+        }
+    }
+}
+
+/// Drops the oldest steps (then the furthest redo steps) until the history fits
+///
+/// Returns false when even the base text alone is too large.
+fn trim_to_size(history: &mut UndoHistory, max_bytes: usize) -> bool {
+    let group_size = |group: &Vec<Edit>| -> usize {
+        group
+            .iter()
+            .map(|edit| match edit {
+                Edit::Insert { text, .. } => text.len() + 24,
+                Edit::Delete { .. } => 24,
+            })
+            .sum::<usize>()
+            + 6
+    };
+    let mut size = history.base.len() + history.groups.iter().map(group_size).sum::<usize>();
+
+    while size > max_bytes && !history.groups.is_empty() {
+        if history.position > 0 {
+            // Fold the oldest step into the base text
+            let oldest = history.groups.remove(0);
+            size -= group_size(&oldest) + history.base.len();
+            for edit in &oldest {
+                apply_to_text(&mut history.base, edit);
+            }
+            size += history.base.len();
+            history.position -= 1;
+        } else if let Some(newest) = history.groups.pop() {
+            size -= group_size(&newest);
+        }
+    }
+    size <= max_bytes && !history.groups.is_empty()
+}
+
+/// Writes a history in the cache file format
+///
+/// Header lines ("path", "hash", "position"), then the base text and the groups.
+/// Text is stored as a byte count followed by the raw text on the next line.
+fn serialize(history: &UndoHistory, path: &Path, hash: u64) -> String {
+    let mut out = String::new();
+    out.push_str(&format!("path {}\n", path.to_string_lossy()));
+    out.push_str(&format!("hash {:016x}\n", hash));
+    out.push_str(&format!("position {}\n", history.position));
+    out.push_str(&format!("base {}\n{}\n", history.base.len(), history.base));
+    for group in &history.groups {
+        out.push_str("group\n");
+        for edit in group {
+            match edit {
+                Edit::Insert { offset, text } => out.push_str(&format!("insert {} {}\n{}\n", offset, text.len(), text)),
+                Edit::Delete { start, end } => out.push_str(&format!("delete {} {}\n", start, end)),
+            }
+        }
+    }
+    out
+}
+
+/// Reads a history written by `serialize`, with its file path and content hash
+fn parse(contents: &str) -> Option<(PathBuf, u64, UndoHistory)> {
+    let mut rest = contents;
+
+    // Takes the next line, without its line break
+    fn next_line<'a>(rest: &mut &'a str) -> Option<&'a str> {
+        let (line, remaining) = rest.split_once('\n')?;
+        *rest = remaining;
+        Some(line)
+    }
+    // Takes `length` bytes of raw text followed by a line break
+    fn next_text(rest: &mut &str, length: usize) -> Option<String> {
+        let text = rest.get(..length)?.to_string();
+        *rest = rest.get(length..)?.strip_prefix('\n')?;
+        Some(text)
+    }
+
+    let path = PathBuf::from(next_line(&mut rest)?.strip_prefix("path ")?);
+    let hash = u64::from_str_radix(next_line(&mut rest)?.strip_prefix("hash ")?, 16).ok()?;
+    let position = next_line(&mut rest)?.strip_prefix("position ")?.parse().ok()?;
+    let base_length = next_line(&mut rest)?.strip_prefix("base ")?.parse().ok()?;
+    let base = next_text(&mut rest, base_length)?;
+
+    let mut groups: Vec<Vec<Edit>> = Vec::new();
+    while !rest.is_empty() {
+        let line = next_line(&mut rest)?;
+        let mut words = line.split(' ');
+        match words.next()? {
+            "group" => groups.push(Vec::new()),
+            "insert" => {
+                let offset = words.next()?.parse().ok()?;
+                let length = words.next()?.parse().ok()?;
+                let text = next_text(&mut rest, length)?;
+                groups.last_mut()?.push(Edit::Insert { offset, text });
+            }
+            "delete" => {
+                let start = words.next()?.parse().ok()?;
+                let end = words.next()?.parse().ok()?;
+                groups.last_mut()?.push(Edit::Delete { start, end });
+            }
+            _ => return None,
+        }
+    }
+
+    if position > groups.len() {
+        return None;
+    }
+    Some((path, hash, UndoHistory { base, groups, position }))
+}
+
+/// Keeps only the most recently saved histories
+fn prune_cache() {
+    let Ok(entries) = fs::read_dir(cache_dir()) else {
+        return;
+    };
+    let mut files: Vec<(std::time::SystemTime, PathBuf)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let modified = entry.metadata().and_then(|metadata| metadata.modified()).ok()?;
+            Some((modified, entry.path()))
+        })
+        .collect();
+    if files.len() <= MAX_CACHED_FILES {
+        return;
+    }
+    files.sort_by(|a, b| b.0.cmp(&a.0));
+    for (_, path) in files.into_iter().skip(MAX_CACHED_FILES) {
+        let _ = fs::remove_file(path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(offset: i32, text: &str) -> Edit {
+        Edit::Insert { offset, text: text.to_string() }
+    }
+
+    #[test]
+    fn undo_of_merged_typing_covers_every_typed_step() {
+        let mut log = EditLog::new(UndoHistory { base: "x".to_string(), ..Default::default() });
+        log.record(Edit::Delete { start: 0, end: 1 });
+        log.record(insert(0, "a"));
+        log.record(insert(1, "b"));
+        assert_eq!(log.history.position, 3);
+
+        // The undo manager reverts both typed characters at once
+        log.replay = Some((Replay::Undo, 0));
+        log.record(Edit::Delete { start: 0, end: 2 });
+        log.finish_replay();
+        assert_eq!(log.history.position, 1);
+
+        log.replay = Some((Replay::Redo, 0));
+        log.record(insert(0, "ab"));
+        log.finish_replay();
+        assert_eq!(log.history.position, 3);
+        assert!(log.consistent);
+    }
+
+    #[test]
+    fn user_action_is_one_step_and_new_edits_drop_redo() {
+        let mut log = EditLog::new(UndoHistory::default());
+        log.in_user_action = true;
+        log.record(insert(0, "one"));
+        log.record(insert(3, " two"));
+        log.in_user_action = false;
+        let group = std::mem::take(&mut log.pending);
+        log.push_group(group);
+        log.record(insert(7, "!"));
+        assert_eq!(log.history.groups.len(), 2);
+
+        log.replay = Some((Replay::Undo, 0));
+        log.record(Edit::Delete { start: 7, end: 8 });
+        log.finish_replay();
+        log.record(insert(7, "?"));
+        assert_eq!(log.history.groups, vec![vec![insert(0, "one"), insert(3, " two")], vec![insert(7, "?")]]);
+        assert_eq!(log.history.position, 2);
+    }
+
+    #[test]
+    fn unmatched_undo_marks_the_log_inconsistent() {
+        let mut log = EditLog::new(UndoHistory::default());
+        log.record(insert(0, "a"));
+        log.replay = Some((Replay::Undo, 0));
+        log.record(Edit::Delete { start: 0, end: 5 });
+        log.finish_replay();
+        assert!(!log.consistent);
+    }
+}
@@ -2,10 +2,11 @@
 // This module contains helper functions used throughout the application
 
 use gtk4::prelude::*;
-use gtk4::{Button, ListBox, MenuButton, pango, ApplicationWindow, EventControllerKey, gdk, glib, Entry};
+use gtk4::{Button, ListBox, MenuButton, pango, ApplicationWindow, EventControllerKey, gdk, gio, glib, Entry, TextBuffer};
 use std::path::PathBuf;
 use std::rc::Rc;
 use std::cell::RefCell;
+use std::marker::PhantomData;
 use mime_guess;
 use mime_guess::Mime;
 use home;
@@ -19,6 +20,55 @@ pub enum FileSelectionSource {
     DirectClick,
 }
 
+/// State of one type that a module keeps on each of its views or buffers
+///
+/// The state is stored as object data under a fixed key, so it is dropped with
+/// the object and found without searching a list of views.
+pub struct ObjectState<T> {
+    key: &'static str,
+    value_type: PhantomData<fn() -> T>,
+}
+
+impl<T: Clone + 'static> ObjectState<T> {
+    /// Declares the state stored under `key`, which must not be used with any other type
+    pub const fn new(key: &'static str) -> Self {
+        ObjectState { key, value_type: PhantomData }
+    }
+
+    /// Stores the state on an object, replacing the previous value
+    pub fn set(&self, object: &impl IsA<glib::Object>, value: T) {
+        // SAFETY: data under this key is only ever stored and read as a T, through this type
+        unsafe { object.set_data(self.key, value) }
+    }
+
+    /// Returns a copy of the state stored on an object
+    pub fn get(&self, object: &impl IsA<glib::Object>) -> Option<T> {
+        // SAFETY: see `set`
+        unsafe { object.data::<T>(self.key).map(|value| value.as_ref().clone()) }
+    }
+}
+
+/// Returns the first and last line touched by the selection (or the cursor line)
+///
+/// A selection ending at the very start of a line does not include that line.
+/// Shared by the commands that work on whole lines.
+pub fn selected_lines(buffer: &TextBuffer) -> (i32, i32) {
+    match buffer.selection_bounds() {
+        Some((start, end)) => {
+            let last_line = if end.starts_line() && end.line() > start.line() {
+                end.line() - 1
+            } else {
+                end.line()
+            };
+            (start.line(), last_line)
+        }
+        None => {
+            let line = buffer.iter_at_mark(&buffer.get_insert()).line();
+            (line, line)
+        }
+    }
+}
+
 /// Checks if a MIME type is supported for editing in the text editor
 ///
 /// This function determines whether a file with the given MIME type
@@ -608,10 +658,12 @@ fn restore_path_buttons(
     update_path_buttons(path_box, current_dir, file_list_box, active_tab_path);
 }
 
-/// Sets up common keyboard shortcuts for the application
+/// Sets up the window commands and their keyboard shortcuts
 ///
-/// This function adds keyboard shortcuts like Ctrl+S for saving, Ctrl+O for opening files,
-/// Ctrl+N for new files, Ctrl+Tab for navigating tabs, and other standard editor shortcuts.
+/// Every command (save, open, new file, tab switching, find, go to line, …) is
+/// registered as a "win" action. Key presses that reach the window are looked up
+/// in the bindings loaded from keybindings.conf (see the keybindings module), so
+/// the defaults such as Ctrl+S or Ctrl+Tab can be changed by the user.
 pub fn setup_keyboard_shortcuts(
     window: &ApplicationWindow, 
     save_button: &Button, 
@@ -622,201 +674,110 @@ pub fn setup_keyboard_shortcuts(
     path_box: Option<&gtk4::Box>,
     current_dir: Option<&Rc<RefCell<PathBuf>>>,
     file_list_box: Option<&gtk4::ListBox>,
-    active_tab_path: Option<&Rc<RefCell<Option<PathBuf>>>>
+    active_tab_path: Option<&Rc<RefCell<Option<PathBuf>>>>,
+    sidebar_stack: Option<&gtk4::Stack>
 ) {
-    // Create a key event controller
-    let key_controller = EventControllerKey::new();
-    
-    // Clone button references for use in the closure
-    let save_button_clone = save_button.clone();
-    let save_as_button_clone = save_as_button.clone();
-    let open_button_clone = open_button.clone();
-    let new_button_clone = new_button.clone();
+    // File operations reuse the header bar buttons
+    for (name, button) in [("save", save_button), ("save-as", save_as_button), ("open", open_button), ("new-file", new_button)] {
+        let button = button.clone();
+        add_window_action(window, name, move || button.emit_clicked());
+    }
+    add_window_action(window, "close-tab", || crate::commands::close_active_tab(false));
     let window_clone = window.clone();
-    
-    // Clone notebook for use in the closure
-    let editor_notebook_clone = editor_notebook.cloned();
-    
-    // Clone path-related references for Ctrl+L functionality
-    let path_box_clone = path_box.cloned();
-    let current_dir_clone = current_dir.cloned();
-    let file_list_box_clone = file_list_box.cloned();
-    let active_tab_path_clone = active_tab_path.cloned();
-    
-    // Connect the key pressed event
-    key_controller.connect_key_pressed(move |_controller, keyval, _keycode, state| {
-        // Check modifier keys
-        let ctrl_pressed = state.contains(gdk::ModifierType::CONTROL_MASK);
-        let shift_pressed = state.contains(gdk::ModifierType::SHIFT_MASK);
-        let alt_pressed = state.contains(gdk::ModifierType::ALT_MASK);
-        
-        // Handle keyboard shortcuts with Ctrl modifier
-        if ctrl_pressed && !alt_pressed {
-            match keyval.name().as_deref() {
-                // File operations
-                // Ctrl+S: Save or Ctrl+Shift+S: Save As
-                Some("s") => {
-                    if shift_pressed {
-                        save_as_button_clone.emit_clicked();
-                        println!("Keyboard shortcut: Ctrl+Shift+S (Save As)");
-                        return glib::Propagation::Stop;
-                    } else {
-                        save_button_clone.emit_clicked();
-                        println!("Keyboard shortcut: Ctrl+S (Save)");
-                        return glib::Propagation::Stop; // Event handled
-                    }
-                },
-                // Ctrl+Shift+S: Save As (uppercase S)
-                Some("S") => {
-                    save_as_button_clone.emit_clicked();
-                    println!("Keyboard shortcut: Ctrl+Shift+S (Save As)");
-                    return glib::Propagation::Stop;
-                },
-                // Ctrl+O: Open
-                Some("o") => {
-                    if !shift_pressed {
-                        open_button_clone.emit_clicked();
-                        println!("Keyboard shortcut: Ctrl+O (Open)");
-                        return glib::Propagation::Stop;
-                    }
-                    return glib::Propagation::Proceed;
-                },
-                // Ctrl+N: New file
-                Some("n") => {
-                    if !shift_pressed {
-                        new_button_clone.emit_clicked();
-                        println!("Keyboard shortcut: Ctrl+N (New File)");
-                        return glib::Propagation::Stop;
-                    }
-                    return glib::Propagation::Proceed;
-                },
-                // Ctrl+Q or Ctrl+W: Quit/Close
-                Some("q") | Some("w") => {
-                    // For Ctrl+Q, close the entire application
-                    if keyval.name().as_deref() == Some("q") {
-                        println!("Keyboard shortcut: Ctrl+Q (Quit)");
-                        window_clone.close();
-                        return glib::Propagation::Stop;
-                    }
-                    // For Ctrl+W, we could close the current tab (not implemented here)
-                    if keyval.name().as_deref() == Some("w") {
-                        println!("Keyboard shortcut: Ctrl+W (Close Tab) - Not implemented yet");
-                        // Future implementation could close the current tab
-                        return glib::Propagation::Proceed;
-                    }
-                    return glib::Propagation::Proceed;
-                },
-                // Ctrl+Tab: Next tab
-                Some("Tab") => {
-                    if let Some(notebook) = &editor_notebook_clone {
-                        if let Some(current_page) = notebook.current_page() {
-                            let page_count = notebook.n_pages();
-                            if page_count > 0 {
-                                let next_page = if shift_pressed {
-                                    // Ctrl+Shift+Tab: Go to previous tab
-                                    if current_page == 0 { page_count - 1 } else { current_page - 1 }
-                                } else {
-                                    // Ctrl+Tab: Go to next tab
-                                    (current_page + 1) % page_count
-                                };
-                                notebook.set_current_page(Some(next_page));
-                                println!("Keyboard shortcut: Ctrl+{}Tab (Switch Tab)",
-                                    if shift_pressed { "Shift+" } else { "" });
-                                return glib::Propagation::Stop;
-                            }
-                        }
-                    }
-                    return glib::Propagation::Proceed;
-                },
-                // Ctrl+PageDown: Next tab
-                Some("Page_Down") => {
-                    if let Some(notebook) = &editor_notebook_clone {
-                        if let Some(current_page) = notebook.current_page() {
-                            let page_count = notebook.n_pages();
-                            if page_count > 0 {
-                                let next_page = (current_page + 1) % page_count;
-                                notebook.set_current_page(Some(next_page));
-                                println!("Keyboard shortcut: Ctrl+PageDown (Next Tab)");
-                                return glib::Propagation::Stop;
-                            }
-                        }
-                    }
-                    return glib::Propagation::Proceed;
-                },
-                // Ctrl+PageUp: Previous tab
-                Some("Page_Up") => {
-                    if let Some(notebook) = &editor_notebook_clone {
-                        if let Some(current_page) = notebook.current_page() {
-                            let page_count = notebook.n_pages();
-                            if page_count > 0 {
-                                let prev_page = if current_page == 0 { page_count - 1 } else { current_page - 1 };
-                                notebook.set_current_page(Some(prev_page));
-                                println!("Keyboard shortcut: Ctrl+PageUp (Previous Tab)");
-                                return glib::Propagation::Stop;
-                            }
-                        }
-                    }
-                    return glib::Propagation::Proceed;
-                },
-                // Ctrl+F: Find - Placeholder for future implementation
-                Some("f") => {
-                    println!("Keyboard shortcut: Ctrl+F (Find) - Not implemented yet");
-                    // Implementation of Find functionality could be added here
-                    return glib::Propagation::Proceed;
-                },
-                // Ctrl+H: Replace - Placeholder for future implementation
-                Some("h") => {
-                    println!("Keyboard shortcut: Ctrl+H (Replace) - Not implemented yet");
-                    // Implementation of Replace functionality could be added here
-                    return glib::Propagation::Proceed;
-                },
-                // Ctrl+Z: Undo - Managed by GtkTextView but log for debugging
-                Some("z") => {
-                    println!("Keyboard shortcut: Ctrl+Z (Undo) - Handled by GtkTextView");
-                    return glib::Propagation::Proceed; // Let GtkTextView handle it
-                },
-                // Ctrl+Y/Ctrl+Shift+Z: Redo - Managed by GtkTextView but log for debugging
-                Some("y") | Some("Z") => {
-                    println!("Keyboard shortcut: Ctrl+{} (Redo) - Handled by GtkTextView", 
-                             if keyval.name().as_deref() == Some("y") { "Y" } else { "Shift+Z" });
-                    return glib::Propagation::Proceed; // Let GtkTextView handle it
-                },
-                // Ctrl+L: Focus location bar / Edit path manually
-                Some("l") => {
-                    println!("Keyboard shortcut: Ctrl+L (Edit Path)");
-                    // Call the function to toggle path input mode
-                    if let (Some(pb), Some(cd), Some(flb), Some(atp)) = (
-                        &path_box_clone, 
-                        &current_dir_clone, 
-                        &file_list_box_clone, 
-                        &active_tab_path_clone
-                    ) {
-                        toggle_path_input_mode(pb, cd, flb, atp);
-                        return glib::Propagation::Stop;
+    add_window_action(window, "quit", move || window_clone.close());
+
+    // Tab navigation, wrapping around at either end
+    if let Some(notebook) = editor_notebook {
+        for (name, step) in [("next-tab", 1), ("previous-tab", -1)] {
+            let notebook = notebook.clone();
+            add_window_action(window, name, move || {
+                let page_count = notebook.n_pages() as i32;
+                if let Some(current_page) = notebook.current_page() {
+                    if page_count > 0 {
+                        let page = (current_page as i32 + step).rem_euclid(page_count);
+                        notebook.set_current_page(Some(page as u32));
                     }
-                    return glib::Propagation::Proceed;
-                },
-                // Let other Ctrl shortcuts pass through to the editor (like Ctrl+C, Ctrl+V)
-                _ => {}
-            }
+                }
+            });
         }
-        
-        // Let the event propagate to other handlers (like the text editor's built-in shortcuts)
-        glib::Propagation::Proceed
-    });
+
+        // Find and replace in the current tab
+        for (name, with_replace) in [("find", false), ("replace", true)] {
+            let notebook = notebook.clone();
+            add_window_action(window, name, move || crate::search::show_find_bar(&notebook, with_replace));
+        }
+        for (name, backwards) in [("find-next", false), ("find-previous", true)] {
+            let notebook = notebook.clone();
+            add_window_action(window, name, move || crate::search::find_in_current_tab(&notebook, backwards));
+        }
+
+        let notebook_clone = notebook.clone();
+        let window_clone = window.clone();
+        add_window_action(window, "goto-line", move || {
+            crate::goto_line::show_goto_line_dialog(&window_clone, &notebook_clone);
+        });
+    }
+
+    // Project-wide search in the sidebar
+    if let Some(stack) = sidebar_stack {
+        let stack = stack.clone();
+        let editor_notebook_clone = editor_notebook.cloned();
+        add_window_action(window, "find-in-files", move || {
+            crate::find_in_files::show_find_in_files(&stack, editor_notebook_clone.as_ref());
+        });
+    }
+
+    // Edit the path bar manually
+    if let (Some(path_box), Some(current_dir), Some(file_list_box), Some(active_tab_path)) =
+        (path_box, current_dir, file_list_box, active_tab_path)
+    {
+        let path_box = path_box.clone();
+        let current_dir = current_dir.clone();
+        let file_list_box = file_list_box.clone();
+        let active_tab_path = active_tab_path.clone();
+        add_window_action(window, "edit-path", move || {
+            toggle_path_input_mode(&path_box, &current_dir, &file_list_box, &active_tab_path);
+        });
+    }
+
+    // The shortcuts cheat sheet and the page to change the shortcuts
+    let window_clone = window.clone();
+    add_window_action(window, "show-shortcuts", move || crate::keybindings::show_shortcuts_window(&window_clone));
+    let window_clone = window.clone();
+    add_window_action(window, "edit-keybindings", move || crate::keybindings::show_keybindings_dialog(&window_clone));
+    let window_clone = window.clone();
+    add_window_action(window, "quick-open", move || crate::quick_open::show_quick_open(&window_clone));
+    let window_clone = window.clone();
+    add_window_action(window, "command-palette", move || crate::command_palette::show_command_palette(&window_clone));
+
+    // Emacs chords typed outside a source view
+    let key_controller = EventControllerKey::new();
+    key_controller.connect_key_pressed(move |_controller, keyval, _keycode, state| handle_window_key(keyval, state));
     
     // Add the controller to the window
     window.add_controller(key_controller);
+
+    // The focused widget decides which keys the shortcuts leave to it
+    window.connect_focus_widget_notify(|_| crate::keybindings::register_accels());
+    crate::keybindings::register_accels();
     
-    // Log that keyboard shortcuts have been set up
-    println!("Keyboard shortcuts initialized:");
-    println!("  - Ctrl+S: Save");
-    println!("  - Ctrl+Shift+S: Save As");
-    println!("  - Ctrl+O: Open");
-    println!("  - Ctrl+N: New file");
-    println!("  - Ctrl+L: Edit path manually");
-    println!("  - Ctrl+Q: Quit application");
-    println!("  - Ctrl+Tab/Ctrl+Shift+Tab: Switch between tabs");
-    println!("  - Ctrl+PageDown/Ctrl+PageUp: Navigate between tabs");
-    println!("  - Other standard shortcuts (Ctrl+C, Ctrl+V, etc.) handled by GTK");
+    println!("Keyboard shortcuts loaded from {}", crate::keybindings::keybindings_path().display());
+}
+
+/// Runs the Emacs C-x chords for a key press the focused widget didn't use
+///
+/// The shortcuts themselves are application accelerators (see `keybindings`);
+/// macro playback passes its keys here too.
+pub fn handle_window_key(keyval: gdk::Key, state: gdk::ModifierType) -> glib::Propagation {
+    if crate::emacs::is_enabled() {
+        return crate::emacs::handle_window_key(keyval, state);
+    }
+    glib::Propagation::Proceed
+}
+
+/// Registers a window action ("win.<name>") running a closure
+fn add_window_action(window: &ApplicationWindow, name: &str, activate: impl Fn() + 'static) {
+    let action = gio::SimpleAction::new(name, None);
+    action.connect_activate(move |_, _| activate());
+    window.add_action(&action);
 }
@@ -0,0 +1,195 @@
+// Vim keybindings for the text editor
+// Wraps sourceview5's VimIMContext and routes :w, :q and :e to the editor's own save, close and open paths
+
+use gtk4::prelude::*;
+use gtk4::{gdk, glib, EventControllerFocus, EventControllerKey, Label};
+use sourceview5::{prelude::*, View, VimIMContext};
+use std::cell::RefCell;
+
+use crate::commands;
+use crate::settings::Keymap;
+use crate::utils::ObjectState;
+
+/// Vim input of each source view; the key controller is disabled while Vim mode is off
+const VIM_INPUT: ObjectState<(EventControllerKey, VimIMContext)> = ObjectState::new("vim-input");
+
+thread_local! {
+    // Status bar label showing the mode of the focused view
+    static MODE_LABEL: RefCell<Option<glib::WeakRef<Label>>> = RefCell::new(None);
+}
+
+/// Checks whether the Vim keymap is selected in the settings
+pub fn is_enabled() -> bool {
+    crate::settings::get_settings().get_keymap() == Keymap::Vim
+}
+
+/// Creates the status bar label showing the Vim mode (and the command line being typed)
+pub fn create_mode_label() -> Label {
+    let label = Label::new(Some("NORMAL"));
+    label.add_css_class("status-item");
+    label.add_css_class("vim-mode");
+    label.set_visible(is_enabled());
+    MODE_LABEL.with(|mode_label| *mode_label.borrow_mut() = Some(label.downgrade()));
+    label
+}
+
+/// Adds Vim input to a source view
+///
+/// The key controller is added first so Vim sees keys before the view's other key
+/// handlers; in normal and visual mode it consumes them, in insert mode it passes
+/// through the keys it doesn't use. The window's shortcuts leave it the keys of
+/// `is_reserved_key`. It stays inactive until Vim mode is enabled.
+pub fn attach(view: &View) {
+    let context = VimIMContext::new();
+    context.set_client_widget(Some(view));
+
+    let key_controller = EventControllerKey::new();
+    key_controller.set_im_context(Some(&context));
+    key_controller.set_propagation_phase(gtk4::PropagationPhase::None);
+    view.add_controller(key_controller.clone());
+
+    // :w and :w <path>
+    context.connect_write(|_, _, path| write(path));
+    // :e <path>
+    context.connect_edit(|_, _, path| edit(path));
+    // :q, :q!, :wq and :x; other commands are left to the context
+    context.connect_execute_command(|_, command| execute_command(command));
+
+    let view_for_mode = view.clone();
+    context.connect_command_bar_text_notify(move |context| {
+        if view_for_mode.has_focus() {
+            update_mode_label(context);
+        }
+    });
+    let view_for_mode = view.clone();
+    context.connect_command_text_notify(move |context| {
+        if view_for_mode.has_focus() {
+            update_mode_label(context);
+        }
+    });
+
+    // Show the mode of whichever view gets the focus
+    let focus_controller = EventControllerFocus::new();
+    let context_for_focus = context.clone();
+    focus_controller.connect_enter(move |_| {
+        if is_enabled() {
+            update_mode_label(&context_for_focus);
+        }
+    });
+    view.add_controller(focus_controller);
+
+    VIM_INPUT.set(view, (key_controller, context));
+}
+
+/// Turns Vim input on or off for a view, following the settings
+pub fn apply_vim_settings(view: &View) {
+    let enabled = is_enabled();
+    if let Some((key_controller, context)) = VIM_INPUT.get(view) {
+        let phase = if enabled { gtk4::PropagationPhase::Capture } else { gtk4::PropagationPhase::None };
+        if key_controller.propagation_phase() != phase {
+            key_controller.set_propagation_phase(phase);
+            context.reset();
+            if !enabled {
+                // Normal mode draws a block cursor through overwrite mode
+                view.set_overwrite(false);
+            }
+        }
+    }
+
+    MODE_LABEL.with(|mode_label| {
+        if let Some(label) = mode_label.borrow().as_ref().and_then(|weak| weak.upgrade()) {
+            label.set_visible(enabled);
+        }
+    });
+}
+
+/// Checks whether a key is a Vim command, so shortcuts must leave it to Vim input
+///
+/// These are the Ctrl keys Vim uses for scrolling, redo, the jump list and
+/// visual block mode, plus Ctrl+[ for Escape and Ctrl+W in insert mode.
+pub fn is_reserved_key(keyval: gdk::Key, state: gdk::ModifierType) -> bool {
+    let others = gdk::ModifierType::SHIFT_MASK | gdk::ModifierType::ALT_MASK | gdk::ModifierType::SUPER_MASK;
+    state.contains(gdk::ModifierType::CONTROL_MASK)
+        && !state.intersects(others)
+        && matches!(
+            keyval,
+            gdk::Key::b | gdk::Key::d | gdk::Key::e | gdk::Key::f | gdk::Key::o | gdk::Key::r | gdk::Key::u
+                | gdk::Key::v | gdk::Key::w | gdk::Key::y | gdk::Key::bracketleft
+        )
+}
+
+/// Returns the mode shown for a command bar text
+///
+/// The context only exposes the text Vim itself would show ("-- INSERT --", ":w"),
+/// so the mode is read from it; an empty command bar means normal mode.
+fn mode_name(command_bar_text: &str) -> String {
+    let text = command_bar_text.trim();
+    if text.starts_with(':') || text.starts_with('/') || text.starts_with('?') {
+        return "COMMAND".to_string();
+    }
+    match text.strip_prefix("--").and_then(|rest| rest.strip_suffix("--")) {
+        Some(mode) => mode.trim().to_string(),
+        None => "NORMAL".to_string(),
+    }
+}
+
+/// Shows a view's Vim mode, pending keys and command line in the status bar
+fn update_mode_label(context: &VimIMContext) {
+    let command_bar_text = context.command_bar_text();
+    let mode = mode_name(&command_bar_text);
+    let text = if mode == "COMMAND" {
+        // The command line being typed is shown as-is
+        command_bar_text.to_string()
+    } else {
+        let pending = context.command_text();
+        if pending.is_empty() {
+            mode
+        } else {
+            format!("{}  {}", mode, pending)
+        }
+    };
+
+    MODE_LABEL.with(|mode_label| {
+        if let Some(label) = mode_label.borrow().as_ref().and_then(|weak| weak.upgrade()) {
+            label.set_text(&text);
+        }
+    });
+}
+
+/// Handles :w like the Save button and :w <path> like Save As to that path
+fn write(path: Option<&str>) {
+    match path.map(str::trim).filter(|path| !path.is_empty()) {
+        Some(path) => {
+            commands::save_as_path(&commands::resolve_path(path));
+        }
+        None => commands::save(),
+    }
+}
+
+/// Handles :e <path> by opening the file like the file list does
+fn edit(path: Option<&str>) {
+    let Some(path) = path.map(str::trim).filter(|path| !path.is_empty()) else {
+        return;
+    };
+    let path = commands::resolve_path(path);
+    if !path.is_file() {
+        eprintln!("Vim: no such file {}", path.display());
+        return;
+    }
+    commands::open_path(&path);
+}
+
+/// Handles the commands the context leaves to the application
+///
+/// Returns true when the command was handled.
+fn execute_command(command: &str) -> bool {
+    let command = command.trim().trim_start_matches(':');
+    match command {
+        "q" | "quit" | "close" => commands::close_active_tab(false),
+        "q!" | "quit!" => commands::close_active_tab(true),
+        // Closing waits for the save, which asks for a path first in an untitled tab
+        "wq" | "x" | "exit" => commands::save_then(|| commands::close_active_tab(false)),
+        _ => return false,
+    }
+    true
+}
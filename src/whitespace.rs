@@ -0,0 +1,339 @@
+// Whitespace display for the text editor
+// Draws spaces, tabs and trailing whitespace, indentation guides, and warns about mixed indentation
+
+use gtk4::prelude::*;
+use gtk4::{glib, Box as GtkBox, Button, DrawingArea, Label, Orientation, Revealer, RevealerTransitionType, ScrolledWindow, TextBuffer, TextWindowType};
+use sourceview5::{prelude::*, SpaceLocationFlags, SpaceTypeFlags, View};
+
+use crate::utils::ObjectState;
+
+/// Widget name used to locate the mixed indentation banner inside an editor page
+const MIXED_INDENT_BANNER_NAME: &str = "mixed-indent-banner";
+
+/// Whether a view shows indentation guides (checked when drawing)
+const SHOW_INDENT_GUIDES: ObjectState<bool> = ObjectState::new("show-indent-guides");
+
+/// The indentation guides layer drawn over a view, redrawn when the setting changes
+const INDENT_GUIDES_LAYER: ObjectState<glib::WeakRef<DrawingArea>> = ObjectState::new("indent-guides-layer");
+
+/// How far to look for a non-blank line when drawing guides through blank lines
+const BLANK_LINE_LOOKAHEAD: i32 = 100;
+
+/// Applies the whitespace settings to a source view
+///
+/// Settings: `draw_spaces`, `draw_tabs`, `draw_trailing_whitespace` and
+/// `show_indent_guides`. Called when a view is created and when settings change.
+pub fn apply_whitespace_settings(view: &View) {
+    let settings = crate::settings::get_settings();
+    let draw_spaces = settings.get_bool("draw_spaces", false);
+    let draw_tabs = settings.get_bool("draw_tabs", false);
+    let draw_trailing = settings.get_bool("draw_trailing_whitespace", false);
+    let show_guides = settings.get_bool("show_indent_guides", false);
+
+    // Spaces and tabs everywhere, as selected
+    let mut types = SpaceTypeFlags::NONE;
+    if draw_spaces {
+        types |= SpaceTypeFlags::SPACE | SpaceTypeFlags::NBSP;
+    }
+    if draw_tabs {
+        types |= SpaceTypeFlags::TAB;
+    }
+
+    let space_drawer = view.space_drawer();
+    space_drawer.set_types_for_locations(SpaceLocationFlags::ALL, SpaceTypeFlags::NONE);
+    space_drawer.set_types_for_locations(SpaceLocationFlags::LEADING | SpaceLocationFlags::INSIDE_TEXT, types);
+
+    // Trailing whitespace is drawn whatever its type
+    let trailing_types = if draw_trailing {
+        SpaceTypeFlags::SPACE | SpaceTypeFlags::NBSP | SpaceTypeFlags::TAB
+    } else {
+        types
+    };
+    space_drawer.set_types_for_locations(SpaceLocationFlags::TRAILING, trailing_types);
+    space_drawer.set_enable_matrix(draw_spaces || draw_tabs || draw_trailing);
+
+    SHOW_INDENT_GUIDES.set(view, show_guides);
+    if let Some(guides) = INDENT_GUIDES_LAYER.get(view).and_then(|guides| guides.upgrade()) {
+        guides.queue_draw();
+    }
+    view.queue_draw();
+}
+
+/// Creates the indentation guides layer for a source view
+///
+/// GtkTextView cannot be drawn on without subclassing, so the guides are a
+/// transparent DrawingArea placed over the scrolled view. It only draws when the
+/// view has guides enabled (see `apply_whitespace_settings`) and ignores input.
+pub fn create_indent_guides(view: &View, scrolled_window: &ScrolledWindow) -> DrawingArea {
+    let guides = DrawingArea::new();
+    guides.set_can_target(false);
+    guides.set_hexpand(true);
+    guides.set_vexpand(true);
+
+    let view_for_draw = view.clone();
+    guides.set_draw_func(move |area, cr, _, _| {
+        if SHOW_INDENT_GUIDES.get(&view_for_draw).unwrap_or(false) {
+            draw_indent_guides(&view_for_draw, area, cr);
+        }
+    });
+
+    // Redraw whenever the visible text or its layout can change
+    let redraw = {
+        let guides = guides.clone();
+        move || guides.queue_draw()
+    };
+    let redraw_clone = redraw.clone();
+    scrolled_window.vadjustment().connect_value_changed(move |_| redraw_clone());
+    let redraw_clone = redraw.clone();
+    scrolled_window.hadjustment().connect_value_changed(move |_| redraw_clone());
+    let redraw_clone = redraw.clone();
+    view.buffer().connect_changed(move |_| redraw_clone());
+    view.connect_notify_local(Some("tab-width"), move |_, _| redraw());
+
+    INDENT_GUIDES_LAYER.set(view, guides.downgrade());
+    guides
+}
+
+/// Draws a vertical line at each indentation level of the visible lines
+fn draw_indent_guides(view: &View, area: &DrawingArea, cr: &gtk4::cairo::Context) {
+    let buffer = view.buffer();
+    let tab_width = view.tab_width().max(1) as usize;
+    let indent_width = match view.indent_width() {
+        width if width > 0 => width as usize,
+        _ => tab_width,
+    };
+
+    // Width of one column in the view's font
+    let char_width = view.create_pango_layout(Some(" ")).pixel_size().0 as f64;
+    if char_width <= 0.0 {
+        return;
+    }
+
+    let visible = view.visible_rect();
+    let (top_iter, _) = view.line_at_y(visible.y());
+    let last_line = view.line_at_y(visible.y() + visible.height()).0.line();
+
+    cr.set_source_rgba(0.5, 0.5, 0.5, 0.35);
+    cr.set_line_width(1.0);
+
+    for line in top_iter.line()..=last_line {
+        let Some(line_start) = buffer.iter_at_line(line) else {
+            break;
+        };
+        let columns = guide_columns(&buffer, line, tab_width);
+        if columns == 0 {
+            continue;
+        }
+
+        let (line_y, line_height) = view.line_yrange(&line_start);
+        let line_x = view.iter_location(&line_start).x();
+
+        let mut column = 0;
+        while column < columns {
+            let buffer_x = line_x + (column as f64 * char_width) as i32;
+            let (window_x, window_y) = view.buffer_to_window_coords(TextWindowType::Widget, buffer_x, line_y);
+            if let Some((x, y)) = view.translate_coordinates(area, window_x as f64, window_y as f64) {
+                cr.move_to(x.floor() + 0.5, y);
+                cr.line_to(x.floor() + 0.5, y + line_height as f64);
+            }
+            column += indent_width;
+        }
+    }
+
+    if let Err(e) = cr.stroke() {
+        eprintln!("Failed to draw indentation guides: {}", e);
+    }
+}
+
+/// Returns how many columns of indentation guides a line gets
+///
+/// Blank lines continue the guides of the surrounding block: they use the smaller
+/// indentation of the nearest non-blank lines above and below.
+fn guide_columns(buffer: &TextBuffer, line: i32, tab_width: usize) -> usize {
+    if let Some(columns) = line_indent_columns(buffer, line, tab_width) {
+        return columns;
+    }
+
+    let above = (1..=BLANK_LINE_LOOKAHEAD)
+        .map(|distance| line - distance)
+        .take_while(|l| *l >= 0)
+        .find_map(|l| line_indent_columns(buffer, l, tab_width));
+    let below = (1..=BLANK_LINE_LOOKAHEAD)
+        .map(|distance| line + distance)
+        .take_while(|l| *l < buffer.line_count())
+        .find_map(|l| line_indent_columns(buffer, l, tab_width));
+
+    match (above, below) {
+        (Some(above), Some(below)) => above.min(below),
+        _ => 0,
+    }
+}
+
+/// Returns the indentation width of a line in columns (None for blank lines)
+fn line_indent_columns(buffer: &TextBuffer, line: i32, tab_width: usize) -> Option<usize> {
+    let mut iter = buffer.iter_at_line(line)?;
+    let mut columns = 0;
+    while !iter.ends_line() {
+        match iter.char() {
+            ' ' => columns += 1,
+            '\t' => columns += tab_width - columns % tab_width,
+            _ => return Some(columns),
+        }
+        iter.forward_char();
+    }
+    None
+}
+
+/// Counts the lines indented with tabs and with spaces
+///
+/// Returns (tab-indented lines, space-indented lines). Lines indented with a single
+/// space are ignored, as are space-indented lines continuing a block comment (" * ").
+pub fn count_indentation_styles(text: &str) -> (usize, usize) {
+    let mut tab_lines = 0;
+    let mut space_lines = 0;
+
+    for line in text.lines() {
+        let indent: String = line.chars().take_while(|c| *c == ' ' || *c == '\t').collect();
+        let rest = &line[indent.len()..];
+        if rest.is_empty() {
+            continue; // Blank or whitespace-only lines say nothing about the style
+        }
+        if indent.starts_with('\t') {
+            tab_lines += 1;
+        } else if indent.len() >= 2 && !rest.starts_with('*') {
+            space_lines += 1;
+        }
+    }
+
+    (tab_lines, space_lines)
+}
+
+/// Rewrites the indentation of every line with tabs or with spaces
+///
+/// Alignment that is not a multiple of the tab width stays as spaces after the tabs.
+/// The whole conversion is a single undoable action.
+pub fn convert_indentation(buffer: &TextBuffer, use_tabs: bool, tab_width: usize) {
+    let tab_width = tab_width.max(1);
+
+    buffer.begin_user_action();
+    for line in 0..buffer.line_count() {
+        let Some(mut start) = buffer.iter_at_line(line) else {
+            continue;
+        };
+        let mut end = start.clone();
+        let mut columns = 0;
+        let mut current = String::new();
+        while !end.ends_line() && (end.char() == ' ' || end.char() == '\t') {
+            let character = end.char();
+            columns += if character == '\t' { tab_width - columns % tab_width } else { 1 };
+            current.push(character);
+            end.forward_char();
+        }
+        if current.is_empty() {
+            continue;
+        }
+
+        let replacement = if use_tabs {
+            format!("{}{}", "\t".repeat(columns / tab_width), " ".repeat(columns % tab_width))
+        } else {
+            " ".repeat(columns)
+        };
+        if replacement != current {
+            buffer.delete(&mut start, &mut end);
+            buffer.insert(&mut start, &replacement);
+        }
+    }
+    buffer.end_user_action();
+}
+
+/// Creates the banner warning that a file mixes tab and space indentation
+///
+/// The banner stays hidden until `check_mixed_indentation` finds mixed indentation.
+/// Its buttons convert the whole buffer to one style.
+pub fn create_mixed_indent_banner(view: &View) -> Revealer {
+    let revealer = Revealer::new();
+    revealer.set_widget_name(MIXED_INDENT_BANNER_NAME);
+    revealer.set_transition_type(RevealerTransitionType::SlideDown);
+    revealer.set_reveal_child(false);
+
+    let banner = GtkBox::new(Orientation::Horizontal, 6);
+    banner.add_css_class("mixed-indent-banner");
+
+    let message = Label::new(Some("This file mixes tab and space indentation."));
+    message.set_halign(gtk4::Align::Start);
+    message.set_hexpand(true);
+    message.set_wrap(true);
+    banner.append(&message);
+
+    let spaces_button = Button::with_label("Convert to Spaces");
+    let tabs_button = Button::with_label("Convert to Tabs");
+    let dismiss_button = Button::from_icon_name("window-close-symbolic");
+    dismiss_button.set_tooltip_text(Some("Dismiss"));
+    dismiss_button.add_css_class("flat");
+    banner.append(&spaces_button);
+    banner.append(&tabs_button);
+    banner.append(&dismiss_button);
+
+    revealer.set_child(Some(&banner));
+
+    for (button, use_tabs) in [(&spaces_button, false), (&tabs_button, true)] {
+        let view = view.clone();
+        let revealer = revealer.clone();
+        button.connect_clicked(move |_| {
+            convert_indentation(&view.buffer(), use_tabs, view.tab_width() as usize);
+            // New indentation follows the chosen style
+            view.set_insert_spaces_instead_of_tabs(!use_tabs);
+            revealer.set_reveal_child(false);
+            println!("Converted indentation to {}", if use_tabs { "tabs" } else { "spaces" });
+        });
+    }
+
+    let revealer_clone = revealer.clone();
+    dismiss_button.connect_clicked(move |_| revealer_clone.set_reveal_child(false));
+
+    // Fixing the indentation by hand also dismisses the banner
+    let revealer_clone = revealer.clone();
+    view.buffer().connect_changed(move |buffer| {
+        if !revealer_clone.reveals_child() {
+            return;
+        }
+        let revealer = revealer_clone.clone();
+        let buffer = buffer.clone();
+        glib::idle_add_local_once(move || {
+            let text = buffer.text(&buffer.start_iter(), &buffer.end_iter(), true);
+            let (tab_lines, space_lines) = count_indentation_styles(&text);
+            if tab_lines == 0 || space_lines == 0 {
+                revealer.set_reveal_child(false);
+            }
+        });
+    });
+
+    revealer
+}
+
+/// Shows the mixed indentation banner of an editor page if its buffer needs it
+///
+/// Called after a file is loaded into a new tab.
+pub fn check_mixed_indentation(page: &gtk4::Widget) {
+    let Some(text_view) = crate::handlers::find_text_view_in_page(page) else {
+        return;
+    };
+    let buffer = text_view.buffer();
+    let text = buffer.text(&buffer.start_iter(), &buffer.end_iter(), true);
+    let (tab_lines, space_lines) = count_indentation_styles(&text);
+    if tab_lines == 0 || space_lines == 0 {
+        return;
+    }
+
+    let mut child = page.first_child();
+    while let Some(current_child) = child {
+        if current_child.widget_name() == MIXED_INDENT_BANNER_NAME {
+            if let Some(revealer) = current_child.downcast_ref::<Revealer>() {
+                println!("Mixed indentation: {} lines use tabs, {} use spaces", tab_lines, space_lines);
+                revealer.set_reveal_child(true);
+            }
+            return;
+        }
+        child = current_child.next_sibling();
+    }
+}
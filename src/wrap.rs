@@ -0,0 +1,317 @@
+// Line wrapping and right-margin rulers for the text editor
+// Applies the wrap mode of each view, toggles it with Alt+Z, and draws a ruler at each configured column
+
+use gtk4::prelude::*;
+use gtk4::{gio, glib, Adjustment, DrawingArea, ScrolledWindow, TextWindowType};
+use sourceview5::{prelude::*, Buffer, View};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use crate::settings::WrapMode;
+use crate::utils::ObjectState;
+
+/// Column used by "Wrap at Margin Column" when no margin or ruler is set
+const DEFAULT_MARGIN_COLUMN: u32 = 80;
+
+/// Wrapping state of one view
+struct WrapState {
+    view: glib::WeakRef<View>,
+    /// Mode chosen for this view with Alt+Z or the context menu, overriding the settings
+    override_mode: Cell<Option<WrapMode>>,
+    /// Right margin of the view apart from margin wrapping
+    base_right_margin: Cell<i32>,
+    /// Right margin last set by margin wrapping, to tell it from margins set elsewhere
+    widened_right_margin: Cell<Option<i32>>,
+    /// Text window width, character width and column the margin wrap was last computed for
+    margin_layout: Cell<(i32, i32, u32)>,
+    /// The "wrap.mode" action, whose state shows the mode in the context menu
+    mode_action: gio::SimpleAction,
+    /// Ruler layer drawn over the view, once the editor page is built
+    rulers: RefCell<Option<glib::WeakRef<DrawingArea>>>,
+    /// Horizontal adjustment whose page size margin wrapping follows, with its handler
+    page_size_handler: RefCell<Option<(Adjustment, glib::SignalHandlerId)>>,
+}
+
+/// Wrapping state of each source view
+const WRAP_STATE: ObjectState<Rc<WrapState>> = ObjectState::new("wrap-state");
+
+/// Returns the mode a view wraps with: its own, else the setting for its language
+fn effective_mode(state: &WrapState, view: &View) -> WrapMode {
+    if let Some(mode) = state.override_mode.get() {
+        return mode;
+    }
+    let language = view
+        .buffer()
+        .downcast::<Buffer>()
+        .ok()
+        .and_then(|buffer| buffer.language())
+        .map(|language| language.id().to_string());
+    crate::settings::get_settings().get_wrap_mode(language.as_deref())
+}
+
+/// Returns the column "Wrap at Margin Column" wraps at
+///
+/// The view's right margin column (set by EditorConfig's `max_line_length`) comes
+/// first, then the first ruler column. This is the column sourceview draws its
+/// margin line at, unrelated to the right margin in pixels that margin wrapping widens.
+fn margin_column(view: &View) -> u32 {
+    if view.shows_right_margin() {
+        return view.right_margin_position();
+    }
+    crate::settings::get_settings().get_ruler_columns().first().copied().unwrap_or(DEFAULT_MARGIN_COLUMN)
+}
+
+/// Sets a view's wrap mode and updates the context menu and rulers
+fn apply_mode(state: &WrapState, view: &View) {
+    let mode = effective_mode(state, view);
+    view.set_wrap_mode(match mode {
+        WrapMode::None => gtk4::WrapMode::None,
+        WrapMode::Word => gtk4::WrapMode::Word,
+        WrapMode::Char => gtk4::WrapMode::Char,
+        WrapMode::Margin => gtk4::WrapMode::WordChar,
+    });
+    if mode != WrapMode::Margin {
+        view.set_right_margin(base_right_margin(state, view));
+        state.widened_right_margin.set(None);
+    }
+    state.margin_layout.set((0, 0, 0));
+    state.mode_action.set_state(&mode.key().to_variant());
+    update_margin_wrap(state, view);
+
+    if let Some(rulers) = state.rulers.borrow().as_ref().and_then(|rulers| rulers.upgrade()) {
+        rulers.queue_draw();
+    }
+}
+
+/// Returns the right margin of a view apart from margin wrapping
+///
+/// A margin other than the one margin wrapping set was set elsewhere, so it becomes
+/// the new base rather than being overwritten with the margin the view started with.
+fn base_right_margin(state: &WrapState, view: &View) -> i32 {
+    let current = view.right_margin();
+    if state.widened_right_margin.get() != Some(current) {
+        state.base_right_margin.set(current);
+    }
+    state.base_right_margin.get()
+}
+
+/// Narrows the text of a view wrapping at the margin column to that column
+///
+/// GtkTextView only wraps at the edge of its text window, so the right margin is
+/// widened until the text area is as wide as the margin column. Only changes the
+/// margin when the view's width, font or column changed.
+fn update_margin_wrap(state: &WrapState, view: &View) {
+    if effective_mode(state, view) != WrapMode::Margin {
+        return;
+    }
+    let text_width = view.visible_rect().width();
+    let char_width = view.create_pango_layout(Some("0")).pixel_size().0;
+    let column = margin_column(view);
+    if text_width <= 0 || char_width <= 0 || state.margin_layout.get() == (text_width, char_width, column) {
+        return;
+    }
+    state.margin_layout.set((text_width, char_width, column));
+
+    let wrapped_width = column as i32 * char_width;
+    let right_margin = (text_width - view.left_margin() - wrapped_width).max(base_right_margin(state, view));
+    view.set_right_margin(right_margin);
+    state.widened_right_margin.set(Some(right_margin));
+}
+
+/// Follows the page size of a view's current horizontal adjustment for margin wrapping
+fn follow_page_size(state: &Rc<WrapState>, view: &View) {
+    if let Some((adjustment, handler)) = state.page_size_handler.borrow_mut().take() {
+        adjustment.disconnect(handler);
+    }
+    let Some(adjustment) = view.hadjustment() else {
+        return;
+    };
+    let weak_state = Rc::downgrade(state);
+    let handler = adjustment.connect_notify_local(Some("page-size"), move |_, _| {
+        if let Some(state) = weak_state.upgrade() {
+            if let Some(view) = state.view.upgrade() {
+                update_margin_wrap(&state, &view);
+            }
+        }
+    });
+    *state.page_size_handler.borrow_mut() = Some((adjustment, handler));
+}
+
+/// Creates the "wrap" actions: toggle (Alt+Z) and the mode picked in the context menu
+fn create_wrap_actions(state: &Rc<WrapState>) -> gio::SimpleActionGroup {
+    let group = gio::SimpleActionGroup::new();
+
+    // Toggling switches between no wrap and the configured mode, or word wrap if that is none.
+    // The view owns this action, which keeps the state alive as long as the view.
+    let toggle = gio::SimpleAction::new("toggle", None);
+    let state_for_toggle = state.clone();
+    toggle.connect_activate(move |_, _| {
+        let state = &state_for_toggle;
+        let Some(view) = state.view.upgrade() else {
+            return;
+        };
+        crate::macros::record_action("wrap.toggle");
+        let new_mode = if effective_mode(state, &view) != WrapMode::None {
+            WrapMode::None
+        } else {
+            state.override_mode.set(None);
+            match effective_mode(state, &view) {
+                WrapMode::None => WrapMode::Word,
+                configured => configured,
+            }
+        };
+        state.override_mode.set(Some(new_mode));
+        apply_mode(state, &view);
+    });
+    group.add_action(&toggle);
+
+    let weak_state = Rc::downgrade(state);
+    state.mode_action.connect_activate(move |_, parameter| {
+        let Some(state) = weak_state.upgrade() else {
+            return;
+        };
+        let mode = parameter.and_then(|parameter| parameter.str()).and_then(WrapMode::from_key);
+        if let (Some(view), Some(mode)) = (state.view.upgrade(), mode) {
+            crate::macros::record_action(&format!("wrap.mode::{}", mode.key()));
+            state.override_mode.set(Some(mode));
+            apply_mode(&state, &view);
+        }
+    });
+    group.add_action(&state.mode_action);
+
+    group
+}
+
+/// Adds wrapping to a source view
+///
+/// The view wraps as configured for its language (`wrap_mode.<language id>`, else
+/// `wrap_mode`) until Alt+Z or the context menu's "Wrap" submenu picks a mode for
+/// this view only. The keys are the defaults of the "wrap" actions.
+pub fn attach(view: &View) {
+    let mode_action = gio::SimpleAction::new_stateful(
+        "mode",
+        Some(glib::VariantTy::STRING),
+        &WrapMode::None.key().to_variant(),
+    );
+    let state = Rc::new(WrapState {
+        view: view.downgrade(),
+        override_mode: Cell::new(None),
+        base_right_margin: Cell::new(view.right_margin()),
+        widened_right_margin: Cell::new(None),
+        margin_layout: Cell::new((0, 0, 0)),
+        mode_action,
+        rulers: RefCell::new(None),
+        page_size_handler: RefCell::new(None),
+    });
+    view.insert_action_group("wrap", Some(&create_wrap_actions(&state)));
+
+    // Files get their language after the view is created, and the mode may depend on it
+    let weak_state = Rc::downgrade(&state);
+    view.buffer().connect_notify_local(Some("language"), move |_, _| {
+        if let Some(state) = weak_state.upgrade() {
+            if let Some(view) = state.view.upgrade() {
+                apply_mode(&state, &view);
+            }
+        }
+    });
+    // The margin column also moves with EditorConfig's max_line_length, which sets
+    // the column and then shows the margin line, or hides it for "off"
+    for property in ["right-margin-position", "show-right-margin"] {
+        let weak_state = Rc::downgrade(&state);
+        view.connect_notify_local(Some(property), move |view, _| {
+            if let Some(state) = weak_state.upgrade() {
+                apply_mode(&state, view);
+            }
+        });
+    }
+
+    // Margin wrapping follows the width of the view, which the scrolled window reports as the page size
+    follow_page_size(&state, view);
+    let weak_state = Rc::downgrade(&state);
+    view.connect_notify_local(Some("hadjustment"), move |view, _| {
+        if let Some(state) = weak_state.upgrade() {
+            follow_page_size(&state, view);
+        }
+    });
+
+    let wrap_menu = gio::Menu::new();
+    for mode in WrapMode::ALL {
+        let item = gio::MenuItem::new(Some(mode.label()), None);
+        item.set_action_and_target_value(Some("wrap.mode"), Some(&mode.key().to_variant()));
+        wrap_menu.append_item(&item);
+    }
+    let section = gio::Menu::new();
+    section.append_submenu(Some("Wrap"), &wrap_menu);
+    crate::syntax::add_context_menu_section(view, &section);
+
+    apply_mode(&state, view);
+    WRAP_STATE.set(view, state);
+}
+
+/// Re-applies the wrap settings to a view after they changed
+///
+/// A mode picked for the view with Alt+Z or the context menu stays in place.
+pub fn apply_wrap_settings(view: &View) {
+    if let Some(state) = WRAP_STATE.get(view) {
+        apply_mode(&state, view);
+    }
+}
+
+/// Creates the right-margin ruler layer for a source view
+///
+/// Like the indentation guides, the rulers are a transparent DrawingArea placed
+/// over the scrolled view; it draws a line at each column of `right_margin.columns`.
+pub fn create_margin_rulers(view: &View, scrolled_window: &ScrolledWindow) -> DrawingArea {
+    let rulers = DrawingArea::new();
+    rulers.set_can_target(false);
+    rulers.set_hexpand(true);
+    rulers.set_vexpand(true);
+
+    let view_for_draw = view.clone();
+    rulers.set_draw_func(move |area, cr, _, height| draw_rulers(&view_for_draw, area, cr, height));
+
+    // Redraw when the text scrolls sideways or the view's font or margins change
+    let rulers_clone = rulers.clone();
+    scrolled_window.hadjustment().connect_value_changed(move |_| rulers_clone.queue_draw());
+    let rulers_clone = rulers.clone();
+    view.connect_notify_local(Some("left-margin"), move |_, _| rulers_clone.queue_draw());
+
+    if let Some(state) = WRAP_STATE.get(view) {
+        *state.rulers.borrow_mut() = Some(rulers.downgrade());
+    }
+    rulers
+}
+
+/// Draws a vertical line at each ruler column
+fn draw_rulers(view: &View, area: &DrawingArea, cr: &gtk4::cairo::Context, height: i32) {
+    let columns = crate::settings::get_settings().get_ruler_columns();
+    if columns.is_empty() {
+        return;
+    }
+
+    // Width of one column in the view's font
+    let char_width = view.create_pango_layout(Some("0")).pixel_size().0 as f64;
+    if char_width <= 0.0 {
+        return;
+    }
+    let line_x = view.iter_location(&view.buffer().start_iter()).x();
+
+    cr.set_source_rgba(0.5, 0.5, 0.5, 0.45);
+    cr.set_line_width(1.0);
+    for column in columns {
+        let buffer_x = line_x + (column as f64 * char_width) as i32;
+        // Columns scrolled out of view to the left would be drawn over the gutter
+        if view.buffer_to_window_coords(TextWindowType::Text, buffer_x, 0).0 < 0 {
+            continue;
+        }
+        let (window_x, _) = view.buffer_to_window_coords(TextWindowType::Widget, buffer_x, 0);
+        if let Some((x, _)) = view.translate_coordinates(area, window_x as f64, 0.0) {
+            cr.move_to(x.floor() + 0.5, 0.0);
+            cr.line_to(x.floor() + 0.5, height as f64);
+        }
+    }
+
+    if let Err(e) = cr.stroke() {
+        eprintln!("Failed to draw margin rulers: {}", e);
+    }
+}